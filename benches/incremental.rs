@@ -0,0 +1,130 @@
+// benches/incremental.rs
+//
+// Compares parsing a large configuration after collecting its whole `data:`
+// line, as the listener does by default, with decoding it incrementally while
+// the line arrives, as it does with `incremental_parsing`. The event is fed in
+// network-sized chunks. Besides the criterion timings, the peak heap usage of
+// both paths is printed; it is what the process RSS grows by while parsing.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use serde_json::{from_slice, Value};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[path = "../src/parser.rs"]
+#[allow(dead_code)]
+mod parser;
+
+#[path = "../src/incremental.rs"]
+#[allow(dead_code)]
+mod incremental;
+
+#[derive(serde::Deserialize)]
+#[allow(dead_code)]
+struct ServerConfig {
+    settings: BTreeMap<String, Value>,
+}
+
+/// Size of the chunks the event is fed in, similar to a network read.
+const CHUNK_SIZE: usize = 16 * 1024;
+
+struct PeakAllocator;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for PeakAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(current, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: PeakAllocator = PeakAllocator;
+
+/// Builds a single SSE event carrying `keys` string settings.
+fn event(keys: usize) -> Vec<u8> {
+    let settings: BTreeMap<String, Value> = (0..keys)
+        .map(|i| (format!("key_{}", i), Value::from(format!("value {} of a moderately long setting", i))))
+        .collect();
+    let payload = serde_json::json!({ "settings": settings });
+    format!("data: {}\n\n", payload).into_bytes()
+}
+
+fn buffered_path(event: &[u8]) -> usize {
+    let mut parser = parser::LineParser::new(None);
+    let mut keys = 0;
+    for chunk in event.chunks(CHUNK_SIZE) {
+        let _ = parser.feed(chunk, |piece| {
+            if let parser::Piece::Line(line) = piece {
+                if let parser::Line::Field { name: b"data", value } = parser::classify(line) {
+                    keys = from_slice::<ServerConfig>(value).map_or(0, |config| config.settings.len());
+                }
+            }
+        });
+    }
+    keys
+}
+
+fn incremental_path(event: &[u8]) -> usize {
+    let mut parser = parser::LineParser::new(None).streaming_data(false);
+    let mut decoder = None;
+    let mut keys = 0;
+    for chunk in event.chunks(CHUNK_SIZE) {
+        let _ = parser.feed(chunk, |piece| match piece {
+            parser::Piece::DataStart => decoder = Some(incremental::SettingsDecoder::default()),
+            parser::Piece::DataPart(part) => {
+                if let Some(decoder) = decoder.as_mut() {
+                    decoder.feed(part);
+                }
+            },
+            parser::Piece::DataEnd => {
+                if let Some(decoder) = decoder.take() {
+                    keys = decoder.finish().map_or(0, |settings| settings.len());
+                }
+            },
+            parser::Piece::Line(_) => {},
+        });
+    }
+    keys
+}
+
+/// Returns the peak heap usage of `f` above what was allocated before it ran.
+fn peak_heap(f: impl FnOnce() -> usize) -> usize {
+    let before = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(before, Ordering::Relaxed);
+    black_box(f());
+    PEAK.load(Ordering::Relaxed) - before
+}
+
+fn bench_incremental(c: &mut Criterion) {
+    for keys in [1_000, 100_000] {
+        let event = event(keys);
+        assert_eq!(buffered_path(&event), keys);
+        assert_eq!(incremental_path(&event), keys);
+
+        println!(
+            "{} keys ({} KiB event): buffered path peaks at {} KiB, incremental path at {} KiB",
+            keys,
+            event.len() / 1024,
+            peak_heap(|| buffered_path(&event)) / 1024,
+            peak_heap(|| incremental_path(&event)) / 1024,
+        );
+
+        c.bench_function(&format!("buffered_path/{}", keys), |b| b.iter(|| buffered_path(black_box(&event))));
+        c.bench_function(&format!("incremental_path/{}", keys), |b| {
+            b.iter(|| incremental_path(black_box(&event)))
+        });
+    }
+}
+
+criterion_group!(benches, bench_incremental);
+criterion_main!(benches);
@@ -0,0 +1,133 @@
+// benches/parse.rs
+//
+// Compares the original per-chunk decode path (copy the chunk into a `String`,
+// strip the field name, then parse) with the borrowed line parser used by the
+// listener, for events that arrive in one chunk and for events split across
+// several, which the borrowed parser joins in a reused scratch buffer. Besides
+// the criterion timings, the number of heap allocations per event is printed
+// for every path.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use serde_json::{from_slice, Value};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[path = "../src/parser.rs"]
+#[allow(dead_code)]
+mod parser;
+
+#[derive(serde::Deserialize)]
+#[allow(dead_code)]
+struct ServerConfig {
+    settings: BTreeMap<String, Value>,
+}
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Builds a single SSE event carrying `keys` settings.
+fn event(keys: usize) -> Vec<u8> {
+    let settings: BTreeMap<String, Value> = (0..keys)
+        .map(|i| (format!("key_{}", i), Value::from(i)))
+        .collect();
+    let payload = serde_json::json!({ "settings": settings });
+    format!("data: {}\n\n", payload).into_bytes()
+}
+
+fn copying_path(chunk: &[u8]) {
+    let text = String::from_utf8(chunk.to_vec()).unwrap_or_else(|_| "".to_string());
+    if text.starts_with("data: ") {
+        let json_part = text.trim_start_matches("data: ").trim();
+        black_box(from_slice::<ServerConfig>(json_part.as_bytes()).ok());
+    }
+}
+
+fn borrowed_path(parser: &mut parser::LineParser, chunk: &[u8]) {
+    let _ = parser.feed(chunk, |piece| {
+        if let parser::Piece::Line(line) = piece {
+            if let parser::Line::Field { name: b"data", value } = parser::classify(line) {
+                black_box(from_slice::<ServerConfig>(value).ok());
+            }
+        }
+    });
+}
+
+/// Joins an event split into `chunks` in a buffer of its own, as a decoder
+/// without a scratch buffer has to, then decodes it like `copying_path`.
+fn copying_split_path(chunks: &[&[u8]]) {
+    let mut event = Vec::new();
+    for chunk in chunks {
+        event.extend_from_slice(chunk);
+    }
+    copying_path(&event);
+}
+
+fn borrowed_split_path(parser: &mut parser::LineParser, chunks: &[&[u8]]) {
+    for chunk in chunks {
+        borrowed_path(parser, chunk);
+    }
+}
+
+fn allocations(mut f: impl FnMut()) -> usize {
+    const ROUNDS: usize = 100;
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..ROUNDS {
+        f();
+    }
+    (ALLOCATIONS.load(Ordering::Relaxed) - before) / ROUNDS
+}
+
+fn bench_parse(c: &mut Criterion) {
+    for keys in [10, 10_000] {
+        let chunk = event(keys);
+        let mut parser = parser::LineParser::new(None);
+
+        println!(
+            "{} keys: copying path {} allocations/event, borrowed path {} allocations/event",
+            keys,
+            allocations(|| copying_path(&chunk)),
+            allocations(|| borrowed_path(&mut parser, &chunk)),
+        );
+
+        c.bench_function(&format!("copying_path/{}", keys), |b| b.iter(|| copying_path(black_box(&chunk))));
+        c.bench_function(&format!("borrowed_path/{}", keys), |b| {
+            b.iter(|| borrowed_path(&mut parser, black_box(&chunk)))
+        });
+
+        // The same event in four reads, the data line spanning all of them.
+        let chunks: Vec<&[u8]> = chunk.chunks(chunk.len().div_ceil(4)).collect();
+        println!(
+            "{} keys in {} chunks: copying path {} allocations/event, borrowed path {} allocations/event",
+            keys,
+            chunks.len(),
+            allocations(|| copying_split_path(&chunks)),
+            allocations(|| borrowed_split_path(&mut parser, &chunks)),
+        );
+
+        c.bench_function(&format!("copying_split_path/{}", keys), |b| {
+            b.iter(|| copying_split_path(black_box(&chunks)))
+        });
+        c.bench_function(&format!("borrowed_split_path/{}", keys), |b| {
+            b.iter(|| borrowed_split_path(&mut parser, black_box(&chunks)))
+        });
+    }
+}
+
+criterion_group!(benches, bench_parse);
+criterion_main!(benches);
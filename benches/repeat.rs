@@ -0,0 +1,67 @@
+// benches/repeat.rs
+//
+// Compares handling a configuration the server re-sends unchanged by parsing it
+// and comparing it with the last one, as `skip_unchanged` does, with
+// recognizing the repeated payload before parsing it, as
+// `skip_repeated_payloads` does. A changed payload of the same size is timed as
+// well, as the repeat check adds to the cost of every payload that does get
+// parsed.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use serde_json::{from_slice, Value};
+use std::collections::BTreeMap;
+
+#[path = "../src/repeat.rs"]
+#[allow(dead_code)]
+mod repeat;
+
+#[derive(serde::Deserialize, PartialEq)]
+struct ServerConfig {
+    settings: BTreeMap<String, Value>,
+}
+
+/// Builds the `data:` payload of a configuration carrying `keys` settings,
+/// the last of which is set to `last`.
+fn payload(keys: usize, last: usize) -> Vec<u8> {
+    let mut settings: BTreeMap<String, Value> = (0..keys).map(|i| (format!("key_{}", i), Value::from(i))).collect();
+    settings.insert(format!("key_{}", keys - 1), Value::from(last));
+    serde_json::to_vec(&serde_json::json!({ "settings": settings })).unwrap()
+}
+
+/// Parses `payload` and returns whether it differs from `last`.
+fn parse_and_compare(last: &ServerConfig, payload: &[u8]) -> bool {
+    from_slice::<ServerConfig>(payload).is_ok_and(|config| config != *last)
+}
+
+/// Checks `payload` for a repeat, parsing it only if it is not one.
+fn check_then_parse(filter: &mut repeat::RepeatFilter, payload: &[u8]) -> bool {
+    filter.admit(payload) && from_slice::<ServerConfig>(payload).is_ok()
+}
+
+fn bench_repeat(c: &mut Criterion) {
+    for keys in [10, 10_000] {
+        let repeated = payload(keys, 0);
+        let changed = payload(keys, 1);
+        let last = from_slice::<ServerConfig>(&repeated).unwrap();
+        let mut filter = repeat::RepeatFilter::default();
+        filter.admit(&repeated);
+        filter.record(&repeated);
+
+        c.bench_function(&format!("parse_and_compare/repeated/{}", keys), |b| {
+            b.iter(|| parse_and_compare(&last, black_box(&repeated)))
+        });
+        c.bench_function(&format!("check_then_parse/repeated/{}", keys), |b| {
+            b.iter(|| check_then_parse(&mut filter, black_box(&repeated)))
+        });
+        c.bench_function(&format!("parse_and_compare/changed/{}", keys), |b| {
+            b.iter(|| parse_and_compare(&last, black_box(&changed)))
+        });
+        // The changed payload is admitted but never recorded, so it is parsed every time.
+        c.bench_function(&format!("check_then_parse/changed/{}", keys), |b| {
+            b.iter(|| check_then_parse(&mut filter, black_box(&changed)))
+        });
+    }
+}
+
+criterion_group!(benches, bench_repeat);
+criterion_main!(benches);
@@ -1,6 +1,6 @@
 // examples/example.rs
+// Run `cargo run --example server` first to serve the endpoint below locally.
 use config_sdk::{start_listening_for_updates, ServerConfig};
-use tokio;
 
 #[tokio::main]
 async fn main() {
@@ -0,0 +1,111 @@
+// examples/server.rs
+//! A minimal SSE configuration server to run the client examples against.
+//!
+//! Serves `http://localhost:8080/sse/dev`, sending a `ServerConfig` with an
+//! incrementing `version` setting on every tick. Each event carries an `id:`
+//! equal to its version, and a reconnecting client that sends `Last-Event-ID`
+//! resumes right after that version.
+//!
+//! ```text
+//! cargo run --example server -- [--interval-ms 1000] [--disconnect-after 5]
+//! ```
+//!
+//! `--disconnect-after N` drops every connection after `N` events, to exercise
+//! how a client handles a lost connection and resumes with `Last-Event-ID`.
+use serde_json::json;
+use std::env;
+use std::error::Error;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+const ADDRESS: &str = "127.0.0.1:8080";
+
+#[derive(Debug, Clone, Copy)]
+struct Options {
+    interval: Duration,
+    disconnect_after: Option<u64>,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut options = Options { interval: Duration::from_secs(1), disconnect_after: None };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || -> Result<u64, String> {
+            let value = args.next().ok_or_else(|| format!("{} needs a value", arg))?;
+            value.parse().map_err(|_| format!("invalid value {:?} for {}", value, arg))
+        };
+        match arg.as_str() {
+            "--interval-ms" => options.interval = Duration::from_millis(value()?),
+            "--disconnect-after" => options.disconnect_after = Some(value()?),
+            _ => return Err(format!("unknown argument {:?}", arg)),
+        }
+    }
+    Ok(options)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let options = parse_options()?;
+    let listener = TcpListener::bind(ADDRESS).await?;
+    println!("Serving configuration events on http://{}/sse/dev", ADDRESS);
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        tokio::spawn(async move {
+            if let Err(e) = serve(stream, options).await {
+                eprintln!("Connection from {} ended: {}", peer, e);
+            }
+        });
+    }
+}
+
+/// Reads one request from `stream` and answers it with an event stream.
+async fn serve(stream: TcpStream, options: Options) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/").to_string();
+
+    let mut last_event_id = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await? == 0 || header.trim_end().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("last-event-id") {
+                last_event_id = value.trim().parse::<u64>().ok();
+            }
+        }
+    }
+
+    if path != "/sse/dev" {
+        writer.write_all(b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n").await?;
+        return Ok(());
+    }
+
+    println!("Client connected, resuming after {:?}", last_event_id);
+    writer
+        .write_all(b"HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\ncache-control: no-cache\r\nconnection: close\r\n\r\n")
+        .await?;
+    writer.write_all(format!(": heartbeat-interval={}\n\n", options.interval.as_secs().max(1)).as_bytes()).await?;
+
+    let mut version = last_event_id.unwrap_or(0);
+    let mut sent = 0;
+    loop {
+        version += 1;
+        let config = json!({ "settings": { "version": version, "log_level": "info" } });
+        writer.write_all(format!("id: {}\ndata: {}\n\n", version, config).as_bytes()).await?;
+        writer.flush().await?;
+
+        sent += 1;
+        if options.disconnect_after.is_some_and(|limit| sent >= limit) {
+            println!("Dropping the connection after {} events", sent);
+            return Ok(());
+        }
+        tokio::time::sleep(options.interval).await;
+    }
+}
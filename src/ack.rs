@@ -0,0 +1,66 @@
+// ack.rs
+
+use crate::client::SseClient;
+use crate::errors::ConfigError;
+use slog::{debug, warn, Logger};
+
+/// Default number of times a failed acknowledgment is retried.
+pub const DEFAULT_ACK_RETRIES: u32 = 3;
+
+/// Acknowledges the event with ID `id` to the client's ack URL, if it has one.
+///
+/// The acknowledgment is sent on a task of its own, so the listener carries on
+/// with the next event meanwhile. A failed attempt is retried after the
+/// client's backoff delay; once the retries are used up the failure is passed
+/// to the error hook and the server is left to redeliver the event.
+pub(crate) fn acknowledge(sse_client: &SseClient, log: &Logger, id: &str) {
+    if sse_client.ack_url.is_none() {
+        return;
+    }
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        warn!(log, "Cannot acknowledge event outside of a tokio runtime"; "id" => id);
+        return;
+    };
+    let (sse_client, log, id) = (sse_client.clone(), log.clone(), id.to_string());
+    runtime.spawn(async move { send_with_retries(&sse_client, &log, &id).await });
+}
+
+async fn send_with_retries(sse_client: &SseClient, log: &Logger, id: &str) {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let reason = match send(sse_client, id).await {
+            Ok(()) => {
+                debug!(log, "Event acknowledged"; "id" => id, "attempt" => attempt);
+                return;
+            },
+            Err(reason) => reason,
+        };
+        if attempt > sse_client.ack_retries {
+            warn!(log, "Giving up acknowledging event"; "id" => id, "attempts" => attempt, "error" => &reason);
+            sse_client.report_error(&ConfigError::AckFailed { id: id.to_string(), reason });
+            return;
+        }
+        let delay = sse_client.backoff.delay_for_attempt(attempt);
+        warn!(log, "Failed to acknowledge event, retrying";
+            "id" => id, "attempt" => attempt, "error" => &reason, "delay_ms" => delay.as_millis() as u64);
+        sse_client.clock.sleep(delay).await;
+    }
+}
+
+/// Posts `{"id": id}` to the ack URL, returning why it failed if it did.
+async fn send(sse_client: &SseClient, id: &str) -> Result<(), String> {
+    let Some(url) = sse_client.ack_url.as_deref() else {
+        return Ok(());
+    };
+    let mut request = sse_client.http.post(url).json(&serde_json::json!({ "id": id }));
+    if let Some(token) = &sse_client.bearer_token {
+        request = request.bearer_auth(token);
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("unexpected HTTP status {}", response.status()))
+    }
+}
@@ -0,0 +1,102 @@
+// affinity.rs
+
+use reqwest::header::{HeaderMap, SET_COOKIE};
+use reqwest::Url;
+use slog::{debug, Logger};
+
+/// The cookies a listener echoes back to the server it reconnects to, see
+/// [`SseClientBuilder::sticky_sessions`](crate::SseClientBuilder::sticky_sessions).
+///
+/// Only the name, value and `Secure` flag of a cookie are kept, under the
+/// host and port that set it. Its other attributes are ignored, except that a
+/// `Max-Age` of zero or less removes it, as servers do to end a session.
+#[derive(Debug, Default)]
+pub(crate) struct CookieJar {
+    /// Every cookie, in the order they were first set.
+    cookies: Vec<Cookie>,
+}
+
+#[derive(Debug)]
+struct Cookie {
+    host: String,
+    port: Option<u16>,
+    name: String,
+    value: String,
+    /// Whether the cookie may only be sent over `https`.
+    secure: bool,
+}
+
+/// What `Set-Cookie` says about a cookie.
+struct SetCookie<'a> {
+    name: &'a str,
+    value: &'a str,
+    secure: bool,
+    expired: bool,
+}
+
+impl CookieJar {
+    /// Stores the cookies set by the `Set-Cookie` headers of a response from
+    /// `url`.
+    pub(crate) fn store(&mut self, url: &Url, headers: &HeaderMap, log: &Logger) {
+        let Some(host) = url.host_str() else {
+            return;
+        };
+        let port = url.port_or_known_default();
+        for header in headers.get_all(SET_COOKIE) {
+            let Some(set) = header.to_str().ok().and_then(parse_set_cookie) else {
+                continue;
+            };
+            self.cookies.retain(|cookie| !(cookie.host == host && cookie.port == port && cookie.name == set.name));
+            if set.expired {
+                debug!(log, "Server removed affinity cookie"; "host" => host, "name" => set.name);
+            } else {
+                debug!(log, "Stored affinity cookie"; "host" => host, "name" => set.name, "secure" => set.secure);
+                self.cookies.push(Cookie {
+                    host: host.to_string(),
+                    port,
+                    name: set.name.to_string(),
+                    value: set.value.to_string(),
+                    secure: set.secure,
+                });
+            }
+        }
+    }
+
+    /// Returns the `Cookie` header to send with a request to `url`, if any
+    /// cookie was set by its host and port. `Secure` cookies are left out
+    /// unless `url` is `https`.
+    pub(crate) fn header_for(&self, url: &str) -> Option<String> {
+        let url = Url::parse(url).ok()?;
+        let host = url.host_str()?;
+        let port = url.port_or_known_default();
+        let https = url.scheme() == "https";
+        let pairs: Vec<String> = self
+            .cookies
+            .iter()
+            .filter(|cookie| cookie.host == host && cookie.port == port && (https || !cookie.secure))
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect();
+        (!pairs.is_empty()).then(|| pairs.join("; "))
+    }
+}
+
+/// Parses the `Set-Cookie` header `header`.
+fn parse_set_cookie(header: &str) -> Option<SetCookie<'_>> {
+    let mut parts = header.split(';');
+    let (name, value) = parts.next()?.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    let mut set = SetCookie { name, value: value.trim(), secure: false, expired: false };
+    for attribute in parts {
+        let (attribute, argument) = attribute.split_once('=').unwrap_or((attribute, ""));
+        let attribute = attribute.trim();
+        if attribute.eq_ignore_ascii_case("secure") {
+            set.secure = true;
+        } else if attribute.eq_ignore_ascii_case("max-age") {
+            set.expired = argument.trim().parse::<i64>().is_ok_and(|age| age <= 0);
+        }
+    }
+    Some(set)
+}
@@ -0,0 +1,46 @@
+// backoff.rs
+
+use std::time::Duration;
+
+/// Default delay after the first failed connection attempt.
+pub const DEFAULT_INITIAL_DELAY: Duration = Duration::from_secs(2);
+
+/// Default factor by which the backoff delay grows after every failed attempt.
+pub const DEFAULT_BACKOFF_FACTOR: f64 = 2.0;
+
+/// The exponential backoff curve used between connection attempts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Backoff {
+    pub(crate) initial_delay: Duration,
+    pub(crate) factor: f64,
+    pub(crate) max_delay: Option<Duration>,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            initial_delay: DEFAULT_INITIAL_DELAY,
+            factor: DEFAULT_BACKOFF_FACTOR,
+            max_delay: None,
+        }
+    }
+}
+
+impl Backoff {
+    /// Returns how long to wait after the failed connection attempt number
+    /// `attempt`, counting from 1.
+    ///
+    /// The delay is `initial_delay * factor^(attempt - 1)`, clamped to
+    /// `max_delay`. With the defaults that is 2s, 4s, 8s, ...
+    pub(crate) fn delay_for_attempt(&self, attempt: u32) -> Duration {
+        let exponent = i32::try_from(attempt.saturating_sub(1)).unwrap_or(i32::MAX);
+        let secs = self.initial_delay.as_secs_f64() * self.factor.powi(exponent);
+        // Durations too large to represent are treated as "wait forever",
+        // which `max_delay` then brings back down.
+        let delay = Duration::try_from_secs_f64(secs).unwrap_or(Duration::MAX);
+        match self.max_delay {
+            Some(max_delay) => delay.min(max_delay),
+            None => delay,
+        }
+    }
+}
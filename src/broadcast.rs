@@ -0,0 +1,228 @@
+// broadcast.rs
+
+use crate::client::SseClient;
+use crate::handle::ListenerHandle;
+use crate::logger::{configure_logging, LogGuard};
+use crate::metrics::Metrics;
+use crate::models::{ConfigEvent, EventMeta};
+use slog::{warn, Logger};
+use std::fmt;
+use std::sync::{Arc, Weak};
+use tokio::sync::broadcast::{self, error::RecvError, error::TryRecvError};
+use tokio::sync::watch;
+
+/// What a [`ConfigSubscriber`] received.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BroadcastEvent {
+    /// The next configuration, in the order the listener dispatched them.
+    Config(ConfigEvent),
+    /// The subscriber fell so far behind that the broadcast dropped
+    /// configurations it had not received yet.
+    ///
+    /// The configurations still buffered for the subscriber are skipped as
+    /// well, so it resumes with the most recent configuration instead of
+    /// working through stale ones.
+    Lagged {
+        /// How many configurations the subscriber missed.
+        skipped: u64,
+        /// The most recent configuration dispatched by the listener, to
+        /// recover from. It may be delivered once more as the next
+        /// [`Config`](Self::Config).
+        latest: Option<ConfigEvent>,
+    },
+}
+
+/// Fans the configurations of a listener out to any number of subscribers,
+/// created with [`SseClient::spawn_broadcast`].
+///
+/// Every [subscriber](Self::subscribe) receives every configuration dispatched
+/// after it subscribed. The broadcast never holds back the listener: it keeps
+/// up to [`broadcast_capacity`](crate::SseClientBuilder::broadcast_capacity)
+/// configurations, and a subscriber that falls further behind misses the
+/// oldest ones and is told so with [`BroadcastEvent::Lagged`]. Missed
+/// configurations are counted in `MetricsSnapshot::broadcast_lagged`.
+///
+/// Cloning the broadcast is cheap; clones share the same subscribers.
+#[derive(Clone)]
+pub struct ConfigBroadcast {
+    sender: Weak<broadcast::Sender<ConfigEvent>>,
+    latest: watch::Receiver<Option<ConfigEvent>>,
+    shared: Arc<Shared>,
+}
+
+/// Receives the configurations of a [`ConfigBroadcast`].
+///
+/// A subscriber created with [`ConfigBroadcast::subscribe_as`] carries a name
+/// and logs a warning with it whenever it lags, so the consumers that cannot
+/// keep up can be told apart. Lagging anonymous subscribers are only counted
+/// in the client's metrics.
+pub struct ConfigSubscriber {
+    receiver: broadcast::Receiver<ConfigEvent>,
+    latest: watch::Receiver<Option<ConfigEvent>>,
+    name: Option<String>,
+    shared: Arc<Shared>,
+}
+
+/// What the broadcast and its subscribers report lag to.
+struct Shared {
+    log: Logger,
+    metrics: Arc<Metrics>,
+    // Declared last so that every record is flushed when the last user is dropped.
+    _log_guard: LogGuard,
+}
+
+impl SseClient {
+    /// Starts a listener on a background task that broadcasts every
+    /// configuration to any number of subscribers.
+    ///
+    /// Unlike [`spawn_channel`](Self::spawn_channel), a slow subscriber does
+    /// not slow down the listener or the other subscribers; it misses
+    /// configurations instead, see [`ConfigBroadcast`]. Subscribers see `None`
+    /// from [`recv`](ConfigSubscriber::recv) once the listener has stopped.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use config_sdk::{BroadcastEvent, SseClientBuilder};
+    ///
+    /// # async fn run() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/sse")
+    ///     .broadcast_capacity(64)
+    ///     .build()?;
+    /// let (_handle, broadcast) = client.spawn_broadcast();
+    /// let mut audit = broadcast.subscribe_as("audit");
+    ///
+    /// while let Some(event) = audit.recv().await {
+    ///     match event {
+    ///         BroadcastEvent::Config(event) => println!("config is now {:?}", event.config),
+    ///         BroadcastEvent::Lagged { skipped, latest } => {
+    ///             println!("missed {} configs, config is now {:?}", skipped, latest.map(|event| event.config));
+    ///         },
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn spawn_broadcast(&self) -> (ListenerHandle, ConfigBroadcast) {
+        let (sender, _) = broadcast::channel(self.broadcast_capacity);
+        let sender = Arc::new(sender);
+        let (latest_tx, latest_rx) = watch::channel(None);
+        let (log, log_guard) = configure_logging(self.log_settings, self.metrics());
+        let broadcast = ConfigBroadcast {
+            sender: Arc::downgrade(&sender),
+            latest: latest_rx,
+            shared: Arc::new(Shared { log, metrics: self.metrics(), _log_guard: log_guard }),
+        };
+        let handle = self.spawn_with(move |config, raw: &[u8], meta: &EventMeta| {
+            let event = ConfigEvent { config, raw: raw.to_vec(), meta: meta.clone() };
+            // Published as the latest first, so a subscriber recovering from
+            // lag never misses it, at worst receiving it twice.
+            latest_tx.send_replace(Some(event.clone()));
+            // Without subscribers there is nobody to send it to.
+            let _ = sender.send(event);
+        });
+        (handle, broadcast)
+    }
+}
+
+impl ConfigBroadcast {
+    /// Creates an anonymous subscriber that receives every configuration
+    /// dispatched from now on.
+    pub fn subscribe(&self) -> ConfigSubscriber {
+        let receiver = match self.sender.upgrade() {
+            Some(sender) => sender.subscribe(),
+            // The listener has stopped: subscribe to a channel that is closed already.
+            None => broadcast::channel(1).1,
+        };
+        ConfigSubscriber { receiver, latest: self.latest.clone(), name: None, shared: Arc::clone(&self.shared) }
+    }
+
+    /// Creates a subscriber like [`subscribe`](Self::subscribe) that logs a
+    /// warning naming it whenever it lags.
+    pub fn subscribe_as(&self, name: impl Into<String>) -> ConfigSubscriber {
+        ConfigSubscriber { name: Some(name.into()), ..self.subscribe() }
+    }
+
+    /// Returns the most recent configuration dispatched by the listener, or
+    /// `None` if there has not been any yet.
+    pub fn latest(&self) -> Option<ConfigEvent> {
+        self.latest.borrow().clone()
+    }
+
+    /// Returns the number of subscribers, or 0 once the listener has stopped.
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.upgrade().map_or(0, |sender| sender.receiver_count())
+    }
+}
+
+impl ConfigSubscriber {
+    /// Waits for the next configuration, or returns `None` once the listener
+    /// has stopped and every configuration sent before has been received.
+    ///
+    /// If the subscriber fell behind and missed configurations, the
+    /// configurations still buffered for it are skipped and
+    /// [`BroadcastEvent::Lagged`] is returned with the most recent one.
+    pub async fn recv(&mut self) -> Option<BroadcastEvent> {
+        match self.receiver.recv().await {
+            Ok(event) => Some(BroadcastEvent::Config(event)),
+            Err(RecvError::Lagged(skipped)) => Some(self.catch_up(skipped)),
+            Err(RecvError::Closed) => None,
+        }
+    }
+
+    /// Returns the next configuration if one has been received already,
+    /// without waiting, like [`recv`](Self::recv) otherwise.
+    pub fn try_recv(&mut self) -> Option<BroadcastEvent> {
+        match self.receiver.try_recv() {
+            Ok(event) => Some(BroadcastEvent::Config(event)),
+            Err(TryRecvError::Lagged(skipped)) => Some(self.catch_up(skipped)),
+            Err(TryRecvError::Empty | TryRecvError::Closed) => None,
+        }
+    }
+
+    /// Returns the most recent configuration dispatched by the listener,
+    /// regardless of which configurations this subscriber has received.
+    pub fn latest(&self) -> Option<ConfigEvent> {
+        self.latest.borrow().clone()
+    }
+
+    /// Returns the subscriber's name, if it was created with
+    /// [`ConfigBroadcast::subscribe_as`].
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Skips what is left in the buffer after missing `skipped`
+    /// configurations and reports the lag.
+    fn catch_up(&mut self, mut skipped: u64) -> BroadcastEvent {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(_) => skipped += 1,
+                Err(TryRecvError::Lagged(more)) => skipped += more,
+                Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+            }
+        }
+        self.shared.metrics.record_broadcast_lagged(skipped);
+        if let Some(name) = &self.name {
+            warn!(self.shared.log, "Broadcast subscriber fell behind, resuming with the latest configuration";
+                "subscriber" => name, "skipped" => skipped);
+        }
+        BroadcastEvent::Lagged { skipped, latest: self.latest() }
+    }
+}
+
+impl fmt::Debug for ConfigBroadcast {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConfigBroadcast")
+            .field("subscriber_count", &self.subscriber_count())
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for ConfigSubscriber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConfigSubscriber")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,200 @@
+// cache.rs
+
+use crate::errors::ConfigError;
+use crate::models::ServerConfig;
+use futures::future::BoxFuture;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// The first two bytes of every gzip stream.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Where the listener persists the last configuration it received, so a
+/// restarted listener can hand it to the handler before the server is
+/// reachable.
+///
+/// [`FileStore`] keeps the configuration in a local file. Other stores, set
+/// with [`SseClientBuilder::cache_store`](crate::SseClientBuilder::cache_store),
+/// can keep it anywhere, e.g. in a key-value store shared by a fleet of
+/// services or in memory for tests. The methods return boxed futures so that a
+/// store can be used as a trait object.
+///
+/// Errors returned by a store are logged and passed to the
+/// [error hook](crate::SseClientBuilder::on_error); a configuration that cannot
+/// be loaded is treated as missing.
+///
+/// # Example
+///
+/// A store that keeps the configuration in memory:
+///
+/// ```
+/// use config_sdk::{ConfigError, ConfigStore, ServerConfig, SseClientBuilder};
+/// use futures::future::BoxFuture;
+/// use std::sync::{Arc, Mutex};
+///
+/// #[derive(Debug, Clone, Default)]
+/// struct MemoryStore(Arc<Mutex<Option<ServerConfig>>>);
+///
+/// impl ConfigStore for MemoryStore {
+///     fn load(&self) -> BoxFuture<'_, Result<Option<ServerConfig>, ConfigError>> {
+///         Box::pin(async move { Ok(self.0.lock().unwrap().clone()) })
+///     }
+///
+///     fn save<'a>(&'a self, config: &'a ServerConfig) -> BoxFuture<'a, Result<(), ConfigError>> {
+///         Box::pin(async move {
+///             *self.0.lock().unwrap() = Some(config.clone());
+///             Ok(())
+///         })
+///     }
+///
+///     fn clear(&self) -> BoxFuture<'_, Result<(), ConfigError>> {
+///         Box::pin(async move {
+///             *self.0.lock().unwrap() = None;
+///             Ok(())
+///         })
+///     }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), ConfigError> {
+/// let store = MemoryStore::default();
+/// let cached = ServerConfig::builder().set("level", 3).build();
+/// store.save(&cached).await?;
+///
+/// // Nothing listens on this port, so the cached configuration is all there is.
+/// let seen = Arc::new(Mutex::new(Vec::new()));
+/// let handled = Arc::clone(&seen);
+/// let _ = SseClientBuilder::new("http://127.0.0.1:9/sse")
+///     .max_retries(1)
+///     .cache_store(store)
+///     .build()?
+///     .listen(move |config: ServerConfig| handled.lock().unwrap().push(config))
+///     .await;
+///
+/// assert_eq!(*seen.lock().unwrap(), [cached]);
+/// # Ok(())
+/// # }
+/// ```
+pub trait ConfigStore: Send + Sync + fmt::Debug {
+    /// Loads the stored configuration, or `None` if nothing has been stored
+    /// yet.
+    fn load(&self) -> BoxFuture<'_, Result<Option<ServerConfig>, ConfigError>>;
+
+    /// Replaces the stored configuration with `config`.
+    fn save<'a>(&'a self, config: &'a ServerConfig) -> BoxFuture<'a, Result<(), ConfigError>>;
+
+    /// Deletes the stored configuration, after the server removed it.
+    fn clear(&self) -> BoxFuture<'_, Result<(), ConfigError>>;
+}
+
+/// The [`ConfigStore`] behind
+/// [`SseClientBuilder::cache_file`](crate::SseClientBuilder::cache_file),
+/// which keeps the configuration in a file.
+///
+/// The file holds the `ServerConfig` serialized as JSON, optionally gzipped.
+/// Reading detects gzip from the content rather than from the settings, so a
+/// file written by a differently configured client is still understood.
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    path: PathBuf,
+    compress: bool,
+}
+
+impl FileStore {
+    /// Creates a store keeping the configuration in the file at `path`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileStore { path: path.into(), compress: false }
+    }
+
+    /// Gzips the file. Requires the `compression` feature.
+    #[cfg(feature = "compression")]
+    pub fn compress(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    async fn read(&self) -> Result<Option<ServerConfig>, ConfigError> {
+        let bytes = match tokio::fs::read(&self.path).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let json = if bytes.starts_with(&GZIP_MAGIC) { decompress(&bytes)? } else { bytes };
+        Ok(Some(serde_json::from_slice(&json)?))
+    }
+
+    /// Writes to a temporary file next to the cache file and renames it over
+    /// it, so a crash mid-write never leaves a truncated cache.
+    async fn write(&self, config: &ServerConfig) -> Result<(), ConfigError> {
+        let json = serde_json::to_vec(config)?;
+        let bytes = if self.compress { compress(&json)? } else { json };
+
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        tokio::fs::write(&tmp, bytes).await?;
+        tokio::fs::rename(&tmp, &self.path).await?;
+        Ok(())
+    }
+
+    async fn remove(&self) -> Result<(), ConfigError> {
+        match tokio::fs::remove_file(&self.path).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ConfigStore for FileStore {
+    /// Returns `Ok(None)` if there is no cache file yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::Io` if the file cannot be read or decompressed and
+    /// `ConfigError::JsonParse` if it does not hold a configuration, e.g. because
+    /// it was written by an incompatible version.
+    fn load(&self) -> BoxFuture<'_, Result<Option<ServerConfig>, ConfigError>> {
+        Box::pin(self.read())
+    }
+
+    fn save<'a>(&'a self, config: &'a ServerConfig) -> BoxFuture<'a, Result<(), ConfigError>> {
+        Box::pin(self.write(config))
+    }
+
+    fn clear(&self) -> BoxFuture<'_, Result<(), ConfigError>> {
+        Box::pin(self.remove())
+    }
+}
+
+#[cfg(feature = "compression")]
+fn compress(json: &[u8]) -> io::Result<Vec<u8>> {
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(json)?;
+    encoder.finish()
+}
+
+#[cfg(not(feature = "compression"))]
+fn compress(json: &[u8]) -> io::Result<Vec<u8>> {
+    // Compression can only be requested with the feature enabled.
+    Ok(json.to_vec())
+}
+
+#[cfg(feature = "compression")]
+fn decompress(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut json = Vec::new();
+    flate2::read::GzDecoder::new(bytes).read_to_end(&mut json)?;
+    Ok(json)
+}
+
+#[cfg(not(feature = "compression"))]
+fn decompress(_bytes: &[u8]) -> io::Result<Vec<u8>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "cache file is gzip-compressed but the `compression` feature is disabled",
+    ))
+}
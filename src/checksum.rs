@@ -0,0 +1,81 @@
+// checksum.rs
+
+use crate::errors::ConfigError;
+use crate::models::ServerConfig;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+
+/// The hash function a configuration checksum is computed with, see
+/// [`SseClientBuilder::checksum`](crate::SseClientBuilder::checksum).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ChecksumAlgorithm {
+    /// SHA-256, sent as 64 hexadecimal digits.
+    #[default]
+    Sha256,
+}
+
+/// Removes the setting named `key` from `config` and checks that it holds the
+/// checksum of the remaining settings.
+///
+/// # Errors
+///
+/// Returns `ConfigError::ChecksumMismatch` if the setting is missing, is not a
+/// string, or does not match the checksum computed from the configuration.
+pub(crate) fn verify(config: &mut ServerConfig, key: &str, algorithm: ChecksumAlgorithm) -> Result<(), ConfigError> {
+    let expected = config.settings.remove(key);
+    let mut canonical = String::new();
+    write_object(&mut canonical, config.settings.iter());
+    let actual = match algorithm {
+        ChecksumAlgorithm::Sha256 => hex(&Sha256::digest(canonical.as_bytes())),
+    };
+    match expected {
+        Some(Value::String(expected)) if expected.eq_ignore_ascii_case(&actual) => Ok(()),
+        expected => Err(ConfigError::ChecksumMismatch {
+            expected: expected.map(|expected| expected.as_str().map_or_else(|| expected.to_string(), str::to_string)),
+            actual,
+        }),
+    }
+}
+
+/// Writes `value` as compact JSON with the keys of every object in
+/// lexicographic order of their UTF-8 bytes.
+fn write_canonical(out: &mut String, value: &Value) {
+    match value {
+        Value::Object(map) => write_object(out, map.iter()),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(out, item);
+            }
+            out.push(']');
+        },
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+fn write_object<'a>(out: &mut String, entries: impl Iterator<Item = (&'a String, &'a Value)>) {
+    let mut entries: Vec<_> = entries.collect();
+    entries.sort_unstable_by(|(a, _), (b, _)| a.as_bytes().cmp(b.as_bytes()));
+    out.push('{');
+    for (i, (key, value)) in entries.into_iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str(&Value::from(key.as_str()).to_string());
+        out.push(':');
+        write_canonical(out, value);
+    }
+    out.push('}');
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::with_capacity(bytes.len() * 2), |mut out, byte| {
+        let _ = write!(out, "{:02x}", byte);
+        out
+    })
+}
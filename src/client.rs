@@ -0,0 +1,3949 @@
+// client.rs
+
+use crate::ack::DEFAULT_ACK_RETRIES;
+use crate::backoff::Backoff;
+use crate::maintenance::{MaintenanceWindow, DEFAULT_MAINTENANCE_RETRY_INTERVAL};
+use crate::cache::{ConfigStore, FileStore};
+#[cfg(feature = "checksum")]
+use crate::checksum::ChecksumAlgorithm;
+use crate::clock::{Clock, TokioClock};
+use crate::endpoints::{Endpoint, DEFAULT_ENDPOINT_COOLDOWN, DEFAULT_ENDPOINT_WEIGHT};
+use crate::errors::{ConfigError, ErrorPolicy};
+use crate::event_id::EventIdStrategy;
+use crate::format::PayloadFormat;
+use crate::framing::Framing;
+#[cfg(feature = "status-server")]
+use crate::health::StatusSlot;
+use crate::handle::{mark_ready, ready_channel, DispatchInfo, ListenerHandle, Remote};
+use crate::history::ChangeHistory;
+use crate::listener::{listen, listen_concurrently, listen_controlled, listen_fanout, listen_raw, listen_to_channel};
+use crate::logger::{LogFormat, LogOverflow, LogSettings};
+use crate::metrics::Metrics;
+use crate::models::{ConfigEvent, EventMeta, FlattenOptions, MergePolicy, NullValues, ServerConfig, WireFormat};
+use crate::rollback::Snapshots;
+use crate::schema::{Schema, TypedServerConfig};
+use crate::snapshot::LastModified;
+use crate::status::{default_status_policy, ContentTypePolicy, StatusAction};
+use reqwest::redirect::Policy;
+use serde_json::Value;
+use slog::{debug, Logger};
+use reqwest::{Client, Method, StatusCode, Url};
+#[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+use reqwest::Identity;
+use std::env;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+#[cfg(feature = "status-server")]
+use std::net::SocketAddr;
+use std::future::Future;
+use std::path::PathBuf;
+#[cfg(all(feature = "http3", reqwest_unstable))]
+use std::sync::Mutex;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+#[cfg(all(feature = "http3", reqwest_unstable))]
+use tokio::time::Instant;
+
+/// Default number of connection attempts made before the listener gives up.
+pub const DEFAULT_MAX_RETRIES: u32 = 5;
+
+/// Default number of redirects followed for a single connection attempt.
+pub const DEFAULT_MAX_REDIRECTS: usize = 5;
+
+/// Default delay before retrying after a [`StatusAction::RetrySoon`] status.
+pub const DEFAULT_RETRY_SOON_DELAY: Duration = Duration::from_secs(1);
+
+/// Default number of configurations buffered by [`SseClient::spawn_channel`]
+/// before the listener stops reading from the network.
+pub const DEFAULT_EVENT_CHANNEL_CAPACITY: usize = 16;
+
+/// Default number of configurations a broadcast started with
+/// [`SseClient::spawn_broadcast`] keeps for subscribers that fall behind.
+pub const DEFAULT_BROADCAST_CAPACITY: usize = 16;
+
+/// Default number of handler calls [`SseClient::spawn_concurrent`] runs at once.
+pub const DEFAULT_CONCURRENCY: usize = 1;
+
+/// Default SSE event type with which the server signals that the
+/// configuration was removed, see [`SseClientBuilder::removal_event`].
+pub const DEFAULT_REMOVAL_EVENT: &str = "config-removed";
+
+/// The latest configuration, shared between a listener started with
+/// [`SseClient::spawn_shared`] and the rest of the application. It holds `None`
+/// until the first configuration has been dispatched.
+pub type SharedConfig = Arc<RwLock<Option<ServerConfig>>>;
+
+/// `User-Agent` header sent with every request.
+pub(crate) const USER_AGENT: &str = "RichieClient/1.0";
+
+/// Maps the HTTP status of a connection attempt to the listener's reaction.
+pub(crate) type StatusPolicy = Arc<dyn Fn(StatusCode) -> StatusAction + Send + Sync>;
+
+/// Turns the payload of an event into the JSON it encrypts.
+pub(crate) type Decryptor = Arc<dyn Fn(&[u8]) -> Result<Vec<u8>, ConfigError> + Send + Sync>;
+
+/// A body sent with every subscription request.
+#[derive(Clone)]
+pub(crate) struct RequestBody {
+    pub(crate) content_type: String,
+    pub(crate) content: Vec<u8>,
+}
+
+/// Receives the errors the listener recovers from.
+pub(crate) type ErrorHook = Arc<dyn Fn(&ConfigError) + Send + Sync>;
+
+/// Called when the server removes the configuration.
+pub(crate) type RemovalHook = Arc<dyn Fn() + Send + Sync>;
+
+/// Normalizes every configuration before it is filtered and dispatched. An
+/// `Fn` rather than an `FnMut`, because clones of the [`SseClient`] share it
+/// and several listeners may call it at once.
+pub(crate) type Transform = Arc<dyn Fn(ServerConfig) -> ServerConfig + Send + Sync>;
+
+/// Receives the envelope a configuration was extracted from.
+pub(crate) type EnvelopeHook = Arc<dyn Fn(&Value) + Send + Sync>;
+
+/// Receives how long the configuration has gone without being confirmed
+/// current, when it turns stale or current again.
+pub(crate) type StalenessHook = Arc<dyn Fn(Duration) + Send + Sync>;
+
+/// Environment variable holding the SSE endpoint URL. Required by
+/// [`SseClientBuilder::from_env`].
+pub const ENV_URL: &str = "CONFIG_SSE_URL";
+
+/// Environment variable holding the maximum number of connection attempts.
+/// Optional; defaults to [`DEFAULT_MAX_RETRIES`].
+pub const ENV_MAX_RETRIES: &str = "CONFIG_SSE_MAX_RETRIES";
+
+/// Environment variable holding a bearer token sent in the `Authorization`
+/// header. Optional.
+pub const ENV_TOKEN: &str = "CONFIG_SSE_TOKEN";
+
+/// The IP address family used to connect to the server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AddressFamily {
+    /// Use whichever addresses the host name resolves to.
+    #[default]
+    Any,
+    /// Only connect over IPv4.
+    Ipv4,
+    /// Only connect over IPv6.
+    Ipv6,
+}
+
+/// Builder for an [`SseClient`].
+///
+/// The builder collects everything needed to connect to an SSE configuration
+/// endpoint before any network activity happens. Every setter consumes and
+/// returns the builder, so calls can be chained and a later call overrides an
+/// earlier one.
+///
+/// # Example
+///
+/// ```no_run
+/// use config_sdk::{ServerConfig, SseClientBuilder};
+///
+/// # async fn run() -> Result<(), config_sdk::ConfigError> {
+/// let client = SseClientBuilder::new("http://example.com/sse")
+///     .max_retries(3)
+///     .build()?;
+///
+/// client.listen(|config: ServerConfig| {
+///     println!("Received config update: {:?}", config);
+/// }).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct SseClientBuilder {
+    url: String,
+    primary_weight: u32,
+    failover_urls: Vec<Endpoint>,
+    endpoint_cooldown: Duration,
+    query: Vec<(String, String)>,
+    max_retries: u32,
+    max_total_retry_duration: Option<Duration>,
+    max_line_length: Option<usize>,
+    max_event_size: Option<usize>,
+    max_redirects: usize,
+    require_tls: bool,
+    sticky_sessions: bool,
+    version_key: Option<String>,
+    skip_unchanged: Option<Vec<String>>,
+    skip_repeated_payloads: bool,
+    skip_seen_ids: Option<usize>,
+    transform: Option<Transform>,
+    flatten_keys: Option<FlattenOptions>,
+    merge_updates: Option<MergePolicy>,
+    null_values: NullValues,
+    defaults: Option<ServerConfig>,
+    cache_file: Option<PathBuf>,
+    cache_store: Option<Arc<dyn ConfigStore>>,
+    #[cfg(feature = "compression")]
+    compress_cache: bool,
+    bearer_token: Option<String>,
+    method: Method,
+    request_body: Option<RequestBody>,
+    clock: Arc<dyn Clock>,
+    status_policy: StatusPolicy,
+    retry_soon_delay: Duration,
+    accepted_content_types: Option<Vec<String>>,
+    content_type_policy: ContentTypePolicy,
+    backoff: Backoff,
+    stability_window: Option<Duration>,
+    maintenance_windows: Vec<MaintenanceWindow>,
+    maintenance_retry_interval: Duration,
+    idle_timeout: Option<Duration>,
+    pool_idle_timeout: Option<Duration>,
+    max_connection_lifetime: Option<Duration>,
+    first_config_deadline: Option<Duration>,
+    error_policy: ErrorPolicy,
+    catch_handler_panics: bool,
+    slow_handler_threshold: Option<Duration>,
+    offload_handler: bool,
+    log_settings: LogSettings,
+    event_channel_capacity: usize,
+    broadcast_capacity: usize,
+    concurrency: usize,
+    ordering_key: Option<String>,
+    dry_run: bool,
+    lenient_field_case: bool,
+    framing: Framing,
+    strict_utf8: bool,
+    flush_on_eof: bool,
+    ignore_empty_data: bool,
+    incremental_parsing: bool,
+    error_hook: Option<ErrorHook>,
+    stale_after: Option<Duration>,
+    stale_hook: Option<StalenessHook>,
+    fresh_hook: Option<StalenessHook>,
+    reassert_interval: Option<Duration>,
+    removal_hook: Option<RemovalHook>,
+    removal_event: String,
+    decrypt: Option<Decryptor>,
+    #[cfg(feature = "checksum")]
+    checksum: Option<(String, ChecksumAlgorithm)>,
+    wire_format: WireFormat,
+    payload_format: Option<PayloadFormat>,
+    envelope_pointer: Option<String>,
+    envelope_hook: Option<EnvelopeHook>,
+    change_history: Option<usize>,
+    change_history_payloads: bool,
+    retain_snapshots: Option<usize>,
+    ack_url: Option<String>,
+    ack_retries: u32,
+    event_id_strategy: EventIdStrategy,
+    #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+    identity: Option<Identity>,
+    local_address: Option<IpAddr>,
+    address_family: AddressFamily,
+    #[cfg(feature = "unix-socket")]
+    unix_socket: Option<PathBuf>,
+    #[cfg(all(feature = "http3", reqwest_unstable))]
+    http3: bool,
+    #[cfg(feature = "status-server")]
+    status_address: Option<SocketAddr>,
+}
+
+impl SseClientBuilder {
+    /// Creates a builder for a client that connects to `url`.
+    pub fn new(url: impl Into<String>) -> Self {
+        SseClientBuilder {
+            url: url.into(),
+            primary_weight: DEFAULT_ENDPOINT_WEIGHT,
+            failover_urls: Vec::new(),
+            endpoint_cooldown: DEFAULT_ENDPOINT_COOLDOWN,
+            query: Vec::new(),
+            max_retries: DEFAULT_MAX_RETRIES,
+            max_total_retry_duration: None,
+            max_line_length: None,
+            max_event_size: None,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            require_tls: false,
+            sticky_sessions: false,
+            version_key: None,
+            skip_unchanged: None,
+            skip_repeated_payloads: false,
+            skip_seen_ids: None,
+            transform: None,
+            flatten_keys: None,
+            merge_updates: None,
+            null_values: NullValues::Literal,
+            defaults: None,
+            cache_file: None,
+            cache_store: None,
+            #[cfg(feature = "compression")]
+            compress_cache: false,
+            bearer_token: None,
+            method: Method::GET,
+            request_body: None,
+            clock: Arc::new(TokioClock),
+            status_policy: Arc::new(default_status_policy),
+            retry_soon_delay: DEFAULT_RETRY_SOON_DELAY,
+            accepted_content_types: None,
+            content_type_policy: ContentTypePolicy::Warn,
+            backoff: Backoff::default(),
+            stability_window: None,
+            maintenance_windows: Vec::new(),
+            maintenance_retry_interval: DEFAULT_MAINTENANCE_RETRY_INTERVAL,
+            idle_timeout: None,
+            pool_idle_timeout: None,
+            max_connection_lifetime: None,
+            first_config_deadline: None,
+            error_policy: ErrorPolicy::Terminate,
+            catch_handler_panics: true,
+            slow_handler_threshold: None,
+            offload_handler: false,
+            log_settings: LogSettings::default(),
+            event_channel_capacity: DEFAULT_EVENT_CHANNEL_CAPACITY,
+            broadcast_capacity: DEFAULT_BROADCAST_CAPACITY,
+            concurrency: DEFAULT_CONCURRENCY,
+            ordering_key: None,
+            dry_run: false,
+            lenient_field_case: false,
+            framing: Framing::Sse,
+            strict_utf8: false,
+            flush_on_eof: false,
+            ignore_empty_data: true,
+            incremental_parsing: false,
+            error_hook: None,
+            stale_after: None,
+            stale_hook: None,
+            fresh_hook: None,
+            reassert_interval: None,
+            removal_hook: None,
+            removal_event: DEFAULT_REMOVAL_EVENT.to_string(),
+            decrypt: None,
+            #[cfg(feature = "checksum")]
+            checksum: None,
+            wire_format: WireFormat::default(),
+            payload_format: None,
+            envelope_pointer: None,
+            envelope_hook: None,
+            change_history: None,
+            change_history_payloads: false,
+            retain_snapshots: None,
+            ack_url: None,
+            ack_retries: DEFAULT_ACK_RETRIES,
+            event_id_strategy: EventIdStrategy::default(),
+            #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+            identity: None,
+            local_address: None,
+            address_family: AddressFamily::Any,
+            #[cfg(feature = "unix-socket")]
+            unix_socket: None,
+            #[cfg(all(feature = "http3", reqwest_unstable))]
+            http3: false,
+            #[cfg(feature = "status-server")]
+            status_address: None,
+        }
+    }
+
+    /// Creates a builder populated from environment variables.
+    ///
+    /// The following variables are read:
+    ///
+    /// | Variable                 | Required | Builder method  |
+    /// |--------------------------|----------|-----------------|
+    /// | `CONFIG_SSE_URL`         | yes      | [`new`](Self::new) |
+    /// | `CONFIG_SSE_MAX_RETRIES` | no       | [`max_retries`](Self::max_retries) |
+    /// | `CONFIG_SSE_TOKEN`       | no       | [`bearer_token`](Self::bearer_token) |
+    ///
+    /// Values read from the environment are only defaults: any builder method
+    /// called afterwards overrides them.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::MissingEnvVar` naming the variable if a required
+    /// variable is not set, and `ConfigError::InvalidEnvVar` if a variable is set
+    /// but cannot be parsed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use config_sdk::SseClientBuilder;
+    ///
+    /// # fn run() -> Result<(), config_sdk::ConfigError> {
+    /// // CONFIG_SSE_MAX_RETRIES is ignored in favour of the explicit value.
+    /// let client = SseClientBuilder::from_env()?
+    ///     .max_retries(10)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let url = env::var(ENV_URL).map_err(|_| ConfigError::MissingEnvVar(ENV_URL.to_string()))?;
+        let mut builder = SseClientBuilder::new(url);
+
+        if let Ok(value) = env::var(ENV_MAX_RETRIES) {
+            let max_retries = value.trim().parse().map_err(|_| ConfigError::InvalidEnvVar {
+                name: ENV_MAX_RETRIES.to_string(),
+                value,
+            })?;
+            builder = builder.max_retries(max_retries);
+        }
+        if let Ok(token) = env::var(ENV_TOKEN) {
+            builder = builder.bearer_token(token);
+        }
+
+        Ok(builder)
+    }
+
+    /// Sets the maximum number of connection attempts to make before giving up.
+    ///
+    /// Once the last attempt has failed, the listener passes
+    /// `ConfigError::GaveUp` to the [error hook](Self::on_error), with the
+    /// number of attempts made since it started, the time that took and the
+    /// status of the last response, if any, and then returns the same error.
+    /// With a [retry time budget](Self::max_total_retry_duration) as well, the
+    /// listener gives up on whichever runs out first.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sets how long the listener keeps trying to connect before giving up,
+    /// which unlike the number of attempts does not depend on the backoff.
+    ///
+    /// The budget covers a run of failed attempts, counted from the start of
+    /// the first of them, and starts over whenever the attempt count does,
+    /// e.g. after a [stable connection](Self::stability_window). The delay
+    /// before a retry is shortened so the last attempt is made when the
+    /// budget runs out; if that attempt fails too, the listener gives up with
+    /// `ConfigError::GaveUp` as it does after [`max_retries`](Self::max_retries)
+    /// attempts. Whichever of the two runs out first ends the listener, so to
+    /// only limit the time, raise `max_retries` to `u32::MAX`. Must be greater
+    /// than zero.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::SseClientBuilder;
+    /// use std::time::Duration;
+    ///
+    /// // Retry for up to a minute, however many attempts that takes.
+    /// let client = SseClientBuilder::new("http://example.com/sse")
+    ///     .max_retries(u32::MAX)
+    ///     .max_total_retry_duration(Duration::from_secs(60))
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn max_total_retry_duration(mut self, budget: Duration) -> Self {
+        self.max_total_retry_duration = Some(budget);
+        self
+    }
+
+    /// Limits the length of a single SSE line to `max_line_length` bytes.
+    ///
+    /// A line is measured without its terminator. When a line grows past the
+    /// limit, whether or not it has been terminated yet, the connection is dropped
+    /// and re-established under the usual retry policy, so a server sending one
+    /// enormous line cannot make the client buffer without bound. By default
+    /// lines are unlimited.
+    pub fn max_line_length(mut self, max_line_length: usize) -> Self {
+        self.max_line_length = Some(max_line_length);
+        self
+    }
+
+    /// Limits the data of a single event, its `data` lines joined by line
+    /// feeds, to `max_event_size` bytes.
+    ///
+    /// The lines of an event are collected until the blank line that ends it,
+    /// so without a limit a server sending endless `data` lines makes the
+    /// client buffer without bound, however short each line is. Once the data
+    /// grows past the limit it is discarded, a
+    /// [`ConfigError::EventTooLarge`] is passed to the
+    /// [`on_error`](Self::on_error) hook and counted as a parse error, and the
+    /// rest of the event is skipped. The stream carries on with the next
+    /// event. Data decoded as it arrives with
+    /// [`incremental_parsing`](Self::incremental_parsing) is not collected and
+    /// not limited. By default events are unlimited; the limit must be greater
+    /// than zero.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{ConfigError, SseClientBuilder};
+    ///
+    /// # fn main() -> Result<(), ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/sse").max_event_size(32).build()?;
+    /// let mut decoder = client.decoder();
+    ///
+    /// let decoded = decoder.feed(b"data: {\"a\": 1,\ndata: \"b\": 2,\n");
+    /// assert!(decoded.is_empty());
+    /// let decoded = decoder.feed(b"data: \"c\": \"0123456789abcdef\"}\n\ndata: {\"d\": 1}\n\n");
+    /// assert!(matches!(decoded[0], Err(ConfigError::EventTooLarge(32))));
+    /// assert_eq!(decoded[1].as_ref().unwrap().settings["d"], 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn max_event_size(mut self, max_event_size: usize) -> Self {
+        self.max_event_size = Some(max_event_size);
+        self
+    }
+
+    /// Adds `url` as another endpoint serving the same stream, receiving a
+    /// share of the clients proportional to `weight`.
+    ///
+    /// With failover URLs, each listener connects to an endpoint picked at
+    /// random in proportion to the weights, including the URL passed to
+    /// [`new`](Self::new), whose weight is set by
+    /// [`primary_weight`](Self::primary_weight). An endpoint that fails to
+    /// connect is left out for the [cooldown](Self::endpoint_cooldown) and the
+    /// next attempt picks among the others the same way; once every endpoint
+    /// is cooling down, the one that becomes available first is retried. The
+    /// listener stays on an endpoint as long as it keeps connecting. The
+    /// selected endpoint and its weight are logged. Weights must be at least 1.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::SseClientBuilder;
+    ///
+    /// // Half of the clients go to the primary, the rest to the smaller servers.
+    /// let client = SseClientBuilder::new("https://config-1.example.com/sse")
+    ///     .primary_weight(4)
+    ///     .failover_url("https://config-2.example.com/sse", 3)
+    ///     .failover_url("https://config-3.example.com/sse", 1)
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn failover_url(mut self, url: impl Into<String>, weight: u32) -> Self {
+        self.failover_urls.push(Endpoint { url: url.into(), weight });
+        self
+    }
+
+    /// Sets the weight of the URL passed to [`new`](Self::new) when choosing
+    /// among [failover URLs](Self::failover_url). Defaults to
+    /// [`DEFAULT_ENDPOINT_WEIGHT`].
+    pub fn primary_weight(mut self, weight: u32) -> Self {
+        self.primary_weight = weight;
+        self
+    }
+
+    /// Sets how long an endpoint that failed to connect is left out when
+    /// choosing among [failover URLs](Self::failover_url), capped at a year.
+    /// Defaults to [`DEFAULT_ENDPOINT_COOLDOWN`].
+    pub fn endpoint_cooldown(mut self, cooldown: Duration) -> Self {
+        self.endpoint_cooldown = cooldown;
+        self
+    }
+
+    /// Appends the query parameter `name` with `value` to the URL, e.g. to
+    /// subscribe to a subset of the configuration served by the stream.
+    ///
+    /// Names and values are percent-encoded, so they can hold any character,
+    /// and are appended to any query the URL already has, in the order they
+    /// were added; adding a name twice sends it twice. They are added to the
+    /// URL passed to [`new`](Self::new) and to every
+    /// [failover URL](Self::failover_url), so every connection and reconnect
+    /// subscribes the same way; redirects are followed as the server sends
+    /// them. With a `unix://` URL, they form the query of the request target.
+    ///
+    /// # Errors
+    ///
+    /// [`build`](Self::build) returns `ConfigError::GenericError` if a URL with
+    /// query parameters to append is not a valid URL.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::SseClientBuilder;
+    ///
+    /// // Subscribes to http://example.com/sse?namespace=billing&env=prod&tags=eu+%26+us
+    /// let client = SseClientBuilder::new("http://example.com/sse")
+    ///     .query_param("namespace", "billing")
+    ///     .query_params([("env", "prod"), ("tags", "eu & us")])
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn query_param(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.query.push((name.into(), value.into()));
+        self
+    }
+
+    /// Appends every pair of `params` to the URL as a query parameter, see
+    /// [`query_param`](Self::query_param).
+    pub fn query_params<I, K, V>(mut self, params: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.query.extend(params.into_iter().map(|(name, value)| (name.into(), value.into())));
+        self
+    }
+
+    /// Sets how many redirects are followed for a single connection attempt.
+    ///
+    /// Redirects are followed by the listener itself, which sends the
+    /// `Authorization` and `Last-Event-ID` headers again on every hop, even when
+    /// the redirect points to another host. The final URL is logged once
+    /// connected. A response that is still a redirect after `max_redirects` hops
+    /// counts as a failed attempt. Pass `0` to disable redirects. Defaults to
+    /// [`DEFAULT_MAX_REDIRECTS`].
+    pub fn max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    /// Refuses to talk to the server without TLS, for deployments that must be
+    /// able to show that configuration never travelled in plaintext.
+    ///
+    /// The URL and every [failover URL](Self::failover_url) must then use
+    /// `https`, and so must every other request the client makes, such as
+    /// [snapshots](SseClient::fetch_snapshot) and acknowledgements. A redirect
+    /// to a plaintext URL is not followed: the listener passes
+    /// `ConfigError::InsecureConnection` to the [error hook](Self::on_error)
+    /// and stops with it, as retrying would only be redirected again. Whether
+    /// or not TLS is required, every connection logs its scheme and HTTP
+    /// version, and is counted in the client's [metrics](SseClient::metrics)
+    /// by both. Off by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{ConfigError, SseClientBuilder};
+    ///
+    /// let plaintext = SseClientBuilder::new("http://example.com/sse").require_tls(true).build();
+    /// assert!(matches!(plaintext, Err(ConfigError::InvalidOptions(_))));
+    ///
+    /// assert!(SseClientBuilder::new("https://example.com/sse").require_tls(true).build().is_ok());
+    /// ```
+    pub fn require_tls(mut self, require: bool) -> Self {
+        self.require_tls = require;
+        self
+    }
+
+    /// Echoes the cookies the server sets back to it on every reconnect, so a
+    /// load balancer that pins sessions with a cookie sends a resumed stream to
+    /// the backend that served it before.
+    ///
+    /// Every listener keeps the cookies set by the `Set-Cookie` headers of its
+    /// subscription responses, redirects included, and sends them as a
+    /// `Cookie` header with its next requests to the same host and port. Only
+    /// their name, value and `Secure` flag are kept: a `Secure` cookie is only
+    /// sent over `https`, `Domain`, `Path` and `Expires` are ignored, and a
+    /// `Max-Age` of zero or less removes a cookie. Snapshots and
+    /// acknowledgements do not carry them.
+    ///
+    /// Cookies identify the client to the server, and anyone the server
+    /// shares them with, for as long as the listener runs, which is what makes
+    /// the session sticky but also lets its traffic be linked across
+    /// reconnects. They are held in memory only, never written to the
+    /// [cache](Self::cache_file) or logged beyond their names, and are lost when the
+    /// listener stops. As cookies may carry credentials, pair this with
+    /// [`require_tls`](Self::require_tls) outside of trusted networks. Off by
+    /// default.
+    pub fn sticky_sessions(mut self, sticky: bool) -> Self {
+        self.sticky_sessions = sticky;
+        self
+    }
+
+    /// Skips configurations that are not newer than the last one applied, based
+    /// on the integer setting named `key`.
+    ///
+    /// With this option, a configuration whose `key` setting is less than or
+    /// equal to the version of the last configuration passed to the handler is
+    /// dropped, which protects against replayed and out-of-order events. A
+    /// configuration without the setting, or where it is not an integer, is
+    /// always applied and does not change the recorded version. Versions are
+    /// tracked per listener and survive reconnects.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::SseClientBuilder;
+    ///
+    /// let builder = SseClientBuilder::new("http://example.com/sse").version_key("_version");
+    /// ```
+    pub fn version_key(mut self, key: impl Into<String>) -> Self {
+        self.version_key = Some(key.into());
+        self
+    }
+
+    /// Skips configurations that are equivalent to the last one applied, not
+    /// counting the top-level keys in `ignored_keys`.
+    ///
+    /// This keeps the handler from running when the server re-sends an
+    /// unchanged configuration, or one where only volatile keys such as a
+    /// timestamp differ; see [`ServerConfig::equivalent_ignoring`]. Pass an empty
+    /// list to only skip exact repeats. Can be combined with
+    /// [`version_key`](Self::version_key), in which case a configuration has to
+    /// pass both checks. With [`merge_updates`](Self::merge_updates), it is
+    /// the configuration an update merges into that is compared, so a partial
+    /// update that changes nothing is skipped. Skipped configurations are
+    /// counted in `MetricsSnapshot::skipped`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::SseClientBuilder;
+    ///
+    /// let builder = SseClientBuilder::new("http://example.com/sse")
+    ///     .skip_unchanged(["timestamp", "checksum"]);
+    /// ```
+    ///
+    /// With merged updates:
+    ///
+    /// ```
+    /// use config_sdk::{MergePolicy, SseClientBuilder};
+    ///
+    /// # fn main() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/sse")
+    ///     .merge_updates(MergePolicy::PreferOther)
+    ///     .skip_unchanged(Vec::<String>::new())
+    ///     .build()?;
+    /// let mut decoder = client.decoder();
+    ///
+    /// let decoded = decoder.feed(b"data: {\"level\": 3, \"mode\": \"fast\"}\n\ndata: {\"level\": 3}\n\ndata: {\"level\": 4}\n\n");
+    /// assert_eq!(decoded.len(), 2);
+    /// assert_eq!(decoded[1].as_ref().unwrap().settings["level"], 4);
+    /// assert_eq!(client.metrics().snapshot().skipped, 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn skip_unchanged<I>(mut self, ignored_keys: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.skip_unchanged = Some(ignored_keys.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Skips `data:` payloads that are byte for byte identical to the last one
+    /// applied, without parsing them.
+    ///
+    /// For servers that re-send a large configuration on every heartbeat, this
+    /// saves deserializing it only for [`skip_unchanged`](Self::skip_unchanged)
+    /// or [`version_key`](Self::version_key) to drop it. Payloads are compared
+    /// by a hash first and by their bytes when the hashes match, so a changed
+    /// payload is never skipped, although a payload that only differs in
+    /// formatting is parsed as usual. A payload that failed to parse or to
+    /// verify is parsed again when it is repeated. Skipped payloads are counted
+    /// in `MetricsSnapshot::skipped`. The last payload is kept in memory for
+    /// the comparison. Cannot be combined with
+    /// [`incremental_parsing`](Self::incremental_parsing), which does not keep
+    /// payloads. Defaults to `false`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::SseClientBuilder;
+    ///
+    /// # fn main() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/sse").skip_repeated_payloads(true).build()?;
+    /// let mut decoder = client.decoder();
+    ///
+    /// assert_eq!(decoder.feed(b"data: {\"level\": 3}\n\n").len(), 1);
+    /// // The server re-sends the same payload with its heartbeat.
+    /// assert!(decoder.feed(b"data: {\"level\": 3}\n\n").is_empty());
+    /// assert_eq!(decoder.feed(b"data: {\"level\": 4}\n\n").len(), 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn skip_repeated_payloads(mut self, skip: bool) -> Self {
+        self.skip_repeated_payloads = skip;
+        self
+    }
+
+    /// Skips events whose `id:` is one of the last `capacity` IDs processed,
+    /// for idempotent delivery from servers that may send an event again.
+    ///
+    /// A server that replays events after `Last-Event-ID` on reconnect, or
+    /// that delivers at least once, can send an event the handler has already
+    /// seen. With this option the listener remembers the IDs of the events it
+    /// processed and drops such events before parsing them. Only IDs sent with
+    /// the event itself count: an event without an `id:` field, or with an
+    /// empty one, is always processed, as are IDs made up by the
+    /// [`event_id_strategy`](Self::event_id_strategy). An event that failed to
+    /// parse is not remembered, so it is tried again. IDs are tracked per
+    /// listener and survive reconnects. Skipped events are counted in
+    /// `MetricsSnapshot::skipped`.
+    ///
+    /// Once `capacity` IDs are remembered, the oldest one is forgotten, so
+    /// the capacity has to cover how far back the server may replay. Each ID
+    /// remembered takes its length plus roughly 80 bytes of memory, so 10,000
+    /// IDs of 36-character UUIDs take around 1 MiB. Must be at least 1.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::SseClientBuilder;
+    ///
+    /// # fn main() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/sse").skip_seen_ids(1_000).build()?;
+    /// let mut decoder = client.decoder();
+    ///
+    /// assert_eq!(decoder.feed(b"id: 1\ndata: {\"level\": 1}\n\nid: 2\ndata: {\"level\": 2}\n\n").len(), 2);
+    /// // After a reconnect, the server replays the event with ID 2.
+    /// decoder.reset();
+    /// let decoded = decoder.feed(b"id: 2\ndata: {\"level\": 2}\n\nid: 3\ndata: {\"level\": 3}\n\n");
+    /// assert_eq!(decoded.len(), 1);
+    /// assert_eq!(decoded[0].as_ref().unwrap().settings["level"], 3);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn skip_seen_ids(mut self, capacity: usize) -> Self {
+        self.skip_seen_ids = Some(capacity);
+        self
+    }
+
+    /// Installs a transform that every configuration goes through right after
+    /// it is parsed, e.g. to normalize the payloads of different servers into
+    /// one canonical shape: lowercase keys, defaults for missing settings.
+    ///
+    /// Everything after parsing sees the transformed configuration:
+    /// [`version_key`](Self::version_key) and
+    /// [`skip_unchanged`](Self::skip_unchanged), so a change that the transform
+    /// normalizes away does not reach the handler, merged updates, the cache
+    /// and the handler itself. [`ConfigEvent::raw`] still holds the payload as
+    /// received. The transform runs on the listener task and should return
+    /// quickly. Cannot be used with raw payloads.
+    ///
+    /// The transform is an `Fn`, not an `FnMut`: the client is `Clone`, and
+    /// all clones, and so all listeners and decoders started from them, share
+    /// the one transform and may call it concurrently. A transform that needs
+    /// state has to keep it behind a lock or in an atomic.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{ServerConfig, SseClientBuilder};
+    ///
+    /// # fn main() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/sse")
+    ///     .transform(|config: ServerConfig| ServerConfig {
+    ///         settings: config.settings.into_iter().map(|(key, value)| (key.to_lowercase(), value)).collect(),
+    ///     })
+    ///     .skip_unchanged(Vec::<String>::new())
+    ///     .build()?;
+    /// let mut decoder = client.decoder();
+    ///
+    /// let decoded = decoder.feed(b"data: {\"Level\": 3}\n\n");
+    /// assert_eq!(decoded[0].as_ref().unwrap().settings["level"], 3);
+    /// // Only the case of the key differs, so nothing changed.
+    /// assert!(decoder.feed(b"data: {\"LEVEL\": 3}\n\n").is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn transform(mut self, transform: impl Fn(ServerConfig) -> ServerConfig + Send + Sync + 'static) -> Self {
+        self.transform = Some(Arc::new(transform));
+        self
+    }
+
+    /// Flattens every configuration into dotted keys before passing it to the
+    /// handler, as [`ServerConfig::flatten_with`] does with `options`, for
+    /// applications that expect a flat key-value map.
+    ///
+    /// Only what the handler receives is flattened: filters such as
+    /// [`skip_unchanged`](Self::skip_unchanged), merged updates and the cache
+    /// still see the configuration as the server sent it. Cannot be used with
+    /// raw payloads.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{FlattenOptions, SseClientBuilder};
+    ///
+    /// # fn main() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/sse").flatten_keys(FlattenOptions::new()).build()?;
+    /// let mut decoder = client.decoder();
+    ///
+    /// let decoded = decoder.feed(b"data: {\"db\": {\"hosts\": [\"a\", \"b\"]}}\n\n");
+    /// let config = decoded[0].as_ref().unwrap();
+    /// assert_eq!(config.settings["db.hosts.1"], "b");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn flatten_keys(mut self, options: FlattenOptions) -> Self {
+        self.flatten_keys = Some(options);
+        self
+    }
+
+    /// Treats every event as a partial update that is merged into the
+    /// configuration accumulated so far, instead of a full replacement.
+    ///
+    /// The handler receives the accumulated configuration after each merge; see
+    /// [`ServerConfig::merge`] for how `policy` resolves keys present in both.
+    /// The accumulated configuration survives reconnects, and a
+    /// [cached](Self::cache_file) configuration is used as its starting point.
+    /// Combine with [`null_values`](Self::null_values) to let the server remove
+    /// keys. By default every event replaces the previous configuration.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{ArrayMerge, MergePolicy, NullValues, SseClientBuilder};
+    ///
+    /// let builder = SseClientBuilder::new("http://example.com/sse")
+    ///     .merge_updates(MergePolicy::DeepMerge(ArrayMerge::Replace))
+    ///     .null_values(NullValues::Delete);
+    /// ```
+    pub fn merge_updates(mut self, policy: MergePolicy) -> Self {
+        self.merge_updates = Some(policy);
+        self
+    }
+
+    /// Decides whether a `null` value sent by the server deletes a key or is
+    /// kept as a value.
+    ///
+    /// With [`NullValues::Delete`] and [merged updates](Self::merge_updates), a
+    /// `null` removes the key from the accumulated configuration, following the
+    /// rules of [`ServerConfig::merge_with`]. When every event replaces the
+    /// previous configuration, top-level keys with a `null` value are removed
+    /// from it before it is dispatched. Defaults to [`NullValues::Literal`], for
+    /// servers that send meaningful nulls.
+    pub fn null_values(mut self, nulls: NullValues) -> Self {
+        self.null_values = nulls;
+        self
+    }
+
+    /// Completes every configuration with `defaults` before it is passed to
+    /// the handler, for servers that only send the settings that differ from
+    /// them.
+    ///
+    /// The configuration is deep-merged over the defaults, as by
+    /// [`ServerConfig::merge_with`] with arrays replacing arrays: received
+    /// settings win, and settings the server leaves out, at any level of
+    /// nesting, fall back to their default. With
+    /// [merged updates](Self::merge_updates), the defaults go under the
+    /// accumulated configuration rather than under each update. A `null` the
+    /// server sends replaces the default, or removes it with
+    /// [`NullValues::Delete`].
+    ///
+    /// The handler, the cache, the change history and the dry run's diffs see
+    /// the complete configuration, so a setting the server stops sending shows
+    /// up as a change back to its default. The filters run on the
+    /// configuration as received. Cannot be used with raw payloads.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{ServerConfig, SseClientBuilder};
+    ///
+    /// # fn main() -> Result<(), config_sdk::ConfigError> {
+    /// let defaults = ServerConfig::builder().set("level", 1).set("mode", "safe").build();
+    /// let client = SseClientBuilder::new("http://example.com/sse").defaults(defaults).build()?;
+    /// let mut decoder = client.decoder();
+    ///
+    /// let decoded = decoder.feed(b"data: {\"level\": 3}\n\n");
+    /// let config = decoded[0].as_ref().unwrap();
+    /// assert_eq!(config.settings["level"], 3);
+    /// assert_eq!(config.settings["mode"], "safe");
+    ///
+    /// // The server no longer overrides the level.
+    /// let decoded = decoder.feed(b"data: {}\n\n");
+    /// assert_eq!(decoded[0].as_ref().unwrap().settings["level"], 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn defaults(mut self, defaults: ServerConfig) -> Self {
+        self.defaults = Some(defaults);
+        self
+    }
+
+    /// Persists the last configuration received to `path`.
+    ///
+    /// Every configuration passed to the handler is written to the file, which is
+    /// replaced atomically. When a listener starts and the file holds a valid
+    /// configuration, that configuration is passed to the handler before the
+    /// first connection attempt, so a restarted service has a configuration even
+    /// while the server is unreachable. A cache file that cannot be read or
+    /// parsed, e.g. one written by an incompatible version, is logged and treated
+    /// as missing.
+    ///
+    /// To keep the configuration somewhere else than in a file, use
+    /// [`cache_store`](Self::cache_store) instead.
+    pub fn cache_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.cache_file = Some(path.into());
+        self
+    }
+
+    /// Persists the last configuration received to `store`, like
+    /// [`cache_file`](Self::cache_file) does to a file.
+    ///
+    /// The store is loaded when a listener starts, saved after every
+    /// configuration passed to the handler and cleared when the server removes
+    /// the configuration; see [`ConfigStore`] for an example. Cannot be
+    /// combined with `cache_file`.
+    pub fn cache_store(mut self, store: impl ConfigStore + 'static) -> Self {
+        self.cache_store = Some(Arc::new(store));
+        self
+    }
+
+    /// Gzips the cache file set with [`cache_file`](Self::cache_file).
+    ///
+    /// Compressed and uncompressed cache files are told apart by their content,
+    /// so switching this option does not invalidate an existing cache. A `.json.gz`
+    /// extension is conventional but not required. Requires the `compression`
+    /// feature.
+    #[cfg(feature = "compression")]
+    pub fn compress_cache(mut self, compress: bool) -> Self {
+        self.compress_cache = compress;
+        self
+    }
+
+    /// Sends `token` as a bearer token in the `Authorization` header of every
+    /// connection attempt.
+    pub fn bearer_token(mut self, token: impl Into<String>) -> Self {
+        self.bearer_token = Some(token.into());
+        self
+    }
+
+    /// Sets the HTTP method of the subscription request. Defaults to `GET`.
+    ///
+    /// Servers with a subscription-style API usually expect a `POST` carrying a
+    /// [`request_body`](Self::request_body) that selects what to stream.
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Sends `body` with every connection attempt, including reconnects, with
+    /// `content_type` as its `Content-Type` header.
+    ///
+    /// The body is also sent again when a `307` or `308` redirect is followed.
+    /// A `303` redirect, or a `301` or `302` redirect of a `POST`, is followed
+    /// with a `GET` without a body, as browsers do.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use config_sdk::{Method, SseClientBuilder};
+    ///
+    /// # fn run() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/subscribe")
+    ///     .method(Method::POST)
+    ///     .request_body("application/json", r#"{"namespaces": ["billing", "search"]}"#)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn request_body(mut self, content_type: impl Into<String>, body: impl Into<Vec<u8>>) -> Self {
+        self.request_body = Some(RequestBody { content_type: content_type.into(), content: body.into() });
+        self
+    }
+
+    /// Decides how the listener reacts to the HTTP status of each connection
+    /// attempt.
+    ///
+    /// The policy maps every status to a [`StatusAction`]: connect and read the
+    /// stream, retry with backoff, retry after the short
+    /// [`retry_soon_delay`](Self::retry_soon_delay), or give up with
+    /// `ConfigError::HttpStatus`. The default, [`default_status_policy`](crate::default_status_policy),
+    /// connects on any `2xx` status, gives up on a `4xx` status other than
+    /// `408` and `429`, and retries with backoff otherwise. To only choose which
+    /// statuses are retried, see [`retry_on_status`](Self::retry_on_status).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{SseClientBuilder, StatusAction};
+    /// use reqwest::StatusCode;
+    ///
+    /// let builder = SseClientBuilder::new("http://example.com/sse").status_policy(|status| match status {
+    ///     // The gateway answers 204 when no configuration is available yet.
+    ///     StatusCode::NO_CONTENT => StatusAction::RetrySoon,
+    ///     status if status.is_success() => StatusAction::Connect,
+    ///     StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => StatusAction::Fatal,
+    ///     _ => StatusAction::Retry,
+    /// });
+    /// ```
+    pub fn status_policy(mut self, policy: impl Fn(StatusCode) -> StatusAction + Send + Sync + 'static) -> Self {
+        self.status_policy = Arc::new(policy);
+        self
+    }
+
+    /// Decides which non-`2xx` statuses are retried with backoff; all others
+    /// stop the listener with `ConfigError::HttpStatus`.
+    ///
+    /// This is a shorthand for a [`status_policy`](Self::status_policy) that
+    /// connects on any `2xx` status, and replaces any policy set before. The
+    /// default, [`default_retry_on_status`](crate::default_retry_on_status),
+    /// retries everything but a `4xx` status other than `408` and `429`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::SseClientBuilder;
+    /// use reqwest::StatusCode;
+    ///
+    /// // Only retry gateway errors; anything else is a problem on our side.
+    /// let builder = SseClientBuilder::new("http://example.com/sse").retry_on_status(|status| {
+    ///     matches!(status, StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT)
+    /// });
+    /// ```
+    pub fn retry_on_status(self, retry: impl Fn(StatusCode) -> bool + Send + Sync + 'static) -> Self {
+        self.status_policy(move |status| {
+            if status.is_success() {
+                StatusAction::Connect
+            } else if retry(status) {
+                StatusAction::Retry
+            } else {
+                StatusAction::Fatal
+            }
+        })
+    }
+
+    /// Sets the delay used instead of the backoff delay when the status policy
+    /// returns [`StatusAction::RetrySoon`]. The attempt still counts towards
+    /// [`max_retries`](Self::max_retries). Defaults to
+    /// [`DEFAULT_RETRY_SOON_DELAY`].
+    pub fn retry_soon_delay(mut self, delay: Duration) -> Self {
+        self.retry_soon_delay = delay;
+        self
+    }
+
+    /// Sets the `Content-Type`s that count as an event stream, replacing the
+    /// default of `text/event-stream` and the types of the
+    /// [payload formats](PayloadFormat::from_content_type) the client can
+    /// parse, such as `application/json` or `application/yaml`.
+    ///
+    /// Each entry is a prefix of the header's value, compared ignoring case,
+    /// so `text/event-stream` also accepts `text/event-stream; charset=utf-8`
+    /// and `application/vnd.acme` accepts every vendor type of that family.
+    /// A response whose `Content-Type` matches none of them is handled
+    /// according to the [`content_type_policy`](Self::content_type_policy). A
+    /// response without a `Content-Type` is not checked, and neither are
+    /// streams opened by transports other than HTTP, such as gRPC. At least one
+    /// type has to be accepted.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{Framing, SseClientBuilder};
+    ///
+    /// let builder = SseClientBuilder::new("http://example.com/stream")
+    ///     .framing(Framing::Delimited(b'\n'))
+    ///     .accepted_content_types(["text/event-stream", "application/x-ndjson"]);
+    /// ```
+    pub fn accepted_content_types<I>(mut self, content_types: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.accepted_content_types = Some(content_types.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Sets what the listener does when the server answers with a
+    /// `Content-Type` that is not one of the
+    /// [accepted ones](Self::accepted_content_types), e.g. the HTML error page
+    /// of a proxy. Defaults to [`ContentTypePolicy::Warn`], which logs it and
+    /// reads the stream anyway; [`ContentTypePolicy::Reject`] stops the
+    /// listener with `ConfigError::UnexpectedContentType` instead, which is
+    /// also passed to the [`on_error`](Self::on_error) hook.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{ConfigError, ContentTypePolicy, SseClientBuilder};
+    ///
+    /// let client = SseClientBuilder::new("http://example.com/sse")
+    ///     .content_type_policy(ContentTypePolicy::Reject)
+    ///     .on_error(|e: &ConfigError| {
+    ///         if let ConfigError::UnexpectedContentType(content_type) = e {
+    ///             eprintln!("not an event stream: {}", content_type);
+    ///         }
+    ///     })
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn content_type_policy(mut self, policy: ContentTypePolicy) -> Self {
+        self.content_type_policy = policy;
+        self
+    }
+
+    /// Sets the delay after the first failed connection attempt. Defaults to
+    /// [`DEFAULT_INITIAL_DELAY`](crate::DEFAULT_INITIAL_DELAY).
+    ///
+    /// After the failed attempt number `n` the listener waits
+    /// `initial_delay * factor^(n - 1)`, clamped to the
+    /// [maximum delay](Self::max_delay), where the factor is set through
+    /// [`backoff_factor`](Self::backoff_factor). With an initial delay of 500ms
+    /// and a factor of 1.5 the delays are 500ms, 750ms, 1.125s, 1.6875s, ...; with
+    /// the defaults they are 2s, 4s, 8s, ...
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::SseClientBuilder;
+    /// use std::time::Duration;
+    ///
+    /// let client = SseClientBuilder::new("http://example.com/sse")
+    ///     .initial_delay(Duration::from_millis(500))
+    ///     .backoff_factor(1.5)
+    ///     .max_delay(Duration::from_secs(30))
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn initial_delay(mut self, delay: Duration) -> Self {
+        self.backoff.initial_delay = delay;
+        self
+    }
+
+    /// Sets the factor by which the backoff delay grows after every failed
+    /// attempt; see [`initial_delay`](Self::initial_delay). Must be a finite
+    /// number of at least 1. Defaults to
+    /// [`DEFAULT_BACKOFF_FACTOR`](crate::DEFAULT_BACKOFF_FACTOR).
+    pub fn backoff_factor(mut self, factor: f64) -> Self {
+        self.backoff.factor = factor;
+        self
+    }
+
+    /// Caps the backoff delay at `max_delay`. By default the delay is not
+    /// capped.
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.backoff.max_delay = Some(max_delay);
+        self
+    }
+
+    /// Only treats a connection as successful once it has stayed up for `window`.
+    ///
+    /// When a connection that lasted at least `window` has to be re-established,
+    /// the listener reconnects immediately and the attempt counter starts over,
+    /// so backoff delays and [`max_retries`](Self::max_retries) apply afresh. A
+    /// connection that drops sooner counts as a failed attempt and backoff keeps
+    /// escalating, which keeps a server that accepts connections and then drops
+    /// them from causing a tight reconnect loop. Without a stability window the
+    /// attempt counter never resets.
+    pub fn stability_window(mut self, window: Duration) -> Self {
+        self.stability_window = Some(window);
+        self
+    }
+
+    /// Adds a period during which the server is expected to be down, such as
+    /// a nightly restart. Can be called more than once.
+    ///
+    /// While a window is on, a failed connection attempt is retried after the
+    /// [maintenance retry interval](Self::maintenance_retry_interval) instead
+    /// of an escalating backoff delay, and the listener does not give up:
+    /// attempts made during the window count toward neither
+    /// [`max_retries`](Self::max_retries) nor the
+    /// [retry time budget](Self::max_total_retry_duration). Once the window is
+    /// over, the next failure starts a fresh backoff sequence with both limits
+    /// applying afresh. Connections that succeed during a window are used as
+    /// usual.
+    ///
+    /// Windows are evaluated against the wall-clock time of the client's
+    /// [`clock`](Self::clock), see [`Clock::system_time`].
+    ///
+    /// # Example
+    ///
+    /// A clock whose wall-clock time starts at 02:00 UTC and advances with
+    /// every delay, and a daily window from 02:00 to 02:05:
+    ///
+    /// ```
+    /// use config_sdk::{Clock, ConfigError, MaintenanceWindow, ServerConfig, SseClientBuilder};
+    /// use futures::future::BoxFuture;
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    /// use tokio::time::Instant;
+    ///
+    /// #[derive(Debug, Clone)]
+    /// struct SimulatedClock {
+    ///     wall: Arc<Mutex<SystemTime>>,
+    ///     delays: Arc<Mutex<Vec<Duration>>>,
+    /// }
+    ///
+    /// impl Clock for SimulatedClock {
+    ///     fn now(&self) -> Instant {
+    ///         Instant::now()
+    ///     }
+    ///
+    ///     fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+    ///         *self.wall.lock().unwrap() += duration;
+    ///         self.delays.lock().unwrap().push(duration);
+    ///         Box::pin(async {})
+    ///     }
+    ///
+    ///     fn system_time(&self) -> SystemTime {
+    ///         *self.wall.lock().unwrap()
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let clock = SimulatedClock {
+    ///     wall: Arc::new(Mutex::new(UNIX_EPOCH + Duration::from_secs(2 * 3600))),
+    ///     delays: Arc::default(),
+    /// };
+    /// let minute = Duration::from_secs(60);
+    /// // Nothing listens on this port, so every attempt fails straight away.
+    /// let result = SseClientBuilder::new("http://127.0.0.1:9/sse")
+    ///     .max_retries(2)
+    ///     .maintenance_window(MaintenanceWindow::daily(2 * 60 * minute, 5 * minute))
+    ///     .maintenance_retry_interval(minute)
+    ///     .clock(clock.clone())
+    ///     .build()
+    ///     .unwrap()
+    ///     .listen(|_: ServerConfig| {})
+    ///     .await;
+    ///
+    /// // Five attempts a minute apart during the window, then two more with backoff.
+    /// assert!(matches!(result, Err(ConfigError::GaveUp { total_attempts: 7, .. })));
+    /// assert_eq!(*clock.delays.lock().unwrap(), [minute, minute, minute, minute, minute, Duration::from_secs(2)]);
+    /// # }
+    /// ```
+    pub fn maintenance_window(mut self, window: MaintenanceWindow) -> Self {
+        self.maintenance_windows.push(window);
+        self
+    }
+
+    /// Sets the delay between connection attempts during a
+    /// [maintenance window](Self::maintenance_window). Must be greater than
+    /// zero. Defaults to
+    /// [`DEFAULT_MAINTENANCE_RETRY_INTERVAL`](crate::DEFAULT_MAINTENANCE_RETRY_INTERVAL).
+    pub fn maintenance_retry_interval(mut self, interval: Duration) -> Self {
+        self.maintenance_retry_interval = interval;
+        self
+    }
+
+    /// Drops the connection and reconnects when no data, not even a comment,
+    /// has been received for `timeout`.
+    ///
+    /// This detects connections that stay open but have silently stopped
+    /// delivering events. Without this option, a server can announce its
+    /// keep-alive interval with a comment such as `: heartbeat-interval=15`
+    /// (in seconds), and the listener then uses three times that interval as
+    /// the idle timeout for the rest of the connection; a timeout set here
+    /// always takes precedence over the server's hint. Without either, idle
+    /// connections are kept open indefinitely. Every idle timeout is reported to
+    /// the [error hook](Self::on_error) as [`TimeoutKind::Idle`](crate::TimeoutKind::Idle).
+    ///
+    /// The listener reconnects with the ID of the last complete event as
+    /// `Last-Event-ID`, so an event the connection was dropped in the middle
+    /// of is sent again.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Closes pooled keep-alive connections that have been idle for
+    /// `timeout`, instead of reusing them for the next request.
+    ///
+    /// Reconnects and snapshots reuse idle HTTP/1.1 connections. A server or
+    /// proxy that closes idle connections sooner than the client does leaves
+    /// half-closed connections in the pool, and the request sent on one fails
+    /// at once. The listener recognizes such failures and retries the request
+    /// once on a fresh connection, unless it is a `POST`, which may have
+    /// reached the server and is retried with backoff like any failed
+    /// connection. It also reconnects on a fresh connection when a stream is
+    /// closed before it delivered any data, whatever the
+    /// [error policy](Self::error_policy). Setting the timeout below the
+    /// server's keeps those failures from happening in the first place; a
+    /// timeout of zero disables connection reuse altogether. Defaults to
+    /// `reqwest`'s 90 seconds.
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Closes every connection once it has been open for `lifetime` and
+    /// reconnects, so that long-lived connections do not stay pinned to one
+    /// server behind a load balancer.
+    ///
+    /// Recycling a connection is not a failure: the listener reconnects
+    /// immediately, without backoff, and sends the ID of the last event as
+    /// `Last-Event-ID` so the server can resume the stream where it left off.
+    /// With [failover URLs](Self::failover_url) the endpoint for the new
+    /// connection is picked again by weight, so it may be a different one.
+    /// Disabled by default.
+    pub fn max_connection_lifetime(mut self, lifetime: Duration) -> Self {
+        self.max_connection_lifetime = Some(lifetime);
+        self
+    }
+
+    /// Stops the listener with `ConfigError::NoConfigReceived` if no
+    /// configuration has been passed to the handler within `deadline` of the
+    /// first successful connection.
+    ///
+    /// Unlike the [idle timeout](Self::idle_timeout), this only applies until
+    /// the first configuration, cached or received, and keeps running across
+    /// reconnects. A server that accepts the connection but never sends a
+    /// configuration then fails startup instead of leaving the service waiting
+    /// forever: the error is passed to the [error hook](Self::on_error),
+    /// returned by the listener, and [`ListenerHandle::first_config`] resolves
+    /// to it. Disabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use config_sdk::{ServerConfig, SseClientBuilder};
+    /// use std::time::Duration;
+    ///
+    /// # async fn run() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/sse")
+    ///     .first_config_deadline(Duration::from_secs(10))
+    ///     .build()?;
+    /// let handle = client.spawn(|config: ServerConfig| println!("{:?}", config));
+    ///
+    /// // Fails after ten seconds if the server stays silent.
+    /// handle.first_config().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn first_config_deadline(mut self, deadline: Duration) -> Self {
+        self.first_config_deadline = Some(deadline);
+        self
+    }
+
+    /// Decides what the listener does when reading the event stream fails,
+    /// e.g. because the connection was reset in the middle of an event.
+    ///
+    /// With the default, [`ErrorPolicy::Terminate`], the listener stops and
+    /// returns the error. With [`ErrorPolicy::Reconnect`] the error is logged
+    /// and passed to the [error hook](Self::on_error), and the listener
+    /// reconnects like after a failed connection attempt: with backoff, resuming
+    /// from the last event ID, and giving up once
+    /// [`max_retries`](Self::max_retries) is reached. Connection failures and
+    /// statuses are handled by the [status policy](Self::status_policy)
+    /// whatever the error policy.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{ErrorPolicy, SseClientBuilder};
+    ///
+    /// let builder = SseClientBuilder::new("http://example.com/sse").error_policy(ErrorPolicy::Reconnect);
+    /// ```
+    pub fn error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.error_policy = policy;
+        self
+    }
+
+    /// Decides whether a panic in the handler is contained.
+    ///
+    /// By default each handler invocation is wrapped in
+    /// `std::panic::catch_unwind`: a panic is logged, counted in
+    /// `MetricsSnapshot::handler_panics`, reported to the
+    /// [error hook](Self::on_error) as `ConfigError::HandlerPanicked`, and the
+    /// listener carries on with the next event. Pass `false` to let the panic
+    /// unwind through the listener instead, which ends it (for a spawned
+    /// listener, [`ListenerHandle::join`] then returns an error).
+    pub fn catch_handler_panics(mut self, catch: bool) -> Self {
+        self.catch_handler_panics = catch;
+        self
+    }
+
+    /// Logs a warning for every handler invocation that takes longer than
+    /// `threshold`.
+    ///
+    /// The time of every invocation is recorded in
+    /// `MetricsSnapshot::handler_latency` regardless of this option; the
+    /// warning helps to tell a slow handler apart from a slow network as it
+    /// happens. By default no warning is logged.
+    pub fn slow_handler_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_handler_threshold = Some(threshold);
+        self
+    }
+
+    /// Runs the handler on a thread of tokio's blocking pool instead of
+    /// blocking a runtime worker thread, for handlers that do CPU-heavy work
+    /// such as recompiling a routing table.
+    ///
+    /// Handlers borrow the event from the listener's read buffer, so rather
+    /// than moving each call to `spawn_blocking`, the listener calls it through
+    /// `tokio::task::block_in_place`: the runtime hands the worker's other
+    /// tasks to a new thread while the handler runs, and the listener waits for
+    /// the handler to return. Configurations are therefore still handled one at
+    /// a time and in order, and nothing more is read from the network
+    /// meanwhile. To handle several at once, use
+    /// [`SseClient::spawn_concurrent`], whose workers always run on the
+    /// blocking pool.
+    ///
+    /// Only takes effect on a multi-threaded runtime; on a current-thread
+    /// runtime the handler keeps running inline. Off by default.
+    pub fn offload_handler(mut self, offload: bool) -> Self {
+        self.offload_handler = offload;
+        self
+    }
+
+    /// Sets how many log records each logging output buffers before
+    /// [`log_overflow`](Self::log_overflow) applies.
+    ///
+    /// The listener logs through `slog-async`, which writes records on a
+    /// background thread; a larger buffer absorbs longer bursts, such as a
+    /// reconnect storm. Defaults to
+    /// [`DEFAULT_LOG_CHANNEL_CAPACITY`](crate::DEFAULT_LOG_CHANNEL_CAPACITY).
+    pub fn log_channel_capacity(mut self, capacity: usize) -> Self {
+        self.log_settings.channel_capacity = capacity;
+        self
+    }
+
+    /// Decides what happens to a log record when the logging buffer is full.
+    ///
+    /// With [`LogOverflow::Drop`], the default, the record is discarded and
+    /// counted in `MetricsSnapshot::log_messages_dropped`. With
+    /// [`LogOverflow::Block`] the listener waits for room in the buffer, which
+    /// loses nothing but can stall event processing while the outputs catch up.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{LogOverflow, SseClientBuilder};
+    ///
+    /// let builder = SseClientBuilder::new("http://example.com/sse")
+    ///     .log_channel_capacity(4096)
+    ///     .log_overflow(LogOverflow::Block);
+    /// ```
+    pub fn log_overflow(mut self, overflow: LogOverflow) -> Self {
+        self.log_settings.overflow = overflow;
+        self
+    }
+
+    /// Chooses where the listener writes its log records: to the terminal,
+    /// as JSON to `stdout`, or both.
+    ///
+    /// Without this option the format is [detected](LogFormat::detect): JSON
+    /// only when running in a container, so log collectors do not see every
+    /// record twice, and both outputs otherwise.
+    pub fn log_format(mut self, format: LogFormat) -> Self {
+        self.log_settings.format = Some(format);
+        self
+    }
+
+    /// Sets how many configurations [`SseClient::spawn_channel`] buffers for a
+    /// receiver that has fallen behind.
+    ///
+    /// Once the channel is full the listener stops reading from the network
+    /// until the receiver catches up, so a slow consumer holds back the stream
+    /// instead of letting it queue up in memory. Besides the channel, at most the
+    /// configurations completed by a single network read are held. Must be at
+    /// least 1. Defaults to [`DEFAULT_EVENT_CHANNEL_CAPACITY`].
+    pub fn event_channel_capacity(mut self, capacity: usize) -> Self {
+        self.event_channel_capacity = capacity;
+        self
+    }
+
+    /// Sets how many configurations a broadcast started with
+    /// [`SseClient::spawn_broadcast`] keeps for subscribers that have fallen
+    /// behind.
+    ///
+    /// Unlike [`event_channel_capacity`](Self::event_channel_capacity), a full
+    /// broadcast does not hold back the listener: the oldest configuration is
+    /// dropped instead, and subscribers that had not received it yet are told
+    /// that they [lagged](crate::BroadcastEvent::Lagged). A larger capacity
+    /// lets subscribers fall further behind before they miss configurations,
+    /// at the cost of keeping that many in memory. Must be at least 1.
+    /// Defaults to [`DEFAULT_BROADCAST_CAPACITY`].
+    pub fn broadcast_capacity(mut self, capacity: usize) -> Self {
+        self.broadcast_capacity = capacity;
+        self
+    }
+
+    /// Sets how many handler calls [`SseClient::spawn_concurrent`] runs at once.
+    ///
+    /// Configurations are spread over this many workers by the value of the
+    /// [ordering key](Self::ordering_key), so configurations with the same
+    /// value are always handled one at a time, in the order they arrived. Each
+    /// worker buffers up to [`event_channel_capacity`](Self::event_channel_capacity)
+    /// configurations before the listener stops reading from the network. Other
+    /// ways of listening call their handler sequentially. Must be at least 1.
+    /// Defaults to [`DEFAULT_CONCURRENCY`].
+    pub fn concurrency(mut self, workers: usize) -> Self {
+        self.concurrency = workers;
+        self
+    }
+
+    /// Names the top-level setting that identifies what a configuration is
+    /// about, for [`concurrency`](Self::concurrency).
+    ///
+    /// Configurations with equal values of this setting are handled in order,
+    /// while those with different values may be handled in parallel.
+    /// Configurations without the setting are all handled in order, so without
+    /// an ordering key nothing runs in parallel.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use config_sdk::{ServerConfig, SseClientBuilder};
+    ///
+    /// # async fn run() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/sse")
+    ///     .concurrency(8)
+    ///     .ordering_key("tenant")
+    ///     .build()?;
+    ///
+    /// // Updates for one tenant are applied in order, different tenants in parallel.
+    /// client.spawn_concurrent(|config: ServerConfig| println!("{:?}", config));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ordering_key(mut self, key: impl Into<String>) -> Self {
+        self.ordering_key = Some(key.into());
+        self
+    }
+
+    /// Validates the stream without applying it.
+    ///
+    /// In dry-run mode every event is parsed and compared with the previous
+    /// valid configuration, and the outcome is logged together with running
+    /// counts of valid, invalid and changed events, but the handler is never
+    /// called. A cached configuration is used as the starting point for the
+    /// comparison and the cache is not updated. Because no configuration is
+    /// dispatched, [`ListenerHandle::first_config`] never resolves for a
+    /// dry-run listener.
+    ///
+    /// This makes it safe to point a client at production data to check a new
+    /// configuration schema before rolling it out.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Matches the `data`, `event`, `id` and `retry` field names ignoring ASCII
+    /// case.
+    ///
+    /// The SSE specification makes field names case-sensitive, so a `Data:` line
+    /// is an unknown field and is ignored. This option is a workaround for
+    /// servers that do not follow the specification and should only be enabled
+    /// for such servers. Off by default.
+    pub fn lenient_field_case(mut self, lenient: bool) -> Self {
+        self.lenient_field_case = lenient;
+        self
+    }
+
+    /// Sets how the events of the stream are delimited, for servers that
+    /// stream configurations in a format close to SSE but not quite it.
+    /// Defaults to [`Framing::Sse`].
+    ///
+    /// With any other framing, every payload split off is handled like the
+    /// `data` field of an event of its own, going through the same parsing,
+    /// filters and dispatch. Such streams have no event IDs, event types,
+    /// retry intervals or comments, so the options relying on them, such as
+    /// [`removal_event`](Self::removal_event) or
+    /// [`skip_seen_ids`](Self::skip_seen_ids), have no effect. The
+    /// [maximum line length](Self::max_line_length) limits the length of a
+    /// payload instead, and [`flush_on_eof`](Self::flush_on_eof) completes a
+    /// delimited payload left without its separator.
+    ///
+    /// # Example
+    ///
+    /// A JSON text sequence (RFC 7464), with every payload preceded by an
+    /// ASCII record separator and followed by a line feed:
+    ///
+    /// ```
+    /// use config_sdk::{Framing, SseClientBuilder};
+    ///
+    /// # fn main() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/stream").framing(Framing::Delimited(0x1e)).build()?;
+    /// let mut decoder = client.decoder();
+    ///
+    /// let decoded = decoder.feed(b"\x1e{\"level\": 1}\n\x1e{\"level\"");
+    /// assert_eq!(decoded.len(), 1);
+    /// let decoded = decoder.feed(b": 2}\n");
+    /// assert_eq!(decoded[0].as_ref().unwrap().settings["level"], 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn framing(mut self, framing: Framing) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    /// Checks that the stream is valid UTF-8 and reports where it is not.
+    ///
+    /// By default bytes are passed on as they arrive: a character split across
+    /// network reads is put back together, and invalid bytes only surface as a
+    /// failure to parse the configuration they belong to, or are replaced in an
+    /// `id` or `event` field. In strict mode every invalid sequence is also
+    /// logged and passed to the [error hook](Self::on_error) as
+    /// `ConfigError::InvalidUtf8`, with its byte offset in the stream and the
+    /// bytes around it, which helps to find where a server corrupts the stream.
+    /// Only the first invalid sequence of each network read is reported. The
+    /// offset counts from the start of the current connection. Off by default.
+    pub fn strict_utf8(mut self, strict: bool) -> Self {
+        self.strict_utf8 = strict;
+        self
+    }
+
+    /// Dispatches the last event of a stream that the server closes without
+    /// terminating it.
+    ///
+    /// The SSE specification discards an event that is not followed by a blank
+    /// line, and a `data` line without a line terminator is not even complete,
+    /// so a configuration sent right before a clean disconnect is lost. Some
+    /// servers rely on the end of the stream to terminate their last event;
+    /// with this option, the listener treats the end of the stream as the
+    /// missing terminator and blank line and dispatches what was buffered
+    /// before it stops. A connection that fails with a read error is not
+    /// flushed. Off by default, following the specification.
+    pub fn flush_on_eof(mut self, flush: bool) -> Self {
+        self.flush_on_eof = flush;
+        self
+    }
+
+    /// Ignores events whose `data` lines are empty or hold only whitespace,
+    /// which some servers send as a ping.
+    ///
+    /// Such an event is valid SSE but holds no configuration, so by default it
+    /// is skipped without calling the handler, counting a parse error or
+    /// reaching the [error hook](Self::on_error). When disabled, it is parsed
+    /// like any other payload and fails as invalid JSON, which is useful to
+    /// catch a server that sends empty configurations by mistake. On by
+    /// default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::SseClientBuilder;
+    ///
+    /// # fn main() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/sse").build()?;
+    /// let mut decoder = client.decoder();
+    ///
+    /// assert!(decoder.feed(b"data:\n\ndata:   \n\n").is_empty());
+    /// assert_eq!(client.metrics().snapshot().parse_errors, 0);
+    ///
+    /// let strict = SseClientBuilder::new("http://example.com/sse").ignore_empty_data(false).build()?;
+    /// assert!(strict.decoder().feed(b"data:\n\n")[0].is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ignore_empty_data(mut self, ignore: bool) -> Self {
+        self.ignore_empty_data = ignore;
+        self
+    }
+
+    /// Decodes `data` lines that span several network reads while they arrive,
+    /// instead of collecting each line before parsing it.
+    ///
+    /// Only the setting being read is buffered, so a multi-megabyte
+    /// configuration is parsed with memory bounded by its largest single setting
+    /// rather than by its size, at the cost of slower parsing; the `incremental`
+    /// benchmark compares both. The payload of such a line is not kept: the
+    /// `raw` bytes of its [`ConfigEvent`] are empty. Lines that arrive in a
+    /// single read are parsed as usual. Payloads are accepted or rejected
+    /// exactly as without this option. Off by default, and cannot be combined
+    /// with [`decrypt_fn`](Self::decrypt_fn), which needs the whole payload.
+    pub fn incremental_parsing(mut self, incremental: bool) -> Self {
+        self.incremental_parsing = incremental;
+        self
+    }
+
+    /// Installs a hook that receives the errors the listener recovers from.
+    ///
+    /// These are errors that do not stop the listener and are otherwise only
+    /// logged: payloads that fail to parse or exceed the
+    /// [maximum event size](Self::max_event_size), handler panics, cache read
+    /// or write failures, failed [acknowledgments](Self::ack_url), stream read errors
+    /// under [`ErrorPolicy::Reconnect`], and connect and idle timeouts,
+    /// reported as [`ConfigError::Timeout`]. Errors that end the listener are
+    /// returned from [`SseClient::listen`] instead, except that
+    /// `ConfigError::GaveUp` is also passed to the hook once the
+    /// [retries](Self::max_retries) are used up, as are
+    /// `ConfigError::InsecureConnection` and a rejected
+    /// [content type](Self::content_type_policy). The hook runs on the listener
+    /// task and should return quickly.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{ConfigError, SseClientBuilder};
+    ///
+    /// let builder = SseClientBuilder::new("http://example.com/sse").on_error(|error: &ConfigError| {
+    ///     eprintln!("config stream problem: {}", error);
+    /// });
+    /// ```
+    pub fn on_error(mut self, hook: impl Fn(&ConfigError) + Send + Sync + 'static) -> Self {
+        self.error_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Sets how long the listener may go without a current configuration
+    /// before it is considered stale, see [`on_stale`](Self::on_stale).
+    ///
+    /// The configuration is current while the listener is connected, since
+    /// the server pushes every change, and whenever a configuration arrives,
+    /// even one that is skipped as unchanged. The threshold is counted from
+    /// the later of the two, or from the start of the listener if it has not
+    /// been connected yet, so it measures how long the listener has been
+    /// disconnected and retrying, including while a connection attempt is
+    /// still pending. Must be greater than zero.
+    pub fn stale_after(mut self, threshold: Duration) -> Self {
+        self.stale_after = Some(threshold);
+        self
+    }
+
+    /// Installs a hook that is called when the configuration turns stale, with
+    /// how long it has gone without being confirmed current, for services that
+    /// switch to a degraded or safe mode rather than act on configuration that
+    /// may be out of date.
+    ///
+    /// The hook is called once the [`stale_after`](Self::stale_after)
+    /// threshold has passed, which is required, and not again until the
+    /// configuration has been current in between, as reported to
+    /// [`on_fresh`](Self::on_fresh). A listener that gives up does not turn
+    /// stale after it stopped. The hook runs on the listener task and should
+    /// return quickly.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::SseClientBuilder;
+    /// use std::time::Duration;
+    ///
+    /// let client = SseClientBuilder::new("http://example.com/sse")
+    ///     .stale_after(Duration::from_secs(30))
+    ///     .on_stale(|unconfirmed| eprintln!("configuration unconfirmed for {:?}, switching to safe mode", unconfirmed))
+    ///     .on_fresh(|_| eprintln!("configuration current again"))
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn on_stale(mut self, hook: impl Fn(Duration) + Send + Sync + 'static) -> Self {
+        self.stale_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Installs a hook that is called when a configuration arrives after the
+    /// configuration had turned stale, with how long it had gone without being
+    /// confirmed current. See [`on_stale`](Self::on_stale), which it undoes;
+    /// it also requires [`stale_after`](Self::stale_after).
+    pub fn on_fresh(mut self, hook: impl Fn(Duration) + Send + Sync + 'static) -> Self {
+        self.fresh_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Passes the current configuration to the handler again whenever
+    /// `interval` has passed without the handler being called, for handlers
+    /// that reconcile the application with the configuration and should do so
+    /// periodically even when nothing changed.
+    ///
+    /// The configuration passed again is the last one dispatched, with the
+    /// [`EventMeta`] it was dispatched with, except that its origin is
+    /// [`ConfigOrigin::Reassert`](crate::ConfigOrigin::Reassert), so handlers
+    /// can tell it from a genuine change. Nothing is passed again before the
+    /// first configuration has been dispatched, after the server
+    /// [removed](Self::on_config_removed) it until the next one is, or while
+    /// the listener is paused. It keeps being passed while the listener is disconnected and
+    /// retrying, but stops with the listener. Reassertions are not counted as
+    /// events, recorded in the change history or acknowledged. Must be greater
+    /// than zero.
+    pub fn reassert_interval(mut self, interval: Duration) -> Self {
+        self.reassert_interval = Some(interval);
+        self
+    }
+
+    /// Installs a hook that is called when the server removes the
+    /// configuration, e.g. because the tenant it belongs to was deleted.
+    ///
+    /// The hook is called in two cases:
+    ///
+    /// - The server sends an event of the [removal event](Self::removal_event)
+    ///   type. Its data, if any, is not parsed and no configuration is
+    ///   dispatched for it. Merged updates start over from an empty
+    ///   configuration, the [change filter](Self::skip_unchanged) forgets the
+    ///   last configuration, the [repeat filter](Self::skip_repeated_payloads)
+    ///   the last payload, and the [cache](Self::cache_file) is deleted. The
+    ///   removed configuration is no longer [reasserted](Self::reassert_interval),
+    ///   nor dispatched on [resuming](crate::ListenerHandle::resume) or
+    ///   [clearing a rollback](crate::ListenerHandle::clear_rollback).
+    /// - A configuration without any settings is dispatched after one with
+    ///   settings. The empty configuration is still passed to the handler
+    ///   first.
+    ///
+    /// The hook is not called in a [dry run](Self::dry_run). It runs on the
+    /// listener task and should return quickly.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::SseClientBuilder;
+    ///
+    /// let builder = SseClientBuilder::new("http://example.com/sse/tenant-42")
+    ///     .on_config_removed(|| println!("tenant removed, releasing its resources"));
+    /// ```
+    pub fn on_config_removed(mut self, hook: impl Fn() + Send + Sync + 'static) -> Self {
+        self.removal_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Sets the SSE event type with which the server signals that the
+    /// configuration was removed. Defaults to [`DEFAULT_REMOVAL_EVENT`], i.e.
+    /// the server sends:
+    ///
+    /// ```text
+    /// event: config-removed
+    /// data: {}
+    /// ```
+    ///
+    /// The event is recognized once the blank line that ends it arrives,
+    /// whatever the order of its fields, and its data, if any, is ignored. See
+    /// [`on_config_removed`](Self::on_config_removed).
+    pub fn removal_event(mut self, event_type: impl Into<String>) -> Self {
+        self.removal_event = event_type.into();
+        self
+    }
+
+    /// Decrypts the `data:` payload of every event with `decrypt` before it is
+    /// parsed.
+    ///
+    /// `decrypt` receives the payload as sent by the server, e.g. base64 of an
+    /// AES-GCM ciphertext, and returns the JSON configuration. The crate does not
+    /// implement any cryptography itself; bring the algorithm and key management
+    /// of your choice. An error returned by `decrypt` is handled like a payload
+    /// that fails to parse: it is logged, counted in
+    /// `MetricsSnapshot::parse_errors`, passed to the [error hook](Self::on_error),
+    /// and the listener carries on with the next event.
+    ///
+    /// [`ConfigEvent::raw`](crate::ConfigEvent::raw) still holds the payload as
+    /// received. Note that a [cache file](Self::cache_file) stores the decrypted
+    /// configuration.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{ConfigError, SseClientBuilder};
+    ///
+    /// fn decrypt(ciphertext: &[u8]) -> Result<Vec<u8>, ConfigError> {
+    ///     // A stand-in for a real cipher.
+    ///     Ok(ciphertext.iter().map(|b| b ^ 0x2a).collect())
+    /// }
+    ///
+    /// let builder = SseClientBuilder::new("http://example.com/sse").decrypt_fn(decrypt);
+    /// ```
+    pub fn decrypt_fn(mut self, decrypt: impl Fn(&[u8]) -> Result<Vec<u8>, ConfigError> + Send + Sync + 'static) -> Self {
+        self.decrypt = Some(Arc::new(decrypt));
+        self
+    }
+
+    /// Verifies every configuration against the checksum the server sends
+    /// in its setting named `key`, computed with `algorithm`.
+    ///
+    /// The checksum setting is removed from the configuration, and the
+    /// checksum is computed over the remaining settings in canonical form: a
+    /// JSON object without any whitespace, with the keys of every object,
+    /// nested ones included, sorted by their UTF-8 bytes, and strings and
+    /// numbers written as `serde_json` writes them. That is what Python's
+    /// `json.dumps(settings, sort_keys=True, separators=(",", ":"),
+    /// ensure_ascii=False)` produces for the same settings. The result is
+    /// compared, as lowercase or uppercase hexadecimal digits, with the string
+    /// in `key`.
+    /// A configuration whose checksum is missing or does not match is not
+    /// passed to the handler: it is logged, counted in
+    /// `MetricsSnapshot::parse_errors` and reported to the
+    /// [error hook](Self::on_error) as `ConfigError::ChecksumMismatch`.
+    ///
+    /// The checksum covers each configuration as sent, before it is
+    /// [merged](Self::merge_updates) with earlier ones, and after
+    /// [decryption](Self::decrypt_fn) and
+    /// [envelope extraction](Self::envelope_pointer). Requires the `checksum`
+    /// feature.
+    ///
+    /// # Example
+    ///
+    /// A server publishing `{"settings": {"level": 3}}` adds the SHA-256 of
+    /// `{"level":3}`, sending `{"settings": {"level": 3, "_sha256":
+    /// "c8d699b727ef627e5a749364246158a85fca1de82b2a7ce89bb177d2528b632a"}}`:
+    ///
+    /// ```
+    /// use config_sdk::{ChecksumAlgorithm, SseClientBuilder};
+    ///
+    /// let builder = SseClientBuilder::new("http://example.com/sse").checksum("_sha256", ChecksumAlgorithm::Sha256);
+    /// ```
+    #[cfg(feature = "checksum")]
+    pub fn checksum(mut self, key: impl Into<String>, algorithm: ChecksumAlgorithm) -> Self {
+        self.checksum = Some((key.into(), algorithm));
+        self
+    }
+
+    /// Sets the shape of the configurations the server sends. Defaults to
+    /// [`WireFormat::Auto`].
+    ///
+    /// By default a payload with a `settings` member is read as
+    /// `{"settings": {...}}` and any other object as the settings themselves;
+    /// see the [wire format](ServerConfig#wire-format) of `ServerConfig`. A
+    /// server sending flat configurations that may include a setting named
+    /// `settings` needs [`WireFormat::Flat`], while [`WireFormat::Enveloped`]
+    /// rejects payloads without a `settings` member instead of reading them as
+    /// flat. Applies to the listener and to
+    /// [`SseClient::fetch_snapshot`], and to the value extracted with
+    /// [`envelope_pointer`](Self::envelope_pointer).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{SseClientBuilder, WireFormat};
+    ///
+    /// # fn main() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/sse").wire_format(WireFormat::Flat).build()?;
+    /// let mut decoder = client.decoder();
+    ///
+    /// let decoded = decoder.feed(b"data: {\"level\": 3}\n\ndata: {\"settings\": {\"level\": 4}}\n\n");
+    /// assert_eq!(decoded[0].as_ref().unwrap().settings["level"], 3);
+    /// // Read as a setting named `settings`, not as an envelope.
+    /// assert_eq!(decoded[1].as_ref().unwrap().settings["settings"]["level"], 4);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn wire_format(mut self, format: WireFormat) -> Self {
+        self.wire_format = format;
+        self
+    }
+
+    /// Sets the encoding of the configurations in the `data` field of events,
+    /// instead of detecting it from the response's `Content-Type`.
+    ///
+    /// By default, the encoding is detected anew for every connection, see
+    /// [`PayloadFormat::from_content_type`]: a server that streams YAML can
+    /// answer with `Content-Type: text/event-stream; format=yaml`. When the
+    /// header does not name a format, as with a plain `text/event-stream`, or
+    /// names one whose feature is not enabled, configurations are parsed as
+    /// JSON. [Snapshots](SseClient::fetch_snapshot) are detected the same way
+    /// from their own `Content-Type`, such as `application/yaml`. Setting the
+    /// format here applies it to every connection and snapshot whatever the
+    /// server advertises.
+    ///
+    /// [`incremental_parsing`](Self::incremental_parsing) only parses JSON,
+    /// so with it the `Content-Type` is ignored. Cannot be used with raw
+    /// payloads or gRPC.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{PayloadFormat, SseClientBuilder};
+    ///
+    /// # #[cfg(feature = "yaml")]
+    /// # fn main() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/sse").payload_format(PayloadFormat::Yaml).build()?;
+    /// let mut decoder = client.decoder();
+    ///
+    /// let decoded = decoder.feed(b"data: settings: {level: 3}\n\n");
+    /// assert_eq!(decoded[0].as_ref().unwrap().settings["level"], 3);
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(feature = "yaml"))]
+    /// # fn main() {}
+    /// ```
+    pub fn payload_format(mut self, format: PayloadFormat) -> Self {
+        self.payload_format = Some(format);
+        self
+    }
+
+    /// Extracts the configuration from an envelope around it, at the JSON
+    /// pointer `pointer` (RFC 6901), before it is parsed.
+    ///
+    /// Some servers and API gateways wrap every configuration, e.g.
+    /// `{"type": "config", "payload": {"settings": {...}}, "meta": {...}}`. With
+    /// the pointer `/payload`, the value at that path is parsed as the
+    /// [`ServerConfig`] and the rest of the envelope is discarded, or passed to
+    /// the [envelope hook](Self::on_envelope). A payload without a value at
+    /// `pointer` is handled like one that fails to parse. Decryption, if any,
+    /// applies to the whole envelope. [`ConfigEvent::raw`] still holds the
+    /// envelope as received. Cannot be combined with
+    /// [`incremental_parsing`](Self::incremental_parsing).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::SseClientBuilder;
+    ///
+    /// let builder = SseClientBuilder::new("http://example.com/sse").envelope_pointer("/payload");
+    /// ```
+    pub fn envelope_pointer(mut self, pointer: impl Into<String>) -> Self {
+        self.envelope_pointer = Some(pointer.into());
+        self
+    }
+
+    /// Installs a hook that receives the envelope of every configuration
+    /// extracted through [`envelope_pointer`](Self::envelope_pointer), with the
+    /// configuration itself replaced by `null`, e.g. to log the envelope's
+    /// metadata.
+    ///
+    /// The hook is called before the configuration is filtered or passed to
+    /// the handler. It runs on the listener task and should return quickly.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::SseClientBuilder;
+    /// use serde_json::Value;
+    ///
+    /// let builder = SseClientBuilder::new("http://example.com/sse")
+    ///     .envelope_pointer("/payload")
+    ///     .on_envelope(|envelope: &Value| println!("config published by {}", envelope["meta"]["author"]));
+    /// ```
+    pub fn on_envelope(mut self, hook: impl Fn(&Value) + Send + Sync + 'static) -> Self {
+        self.envelope_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Retains the last `capacity` configuration changes of each listener
+    /// spawned from the client, for inspection through
+    /// [`ListenerHandle::recent_changes`].
+    ///
+    /// Each [`ConfigChangeRecord`](crate::ConfigChangeRecord) holds when the
+    /// change was passed to the handler, the ID of its event and the keys it
+    /// changed. Once `capacity` changes are retained, the oldest is dropped
+    /// for every new one. Must be at least 1. Disabled by default.
+    pub fn change_history(mut self, capacity: usize) -> Self {
+        self.change_history = Some(capacity);
+        self
+    }
+
+    /// Also retains the payload of every change kept by
+    /// [`change_history`](Self::change_history).
+    ///
+    /// Payloads can be large and may contain secrets, so they are not retained
+    /// by default.
+    pub fn change_history_payloads(mut self, enabled: bool) -> Self {
+        self.change_history_payloads = enabled;
+        self
+    }
+
+    /// Retains the last `capacity` configurations received by each listener
+    /// spawned from the client, whole, so that the listener can be
+    /// [rolled back](ListenerHandle::rollback_to) to one of them when the
+    /// server pushes a bad configuration.
+    ///
+    /// A configuration is retained as it is passed to the handler, under its
+    /// [version](Self::version_key) if the client has a version key and the
+    /// configuration holds it, or else under the ID of its event.
+    /// Configurations with neither are not retained, and a configuration
+    /// retained under the same version as an earlier one replaces it. Once
+    /// `capacity` configurations are retained, the oldest is dropped for every
+    /// new one. Every configuration is kept in memory with its payload, so
+    /// the capacity should stay small for large configurations. Must be at
+    /// least 1. Disabled by default.
+    pub fn retain_snapshots(mut self, capacity: usize) -> Self {
+        self.retain_snapshots = Some(capacity);
+        self
+    }
+
+    /// Acknowledges every event with an ID to `url` once it has been handled,
+    /// for servers that redeliver events until they are acknowledged.
+    ///
+    /// After the handler returns for a configuration whose event had an ID,
+    /// from an `id:` field or the [event ID strategy](Self::event_id_strategy),
+    /// the listener POSTs `{"id": "<event id>"}` to `url`, with the
+    /// client's bearer token if it has one. Acknowledgments are sent in the
+    /// background and do not hold up the next event. Nothing is acknowledged
+    /// for an event whose handler panicked, nor for events that are skipped,
+    /// replaced by a newer one while the listener is paused or only checked in
+    /// a [dry run](Self::dry_run). With [`SseClient::spawn_channel`] and
+    /// [`SseClient::spawn_watch`] an event counts as handled once it has been
+    /// passed on to the receiver.
+    ///
+    /// A failed acknowledgment is retried up to [`ack_retries`](Self::ack_retries)
+    /// times with the client's backoff delays, then reported to the
+    /// [error hook](Self::on_error) as [`ConfigError::AckFailed`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::SseClientBuilder;
+    ///
+    /// let builder = SseClientBuilder::new("http://example.com/sse").ack_url("http://example.com/ack");
+    /// ```
+    pub fn ack_url(mut self, url: impl Into<String>) -> Self {
+        self.ack_url = Some(url.into());
+        self
+    }
+
+    /// Sets how many times a failed acknowledgment is retried before it is
+    /// reported to the error hook. Defaults to [`DEFAULT_ACK_RETRIES`]. See
+    /// [`ack_url`](Self::ack_url).
+    pub fn ack_retries(mut self, retries: u32) -> Self {
+        self.ack_retries = retries;
+        self
+    }
+
+    /// Sets how events are identified when the server sends them without an
+    /// `id:` field. Defaults to [`EventIdStrategy::Server`], which leaves them
+    /// without an ID.
+    ///
+    /// Acknowledgments, the [change history](Self::change_history) and
+    /// handlers keying on [`EventMeta::id`] all need an ID, so with a server
+    /// that never sends one they only work once the listener makes IDs up:
+    /// [`EventIdStrategy::Counter`] numbers events in the order they arrive,
+    /// while [`EventIdStrategy::ContentHash`] gives identical payloads the same
+    /// ID so repeated configurations can be told apart from new ones.
+    ///
+    /// A made-up ID is only reported with the event it was made for. It is
+    /// never sent to the server as `Last-Event-ID`, and once the server sends
+    /// an ID of its own, that one is used for every event after it, as the
+    /// SSE specification requires. A configuration loaded from the cache gets
+    /// no ID.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{EventIdStrategy, SseClientBuilder};
+    ///
+    /// let builder = SseClientBuilder::new("http://example.com/sse")
+    ///     .ack_url("http://example.com/ack")
+    ///     .event_id_strategy(EventIdStrategy::Counter);
+    /// ```
+    pub fn event_id_strategy(mut self, strategy: EventIdStrategy) -> Self {
+        self.event_id_strategy = strategy;
+        self
+    }
+
+    /// Replaces the clock used for backoff delays.
+    ///
+    /// Intended for tests that need to control or observe the timing of the
+    /// retry loop; see [`Clock`] for an example. Defaults to [`TokioClock`].
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Presents `identity` as the client certificate during the TLS handshake.
+    ///
+    /// This is required by servers that enforce mutual TLS (mTLS). Requires either
+    /// the `native-tls` or the `rustls-tls` feature of this crate, which enable the
+    /// matching TLS backend in `reqwest`.
+    #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+    pub fn identity(mut self, identity: Identity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Loads the client certificate from a DER-encoded PKCS#12 archive protected
+    /// by `password`.
+    ///
+    /// Requires the `native-tls` feature.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::Request` if the archive cannot be decoded.
+    #[cfg(feature = "native-tls")]
+    pub fn pkcs12_identity(self, der: &[u8], password: &str) -> Result<Self, ConfigError> {
+        Ok(self.identity(Identity::from_pkcs12_der(der, password)?))
+    }
+
+    /// Loads the client certificate from a PEM-encoded certificate chain and a
+    /// PKCS#8 private key.
+    ///
+    /// With the `rustls-tls` feature the certificate and key are concatenated and
+    /// parsed by rustls; otherwise the `native-tls` backend is used.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::Request` if the certificate or key cannot be decoded.
+    #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+    pub fn pem_identity(self, cert: &[u8], key: &[u8]) -> Result<Self, ConfigError> {
+        #[cfg(feature = "rustls-tls")]
+        let identity = Identity::from_pem(&[cert, b"\n", key].concat())?;
+        #[cfg(not(feature = "rustls-tls"))]
+        let identity = Identity::from_pkcs8_pem(cert, key)?;
+        Ok(self.identity(identity))
+    }
+
+    /// Binds outgoing connections to the local address `addr`, e.g. to egress
+    /// through a specific interface on a multi-homed host.
+    ///
+    /// Only servers reachable over the same address family as `addr` can be
+    /// connected to: an IPv6 local address restricts connections to the IPv6
+    /// addresses the server's host name resolves to, and likewise for IPv4.
+    pub fn local_address(mut self, addr: IpAddr) -> Self {
+        self.local_address = Some(addr);
+        self
+    }
+
+    /// Restricts connections to one IP address family.
+    ///
+    /// Only the addresses of that family the server's host name resolves to are
+    /// tried. Can be combined with [`local_address`](Self::local_address) as
+    /// long as the local address belongs to the same family. Defaults to
+    /// [`AddressFamily::Any`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{AddressFamily, SseClientBuilder};
+    ///
+    /// let client = SseClientBuilder::new("http://config.internal/sse")
+    ///     .address_family(AddressFamily::Ipv6)
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn address_family(mut self, family: AddressFamily) -> Self {
+        self.address_family = family;
+        self
+    }
+
+    /// Connects through the Unix domain socket at `path` instead of over TCP.
+    ///
+    /// The URL passed to [`new`](Self::new) still provides the request target and
+    /// the `Host` header, so `http://localhost/events` requests `/events` over
+    /// the socket. As a shorthand, a URL of the form `unix:///run/config.sock`
+    /// connects to that socket and requests `/`. Everything else, including the
+    /// handler interface, works exactly as over TCP, except that redirects are
+    /// not followed. Requires the `unix-socket` feature and a Unix platform.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use config_sdk::{ServerConfig, SseClientBuilder};
+    ///
+    /// # async fn run() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://localhost/events")
+    ///     .unix_socket("/run/config-daemon.sock")
+    ///     .build()?;
+    ///
+    /// client.listen(|config: ServerConfig| println!("{:?}", config)).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "unix-socket")]
+    pub fn unix_socket(mut self, path: impl Into<PathBuf>) -> Self {
+        self.unix_socket = Some(path.into());
+        self
+    }
+
+    /// Tries HTTP/3 over QUIC before HTTP/2 and HTTP/1.1. Experimental.
+    ///
+    /// When enabled, connections to `https` URLs are first attempted over
+    /// HTTP/3. If that attempt fails, for example because UDP is blocked on
+    /// the way to the server, the connection is made over TCP instead and the
+    /// client keeps using TCP for the next five minutes before trying HTTP/3
+    /// again; clones of the client share that decision. Plain `http` URLs are always requested over TCP. The event
+    /// stream is parsed and reconnected the same way whichever protocol
+    /// carries it.
+    ///
+    /// HTTP/3 requires the rustls TLS backend, which this option selects, so
+    /// a client identity must be one rustls accepts. Requires the `http3`
+    /// feature and building with `RUSTFLAGS="--cfg reqwest_unstable"`, which
+    /// reqwest's experimental `http3` feature needs.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use config_sdk::SseClientBuilder;
+    ///
+    /// let client = SseClientBuilder::new("https://edge.example.com/sse")
+    ///     .http3(true)
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    #[cfg(all(feature = "http3", reqwest_unstable))]
+    pub fn http3(mut self, enabled: bool) -> Self {
+        self.http3 = enabled;
+        self
+    }
+
+    /// Serves the health of every listener as JSON over HTTP on `address`.
+    ///
+    /// Nothing is bound unless this is set. When it is, the address is bound
+    /// as the first listener of the client, or of one of its clones, starts,
+    /// and served until the last one stops. Every listener running in between
+    /// is listed, in the order they started; a `GET` to any path returns a
+    /// document like the following, with the client's
+    /// [`metrics`](SseClient::metrics), which count the events of all of them:
+    ///
+    /// ```json
+    /// {"connected": true,
+    ///  "listeners": [{"connected": true, "url": "https://config.example.com/sse",
+    ///                 "last_update_ms": 1760500000000, "last_event_id": "42"}],
+    ///  "metrics": {"events": 3, "reconnects": 1, "...": 0}}
+    /// ```
+    ///
+    /// `last_update_ms` is when the listener last received a configuration,
+    /// in milliseconds since the Unix epoch. The top-level `connected` is
+    /// `true` when every listener is connected. Every path answers with
+    /// status 200, except `/ready`, which answers with 503 unless every
+    /// listener is connected, for use as a readiness probe. The listeners of
+    /// a [load test](SseClient::load_test) are not served.
+    ///
+    /// If the address cannot be bound, the listener logs a warning, passes the
+    /// error to the [`on_error`](Self::on_error) hook and carries on without
+    /// the endpoint. Requires the `status-server` feature.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::SseClientBuilder;
+    ///
+    /// // Every listener spawned from the client is served at http://127.0.0.1:9090/.
+    /// let client = SseClientBuilder::new("http://example.com/sse")
+    ///     .status_address(([127, 0, 0, 1], 9090))
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    #[cfg(feature = "status-server")]
+    pub fn status_address(mut self, address: impl Into<SocketAddr>) -> Self {
+        self.status_address = Some(address.into());
+        self
+    }
+
+    /// Checks the options for values and combinations that [`build`](Self::build)
+    /// would reject, without building anything or touching the network.
+    ///
+    /// `build` runs the same checks, so calling this first is only needed to
+    /// report misconfiguration separately, e.g. when validating a service's
+    /// configuration at startup. These combinations are incompatible:
+    ///
+    /// - [`incremental_parsing`](Self::incremental_parsing) with
+    ///   [`decrypt_fn`](Self::decrypt_fn) or
+    ///   [`envelope_pointer`](Self::envelope_pointer), as a payload has to be
+    ///   complete to be decrypted or unwrapped.
+    /// - [`incremental_parsing`](Self::incremental_parsing) with
+    ///   [`skip_repeated_payloads`](Self::skip_repeated_payloads), as streamed
+    ///   payloads are not kept to be compared.
+    /// - [`incremental_parsing`](Self::incremental_parsing) with a
+    ///   [`payload_format`](Self::payload_format) other than JSON, as only JSON
+    ///   is parsed incrementally.
+    /// - [`incremental_parsing`](Self::incremental_parsing) with a
+    ///   [`framing`](Self::framing) other than SSE, as only `data` lines are
+    ///   parsed incrementally.
+    /// - [`strict_utf8`](Self::strict_utf8) with
+    ///   [`Framing::LengthPrefixed`], as the length prefixes are binary.
+    /// - A [`local_address`](Self::local_address) of a different family than
+    ///   the one required by [`address_family`](Self::address_family).
+    /// - [`change_history_payloads`](Self::change_history_payloads) without a
+    ///   [`change_history`](Self::change_history) to keep them in.
+    /// - [`on_stale`](Self::on_stale) or [`on_fresh`](Self::on_fresh) without
+    ///   a [`stale_after`](Self::stale_after) threshold.
+    /// - A Unix domain socket together with [failover URLs](Self::failover_url)
+    ///   or HTTP/3, which only apply to connections over the network.
+    /// - [`require_tls`](Self::require_tls) with a URL or failover URL that
+    ///   does not use `https`, or with a Unix domain socket.
+    ///
+    /// Options that only matter for parsed configurations cannot be combined
+    /// with [`SseClient::listen_raw`] either; that is checked when the listener
+    /// starts.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::InvalidOptions` for an incompatible combination,
+    /// if the backoff factor is not a finite number of at least 1, if the
+    /// event channel capacity, the concurrency, an endpoint weight, the retry
+    /// time budget, the staleness threshold, the reassert interval, the
+    /// maximum connection lifetime or the number of changes or snapshots
+    /// retained is 0, if no content type is accepted, or if the envelope
+    /// pointer is not a JSON pointer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{ConfigError, SseClientBuilder};
+    ///
+    /// let builder = SseClientBuilder::new("http://example.com/sse").change_history_payloads(true);
+    /// assert!(matches!(builder.validate(), Err(ConfigError::InvalidOptions(_))));
+    ///
+    /// let builder = SseClientBuilder::new("http://example.com/sse").change_history(0);
+    /// assert!(matches!(builder.validate(), Err(ConfigError::InvalidOptions(_))));
+    /// ```
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if !(self.backoff.factor.is_finite() && self.backoff.factor >= 1.0) {
+            return Err(ConfigError::InvalidOptions(format!(
+                "Backoff factor must be a finite number of at least 1, got {}",
+                self.backoff.factor
+            )));
+        }
+        if self.event_channel_capacity == 0 {
+            return Err(ConfigError::InvalidOptions("Event channel capacity must be at least 1".to_string()));
+        }
+        if self.broadcast_capacity == 0 || self.broadcast_capacity > usize::MAX / 2 {
+            return Err(ConfigError::InvalidOptions(format!(
+                "Broadcast capacity must be between 1 and {}, got {}",
+                usize::MAX / 2,
+                self.broadcast_capacity
+            )));
+        }
+        if self.concurrency == 0 {
+            return Err(ConfigError::InvalidOptions("Concurrency must be at least 1".to_string()));
+        }
+        if self.stale_after == Some(Duration::ZERO) {
+            return Err(ConfigError::InvalidOptions("The staleness threshold must be greater than zero".to_string()));
+        }
+        if self.max_total_retry_duration == Some(Duration::ZERO) {
+            return Err(ConfigError::InvalidOptions("The retry time budget must be greater than zero".to_string()));
+        }
+        if self.maintenance_retry_interval.is_zero() {
+            return Err(ConfigError::InvalidOptions("The maintenance retry interval must be greater than zero".to_string()));
+        }
+        if self.reassert_interval == Some(Duration::ZERO) {
+            return Err(ConfigError::InvalidOptions("The reassert interval must be greater than zero".to_string()));
+        }
+        if self.accepted_content_types.as_ref().is_some_and(Vec::is_empty) {
+            return Err(ConfigError::InvalidOptions("At least one content type must be accepted".to_string()));
+        }
+        if self.change_history == Some(0) {
+            return Err(ConfigError::InvalidOptions("The number of changes retained must be at least 1".to_string()));
+        }
+        if self.retain_snapshots == Some(0) {
+            return Err(ConfigError::InvalidOptions("The number of snapshots retained must be at least 1".to_string()));
+        }
+        if self.max_event_size == Some(0) {
+            return Err(ConfigError::InvalidOptions("The maximum event size must be at least 1".to_string()));
+        }
+        if self.skip_seen_ids == Some(0) {
+            return Err(ConfigError::InvalidOptions("The number of event IDs remembered must be at least 1".to_string()));
+        }
+        if self.primary_weight == 0 || self.failover_urls.iter().any(|endpoint| endpoint.weight == 0) {
+            return Err(ConfigError::InvalidOptions("Endpoint weights must be at least 1".to_string()));
+        }
+        if self.max_connection_lifetime.is_some_and(|lifetime| lifetime.is_zero()) {
+            return Err(ConfigError::InvalidOptions("Maximum connection lifetime must be greater than zero".to_string()));
+        }
+
+        if self.envelope_pointer.as_deref().is_some_and(|pointer| !pointer.is_empty() && !pointer.starts_with('/')) {
+            return Err(ConfigError::InvalidOptions("Envelope pointer must be empty or start with '/'".to_string()));
+        }
+
+        if self.incremental_parsing && self.decrypt.is_some() {
+            return Err(ConfigError::InvalidOptions("incremental parsing cannot be combined with decryption".to_string()));
+        }
+        if self.incremental_parsing && self.envelope_pointer.is_some() {
+            return Err(ConfigError::InvalidOptions(
+                "incremental parsing cannot be combined with an envelope pointer".to_string(),
+            ));
+        }
+        if self.incremental_parsing && self.skip_repeated_payloads {
+            return Err(ConfigError::InvalidOptions(
+                "incremental parsing cannot be combined with skipping repeated payloads".to_string(),
+            ));
+        }
+        if self.incremental_parsing && self.payload_format.is_some_and(|format| format != PayloadFormat::Json) {
+            return Err(ConfigError::InvalidOptions("incremental parsing only supports JSON payloads".to_string()));
+        }
+        if self.incremental_parsing && self.framing != Framing::Sse {
+            return Err(ConfigError::InvalidOptions("incremental parsing only supports SSE framing".to_string()));
+        }
+        if self.strict_utf8 && self.framing == Framing::LengthPrefixed {
+            return Err(ConfigError::InvalidOptions(
+                "strict UTF-8 cannot be combined with length-prefixed framing".to_string(),
+            ));
+        }
+        self.bind_address()?;
+        if self.cache_file.is_some() && self.cache_store.is_some() {
+            return Err(ConfigError::InvalidOptions("a cache file cannot be combined with a cache store".to_string()));
+        }
+        if self.change_history_payloads && self.change_history.is_none() {
+            return Err(ConfigError::InvalidOptions(
+                "change history payloads require a change history capacity".to_string(),
+            ));
+        }
+        if (self.stale_hook.is_some() || self.fresh_hook.is_some()) && self.stale_after.is_none() {
+            return Err(ConfigError::InvalidOptions("staleness hooks require a stale_after threshold".to_string()));
+        }
+        if self.require_tls {
+            let mut urls = std::iter::once(&self.url).chain(self.failover_urls.iter().map(|endpoint| &endpoint.url));
+            if let Some(url) = urls.find(|url| !url.get(..8).is_some_and(|scheme| scheme.eq_ignore_ascii_case("https://"))) {
+                return Err(ConfigError::InvalidOptions(format!("TLS is required, but {} does not use https", url)));
+            }
+        }
+        #[cfg(feature = "unix-socket")]
+        if self.unix_socket.is_some() || self.url.starts_with("unix://") {
+            if self.require_tls {
+                return Err(ConfigError::InvalidOptions("TLS cannot be required over a Unix domain socket".to_string()));
+            }
+            if !self.failover_urls.is_empty() {
+                return Err(ConfigError::InvalidOptions("failover URLs cannot be used with a Unix domain socket".to_string()));
+            }
+            #[cfg(all(feature = "http3", reqwest_unstable))]
+            if self.http3 {
+                return Err(ConfigError::InvalidOptions("HTTP/3 cannot be used with a Unix domain socket".to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the address to bind connections to, if any.
+    ///
+    /// reqwest only tries addresses of the same family as the local address,
+    /// so binding to the unspecified address of a family selects that family.
+    fn bind_address(&self) -> Result<Option<IpAddr>, ConfigError> {
+        match (self.local_address, self.address_family) {
+            (Some(addr), AddressFamily::Ipv4) if addr.is_ipv6() => {
+                Err(ConfigError::InvalidOptions(format!("local address {} is not an IPv4 address", addr)))
+            },
+            (Some(addr), AddressFamily::Ipv6) if addr.is_ipv4() => {
+                Err(ConfigError::InvalidOptions(format!("local address {} is not an IPv6 address", addr)))
+            },
+            (Some(addr), _) => Ok(Some(addr)),
+            (None, AddressFamily::Any) => Ok(None),
+            (None, AddressFamily::Ipv4) => Ok(Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED))),
+            (None, AddressFamily::Ipv6) => Ok(Some(IpAddr::V6(Ipv6Addr::UNSPECIFIED))),
+        }
+    }
+
+    /// Builds the underlying HTTP client and returns the configured [`SseClient`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`validate`](Self::validate), and
+    /// `ConfigError::Request` if the HTTP client cannot be constructed, for
+    /// example because the TLS backend rejects the supplied identity.
+    pub fn build(self) -> Result<SseClient, ConfigError> {
+        self.validate()?;
+        let local_address = self.bind_address()?;
+        let http_builder = || {
+            let builder = Client::builder()
+                .user_agent(USER_AGENT)
+                .local_address(local_address)
+                // Redirects are followed by the listener so headers survive every hop.
+                .redirect(Policy::none())
+                .https_only(self.require_tls);
+            #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+            let builder = match self.identity.clone() {
+                Some(identity) => builder.identity(identity),
+                None => builder,
+            };
+            // reqwest only sets up QUIC connections with the rustls backend.
+            #[cfg(all(feature = "http3", reqwest_unstable))]
+            let builder = if self.http3 { builder.use_rustls_tls() } else { builder };
+            match self.pool_idle_timeout {
+                Some(timeout) if timeout.is_zero() => builder.pool_max_idle_per_host(0),
+                Some(timeout) => builder.pool_idle_timeout(timeout),
+                None => builder,
+            }
+        };
+        let http = http_builder().build()?;
+        // Never reuses a connection, for retrying requests that failed on a stale one.
+        let fresh_http = http_builder().pool_max_idle_per_host(0).build()?;
+
+        #[cfg(feature = "unix-socket")]
+        let unix_socket = self.unix_socket.or_else(|| self.url.strip_prefix("unix://").map(PathBuf::from));
+
+        let url = with_query(&self.url, &self.query)?;
+        let mut endpoints = vec![Endpoint { url: url.clone(), weight: self.primary_weight }];
+        for endpoint in self.failover_urls {
+            endpoints.push(Endpoint { url: with_query(&endpoint.url, &self.query)?, weight: endpoint.weight });
+        }
+
+        Ok(SseClient {
+            http,
+            fresh_http,
+            url,
+            endpoints,
+            endpoint_cooldown: self.endpoint_cooldown,
+            max_retries: self.max_retries,
+            max_total_retry_duration: self.max_total_retry_duration,
+            max_line_length: self.max_line_length,
+            max_event_size: self.max_event_size,
+            max_redirects: self.max_redirects,
+            require_tls: self.require_tls,
+            sticky_sessions: self.sticky_sessions,
+            version_key: self.version_key,
+            skip_unchanged: self.skip_unchanged,
+            skip_repeated_payloads: self.skip_repeated_payloads,
+            skip_seen_ids: self.skip_seen_ids,
+            transform: self.transform,
+            flatten_keys: self.flatten_keys,
+            merge_updates: self.merge_updates,
+            null_values: self.null_values,
+            defaults: self.defaults,
+            cache: self.cache_store.or_else(|| {
+                let store = FileStore::new(self.cache_file?);
+                #[cfg(feature = "compression")]
+                let store = store.compress(self.compress_cache);
+                Some(Arc::new(store))
+            }),
+            bearer_token: self.bearer_token,
+            method: self.method,
+            request_body: self.request_body,
+            clock: self.clock,
+            status_policy: self.status_policy,
+            retry_soon_delay: self.retry_soon_delay,
+            accepted_content_types: self.accepted_content_types,
+            content_type_policy: self.content_type_policy,
+            backoff: self.backoff,
+            stability_window: self.stability_window,
+            maintenance_windows: self.maintenance_windows,
+            maintenance_retry_interval: self.maintenance_retry_interval,
+            idle_timeout: self.idle_timeout,
+            pool_idle_timeout: self.pool_idle_timeout,
+            max_connection_lifetime: self.max_connection_lifetime,
+            first_config_deadline: self.first_config_deadline,
+            error_policy: self.error_policy,
+            catch_handler_panics: self.catch_handler_panics,
+            slow_handler_threshold: self.slow_handler_threshold,
+            offload_handler: self.offload_handler,
+            log_settings: self.log_settings,
+            event_channel_capacity: self.event_channel_capacity,
+            broadcast_capacity: self.broadcast_capacity,
+            concurrency: self.concurrency,
+            ordering_key: self.ordering_key,
+            dry_run: self.dry_run,
+            lenient_field_case: self.lenient_field_case,
+            framing: self.framing,
+            strict_utf8: self.strict_utf8,
+            flush_on_eof: self.flush_on_eof,
+            ignore_empty_data: self.ignore_empty_data,
+            incremental_parsing: self.incremental_parsing,
+            error_hook: self.error_hook,
+            stale_after: self.stale_after,
+            stale_hook: self.stale_hook,
+            fresh_hook: self.fresh_hook,
+            reassert_interval: self.reassert_interval,
+            removal_hook: self.removal_hook,
+            removal_event: self.removal_event,
+            decrypt: self.decrypt,
+            #[cfg(feature = "checksum")]
+            checksum: self.checksum,
+            wire_format: self.wire_format,
+            payload_format: self.payload_format,
+            envelope_pointer: self.envelope_pointer,
+            envelope_hook: self.envelope_hook,
+            change_history: self.change_history,
+            change_history_payloads: self.change_history_payloads,
+            retain_snapshots: self.retain_snapshots,
+            ack_url: self.ack_url,
+            ack_retries: self.ack_retries,
+            event_id_strategy: self.event_id_strategy,
+            metrics: Arc::new(Metrics::default()),
+            last_modified: Arc::new(LastModified::default()),
+            shared_log: None,
+            #[cfg(feature = "unix-socket")]
+            unix_socket,
+            #[cfg(all(feature = "http3", reqwest_unstable))]
+            http3: self.http3,
+            #[cfg(all(feature = "http3", reqwest_unstable))]
+            http3_failed: Arc::new(Mutex::new(None)),
+            #[cfg(feature = "status-server")]
+            status_address: self.status_address,
+            #[cfg(feature = "status-server")]
+            status_server: Arc::new(StatusSlot::default()),
+        })
+    }
+}
+
+/// Appends the query parameters `query` to `url`, percent-encoded.
+fn with_query(url: &str, query: &[(String, String)]) -> Result<String, ConfigError> {
+    if query.is_empty() {
+        return Ok(url.to_string());
+    }
+    let mut parsed = Url::parse(url).map_err(|e| ConfigError::GenericError(format!("Invalid URL {}: {}", url, e)))?;
+    parsed.query_pairs_mut().extend_pairs(query);
+    Ok(parsed.into())
+}
+
+impl fmt::Debug for SseClientBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The bearer token is deliberately left out so it never ends up in logs.
+        let mut debug = f.debug_struct("SseClientBuilder");
+        debug
+            .field("url", &self.url)
+            .field("primary_weight", &self.primary_weight)
+            .field("failover_urls", &self.failover_urls)
+            .field("endpoint_cooldown", &self.endpoint_cooldown)
+            .field("query", &self.query)
+            .field("max_retries", &self.max_retries)
+            .field("max_total_retry_duration", &self.max_total_retry_duration)
+            .field("max_line_length", &self.max_line_length)
+            .field("max_event_size", &self.max_event_size)
+            .field("max_redirects", &self.max_redirects)
+            .field("require_tls", &self.require_tls)
+            .field("sticky_sessions", &self.sticky_sessions)
+            .field("version_key", &self.version_key)
+            .field("skip_unchanged", &self.skip_unchanged)
+            .field("skip_repeated_payloads", &self.skip_repeated_payloads)
+            .field("skip_seen_ids", &self.skip_seen_ids)
+            .field("transform", &self.transform.is_some())
+            .field("flatten_keys", &self.flatten_keys)
+            .field("merge_updates", &self.merge_updates)
+            .field("null_values", &self.null_values)
+            .field("defaults", &self.defaults)
+            .field("cache_file", &self.cache_file)
+            .field("cache_store", &self.cache_store)
+            .field("method", &self.method)
+            .field("request_body_length", &self.request_body.as_ref().map(|body| body.content.len()))
+            .field("retry_soon_delay", &self.retry_soon_delay)
+            .field("accepted_content_types", &self.accepted_content_types)
+            .field("content_type_policy", &self.content_type_policy)
+            .field("backoff", &self.backoff)
+            .field("stability_window", &self.stability_window)
+            .field("maintenance_windows", &self.maintenance_windows)
+            .field("maintenance_retry_interval", &self.maintenance_retry_interval)
+            .field("idle_timeout", &self.idle_timeout)
+            .field("pool_idle_timeout", &self.pool_idle_timeout)
+            .field("max_connection_lifetime", &self.max_connection_lifetime)
+            .field("first_config_deadline", &self.first_config_deadline)
+            .field("error_policy", &self.error_policy)
+            .field("catch_handler_panics", &self.catch_handler_panics)
+            .field("slow_handler_threshold", &self.slow_handler_threshold)
+            .field("offload_handler", &self.offload_handler)
+            .field("log_settings", &self.log_settings)
+            .field("event_channel_capacity", &self.event_channel_capacity)
+            .field("broadcast_capacity", &self.broadcast_capacity)
+            .field("concurrency", &self.concurrency)
+            .field("ordering_key", &self.ordering_key)
+            .field("dry_run", &self.dry_run)
+            .field("lenient_field_case", &self.lenient_field_case)
+            .field("framing", &self.framing)
+            .field("strict_utf8", &self.strict_utf8)
+            .field("flush_on_eof", &self.flush_on_eof)
+            .field("ignore_empty_data", &self.ignore_empty_data)
+            .field("incremental_parsing", &self.incremental_parsing)
+            .field("local_address", &self.local_address)
+            .field("address_family", &self.address_family)
+            .field("error_hook", &self.error_hook.is_some())
+            .field("stale_after", &self.stale_after)
+            .field("reassert_interval", &self.reassert_interval)
+            .field("stale_hook", &self.stale_hook.is_some())
+            .field("fresh_hook", &self.fresh_hook.is_some())
+            .field("removal_hook", &self.removal_hook.is_some())
+            .field("removal_event", &self.removal_event)
+            .field("decrypt", &self.decrypt.is_some())
+            .field("wire_format", &self.wire_format)
+            .field("payload_format", &self.payload_format)
+            .field("envelope_pointer", &self.envelope_pointer)
+            .field("envelope_hook", &self.envelope_hook.is_some())
+            .field("change_history", &self.change_history)
+            .field("change_history_payloads", &self.change_history_payloads)
+            .field("retain_snapshots", &self.retain_snapshots)
+            .field("ack_url", &self.ack_url)
+            .field("ack_retries", &self.ack_retries)
+            .field("event_id_strategy", &self.event_id_strategy)
+            .field("bearer_token", &self.bearer_token.as_ref().map(|_| "<redacted>"));
+        #[cfg(feature = "unix-socket")]
+        debug.field("unix_socket", &self.unix_socket);
+        #[cfg(all(feature = "http3", reqwest_unstable))]
+        debug.field("http3", &self.http3);
+        #[cfg(feature = "status-server")]
+        debug.field("status_address", &self.status_address);
+        #[cfg(feature = "checksum")]
+        debug.field("checksum", &self.checksum);
+        debug.finish_non_exhaustive()
+    }
+}
+
+/// A configured client for an SSE configuration endpoint.
+///
+/// Created through [`SseClientBuilder`]. The client can be used to listen for
+/// updates any number of times; each call to [`SseClient::listen`] or
+/// [`SseClient::listen_for_events`] opens its own connection.
+#[derive(Clone)]
+pub struct SseClient {
+    pub(crate) http: Client,
+    /// A client that opens a new connection for every request.
+    pub(crate) fresh_http: Client,
+    pub(crate) url: String,
+    /// The URL passed to the builder followed by the failover URLs.
+    pub(crate) endpoints: Vec<Endpoint>,
+    pub(crate) endpoint_cooldown: Duration,
+    pub(crate) max_retries: u32,
+    pub(crate) max_total_retry_duration: Option<Duration>,
+    pub(crate) max_line_length: Option<usize>,
+    pub(crate) max_event_size: Option<usize>,
+    pub(crate) max_redirects: usize,
+    pub(crate) require_tls: bool,
+    pub(crate) sticky_sessions: bool,
+    pub(crate) version_key: Option<String>,
+    pub(crate) skip_unchanged: Option<Vec<String>>,
+    pub(crate) skip_repeated_payloads: bool,
+    pub(crate) skip_seen_ids: Option<usize>,
+    pub(crate) transform: Option<Transform>,
+    pub(crate) flatten_keys: Option<FlattenOptions>,
+    pub(crate) merge_updates: Option<MergePolicy>,
+    pub(crate) null_values: NullValues,
+    pub(crate) defaults: Option<ServerConfig>,
+    pub(crate) cache: Option<Arc<dyn ConfigStore>>,
+    pub(crate) bearer_token: Option<String>,
+    pub(crate) method: Method,
+    pub(crate) request_body: Option<RequestBody>,
+    pub(crate) clock: Arc<dyn Clock>,
+    pub(crate) status_policy: StatusPolicy,
+    pub(crate) retry_soon_delay: Duration,
+    pub(crate) accepted_content_types: Option<Vec<String>>,
+    pub(crate) content_type_policy: ContentTypePolicy,
+    pub(crate) backoff: Backoff,
+    pub(crate) stability_window: Option<Duration>,
+    pub(crate) maintenance_windows: Vec<MaintenanceWindow>,
+    pub(crate) maintenance_retry_interval: Duration,
+    pub(crate) idle_timeout: Option<Duration>,
+    pub(crate) pool_idle_timeout: Option<Duration>,
+    pub(crate) max_connection_lifetime: Option<Duration>,
+    pub(crate) first_config_deadline: Option<Duration>,
+    pub(crate) error_policy: ErrorPolicy,
+    pub(crate) catch_handler_panics: bool,
+    pub(crate) slow_handler_threshold: Option<Duration>,
+    pub(crate) offload_handler: bool,
+    pub(crate) log_settings: LogSettings,
+    pub(crate) event_channel_capacity: usize,
+    pub(crate) broadcast_capacity: usize,
+    pub(crate) concurrency: usize,
+    pub(crate) ordering_key: Option<String>,
+    pub(crate) dry_run: bool,
+    pub(crate) lenient_field_case: bool,
+    pub(crate) framing: Framing,
+    pub(crate) strict_utf8: bool,
+    pub(crate) flush_on_eof: bool,
+    pub(crate) ignore_empty_data: bool,
+    pub(crate) incremental_parsing: bool,
+    pub(crate) error_hook: Option<ErrorHook>,
+    pub(crate) stale_after: Option<Duration>,
+    pub(crate) stale_hook: Option<StalenessHook>,
+    pub(crate) fresh_hook: Option<StalenessHook>,
+    pub(crate) reassert_interval: Option<Duration>,
+    pub(crate) removal_hook: Option<RemovalHook>,
+    pub(crate) removal_event: String,
+    pub(crate) decrypt: Option<Decryptor>,
+    #[cfg(feature = "checksum")]
+    pub(crate) checksum: Option<(String, ChecksumAlgorithm)>,
+    pub(crate) wire_format: WireFormat,
+    pub(crate) payload_format: Option<PayloadFormat>,
+    pub(crate) envelope_pointer: Option<String>,
+    pub(crate) envelope_hook: Option<EnvelopeHook>,
+    pub(crate) change_history: Option<usize>,
+    pub(crate) change_history_payloads: bool,
+    pub(crate) retain_snapshots: Option<usize>,
+    pub(crate) ack_url: Option<String>,
+    pub(crate) ack_retries: u32,
+    pub(crate) event_id_strategy: EventIdStrategy,
+    pub(crate) metrics: Arc<Metrics>,
+    pub(crate) last_modified: Arc<LastModified>,
+    /// A logger shared by every listener of the client instead of each one
+    /// configuring its own, as during a load test.
+    pub(crate) shared_log: Option<Logger>,
+    #[cfg(feature = "unix-socket")]
+    pub(crate) unix_socket: Option<PathBuf>,
+    #[cfg(all(feature = "http3", reqwest_unstable))]
+    pub(crate) http3: bool,
+    /// When an HTTP/3 connection attempt last failed. The client connects
+    /// over TCP only until `HTTP3_RETRY_AFTER` has passed since.
+    #[cfg(all(feature = "http3", reqwest_unstable))]
+    pub(crate) http3_failed: Arc<Mutex<Option<Instant>>>,
+    #[cfg(feature = "status-server")]
+    pub(crate) status_address: Option<SocketAddr>,
+    /// The status endpoint shared by the listeners of the client and its
+    /// clones.
+    #[cfg(feature = "status-server")]
+    pub(crate) status_server: Arc<StatusSlot>,
+}
+
+impl fmt::Debug for SseClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("SseClient");
+        debug
+            .field("url", &self.url)
+            .field("endpoints", &self.endpoints)
+            .field("endpoint_cooldown", &self.endpoint_cooldown)
+            .field("max_retries", &self.max_retries)
+            .field("max_total_retry_duration", &self.max_total_retry_duration)
+            .field("max_line_length", &self.max_line_length)
+            .field("max_event_size", &self.max_event_size)
+            .field("max_redirects", &self.max_redirects)
+            .field("require_tls", &self.require_tls)
+            .field("sticky_sessions", &self.sticky_sessions)
+            .field("version_key", &self.version_key)
+            .field("skip_unchanged", &self.skip_unchanged)
+            .field("skip_repeated_payloads", &self.skip_repeated_payloads)
+            .field("skip_seen_ids", &self.skip_seen_ids)
+            .field("transform", &self.transform.is_some())
+            .field("flatten_keys", &self.flatten_keys)
+            .field("merge_updates", &self.merge_updates)
+            .field("null_values", &self.null_values)
+            .field("defaults", &self.defaults)
+            .field("cache", &self.cache)
+            .field("method", &self.method)
+            .field("request_body_length", &self.request_body.as_ref().map(|body| body.content.len()))
+            .field("retry_soon_delay", &self.retry_soon_delay)
+            .field("accepted_content_types", &self.accepted_content_types)
+            .field("content_type_policy", &self.content_type_policy)
+            .field("backoff", &self.backoff)
+            .field("stability_window", &self.stability_window)
+            .field("maintenance_windows", &self.maintenance_windows)
+            .field("maintenance_retry_interval", &self.maintenance_retry_interval)
+            .field("idle_timeout", &self.idle_timeout)
+            .field("pool_idle_timeout", &self.pool_idle_timeout)
+            .field("max_connection_lifetime", &self.max_connection_lifetime)
+            .field("first_config_deadline", &self.first_config_deadline)
+            .field("error_policy", &self.error_policy)
+            .field("catch_handler_panics", &self.catch_handler_panics)
+            .field("slow_handler_threshold", &self.slow_handler_threshold)
+            .field("offload_handler", &self.offload_handler)
+            .field("log_settings", &self.log_settings)
+            .field("event_channel_capacity", &self.event_channel_capacity)
+            .field("broadcast_capacity", &self.broadcast_capacity)
+            .field("concurrency", &self.concurrency)
+            .field("ordering_key", &self.ordering_key)
+            .field("dry_run", &self.dry_run)
+            .field("lenient_field_case", &self.lenient_field_case)
+            .field("framing", &self.framing)
+            .field("strict_utf8", &self.strict_utf8)
+            .field("flush_on_eof", &self.flush_on_eof)
+            .field("ignore_empty_data", &self.ignore_empty_data)
+            .field("incremental_parsing", &self.incremental_parsing)
+            .field("error_hook", &self.error_hook.is_some())
+            .field("stale_after", &self.stale_after)
+            .field("reassert_interval", &self.reassert_interval)
+            .field("stale_hook", &self.stale_hook.is_some())
+            .field("fresh_hook", &self.fresh_hook.is_some())
+            .field("removal_hook", &self.removal_hook.is_some())
+            .field("removal_event", &self.removal_event)
+            .field("decrypt", &self.decrypt.is_some())
+            .field("wire_format", &self.wire_format)
+            .field("payload_format", &self.payload_format)
+            .field("envelope_pointer", &self.envelope_pointer)
+            .field("envelope_hook", &self.envelope_hook.is_some())
+            .field("change_history", &self.change_history)
+            .field("change_history_payloads", &self.change_history_payloads)
+            .field("retain_snapshots", &self.retain_snapshots)
+            .field("ack_url", &self.ack_url)
+            .field("ack_retries", &self.ack_retries)
+            .field("event_id_strategy", &self.event_id_strategy)
+            .field("bearer_token", &self.bearer_token.as_ref().map(|_| "<redacted>"));
+        #[cfg(feature = "unix-socket")]
+        debug.field("unix_socket", &self.unix_socket);
+        #[cfg(all(feature = "http3", reqwest_unstable))]
+        debug.field("http3", &self.http3);
+        #[cfg(feature = "status-server")]
+        debug.field("status_address", &self.status_address);
+        #[cfg(feature = "checksum")]
+        debug.field("checksum", &self.checksum);
+        debug.finish_non_exhaustive()
+    }
+}
+
+impl SseClient {
+    /// Returns `true` if one of the maintenance windows is on, according to
+    /// the client's clock.
+    pub(crate) fn in_maintenance(&self) -> bool {
+        let now = self.clock.system_time();
+        self.maintenance_windows.iter().any(|window| window.contains(now))
+    }
+
+    /// Passes `error` to the error hook, if one is installed.
+    pub(crate) fn report_error(&self, error: &ConfigError) {
+        if let Some(hook) = &self.error_hook {
+            hook(error);
+        }
+    }
+
+    /// Creates the state shared between a new listener and its handle, for a
+    /// listener that dispatches to `handlers` handlers, raw payloads if `raw`.
+    fn remote(&self, handlers: usize, raw: bool) -> Arc<Remote> {
+        let history = self.change_history.map(|capacity| ChangeHistory::new(capacity, self.change_history_payloads));
+        let snapshots = self.retain_snapshots.map(Snapshots::new);
+        Arc::new(Remote::new(self.metrics(), history).retaining(snapshots).dispatching(self.dispatch_info(handlers, raw)))
+    }
+
+    /// Describes what a listener of this client dispatches, see
+    /// [`ListenerHandle::dispatch_info`].
+    fn dispatch_info(&self, handlers: usize, raw: bool) -> DispatchInfo {
+        let subscription = reqwest::Url::parse(&self.url)
+            .map(|url| url.query_pairs().into_owned().collect())
+            .unwrap_or_default();
+        let filters = [
+            ("skip_seen_ids", self.skip_seen_ids.is_some()),
+            ("skip_repeated_payloads", self.skip_repeated_payloads),
+            ("version_key", self.version_key.is_some()),
+            ("skip_unchanged", self.skip_unchanged.is_some()),
+        ];
+        let transforms = [
+            ("transform", self.transform.is_some()),
+            ("merge_updates", self.merge_updates.is_some()),
+            ("defaults", self.defaults.is_some()),
+            ("flatten_keys", self.flatten_keys.is_some()),
+        ];
+        let set = |options: &[(&'static str, bool)]| options.iter().filter(|(_, set)| *set).map(|(name, _)| *name).collect();
+        DispatchInfo {
+            handlers,
+            raw,
+            subscription,
+            removal_event: self.removal_event.clone(),
+            filters: set(&filters),
+            transforms: set(&transforms),
+        }
+    }
+
+    /// Returns `true` if `content_type` is one of the accepted content types,
+    /// or by default an event stream or a payload format the client parses.
+    pub(crate) fn accepts_content_type(&self, content_type: &str) -> bool {
+        let content_type = content_type.trim_start();
+        let starts_with = |accepted: &str| {
+            content_type.get(..accepted.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(accepted))
+        };
+        match &self.accepted_content_types {
+            Some(accepted) => accepted.iter().any(|accepted| starts_with(accepted)),
+            None => starts_with("text/event-stream") || PayloadFormat::from_content_type(content_type).is_some(),
+        }
+    }
+
+    /// Checks that none of the client's options requires parsed
+    /// configurations, which a raw listener does not have.
+    pub(crate) fn check_raw(&self) -> Result<(), ConfigError> {
+        let conflicting = [
+            ("version_key", self.version_key.is_some()),
+            ("skip_unchanged", self.skip_unchanged.is_some()),
+            ("skip_repeated_payloads", self.skip_repeated_payloads),
+            ("transform", self.transform.is_some()),
+            ("flatten_keys", self.flatten_keys.is_some()),
+            ("merge_updates", self.merge_updates.is_some()),
+            ("defaults", self.defaults.is_some()),
+            ("cache", self.cache.is_some()),
+            ("dry_run", self.dry_run),
+            ("change_history", self.change_history.is_some()),
+            ("retain_snapshots", self.retain_snapshots.is_some()),
+            ("wire_format", self.wire_format != WireFormat::Auto),
+            ("payload_format", self.payload_format.is_some()),
+            ("envelope_pointer", self.envelope_pointer.is_some()),
+            #[cfg(feature = "checksum")]
+            ("checksum", self.checksum.is_some()),
+        ];
+        match conflicting.iter().find(|(_, set)| *set) {
+            Some((option, _)) => Err(ConfigError::InvalidOptions(format!("{} cannot be used with raw payloads", option))),
+            None => Ok(()),
+        }
+    }
+
+    /// Calls the removal hook, if one is installed.
+    pub(crate) fn report_removal(&self) {
+        if let Some(hook) = &self.removal_hook {
+            hook();
+        }
+    }
+
+    /// Returns the counters updated by this client's listeners.
+    ///
+    /// Clones of the client share the same counters.
+    pub fn metrics(&self) -> Arc<Metrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    /// Logs the options the client ended up with at debug level, after
+    /// environment variables, builder calls and defaults have been applied.
+    ///
+    /// Every listener does this once when it starts, which helps to confirm
+    /// that an override took effect. Secrets are not logged: only whether a
+    /// bearer token, request body or decryption function is set. Like every
+    /// debug record, this is compiled out of release builds unless `slog`'s
+    /// `release_max_level_debug` feature is enabled.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use config_sdk::SseClientBuilder;
+    /// use slog::{o, Discard, Logger};
+    ///
+    /// let client = SseClientBuilder::from_env()?.build()?;
+    /// // Any logger works, e.g. the application's own.
+    /// let log = Logger::root(Discard, o!());
+    /// client.log_options(&log);
+    /// # Ok::<(), config_sdk::ConfigError>(())
+    /// ```
+    pub fn log_options(&self, log: &Logger) {
+        let backoff = &self.backoff;
+        let log_format = self.log_settings.format.unwrap_or_else(LogFormat::detect);
+        debug!(log, "Effective client options";
+            "url" => &self.url,
+            "endpoints" => ?self.endpoints.iter().map(|e| (&e.url, e.weight)).collect::<Vec<_>>(),
+            "endpoint_cooldown_ms" => self.endpoint_cooldown.as_millis() as u64,
+            "method" => %self.method,
+            "bearer_token" => self.bearer_token.is_some(),
+            "request_body_content_type" => self.request_body.as_ref().map(|body| body.content_type.as_str()),
+            "max_retries" => self.max_retries,
+            "max_total_retry_duration_ms" => self.max_total_retry_duration.map(|budget| budget.as_millis() as u64),
+            "max_redirects" => self.max_redirects,
+            "require_tls" => self.require_tls,
+            "sticky_sessions" => self.sticky_sessions,
+            "max_line_length" => self.max_line_length,
+            "max_event_size" => self.max_event_size,
+            "initial_delay_ms" => backoff.initial_delay.as_millis() as u64,
+            "backoff_factor" => backoff.factor,
+            "max_delay_ms" => backoff.max_delay.map(|delay| delay.as_millis() as u64),
+            "retry_soon_delay_ms" => self.retry_soon_delay.as_millis() as u64,
+            "accepted_content_types" => ?self.accepted_content_types,
+            "content_type_policy" => ?self.content_type_policy,
+            "stability_window_ms" => self.stability_window.map(|window| window.as_millis() as u64),
+            "maintenance_windows" => ?self.maintenance_windows,
+            "maintenance_retry_interval_ms" => self.maintenance_retry_interval.as_millis() as u64,
+            "idle_timeout_ms" => self.idle_timeout.map(|timeout| timeout.as_millis() as u64),
+            "pool_idle_timeout_ms" => self.pool_idle_timeout.map(|timeout| timeout.as_millis() as u64),
+            "max_connection_lifetime_ms" => self.max_connection_lifetime.map(|lifetime| lifetime.as_millis() as u64),
+            "first_config_deadline_ms" => self.first_config_deadline.map(|deadline| deadline.as_millis() as u64),
+            "error_policy" => ?self.error_policy,
+            "version_key" => self.version_key.as_deref(),
+            "skip_unchanged" => ?self.skip_unchanged,
+            "skip_repeated_payloads" => self.skip_repeated_payloads,
+            "skip_seen_ids" => self.skip_seen_ids,
+            "transform" => self.transform.is_some(),
+            "flatten_keys" => ?self.flatten_keys,
+            "merge_updates" => ?self.merge_updates,
+            "null_values" => ?self.null_values,
+            "defaults" => ?self.defaults.as_ref().map(|defaults| defaults.settings.keys().collect::<Vec<_>>()),
+            "cache" => ?self.cache,
+            "catch_handler_panics" => self.catch_handler_panics,
+            "slow_handler_threshold_ms" => self.slow_handler_threshold.map(|threshold| threshold.as_millis() as u64),
+            "offload_handler" => self.offload_handler,
+            "event_channel_capacity" => self.event_channel_capacity,
+            "broadcast_capacity" => self.broadcast_capacity,
+            "concurrency" => self.concurrency,
+            "ordering_key" => self.ordering_key.as_deref(),
+            "change_history" => self.change_history,
+            "retain_snapshots" => self.retain_snapshots,
+            "ack_url" => self.ack_url.as_deref(),
+            "ack_retries" => self.ack_retries,
+            "event_id_strategy" => ?self.event_id_strategy,
+            "dry_run" => self.dry_run,
+            "lenient_field_case" => self.lenient_field_case,
+            "framing" => ?self.framing,
+            "strict_utf8" => self.strict_utf8,
+            "flush_on_eof" => self.flush_on_eof,
+            "ignore_empty_data" => self.ignore_empty_data,
+            "incremental_parsing" => self.incremental_parsing,
+            "decrypt" => self.decrypt.is_some(),
+            "stale_after_ms" => self.stale_after.map(|threshold| threshold.as_millis() as u64),
+            "reassert_interval_ms" => self.reassert_interval.map(|interval| interval.as_millis() as u64),
+            "wire_format" => ?self.wire_format,
+            "payload_format" => ?self.payload_format,
+            "envelope_pointer" => self.envelope_pointer.as_deref(),
+            "removal_event" => &self.removal_event,
+            "log_format" => ?log_format,
+            "log_channel_capacity" => self.log_settings.channel_capacity,
+            "log_overflow" => ?self.log_settings.overflow);
+        #[cfg(feature = "unix-socket")]
+        debug!(log, "Effective transport options"; "unix_socket" => ?self.unix_socket);
+        #[cfg(all(feature = "http3", reqwest_unstable))]
+        debug!(log, "Effective transport options"; "http3" => self.http3);
+        #[cfg(feature = "status-server")]
+        debug!(log, "Effective status options"; "status_address" => ?self.status_address);
+        #[cfg(feature = "checksum")]
+        debug!(log, "Effective checksum options"; "checksum" => ?self.checksum);
+    }
+
+    /// Listens for configuration updates and passes each parsed `ServerConfig`
+    /// to `update_handler`.
+    ///
+    /// The handler runs on the listener's task, so nothing more is read from the
+    /// network while it runs and a slow handler slows down the stream rather
+    /// than letting events pile up.
+    ///
+    /// See [`start_listening_for_updates`](crate::start_listening_for_updates) for
+    /// the retry behaviour and error conditions.
+    pub async fn listen<F>(&self, mut update_handler: F) -> Result<(), ConfigError>
+    where
+        F: FnMut(ServerConfig) + Send + 'static,
+    {
+        listen(self, move |config, _raw: &[u8], _meta: &EventMeta| update_handler(config)).await
+    }
+
+    /// Listens like [`listen`](Self::listen) until `shutdown` resolves, then
+    /// returns the most recent configuration the handler received.
+    ///
+    /// This is meant for planned shutdowns: pass e.g. `tokio::signal::ctrl_c()`
+    /// or the receiving end of a shutdown channel, and the last configuration can
+    /// be persisted or handed off once the listener has stopped. The listener is
+    /// cancelled at its next `.await`, so a handler that is running when
+    /// `shutdown` resolves is allowed to finish. If the listener ends on its own
+    /// first, its result is returned the same way.
+    ///
+    /// Returns `Ok(None)` if no configuration had been handled by the time the
+    /// listener stopped. A configuration whose handler panicked does not count as
+    /// handled.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error the listener stopped with if it stops on its own before
+    /// `shutdown` resolves; see
+    /// [`start_listening_for_updates`](crate::start_listening_for_updates).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use config_sdk::{ServerConfig, SseClientBuilder};
+    ///
+    /// # async fn run() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/sse").build()?;
+    /// let shutdown = async {
+    ///     let _ = tokio::signal::ctrl_c().await;
+    /// };
+    ///
+    /// let last = client.listen_until(|config: ServerConfig| println!("{:?}", config), shutdown).await?;
+    /// if let Some(config) = last {
+    ///     println!("Stopped while holding {:?}", config);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn listen_until<F, S>(&self, mut update_handler: F, shutdown: S) -> Result<Option<ServerConfig>, ConfigError>
+    where
+        F: FnMut(ServerConfig) + Send + 'static,
+        S: Future<Output = ()>,
+    {
+        let mut latest = None;
+        let result = {
+            let listener = listen(self, |config, _raw: &[u8], _meta: &EventMeta| {
+                update_handler(config.clone());
+                latest = Some(config);
+            });
+            tokio::select! {
+                result = listener => Some(result),
+                _ = shutdown => None,
+            }
+        };
+        match result {
+            Some(Err(e)) => Err(e),
+            Some(Ok(())) | None => Ok(latest),
+        }
+    }
+
+    /// Listens like [`listen`](Self::listen), but passes each configuration to
+    /// `update_handler` coerced to the types declared in `schema`.
+    ///
+    /// Settings that cannot be coerced are left out of the typed configuration
+    /// and reported to the [error hook](SseClientBuilder::on_error), one
+    /// `ConfigError::InvalidSetting` per key; the rest of the configuration is
+    /// still delivered. See [`Schema::coerce`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use config_sdk::{Schema, SettingType, SseClientBuilder, TypedServerConfig};
+    ///
+    /// # async fn run() -> Result<(), config_sdk::ConfigError> {
+    /// let schema = Schema::new()
+    ///     .key("timeout", SettingType::Unsigned)
+    ///     .key("tags", SettingType::StringList);
+    /// let client = SseClientBuilder::new("http://example.com/sse")
+    ///     .on_error(|e| eprintln!("{}", e))
+    ///     .build()?;
+    ///
+    /// client.listen_typed(schema, |config: TypedServerConfig| {
+    ///     let timeout = config.get::<u64>("timeout").unwrap_or(30);
+    ///     println!("timeout is {}", timeout);
+    /// }).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn listen_typed<F>(&self, schema: Schema, mut update_handler: F) -> Result<(), ConfigError>
+    where
+        F: FnMut(TypedServerConfig) + Send + 'static,
+    {
+        listen(self, move |config, _raw: &[u8], _meta: &EventMeta| {
+            let (typed, errors) = schema.coerce(&config);
+            for error in &errors {
+                self.report_error(error);
+            }
+            update_handler(typed)
+        })
+        .await
+    }
+
+    /// Listens for configuration updates and passes each one to `event_handler`
+    /// together with its raw payload.
+    ///
+    /// See [`start_listening_for_events`](crate::start_listening_for_events).
+    pub async fn listen_for_events<F>(&self, mut event_handler: F) -> Result<(), ConfigError>
+    where
+        F: FnMut(ConfigEvent) + Send + 'static,
+    {
+        listen(self, move |config, raw: &[u8], meta: &EventMeta| {
+            event_handler(ConfigEvent { config, raw: raw.to_vec(), meta: meta.clone() })
+        })
+        .await
+    }
+
+    /// Listens for events and passes the payload of each one to
+    /// `payload_handler` without parsing it.
+    ///
+    /// The payload is handed over as the server sent it, or decrypted if the
+    /// client [decrypts](SseClientBuilder::decrypt_fn) events.
+    /// This turns the client into a general SSE byte-stream client: reconnects,
+    /// backoff, `Last-Event-ID`, decryption and metrics work as usual, but no
+    /// JSON parsing takes place, so a payload can never fail to parse.
+    ///
+    /// # Errors
+    ///
+    /// Besides the errors of [`listen`](Self::listen), returns
+    /// `ConfigError::InvalidOptions`, naming the offending option, before
+    /// connecting if the client has any of these options, since there is no
+    /// configuration for them to work on:
+    ///
+    /// - a [version key](SseClientBuilder::version_key),
+    /// - a [change filter](SseClientBuilder::skip_unchanged),
+    /// - [skipping repeated payloads](SseClientBuilder::skip_repeated_payloads),
+    /// - a [transform](SseClientBuilder::transform),
+    /// - [flattened keys](SseClientBuilder::flatten_keys),
+    /// - [merging](SseClientBuilder::merge_updates),
+    /// - [defaults](SseClientBuilder::defaults),
+    /// - a [cache file](SseClientBuilder::cache_file),
+    /// - a [dry run](SseClientBuilder::dry_run),
+    /// - a [change history](SseClientBuilder::change_history),
+    /// - [retained snapshots](SseClientBuilder::retain_snapshots),
+    /// - a [wire format](SseClientBuilder::wire_format) other than the default,
+    /// - a [payload format](SseClientBuilder::payload_format),
+    /// - an [envelope pointer](SseClientBuilder::envelope_pointer),
+    /// - a checksum, with the `checksum` feature.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use config_sdk::SseClientBuilder;
+    ///
+    /// # async fn run() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/sse").build()?;
+    ///
+    /// client.listen_raw(|payload: Vec<u8>| {
+    ///     println!("forwarding {} bytes", payload.len());
+    /// }).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn listen_raw<F>(&self, mut payload_handler: F) -> Result<(), ConfigError>
+    where
+        F: FnMut(Vec<u8>) + Send + 'static,
+    {
+        listen_raw(self, move |raw: &[u8], _meta: &EventMeta| payload_handler(raw.to_vec()), None).await
+    }
+
+    /// Starts [`listen`](Self::listen) on a background task and returns a handle
+    /// to it.
+    ///
+    /// The handle can be used to wait for the first configuration, see
+    /// [`ListenerHandle::first_config`], and to stop or join the listener. Must be
+    /// called from within a tokio runtime.
+    pub fn spawn<F>(&self, mut update_handler: F) -> ListenerHandle
+    where
+        F: FnMut(ServerConfig) + Send + 'static,
+    {
+        self.spawn_with(move |config, _raw: &[u8], _meta: &EventMeta| update_handler(config))
+    }
+
+    /// Starts [`listen_for_events`](Self::listen_for_events) on a background task
+    /// and returns a handle to it.
+    pub fn spawn_for_events<F>(&self, mut event_handler: F) -> ListenerHandle
+    where
+        F: FnMut(ConfigEvent) + Send + 'static,
+    {
+        self.spawn_with(move |config, raw: &[u8], meta: &EventMeta| {
+            event_handler(ConfigEvent { config, raw: raw.to_vec(), meta: meta.clone() })
+        })
+    }
+
+    /// Starts [`listen_raw`](Self::listen_raw) on a background task and returns
+    /// a handle to it.
+    ///
+    /// [`ListenerHandle::first_config`] completes once the first payload has
+    /// been handled.
+    pub fn spawn_raw<F>(&self, mut payload_handler: F) -> ListenerHandle
+    where
+        F: FnMut(Vec<u8>) + Send + 'static,
+    {
+        let client = self.clone();
+        let (ready_tx, ready_rx) = ready_channel();
+        let remote = self.remote(1, true);
+        let listener_remote = Arc::clone(&remote);
+        ListenerHandle::spawn(
+            async move {
+                let dispatch = move |raw: &[u8], _meta: &EventMeta| {
+                    payload_handler(raw.to_vec());
+                    mark_ready(&ready_tx);
+                };
+                listen_raw(&client, dispatch, Some(&listener_remote)).await
+            },
+            ready_rx,
+            remote,
+        )
+    }
+
+    /// Starts a listener on a background task that publishes every configuration
+    /// to a `tokio::sync::watch` channel.
+    ///
+    /// The receiver holds `None` until the first configuration has been
+    /// dispatched and always holds the most recent configuration afterwards, so
+    /// any number of tasks can read it or wait for changes.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use config_sdk::SseClientBuilder;
+    ///
+    /// # async fn run() -> Result<(), config_sdk::ConfigError> {
+    /// let (_handle, mut latest) = SseClientBuilder::new("http://example.com/sse").build()?.spawn_watch();
+    ///
+    /// while latest.changed().await.is_ok() {
+    ///     println!("config is now {:?}", *latest.borrow());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn spawn_watch(&self) -> (ListenerHandle, watch::Receiver<Option<ServerConfig>>) {
+        let (latest_tx, latest_rx) = watch::channel(None);
+        let handle = self.spawn_with(move |config, _raw: &[u8], _meta: &EventMeta| {
+            latest_tx.send_replace(Some(config));
+        });
+        (handle, latest_rx)
+    }
+
+    /// Starts a listener on a background task that writes every configuration
+    /// into a lock that the rest of the application reads.
+    ///
+    /// Like [`spawn_watch`](Self::spawn_watch), but for code that reads the
+    /// configuration synchronously, wherever it needs it, rather than waiting
+    /// for changes. A reader holding the lock holds up the next update, so it
+    /// should not keep it across long operations. [`ListenerHandle::first_config`]
+    /// completes once the lock holds the first configuration, so startup can
+    /// wait for it.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use config_sdk::SseClientBuilder;
+    ///
+    /// # async fn run() -> Result<(), config_sdk::ConfigError> {
+    /// let (handle, shared) = SseClientBuilder::new("http://example.com/sse").build()?.spawn_shared();
+    /// handle.first_config().await?;
+    ///
+    /// let config = shared.read().unwrap();
+    /// println!("level is {}", config.as_ref().unwrap().settings["level"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn spawn_shared(&self) -> (ListenerHandle, SharedConfig) {
+        let shared: SharedConfig = Arc::new(RwLock::new(None));
+        let latest = Arc::clone(&shared);
+        let handle = self.spawn_with(move |config, _raw: &[u8], _meta: &EventMeta| {
+            *latest.write().unwrap_or_else(|e| e.into_inner()) = Some(config);
+        });
+        (handle, shared)
+    }
+
+    /// Starts a listener on a background task that sends every configuration to
+    /// a bounded `tokio::sync::mpsc` channel.
+    ///
+    /// Unlike [`spawn_watch`](Self::spawn_watch), which only keeps the latest
+    /// configuration, the receiver sees every event in order. The channel holds
+    /// up to [`event_channel_capacity`](SseClientBuilder::event_channel_capacity)
+    /// events; while it is full the listener does not read from the network, so
+    /// memory use stays bounded however slowly the receiver drains it. Wrap the
+    /// receiver in `tokio_stream::wrappers::ReceiverStream` to consume it as a
+    /// `Stream`. The listener stops with `Ok(())` once the receiver is dropped.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use config_sdk::SseClientBuilder;
+    ///
+    /// # async fn run() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/sse")
+    ///     .event_channel_capacity(4)
+    ///     .build()?;
+    /// let (_handle, mut events) = client.spawn_channel();
+    ///
+    /// while let Some(event) = events.recv().await {
+    ///     println!("{:?} from {:?}", event.config, event.meta.origin);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn spawn_channel(&self) -> (ListenerHandle, mpsc::Receiver<ConfigEvent>) {
+        let client = self.clone();
+        let (sender, receiver) = mpsc::channel(self.event_channel_capacity);
+        let (ready_tx, ready_rx) = ready_channel();
+        let remote = self.remote(1, false);
+        let listener_remote = Arc::clone(&remote);
+        let handle = ListenerHandle::spawn(
+            async move { listen_to_channel(&client, sender, ready_tx, &listener_remote).await },
+            ready_rx,
+            remote,
+        );
+        (handle, receiver)
+    }
+
+    /// Starts a listener on a background task that calls `update_handler` for
+    /// up to [`concurrency`](SseClientBuilder::concurrency) configurations at
+    /// once, and returns a handle to it.
+    ///
+    /// Handler calls run on tokio's blocking thread pool. Configurations with
+    /// the same value of the [ordering key](SseClientBuilder::ordering_key) are
+    /// handled one at a time, in the order they arrived; others may be handled
+    /// in any order. Slow handlers hold up reading from the network once their
+    /// worker's queue is full. Panics are caught and reported like for
+    /// [`spawn`](Self::spawn). Stopping the listener lets the workers finish the
+    /// configurations already queued, and [`ListenerHandle::join`] waits for
+    /// them unless the listener was aborted.
+    pub fn spawn_concurrent<F>(&self, update_handler: F) -> ListenerHandle
+    where
+        F: Fn(ServerConfig) + Send + Sync + 'static,
+    {
+        let client = self.clone();
+        let (ready_tx, ready_rx) = ready_channel();
+        let remote = self.remote(self.concurrency, false);
+        let listener_remote = Arc::clone(&remote);
+        ListenerHandle::spawn(
+            async move { listen_concurrently(&client, update_handler, ready_tx, &listener_remote).await },
+            ready_rx,
+            remote,
+        )
+    }
+
+    /// Starts a listener on a background task that calls every handler of
+    /// `handlers` for each configuration, and returns a handle to it.
+    ///
+    /// The handlers are called one after the other, in the order given, each
+    /// with its own copy of the configuration. Each call is isolated like the
+    /// single handler of [`spawn`](Self::spawn): a panic is caught and logged
+    /// with the handler's index, and the remaining handlers are still called
+    /// and the listener carries on. The panics of one configuration are passed
+    /// to the [error hook](SseClientBuilder::on_error) together, as
+    /// `ConfigError::HandlersPanicked` with the index of every handler that
+    /// panicked, and the event is only [acknowledged](SseClientBuilder::ack_url)
+    /// if none did. With [`catch_handler_panics`](SseClientBuilder::catch_handler_panics)
+    /// turned off, the first panic ends the listener instead.
+    ///
+    /// Handlers of different types can be passed as boxed closures.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use config_sdk::{ServerConfig, SseClientBuilder};
+    ///
+    /// # fn run() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/sse").build()?;
+    /// let handlers: Vec<Box<dyn FnMut(ServerConfig) + Send>> = vec![
+    ///     Box::new(|config: ServerConfig| println!("applying {} settings", config.settings.len())),
+    ///     Box::new(|_: ServerConfig| println!("invalidating caches")),
+    /// ];
+    /// let handle = client.spawn_fanout(handlers);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn spawn_fanout<I, F>(&self, handlers: I) -> ListenerHandle
+    where
+        I: IntoIterator<Item = F>,
+        F: FnMut(ServerConfig) + Send + 'static,
+    {
+        let client = self.clone();
+        let handlers: Vec<F> = handlers.into_iter().collect();
+        let (ready_tx, ready_rx) = ready_channel();
+        let remote = self.remote(handlers.len(), false);
+        let listener_remote = Arc::clone(&remote);
+        ListenerHandle::spawn(
+            async move { listen_fanout(&client, handlers, ready_tx, &listener_remote).await },
+            ready_rx,
+            remote,
+        )
+    }
+
+    pub(crate) fn spawn_with<F>(&self, mut dispatch: F) -> ListenerHandle
+    where
+        F: FnMut(ServerConfig, &[u8], &EventMeta) + Send + 'static,
+    {
+        let client = self.clone();
+        let (ready_tx, ready_rx) = ready_channel();
+        let remote = self.remote(1, false);
+        let listener_remote = Arc::clone(&remote);
+        ListenerHandle::spawn(
+            async move {
+                let dispatch = move |config, raw: &[u8], meta: &EventMeta| {
+                    dispatch(config, raw, meta);
+                    mark_ready(&ready_tx);
+                };
+                listen_controlled(&client, dispatch, &listener_remote).await
+            },
+            ready_rx,
+            remote,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ConfigOrigin;
+    use crate::test_server::{unused_url, Reply, TestServer};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc as std_mpsc;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn retry_budget_ends_the_listener() {
+        let result = SseClientBuilder::new(unused_url().await)
+            .initial_delay(Duration::from_millis(100))
+            .backoff_factor(1.0)
+            .max_retries(u32::MAX)
+            .max_total_retry_duration(Duration::from_millis(350))
+            .build()
+            .unwrap()
+            .listen(|_: ServerConfig| {})
+            .await;
+
+        let Err(ConfigError::GaveUp { total_attempts, elapsed, .. }) = result else {
+            panic!("expected the listener to give up, got {:?}", result);
+        };
+        assert_eq!(total_attempts, 5);
+        assert!(elapsed >= Duration::from_millis(350) && elapsed < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn retry_budget_does_not_count_time_connected() {
+        let server = TestServer::start([Reply::events("data: {\"level\": 3}\n\n")]).await;
+
+        let result = SseClientBuilder::new(&server.url)
+            .idle_timeout(Duration::from_millis(600))
+            .initial_delay(Duration::from_millis(100))
+            .backoff_factor(1.0)
+            .max_retries(u32::MAX)
+            .max_total_retry_duration(Duration::from_millis(300))
+            .build()
+            .unwrap()
+            .listen(|_: ServerConfig| {})
+            .await;
+
+        let Err(ConfigError::GaveUp { total_attempts, .. }) = result else {
+            panic!("expected the listener to give up, got {:?}", result);
+        };
+        // The connection, then retries for 300ms after it was dropped.
+        assert!(total_attempts > 2);
+    }
+
+    #[tokio::test]
+    async fn query_params_are_appended_encoded() {
+        let mut server = TestServer::start([Reply::Close(b"HTTP/1.1 204 No Content\r\n\r\n".to_vec())]).await;
+
+        let client = SseClientBuilder::new(&server.url)
+            .query_param("namespace", "billing")
+            .query_params([("env", "prod"), ("tags", "eu & us")])
+            .max_retries(1)
+            .build()
+            .unwrap();
+        let _ = client.listen(|_: ServerConfig| {}).await;
+
+        assert!(server.request().await.starts_with("get /sse?namespace=billing&env=prod&tags=eu+%26+us http/1.1"));
+    }
+
+    #[tokio::test]
+    async fn sticky_sessions_send_affinity_cookies_back() {
+        // A load balancer that pins the session to a backend on the first
+        // request, then goes silent, so the listener reconnects.
+        let mut server = TestServer::repeat(|| {
+            Reply::Hold(
+                b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+                  Set-Cookie: backend=b2; Path=/; HttpOnly\r\n\
+                  Set-Cookie: session=s1; Secure\r\n\r\ndata: {\"level\": 3}\n\n"
+                    .to_vec(),
+            )
+        })
+        .await;
+
+        let handle = SseClientBuilder::new(&server.url)
+            .sticky_sessions(true)
+            .idle_timeout(Duration::from_millis(100))
+            .build()
+            .unwrap()
+            .spawn(|_: ServerConfig| {});
+
+        assert!(!server.request().await.contains("cookie:"));
+        // The `Secure` cookie is not sent over plain `http`.
+        assert!(server.request().await.contains("cookie: backend=b2\r\n"));
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn rejected_content_type_stops_the_listener() {
+        let response = b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: 6\r\n\r\n<html>";
+        let server = TestServer::start([Reply::Close(response.to_vec())]).await;
+
+        let reported = Arc::new(AtomicBool::new(false));
+        let hook = reported.clone();
+        let result = SseClientBuilder::new(&server.url)
+            .content_type_policy(ContentTypePolicy::Reject)
+            .on_error(move |e: &ConfigError| hook.store(matches!(e, ConfigError::UnexpectedContentType(_)), Ordering::SeqCst))
+            .build()
+            .unwrap()
+            .listen(|_: ServerConfig| {})
+            .await;
+
+        assert!(matches!(result, Err(ConfigError::UnexpectedContentType(content_type)) if content_type == "text/html"));
+        assert!(reported.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn idle_reconnect_resumes_after_the_last_complete_event() {
+        // Event 8 is cut off before the blank line that ends it.
+        let mut server = TestServer::repeat(|| Reply::events("id: 7\ndata: {\"v\": 7}\n\nid: 8\ndata: {\"v\": 8")).await;
+
+        let handle = SseClientBuilder::new(&server.url)
+            .idle_timeout(Duration::from_millis(100))
+            .build()
+            .unwrap()
+            .spawn(|_: ServerConfig| {});
+
+        assert!(!server.request().await.contains("last-event-id:"));
+        assert!(server.request().await.contains("last-event-id: 7\r\n"));
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn request_on_a_closed_pooled_connection_is_retried_at_once() {
+        // The first connection is kept alive after a 503, then closed by the
+        // server as soon as the next request arrives on it.
+        let server = TestServer::start([
+            Reply::CloseOnReuse(b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n".to_vec()),
+            Reply::events("data: {\"level\": 3}\n\n"),
+        ])
+        .await;
+
+        // The second attempt succeeds on a fresh connection, without a third.
+        let handle = SseClientBuilder::new(&server.url)
+            .initial_delay(Duration::from_millis(10))
+            .max_retries(2)
+            .build()
+            .unwrap()
+            .spawn(|_: ServerConfig| {});
+        handle.first_config().await.unwrap();
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn flush_on_eof_dispatches_the_unterminated_event() {
+        // A server that closes the stream right after its last `data` line.
+        let server = TestServer::start([Reply::events_then_close("data: {\"settings\": {\"level\": 3}}")]).await;
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let handled = Arc::clone(&seen);
+        SseClientBuilder::new(&server.url)
+            .flush_on_eof(true)
+            .build()
+            .unwrap()
+            .listen(move |config: ServerConfig| handled.lock().unwrap().push(config.settings["level"].clone()))
+            .await
+            .unwrap();
+
+        assert_eq!(*seen.lock().unwrap(), [3]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn unanswered_connection_turns_stale() {
+        let server = TestServer::start([Reply::Silent]).await;
+
+        let (stale, seen) = std_mpsc::channel();
+        let handle = SseClientBuilder::new(&server.url)
+            .stale_after(Duration::from_millis(100))
+            .on_stale(move |unconfirmed| stale.send(unconfirmed).unwrap())
+            .build()
+            .unwrap()
+            .spawn(|_: ServerConfig| {});
+
+        assert!(seen.recv_timeout(Duration::from_secs(2)).unwrap() >= Duration::from_millis(100));
+        handle.abort();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stale_configuration_turns_fresh_on_reconnect() {
+        // The server sends a configuration and falls silent, drops the next
+        // few connections, then comes back.
+        let server = TestServer::serve(|connection| {
+            Some(match connection {
+                1..=4 => Reply::Close(Vec::new()),
+                _ => Reply::events("data: {\"level\": 3}\n\n"),
+            })
+        })
+        .await;
+
+        let (events, seen) = std_mpsc::channel();
+        let fresh = events.clone();
+        let handle = SseClientBuilder::new(&server.url)
+            .initial_delay(Duration::from_millis(50))
+            .backoff_factor(1.0)
+            .max_retries(20)
+            .idle_timeout(Duration::from_millis(100))
+            .stale_after(Duration::from_millis(100))
+            .on_stale(move |_| events.send("stale").unwrap())
+            .on_fresh(move |_| fresh.send("fresh").unwrap())
+            .build()
+            .unwrap()
+            .spawn(|_: ServerConfig| {});
+
+        assert_eq!(seen.recv_timeout(Duration::from_secs(5)).unwrap(), "stale");
+        assert_eq!(seen.recv_timeout(Duration::from_secs(5)).unwrap(), "fresh");
+        handle.abort();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reassert_passes_the_configuration_again() {
+        let server = TestServer::start([Reply::events("data: {\"level\": 3}\n\n")]).await;
+
+        let (events, seen) = std_mpsc::channel();
+        let handle = SseClientBuilder::new(&server.url)
+            .reassert_interval(Duration::from_millis(50))
+            .build()
+            .unwrap()
+            .spawn_for_events(move |event: ConfigEvent| events.send(event).unwrap());
+
+        let first = seen.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(first.meta.origin, ConfigOrigin::Stream);
+        for _ in 0..2 {
+            let again = seen.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert_eq!(again.meta.origin, ConfigOrigin::Reassert);
+            assert_eq!(again.config, first.config);
+        }
+        handle.abort();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn removed_configuration_is_not_reasserted() {
+        let server = TestServer::start([Reply::events("data: {\"level\": 3}\n\nevent: config-removed\ndata: {}\n\n")]).await;
+
+        let (events, seen) = std_mpsc::channel();
+        let handle = SseClientBuilder::new(&server.url)
+            .reassert_interval(Duration::from_millis(50))
+            .build()
+            .unwrap()
+            .spawn_for_events(move |event: ConfigEvent| events.send(event).unwrap());
+
+        let first = seen.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(first.config.settings["level"], 3);
+        // Several intervals pass without the removed configuration coming back.
+        assert!(seen.recv_timeout(Duration::from_millis(300)).is_err());
+        handle.abort();
+    }
+
+    #[cfg(feature = "status-server")]
+    #[tokio::test]
+    async fn status_server_reports_every_listener() {
+        let server = TestServer::repeat(|| Reply::events("id: 7\ndata: {\"level\": 3}\n\n")).await;
+
+        let address = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let client = SseClientBuilder::new(&server.url).status_address(address).build().unwrap();
+        // Both listeners are served on the same address.
+        let first = client.spawn(|_: ServerConfig| {});
+        let second = client.spawn(|_: ServerConfig| {});
+
+        let status = format!("http://{}/ready", address);
+        let health = loop {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            let Ok(response) = reqwest::get(&status).await else { continue };
+            let health: Value = response.json().await.unwrap();
+            if health["metrics"]["events"] == 2 {
+                break health;
+            }
+        };
+        assert_eq!(health["connected"], true);
+        assert_eq!(health["listeners"][0]["last_event_id"], "7");
+        assert_eq!(health["listeners"][1]["last_event_id"], "7");
+        first.abort();
+        second.abort();
+    }
+
+    #[tokio::test]
+    async fn fanout_isolates_panicking_handlers() {
+        let server = TestServer::start([Reply::events_then_close("data: {\"level\": 1}\n\ndata: {\"level\": 2}\n\n")]).await;
+
+        let failures = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&failures);
+        let client = SseClientBuilder::new(&server.url)
+            .max_retries(1)
+            .on_error(move |error: &ConfigError| {
+                if let ConfigError::HandlersPanicked(panics) = error {
+                    recorded.lock().unwrap().extend(panics.iter().map(|(handler, _)| *handler));
+                }
+            })
+            .build()
+            .unwrap();
+
+        let applied = Arc::new(Mutex::new(Vec::new()));
+        let (first, third) = (Arc::clone(&applied), Arc::clone(&applied));
+        let handlers: Vec<Box<dyn FnMut(ServerConfig) + Send>> = vec![
+            Box::new(move |config: ServerConfig| first.lock().unwrap().push(("first", config.settings["level"].clone()))),
+            Box::new(|_: ServerConfig| panic!("handler #2 is broken")),
+            Box::new(move |config: ServerConfig| third.lock().unwrap().push(("third", config.settings["level"].clone()))),
+        ];
+        let handle = client.spawn_fanout(handlers);
+        // The server closes the stream, so the listener ends after its retry.
+        let _ = handle.join().await;
+
+        // Both configurations reached the other handlers.
+        assert_eq!(*applied.lock().unwrap(), [("first", 1.into()), ("third", 1.into()), ("first", 2.into()), ("third", 2.into())]);
+        // The second handler's panics were reported with its index.
+        assert_eq!(*failures.lock().unwrap(), [1, 1]);
+    }
+}
@@ -0,0 +1,86 @@
+// clock.rs
+
+use futures::future::BoxFuture;
+use std::fmt;
+use std::time::{Duration, SystemTime};
+use tokio::time::Instant;
+
+/// The source of time used by the listener for backoff delays and timing.
+///
+/// The listener measures time with [`now`](Self::now), and only reads the
+/// wall-clock time from [`system_time`](Self::system_time) to tell whether a
+/// [maintenance window](crate::SseClientBuilder::maintenance_window) is on.
+///
+/// The default, [`TokioClock`], delegates to `tokio::time`, so it also follows a
+/// paused tokio clock (`#[tokio::test(start_paused = true)]`). Supplying a custom
+/// clock through [`SseClientBuilder::clock`](crate::SseClientBuilder::clock) lets
+/// tests observe or skip every wait without any real delay.
+///
+/// # Example
+///
+/// A clock that records the requested delays and returns immediately:
+///
+/// ```
+/// use config_sdk::{Clock, SseClientBuilder};
+/// use futures::future::BoxFuture;
+/// use std::sync::{Arc, Mutex};
+/// use std::time::Duration;
+/// use tokio::time::Instant;
+///
+/// #[derive(Debug, Clone, Default)]
+/// struct RecordingClock(Arc<Mutex<Vec<Duration>>>);
+///
+/// impl Clock for RecordingClock {
+///     fn now(&self) -> Instant {
+///         Instant::now()
+///     }
+///
+///     fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+///         self.0.lock().unwrap().push(duration);
+///         Box::pin(async {})
+///     }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let clock = RecordingClock::default();
+/// // Nothing listens on this port, so every attempt fails straight away.
+/// let result = SseClientBuilder::new("http://127.0.0.1:9/sse")
+///     .max_retries(3)
+///     .clock(clock.clone())
+///     .build()
+///     .unwrap()
+///     .listen(|_| {})
+///     .await;
+///
+/// assert!(result.is_err());
+/// assert_eq!(*clock.0.lock().unwrap(), [Duration::from_secs(2), Duration::from_secs(4)]);
+/// # }
+/// ```
+pub trait Clock: Send + Sync + fmt::Debug {
+    /// Returns the current instant.
+    fn now(&self) -> Instant;
+
+    /// Returns a future that completes after `duration` has elapsed.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+
+    /// Returns the current wall-clock time. Defaults to `SystemTime::now()`;
+    /// a clock that simulates the passing of time should override it too.
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// The default [`Clock`], backed by `tokio::time`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioClock;
+
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
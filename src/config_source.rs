@@ -0,0 +1,85 @@
+// config_source.rs
+
+use crate::models::ServerConfig;
+use config::{Map, Source, Value, ValueKind};
+use tokio::sync::watch;
+
+/// Name reported as the origin of every value provided by [`SseSource`].
+const ORIGIN: &str = "config-sse";
+
+/// A [`config::Source`] backed by the latest configuration received from the
+/// SSE stream.
+///
+/// Created from the receiver returned by
+/// [`SseClient::spawn_watch`](crate::SseClient::spawn_watch). Each time the
+/// `config::Config` is built, the source contributes the settings of the most
+/// recent `ServerConfig`, converted to `config::Value`s; before the first
+/// configuration arrives it contributes nothing. Because `config::Config` is a
+/// snapshot, rebuild it (for example whenever the watch channel changes) to pick
+/// up newer settings. Requires the `config-source` feature.
+///
+/// # Example
+///
+/// ```no_run
+/// use config_sdk::{SseClientBuilder, SseSource};
+///
+/// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+/// let (handle, latest) = SseClientBuilder::new("http://example.com/sse").build()?.spawn_watch();
+/// handle.first_config().await?;
+///
+/// let settings = config::Config::builder()
+///     .set_default("timeout", 30)?
+///     // Remote settings override the defaults...
+///     .add_source(SseSource::new(latest))
+///     // ...and environment variables override remote settings.
+///     .add_source(config::Environment::with_prefix("APP"))
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SseSource {
+    latest: watch::Receiver<Option<ServerConfig>>,
+}
+
+impl SseSource {
+    /// Creates a source that reads the configuration currently held by `latest`.
+    pub fn new(latest: watch::Receiver<Option<ServerConfig>>) -> Self {
+        SseSource { latest }
+    }
+}
+
+impl Source for SseSource {
+    fn clone_into_box(&self) -> Box<dyn Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<Map<String, Value>, config::ConfigError> {
+        let origin = ORIGIN.to_string();
+        let latest = self.latest.borrow();
+        Ok(latest
+            .iter()
+            .flat_map(|config| &config.settings)
+            .map(|(key, value)| (key.clone(), convert(&origin, value)))
+            .collect())
+    }
+}
+
+/// Converts a JSON value into the equivalent `config::Value`.
+fn convert(origin: &String, value: &serde_json::Value) -> Value {
+    let kind = match value {
+        serde_json::Value::Null => ValueKind::Nil,
+        serde_json::Value::Bool(b) => ValueKind::Boolean(*b),
+        serde_json::Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => ValueKind::I64(i),
+            (None, Some(u)) => ValueKind::U64(u),
+            _ => ValueKind::Float(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        serde_json::Value::String(s) => ValueKind::String(s.clone()),
+        serde_json::Value::Array(items) => ValueKind::Array(items.iter().map(|item| convert(origin, item)).collect()),
+        serde_json::Value::Object(fields) => ValueKind::Table(
+            fields.iter().map(|(key, value)| (key.clone(), convert(origin, value))).collect(),
+        ),
+    };
+    Value::new(Some(origin), kind)
+}
@@ -0,0 +1,170 @@
+// decoder.rs
+
+use crate::client::SseClient;
+use crate::errors::ConfigError;
+use crate::logger::{configure_logging, LogGuard};
+use crate::models::{EventMeta, ServerConfig};
+use crate::pipeline::Pipeline;
+use slog::{warn, Logger};
+use std::fmt;
+
+/// Turns the bytes of an SSE stream into configurations, leaving the
+/// networking to the caller.
+///
+/// The decoder is the listener's parsing pipeline without the connection
+/// around it, created with [`SseClient::decoder`]. It needs no async runtime,
+/// so it can be driven from a synchronous event loop or across an FFI
+/// boundary: read the stream however suits, and [`feed`](Self::feed) every
+/// chunk to the decoder as it arrives. Chunks do not have to line up with
+/// lines or events.
+///
+/// The client's options for parsing configurations apply: wire format,
+/// envelope, decryption, checksum, filters, merging, strict UTF-8 and the
+/// maximum line length. Errors are returned by `feed` rather than passed to the
+/// error hook. The cache, the dry run, the change history and everything about
+/// connections, such as retries and timeouts, are up to the caller and do not
+/// apply.
+pub struct ConfigDecoder<'a> {
+    sse_client: &'a SseClient,
+    log: Logger,
+    pipeline: Pipeline<'a, fn(ServerConfig, &[u8], &EventMeta)>,
+    // Declared last so that every record is flushed when the decoder is dropped.
+    _log_guard: LogGuard,
+}
+
+impl SseClient {
+    /// Creates a [`ConfigDecoder`] that parses SSE streams read by the caller
+    /// with this client's options.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::SseClientBuilder;
+    ///
+    /// # fn main() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/sse").build()?;
+    /// let mut decoder = client.decoder();
+    ///
+    /// // An event split across two reads, followed by one that is not JSON.
+    /// assert!(decoder.feed(b"id: 7\ndata: {\"level\"").is_empty());
+    /// let decoded = decoder.feed(b": 3}\n\ndata: not json\n\n");
+    ///
+    /// assert_eq!(decoded.len(), 2);
+    /// assert_eq!(decoded[0].as_ref().unwrap().settings["level"], 3);
+    /// assert!(decoded[1].is_err());
+    /// assert_eq!(decoder.last_event_id(), Some("7"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn decoder(&self) -> ConfigDecoder<'_> {
+        let (log, log_guard) = configure_logging(self.log_settings, self.metrics());
+        let dispatch: fn(ServerConfig, &[u8], &EventMeta) = |_config, _raw, _meta| {};
+        let pipeline = Pipeline::new(self, log.clone(), dispatch).without_cache().collecting();
+        ConfigDecoder { sse_client: self, log, pipeline, _log_guard: log_guard }
+    }
+}
+
+impl ConfigDecoder<'_> {
+    /// Feeds the next chunk of the stream and returns the configurations it
+    /// completed, and the errors it caused, in the order they occurred.
+    ///
+    /// An event that is still incomplete at the end of `bytes` is kept until
+    /// a later chunk completes it. A line longer than the client's maximum
+    /// line length is returned as an error; the rest of `bytes` is discarded
+    /// and the decoder starts over as if on a new stream.
+    ///
+    /// An event is decoded once the blank line that ends it arrives. Its
+    /// fields may come in any order, with comments in between, and its `data`
+    /// lines are joined by line feeds into a single payload.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::SseClientBuilder;
+    ///
+    /// # fn main() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/sse").build()?;
+    /// let mut decoder = client.decoder();
+    ///
+    /// let decoded = decoder.feed(
+    ///     b"data: {\"settings\": {\n\
+    ///       : a comment between data lines\n\
+    ///       event: update\n\
+    ///       data:   \"level\": 3,\n\
+    ///       retry: 5000\n\
+    ///       data:   \"name\": \"svc\"}}\n\
+    ///       id: 9\n\
+    ///       \n",
+    /// );
+    ///
+    /// assert_eq!(decoded.len(), 1);
+    /// let config = decoded[0].as_ref().unwrap();
+    /// assert_eq!(config.settings["level"], 3);
+    /// assert_eq!(config.settings["name"], "svc");
+    /// assert_eq!(decoder.last_event_id(), Some("9"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<Result<ServerConfig, ConfigError>> {
+        let (fed, _) = self.pipeline.feed_lines(bytes);
+        let mut decoded = self.pipeline.take_collected();
+        if let Err(e) = fed {
+            let max_line_length = self.sse_client.max_line_length.unwrap_or_default();
+            warn!(self.log, "SSE line exceeds the maximum line length, starting over";
+                "length" => e.length, "max_line_length" => max_line_length);
+            self.pipeline.start_stream();
+            decoded.push(Err(ConfigError::GenericError(format!(
+                "SSE line of at least {} bytes exceeds the maximum line length of {} bytes",
+                e.length, max_line_length
+            ))));
+        }
+        decoded
+    }
+
+    /// Starts decoding a new stream, e.g. after the caller reconnected,
+    /// discarding any partially received event. The last event ID, the
+    /// filters and merged configuration are kept.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::SseClientBuilder;
+    ///
+    /// # fn main() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/sse").build()?;
+    /// let mut decoder = client.decoder();
+    ///
+    /// // The connection drops in the middle of event 8.
+    /// decoder.feed(b"id: 7\ndata: {\"v\": 7}\n\nid: 8\ndata: {\"v\": 8");
+    /// decoder.reset();
+    ///
+    /// // So the stream resumes after event 7, and event 8 is sent again.
+    /// assert_eq!(decoder.last_event_id(), Some("7"));
+    /// let decoded = decoder.feed(b"id: 8\ndata: {\"v\": 8}\n\n");
+    /// assert_eq!(decoded[0].as_ref().unwrap().settings["v"], 8);
+    ///
+    /// // An empty ID resets the last event ID, so none is sent.
+    /// decoder.feed(b"id\n\n");
+    /// assert_eq!(decoder.last_event_id(), None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn reset(&mut self) {
+        self.pipeline.start_stream();
+    }
+
+    /// Returns the ID of the last complete event, to be sent as
+    /// `Last-Event-ID` when the caller reconnects, or `None` if the server
+    /// reset it with an empty `id` field.
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.pipeline.last_event_id()
+    }
+}
+
+impl fmt::Debug for ConfigDecoder<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConfigDecoder")
+            .field("last_event_id", &self.last_event_id())
+            .finish_non_exhaustive()
+    }
+}
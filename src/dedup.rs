@@ -0,0 +1,157 @@
+// dedup.rs
+
+use crate::models::ServerConfig;
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+
+/// Skips configurations whose version is not newer than the last one applied.
+///
+/// The version is read from an integer setting named by `key`. Configurations
+/// without that setting, or where it is not an integer, are always applied and
+/// leave the recorded version unchanged.
+#[derive(Debug)]
+pub(crate) struct VersionFilter {
+    key: String,
+    last_applied: Option<i64>,
+}
+
+impl VersionFilter {
+    pub(crate) fn new(key: String) -> Self {
+        VersionFilter { key, last_applied: None }
+    }
+
+    /// Returns `false` if `config` is older than, or as old as, the last
+    /// configuration admitted, and records its version otherwise.
+    pub(crate) fn admit(&mut self, config: &ServerConfig) -> bool {
+        let Some(version) = config.settings.get(&self.key).and_then(|v| v.as_i64()) else {
+            return true;
+        };
+        if self.last_applied.is_some_and(|last| version <= last) {
+            return false;
+        }
+        self.last_applied = Some(version);
+        true
+    }
+}
+
+/// Skips configurations equivalent to the last one applied, not counting a set
+/// of ignored keys.
+#[derive(Debug)]
+pub(crate) struct ChangeFilter {
+    ignored: Vec<String>,
+    last_applied: Option<ServerConfig>,
+}
+
+impl ChangeFilter {
+    pub(crate) fn new(ignored: Vec<String>) -> Self {
+        ChangeFilter { ignored, last_applied: None }
+    }
+
+    /// Returns `false` if `config` is equivalent to the last configuration
+    /// admitted, and records it otherwise.
+    pub(crate) fn admit(&mut self, config: &ServerConfig) -> bool {
+        let ignored: Vec<&str> = self.ignored.iter().map(String::as_str).collect();
+        if self.last_applied.as_ref().is_some_and(|last| last.equivalent_ignoring(config, &ignored)) {
+            return false;
+        }
+        self.last_applied = Some(config.clone());
+        true
+    }
+
+    /// Forgets the last configuration admitted, so the next one is admitted
+    /// whatever it holds.
+    pub(crate) fn forget(&mut self) {
+        self.last_applied = None;
+    }
+}
+
+/// Remembers the IDs of the most recently processed events, so events the
+/// server sends again, e.g. when it replays from `Last-Event-ID`, are skipped.
+#[derive(Debug)]
+pub(crate) struct SeenIds {
+    capacity: usize,
+    /// The IDs remembered, the one processed longest ago first.
+    order: VecDeque<Arc<str>>,
+    ids: HashSet<Arc<str>>,
+}
+
+impl SeenIds {
+    pub(crate) fn new(capacity: usize) -> Self {
+        SeenIds { capacity, order: VecDeque::new(), ids: HashSet::new() }
+    }
+
+    /// Returns `true` if `id` is one of the IDs remembered.
+    pub(crate) fn contains(&self, id: &str) -> bool {
+        self.ids.contains(id)
+    }
+
+    /// Remembers `id`, forgetting the ID processed longest ago if as many IDs
+    /// as the capacity are remembered already.
+    pub(crate) fn insert(&mut self, id: &str) {
+        if self.ids.contains(id) {
+            return;
+        }
+        if self.order.len() >= self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        let id: Arc<str> = Arc::from(id);
+        self.order.push_back(Arc::clone(&id));
+        self.ids.insert(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn config(settings: Value) -> ServerConfig {
+        serde_json::from_value(json!({ "settings": settings })).unwrap()
+    }
+
+    #[test]
+    fn version_filter_admits_only_newer_versions() {
+        let mut filter = VersionFilter::new("version".to_string());
+        assert!(filter.admit(&config(json!({"version": 2}))));
+        assert!(!filter.admit(&config(json!({"version": 2}))));
+        assert!(!filter.admit(&config(json!({"version": 1}))));
+        assert!(filter.admit(&config(json!({"version": 3}))));
+    }
+
+    #[test]
+    fn version_filter_admits_configurations_without_a_version() {
+        let mut filter = VersionFilter::new("version".to_string());
+        assert!(filter.admit(&config(json!({"version": 5}))));
+        assert!(filter.admit(&config(json!({"level": 1}))));
+        assert!(filter.admit(&config(json!({"version": "6"}))));
+        // Neither changed the recorded version.
+        assert!(!filter.admit(&config(json!({"version": 5}))));
+    }
+
+    #[test]
+    fn change_filter_skips_equivalent_configurations() {
+        let mut filter = ChangeFilter::new(vec!["timestamp".to_string()]);
+        assert!(filter.admit(&config(json!({"level": 1, "timestamp": 1}))));
+        assert!(!filter.admit(&config(json!({"level": 1, "timestamp": 2}))));
+        assert!(filter.admit(&config(json!({"level": 2, "timestamp": 2}))));
+
+        filter.forget();
+        assert!(filter.admit(&config(json!({"level": 2, "timestamp": 3}))));
+    }
+
+    #[test]
+    fn seen_ids_forget_the_oldest_id() {
+        let mut seen = SeenIds::new(2);
+        seen.insert("1");
+        seen.insert("2");
+        // Inserting an ID again does not make room for another.
+        seen.insert("1");
+        assert!(seen.contains("1") && seen.contains("2"));
+
+        seen.insert("3");
+        assert!(!seen.contains("1"));
+        assert!(seen.contains("2") && seen.contains("3"));
+    }
+}
@@ -0,0 +1,44 @@
+// dry_run.rs
+
+use crate::models::{ConfigDiff, ServerConfig};
+
+/// Tallies the events seen by a listener running in dry-run mode.
+///
+/// Valid configurations are compared with the previous valid one, so the
+/// listener can log what applying them would have changed.
+#[derive(Debug, Default)]
+pub(crate) struct DryRun {
+    pub(crate) valid: u64,
+    pub(crate) invalid: u64,
+    pub(crate) changed: u64,
+    previous: Option<ServerConfig>,
+}
+
+impl DryRun {
+    /// Uses `config` as the configuration later events are compared with,
+    /// without counting it as an event.
+    pub(crate) fn set_baseline(&mut self, config: ServerConfig) {
+        self.previous = Some(config);
+    }
+
+    /// Counts a configuration that parsed successfully and returns how it
+    /// differs from the previous one. The first configuration seen is
+    /// reported as adding every key.
+    pub(crate) fn record_valid(&mut self, config: ServerConfig) -> ConfigDiff {
+        let diff = match &self.previous {
+            Some(previous) => previous.diff(&config),
+            None => ServerConfig::default().diff(&config),
+        };
+        self.valid += 1;
+        if !diff.is_empty() {
+            self.changed += 1;
+        }
+        self.previous = Some(config);
+        diff
+    }
+
+    /// Counts a payload that could not be parsed.
+    pub(crate) fn record_invalid(&mut self) {
+        self.invalid += 1;
+    }
+}
@@ -0,0 +1,106 @@
+// endpoints.rs
+
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Default weight of an endpoint.
+pub const DEFAULT_ENDPOINT_WEIGHT: u32 = 1;
+
+/// Default time an endpoint is left out after a failed connection attempt.
+pub const DEFAULT_ENDPOINT_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Longest time an endpoint is left out, whatever the cooldown, so that adding
+/// it to an instant cannot overflow.
+const MAX_ENDPOINT_COOLDOWN: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// A URL serving the configuration stream, with its share of the clients.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Endpoint {
+    pub(crate) url: String,
+    pub(crate) weight: u32,
+}
+
+/// Chooses the endpoint a listener connects to.
+///
+/// An endpoint is picked at random, in proportion to its weight, when the
+/// listener starts and whenever the current endpoint has failed. A failed
+/// endpoint is left out for the cooldown; if every endpoint is cooling down,
+/// the one that becomes available first is used.
+#[derive(Debug)]
+pub(crate) struct EndpointSelector<'a> {
+    endpoints: &'a [Endpoint],
+    cooldown: Duration,
+    /// Until when each endpoint is left out after failing.
+    excluded_until: Vec<Option<Instant>>,
+    current: Option<usize>,
+    random: RandomState,
+    draws: u64,
+}
+
+impl<'a> EndpointSelector<'a> {
+    pub(crate) fn new(endpoints: &'a [Endpoint], cooldown: Duration) -> Self {
+        EndpointSelector {
+            endpoints,
+            cooldown,
+            excluded_until: vec![None; endpoints.len()],
+            current: None,
+            random: RandomState::new(),
+            draws: 0,
+        }
+    }
+
+    /// Returns `true` if there is more than one endpoint to choose from.
+    pub(crate) fn has_failover(&self) -> bool {
+        self.endpoints.len() > 1
+    }
+
+    /// Returns the endpoint to connect to at `now`, and whether it was newly
+    /// selected.
+    pub(crate) fn select(&mut self, now: Instant) -> (&'a Endpoint, bool) {
+        if let Some(current) = self.current.filter(|&current| !self.is_excluded(current, now)) {
+            return (&self.endpoints[current], false);
+        }
+        let available: Vec<usize> = (0..self.endpoints.len()).filter(|&index| !self.is_excluded(index, now)).collect();
+        let selected = if available.is_empty() {
+            (0..self.endpoints.len()).min_by_key(|&index| self.excluded_until[index]).unwrap_or(0)
+        } else {
+            self.pick_weighted(&available)
+        };
+        self.current = Some(selected);
+        (&self.endpoints[selected], true)
+    }
+
+    /// Leaves the current endpoint out for the cooldown, counting from `now`.
+    pub(crate) fn fail(&mut self, now: Instant) {
+        if let Some(current) = self.current.filter(|_| self.has_failover()) {
+            self.excluded_until[current] = Some(now + self.cooldown.min(MAX_ENDPOINT_COOLDOWN));
+        }
+    }
+
+    /// Lets the next [`select`](Self::select) pick an endpoint afresh, without
+    /// leaving the current one out.
+    pub(crate) fn release(&mut self) {
+        self.current = None;
+    }
+
+    fn is_excluded(&self, index: usize, now: Instant) -> bool {
+        self.excluded_until[index].is_some_and(|until| until > now)
+    }
+
+    /// Picks one of `candidates` at random, in proportion to their weights.
+    fn pick_weighted(&mut self, candidates: &[usize]) -> usize {
+        let total: u64 = candidates.iter().map(|&index| u64::from(self.endpoints[index].weight)).sum();
+        self.draws += 1;
+        let mut point = self.random.hash_one(self.draws) % total.max(1);
+        for &index in candidates {
+            let weight = u64::from(self.endpoints[index].weight);
+            if point < weight {
+                return index;
+            }
+            point -= weight;
+        }
+        candidates[0]
+    }
+}
@@ -1,7 +1,13 @@
 // src/errors.rs
+use crate::schema::SettingType;
+use std::error::Error as _;
+use std::fmt;
+use std::io;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum ConfigError {
     #[error("Request error: {0}")]
     Request(#[from] reqwest::Error),
@@ -9,12 +15,173 @@ pub enum ConfigError {
     #[error("JSON parsing error: {0}")]
     JsonParse(#[from] serde_json::Error),
 
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
     #[error("UTF-8 error: {0}")]
     Utf8Error(#[from] std::string::FromUtf8Error),
 
+    #[error("Invalid UTF-8 at byte {offset} of the stream: {context}")]
+    InvalidUtf8 { offset: u64, context: String },
+
+    #[error("Checksum mismatch: expected {}, computed {actual}", expected.as_deref().unwrap_or("none"))]
+    ChecksumMismatch { expected: Option<String>, actual: String },
+
+    #[error("Timed out: {0}")]
+    Timeout(TimeoutKind),
+
+    #[error("Unexpected HTTP status: {0}")]
+    HttpStatus(reqwest::StatusCode),
+
+    #[error("Unexpected content type: {0}")]
+    UnexpectedContentType(String),
+
+    /// The data of an event grew past the
+    /// [maximum event size](crate::SseClientBuilder::max_event_size), in
+    /// bytes, and the event was skipped.
+    #[error("Event data exceeds the maximum event size of {0} bytes")]
+    EventTooLarge(usize),
+
+    #[error("Configuration handler panicked: {0}")]
+    HandlerPanicked(String),
+
+    /// Handlers of a [fan-out listener](crate::SseClient::spawn_fanout) that
+    /// panicked on the same configuration, by their index in the fan-out set,
+    /// with their panic messages.
+    #[error("Configuration handlers panicked: {}", describe_panics(.0))]
+    HandlersPanicked(Vec<(usize, String)>),
+
+    #[error("Failed to acknowledge event {id}: {reason}")]
+    AckFailed { id: String, reason: String },
+
+    #[error("Maximum retries reached, giving up after {total_attempts} attempts in {elapsed:?}")]
+    GaveUp { total_attempts: u64, elapsed: Duration, last_status: Option<reqwest::StatusCode> },
+
     #[error("No configuration received")]
     NoConfigReceived,
 
+    #[error("Missing required environment variable: {0}")]
+    MissingEnvVar(String),
+
+    #[error("Invalid value {value:?} for environment variable {name}")]
+    InvalidEnvVar { name: String, value: String },
+
+    #[error("Setting {key} is not a valid {expected}: {value}")]
+    InvalidSetting { key: String, expected: SettingType, value: serde_json::Value },
+
+    #[cfg(feature = "grpc")]
+    #[error("gRPC error: {0}")]
+    Grpc(Box<tonic::Status>),
+
+    #[error("Refusing to connect without TLS to {0}")]
+    InsecureConnection(String),
+
+    #[error("Invalid client options: {0}")]
+    InvalidOptions(String),
+
     #[error("Configuration error: {0}")]
     GenericError(String),
 }
+
+/// Renders the panics of [`ConfigError::HandlersPanicked`].
+fn describe_panics(panics: &[(usize, String)]) -> String {
+    let panics: Vec<String> = panics.iter().map(|(handler, message)| format!("handler {}: {}", handler, message)).collect();
+    panics.join("; ")
+}
+
+/// Why a setting could not be read from a configuration with one of the typed
+/// getters of [`ServerConfig`](crate::ServerConfig), such as
+/// [`get`](crate::ServerConfig::get) or [`get_u64`](crate::ServerConfig::get_u64).
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum ConfigValueError {
+    /// The configuration has no setting with this key.
+    #[error("Setting {0} is missing")]
+    Missing(String),
+
+    /// The setting holds a JSON value of another type than the one asked for.
+    #[error("Setting {key} has type {found}, expected {expected}")]
+    WrongType { key: String, expected: &'static str, found: &'static str },
+
+    /// The setting could not be deserialized into the type asked for.
+    #[error("Setting could not be deserialized: {0}")]
+    Deserialize(serde_json::Error),
+}
+
+/// The stage of a connection at which a [`ConfigError::Timeout`] occurred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TimeoutKind {
+    /// The server did not answer the subscription request in time.
+    Connect,
+    /// Reading the event stream timed out.
+    Read,
+    /// The connection stayed open but delivered nothing for longer than the
+    /// [idle timeout](crate::SseClientBuilder::idle_timeout).
+    Idle,
+}
+
+/// What the listener does when reading the event stream fails, see
+/// [`SseClientBuilder::error_policy`](crate::SseClientBuilder::error_policy).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// The listener stops and returns the error.
+    #[default]
+    Terminate,
+    /// The error is passed to the error hook and the listener reconnects,
+    /// counting it as a failed connection attempt.
+    Reconnect,
+}
+
+impl fmt::Display for TimeoutKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TimeoutKind::Connect => "connecting to the server",
+            TimeoutKind::Read => "reading the event stream",
+            TimeoutKind::Idle => "no data received within the idle timeout",
+        })
+    }
+}
+
+/// How hyper describes a connection the server closed before answering.
+const INCOMPLETE_MESSAGE: &str = "connection closed before message completed";
+
+impl ConfigError {
+    /// Wraps `error`, turning a timeout into a [`ConfigError::Timeout`] of the
+    /// given kind.
+    pub(crate) fn from_request(error: reqwest::Error, kind: TimeoutKind) -> Self {
+        if error.is_timeout() {
+            ConfigError::Timeout(kind)
+        } else {
+            ConfigError::Request(error)
+        }
+    }
+
+    /// Whether a request or read failed because the server had closed the
+    /// connection, as happens to pooled keep-alive connections the server
+    /// closed while they sat idle.
+    pub(crate) fn is_connection_closed(&self) -> bool {
+        let ConfigError::Request(error) = self else {
+            return false;
+        };
+        let mut source = error.source();
+        while let Some(cause) = source {
+            // hyper's incomplete message error, which has no source and is not
+            // exposed by reqwest but through its message
+            if cause.to_string() == INCOMPLETE_MESSAGE {
+                return true;
+            }
+            if let Some(e) = cause.downcast_ref::<io::Error>() {
+                return matches!(
+                    e.kind(),
+                    io::ErrorKind::ConnectionReset
+                        | io::ErrorKind::ConnectionAborted
+                        | io::ErrorKind::BrokenPipe
+                        | io::ErrorKind::UnexpectedEof
+                );
+            }
+            source = cause.source();
+        }
+        false
+    }
+}
@@ -0,0 +1,62 @@
+// event_id.rs
+
+use crate::models::{ConfigOrigin, EventMeta, ServerConfig};
+use crate::repeat::fnv1a;
+
+/// How the listener identifies events the server sends without an ID, see
+/// [`SseClientBuilder::event_id_strategy`](crate::SseClientBuilder::event_id_strategy).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum EventIdStrategy {
+    /// Only the IDs sent by the server are used; events without one have none.
+    #[default]
+    Server,
+    /// Events are numbered `1`, `2`, `3`, … in the order the listener received
+    /// them, so IDs order events the way they arrived.
+    Counter,
+    /// Events are identified by a hash of their payload, written as 16
+    /// hexadecimal digits, so identical payloads get the same ID.
+    ContentHash,
+}
+
+/// Hands out IDs for the events of one listener, following an
+/// [`EventIdStrategy`].
+#[derive(Debug)]
+pub(crate) struct EventIds {
+    strategy: EventIdStrategy,
+    /// Events numbered so far.
+    counted: u64,
+}
+
+impl EventIds {
+    pub(crate) fn new(strategy: EventIdStrategy) -> Self {
+        EventIds { strategy, counted: 0 }
+    }
+
+    /// Returns the ID to report for an event received with `meta`, if the
+    /// server did not send one, carrying `config` parsed from `payload`.
+    ///
+    /// Configurations loaded from the cache are not events and get no ID. The
+    /// hash of a configuration whose payload was not kept is that of its
+    /// settings written as JSON.
+    pub(crate) fn synthesize(&mut self, meta: &EventMeta, config: &ServerConfig, payload: &[u8]) -> Option<String> {
+        if meta.id.is_some() || meta.origin != ConfigOrigin::Stream {
+            return None;
+        }
+        match self.strategy {
+            EventIdStrategy::Server => None,
+            EventIdStrategy::Counter => {
+                self.counted += 1;
+                Some(self.counted.to_string())
+            },
+            EventIdStrategy::ContentHash => {
+                let hash = if payload.is_empty() {
+                    fnv1a(&serde_json::to_vec(&config.settings).unwrap_or_default())
+                } else {
+                    fnv1a(payload)
+                };
+                Some(format!("{:016x}", hash))
+            },
+        }
+    }
+}
@@ -0,0 +1,114 @@
+// format.rs
+
+use crate::models::{ServerConfig, WireFormat};
+use serde::de::DeserializeSeed;
+#[cfg(any(feature = "yaml", feature = "toml"))]
+use serde::de::Error as _;
+use serde_json::Value;
+
+/// The encoding of the configuration in the `data` field of an event, see
+/// [`SseClientBuilder::payload_format`](crate::SseClientBuilder::payload_format).
+///
+/// Whatever the encoding, the configuration has the shape set by the
+/// [wire format](crate::SseClientBuilder::wire_format). A document spanning
+/// several lines, such as block-style YAML or TOML with tables, is sent as
+/// one `data:` line per line of the document, which are joined back together
+/// before it is parsed. YAML and TOML require the `yaml` and `toml` features.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PayloadFormat {
+    /// JSON, as in `{"settings": {"level": 3}}`.
+    #[default]
+    Json,
+    /// YAML, as in `settings: {level: 3}`. Keys have to be strings.
+    #[cfg(feature = "yaml")]
+    Yaml,
+    /// TOML, as in `settings = {level = 3}`. Dates and times become strings.
+    #[cfg(feature = "toml")]
+    Toml,
+}
+
+impl PayloadFormat {
+    /// Returns the format advertised by the `Content-Type` header
+    /// `content_type`, or `None` if it advertises none this build can parse.
+    ///
+    /// `application/json`, and any media type ending in `+json`, is JSON;
+    /// `application/yaml`, `application/x-yaml` and `text/yaml` are YAML;
+    /// `application/toml` is TOML. An event stream is always sent as
+    /// `text/event-stream`, so its payloads' format is read from a `format`
+    /// parameter instead, holding one of these media types or simply `json`,
+    /// `yaml` or `toml`. Case is ignored.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::PayloadFormat;
+    ///
+    /// assert_eq!(PayloadFormat::from_content_type("application/json; charset=utf-8"), Some(PayloadFormat::Json));
+    /// assert_eq!(PayloadFormat::from_content_type("text/event-stream; format=json"), Some(PayloadFormat::Json));
+    /// assert_eq!(PayloadFormat::from_content_type("text/event-stream"), None);
+    /// assert_eq!(PayloadFormat::from_content_type("text/plain"), None);
+    /// ```
+    pub fn from_content_type(content_type: &str) -> Option<PayloadFormat> {
+        let mut parts = content_type.split(';');
+        let media_type = parts.next()?.trim();
+        if !media_type.eq_ignore_ascii_case("text/event-stream") {
+            return named(media_type);
+        }
+        let (_, hint) = parts
+            .filter_map(|parameter| parameter.split_once('='))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("format"))?;
+        named(hint.trim().trim_matches('"'))
+    }
+
+    /// Parses a configuration of the shape `wire_format` from `payload`.
+    pub(crate) fn parse(self, payload: &[u8], wire_format: WireFormat) -> Result<ServerConfig, serde_json::Error> {
+        if self == PayloadFormat::Json {
+            return wire_format.parse(payload);
+        }
+        wire_format.deserialize(self.to_value(payload)?)
+    }
+
+    /// Parses the document in `payload`. Errors are returned as
+    /// `serde_json::Error`s whatever the format, so they are reported alike.
+    pub(crate) fn to_value(self, payload: &[u8]) -> Result<Value, serde_json::Error> {
+        match self {
+            PayloadFormat::Json => serde_json::from_slice(payload),
+            #[cfg(feature = "yaml")]
+            PayloadFormat::Yaml => serde_yaml::from_slice(payload).map_err(serde_json::Error::custom),
+            #[cfg(feature = "toml")]
+            PayloadFormat::Toml => {
+                let payload = std::str::from_utf8(payload).map_err(serde_json::Error::custom)?;
+                let table: toml::Table = toml::from_str(payload).map_err(serde_json::Error::custom)?;
+                Ok(toml_to_json(toml::Value::Table(table)))
+            },
+        }
+    }
+}
+
+/// Returns the format named by the media type or short name `name`.
+fn named(name: &str) -> Option<PayloadFormat> {
+    match name.to_ascii_lowercase().as_str() {
+        "json" | "application/json" => Some(PayloadFormat::Json),
+        name if name.ends_with("+json") => Some(PayloadFormat::Json),
+        #[cfg(feature = "yaml")]
+        "yaml" | "application/yaml" | "application/x-yaml" | "text/yaml" => Some(PayloadFormat::Yaml),
+        #[cfg(feature = "toml")]
+        "toml" | "application/toml" => Some(PayloadFormat::Toml),
+        _ => None,
+    }
+}
+
+/// Converts a TOML value to JSON, dates and times becoming strings.
+#[cfg(feature = "toml")]
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(string) => Value::String(string),
+        toml::Value::Integer(integer) => Value::from(integer),
+        toml::Value::Float(float) => Value::from(float),
+        toml::Value::Boolean(boolean) => Value::Bool(boolean),
+        toml::Value::Datetime(datetime) => Value::String(datetime.to_string()),
+        toml::Value::Array(items) => items.into_iter().map(toml_to_json).collect(),
+        toml::Value::Table(table) => table.into_iter().map(|(key, value)| (key, toml_to_json(value))).collect(),
+    }
+}
@@ -0,0 +1,348 @@
+// framing.rs
+
+use crate::parser::LineTooLong;
+use serde::de::IgnoredAny;
+
+/// How the events of a stream are delimited, see
+/// [`SseClientBuilder::framing`](crate::SseClientBuilder::framing).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Framing {
+    /// Server-Sent Events: `data:` and other fields, one per line, with a
+    /// blank line ending every event.
+    #[default]
+    Sse,
+    /// Every payload is preceded by its length in bytes, as a 32-bit unsigned
+    /// big-endian integer. Empty payloads are ignored, so a server can send
+    /// four zero bytes to keep the connection alive.
+    LengthPrefixed,
+    /// Payloads are separated by the given byte, such as the ASCII record
+    /// separator `0x1E` of JSON text sequences (RFC 7464).
+    ///
+    /// ASCII whitespace around a payload is ignored, and so are empty
+    /// payloads, so the separator may come before every payload, after it or
+    /// both. A payload is complete when the next separator arrives, or, for
+    /// JSON payloads, as soon as it is followed by a line feed and holds a
+    /// complete JSON text, so a server that ends every payload with a line
+    /// feed, as RFC 7464 requires, does not have to send the next one first.
+    Delimited(u8),
+}
+
+/// Splits a byte stream into payloads according to a [`Framing`] other than
+/// SSE.
+///
+/// Like the [`LineParser`](crate::parser::LineParser), it keeps the part of a
+/// payload received so far until the rest of it arrives, in a buffer that
+/// keeps its capacity between payloads.
+#[derive(Debug)]
+pub(crate) struct FrameSplitter {
+    framing: Framing,
+    /// The bytes of the current payload received so far, including the
+    /// length prefix of a length-prefixed one.
+    partial: Vec<u8>,
+    /// Longest payload accepted, in bytes.
+    max_length: Option<usize>,
+    /// Whether a line feed completes a payload holding a complete JSON text.
+    json: bool,
+    /// The structure of the delimited payload received so far.
+    scan: JsonScan,
+}
+
+/// Tracks the nesting of a JSON text as its bytes arrive, so telling whether
+/// it may be complete does not take parsing it again at every line.
+#[derive(Debug, Default)]
+struct JsonScan {
+    /// Arrays and objects opened and not yet closed.
+    depth: usize,
+    in_string: bool,
+    /// Whether the previous byte was a backslash inside a string.
+    escaped: bool,
+    /// Whether anything but whitespace was seen.
+    content: bool,
+    /// Set once the bytes cannot be the start of a single JSON text.
+    invalid: bool,
+}
+
+/// Number of bytes of the length prefix of [`Framing::LengthPrefixed`].
+const LENGTH_PREFIX: usize = 4;
+
+impl FrameSplitter {
+    /// Creates a splitter for `framing`, or `None` for SSE, which is split into
+    /// lines instead. Payloads longer than `max_length` bytes, if set, are
+    /// rejected.
+    pub(crate) fn new(framing: Framing, max_length: Option<usize>) -> Option<Self> {
+        (framing != Framing::Sse).then(|| FrameSplitter {
+            framing,
+            partial: Vec::new(),
+            max_length,
+            json: true,
+            scan: JsonScan::default(),
+        })
+    }
+
+    /// Sets whether payloads are JSON, so a line feed may complete them. Other
+    /// formats are only complete when the separator arrives.
+    pub(crate) fn json_payloads(&mut self, json: bool) {
+        self.json = json;
+    }
+
+    /// Feeds `chunk` into the splitter and calls `on_frame` for every payload
+    /// it completes.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LineTooLong`] as soon as a payload exceeds the maximum length.
+    /// Payloads completed before it have already been passed to `on_frame`;
+    /// the splitter should be [`reset`](Self::reset) before it is used again.
+    pub(crate) fn feed(&mut self, chunk: &[u8], mut on_frame: impl FnMut(&[u8])) -> Result<(), LineTooLong> {
+        match self.framing {
+            Framing::Sse => Ok(()),
+            Framing::LengthPrefixed => self.feed_length_prefixed(chunk, &mut on_frame),
+            Framing::Delimited(separator) => self.feed_delimited(chunk, separator, &mut on_frame),
+        }
+    }
+
+    fn feed_length_prefixed(&mut self, mut chunk: &[u8], on_frame: &mut impl FnMut(&[u8])) -> Result<(), LineTooLong> {
+        loop {
+            if self.partial.len() < LENGTH_PREFIX {
+                if chunk.is_empty() {
+                    return Ok(());
+                }
+                let taken = chunk.len().min(LENGTH_PREFIX - self.partial.len());
+                self.partial.extend_from_slice(&chunk[..taken]);
+                chunk = &chunk[taken..];
+                continue;
+            }
+            let length = u32::from_be_bytes([self.partial[0], self.partial[1], self.partial[2], self.partial[3]]);
+            let length = usize::try_from(length).unwrap_or(usize::MAX);
+            self.check_length(length)?;
+            let taken = chunk.len().min(LENGTH_PREFIX + length - self.partial.len());
+            self.partial.extend_from_slice(&chunk[..taken]);
+            chunk = &chunk[taken..];
+            if self.partial.len() < LENGTH_PREFIX + length {
+                return Ok(());
+            }
+            if length > 0 {
+                on_frame(&self.partial[LENGTH_PREFIX..]);
+            }
+            self.partial.clear();
+        }
+    }
+
+    fn feed_delimited(&mut self, chunk: &[u8], separator: u8, on_frame: &mut impl FnMut(&[u8])) -> Result<(), LineTooLong> {
+        let mut rest = chunk;
+        while let Some(position) = rest.iter().position(|&b| b == separator || b == b'\n') {
+            let (piece, next) = (&rest[..position], rest[position]);
+            rest = &rest[position + 1..];
+            self.check_length(self.partial.len() + piece.len())?;
+            if next == separator {
+                if self.partial.is_empty() {
+                    emit_trimmed(piece, on_frame);
+                } else {
+                    self.partial.extend_from_slice(piece);
+                    emit_trimmed(&self.partial, on_frame);
+                    self.clear();
+                }
+                continue;
+            }
+            self.append(piece);
+            self.partial.push(b'\n');
+            if self.json && self.scan.may_be_complete() {
+                // Parsed at most once per payload: if it is not valid JSON now,
+                // no more lines can make it so.
+                if is_complete_json(&self.partial) {
+                    emit_trimmed(&self.partial, on_frame);
+                    self.clear();
+                } else {
+                    self.scan.invalid = true;
+                }
+            }
+        }
+        self.check_length(self.partial.len() + rest.len())?;
+        self.append(rest);
+        Ok(())
+    }
+
+    /// Appends `bytes` to the partial payload, following its JSON structure.
+    fn append(&mut self, bytes: &[u8]) {
+        self.partial.extend_from_slice(bytes);
+        if self.json {
+            self.scan.feed(bytes);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.partial.clear();
+        self.scan = JsonScan::default();
+    }
+
+    /// Passes the payload left incomplete when the stream ended to `on_frame`,
+    /// if it can be complete without what is missing. Returns `true` if there
+    /// was such a payload.
+    pub(crate) fn finish(&mut self, mut on_frame: impl FnMut(&[u8])) -> bool {
+        let finished = match self.framing {
+            Framing::Delimited(_) => emit_trimmed(&self.partial, &mut on_frame),
+            // The rest of a length-prefixed payload cannot be guessed.
+            Framing::Sse | Framing::LengthPrefixed => false,
+        };
+        self.clear();
+        finished
+    }
+
+    /// Discards any partially received payload, e.g. after a reconnect.
+    pub(crate) fn reset(&mut self) {
+        self.clear();
+    }
+
+    fn check_length(&self, length: usize) -> Result<(), LineTooLong> {
+        match self.max_length {
+            Some(limit) if length > limit => Err(LineTooLong { length }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Passes `frame` without the ASCII whitespace around it to `on_frame`, unless
+/// nothing is left. Returns whether it was passed.
+fn emit_trimmed(frame: &[u8], on_frame: &mut impl FnMut(&[u8])) -> bool {
+    let frame = frame.trim_ascii();
+    if !frame.is_empty() {
+        on_frame(frame);
+    }
+    !frame.is_empty()
+}
+
+impl JsonScan {
+    fn feed(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if self.invalid {
+                return;
+            }
+            if self.in_string {
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => self.in_string = false,
+                    _ => {},
+                }
+                continue;
+            }
+            match byte {
+                b'"' => self.in_string = true,
+                b'{' | b'[' => self.depth += 1,
+                b'}' | b']' => match self.depth.checked_sub(1) {
+                    Some(depth) => self.depth = depth,
+                    None => self.invalid = true,
+                },
+                _ => {},
+            }
+            self.content |= !byte.is_ascii_whitespace();
+        }
+    }
+
+    /// Returns `true` if the bytes seen so far are balanced, so they may hold
+    /// a complete JSON text.
+    fn may_be_complete(&self) -> bool {
+        self.content && !self.invalid && !self.in_string && self.depth == 0
+    }
+}
+
+/// Returns `true` if `bytes` hold exactly one JSON text, and more than
+/// whitespace.
+fn is_complete_json(bytes: &[u8]) -> bool {
+    !bytes.trim_ascii().is_empty() && serde_json::from_slice::<IgnoredAny>(bytes).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds every chunk of `chunks` into `splitter`, collecting the payloads.
+    fn feed(splitter: &mut FrameSplitter, chunks: &[&[u8]]) -> Result<Vec<String>, LineTooLong> {
+        let mut frames = Vec::new();
+        for chunk in chunks {
+            splitter.feed(chunk, |frame| frames.push(String::from_utf8_lossy(frame).into_owned()))?;
+        }
+        Ok(frames)
+    }
+
+    #[test]
+    fn sse_has_no_splitter() {
+        assert!(FrameSplitter::new(Framing::Sse, None).is_none());
+    }
+
+    #[test]
+    fn splits_length_prefixed_payloads_across_chunks() {
+        let mut splitter = FrameSplitter::new(Framing::LengthPrefixed, None).unwrap();
+        let frames = feed(&mut splitter, &[b"\0\0", b"\0\x02{}\0\0\0\0\0\0\0\x03[", b"1]"]).unwrap();
+        // The empty payload in between is a keep-alive.
+        assert_eq!(frames, ["{}", "[1]"]);
+    }
+
+    #[test]
+    fn rejects_length_prefixes_past_the_limit() {
+        let mut splitter = FrameSplitter::new(Framing::LengthPrefixed, Some(2)).unwrap();
+        // Rejected from the prefix alone, before the payload arrives.
+        assert_eq!(feed(&mut splitter, &[b"\0\0\0\x03"]), Err(LineTooLong { length: 3 }));
+    }
+
+    #[test]
+    fn splits_delimited_payloads() {
+        let mut splitter = FrameSplitter::new(Framing::Delimited(0x1e), None).unwrap();
+        let frames = feed(&mut splitter, &[b"\x1e{\"a\": 1}\x1e \x1e{\"a\"", b": 2}\x1e"]).unwrap();
+        assert_eq!(frames, ["{\"a\": 1}", "{\"a\": 2}"]);
+    }
+
+    #[test]
+    fn line_feed_completes_a_complete_json_text() {
+        let mut splitter = FrameSplitter::new(Framing::Delimited(0x1e), None).unwrap();
+        let frames = feed(&mut splitter, &[b"\x1e{\"a\":\n", b"\"}\\n\"}\n\x1e[1,\n2]\n"]).unwrap();
+        assert_eq!(frames, ["{\"a\":\n\"}\\n\"}", "[1,\n2]"]);
+    }
+
+    #[test]
+    fn line_feed_does_not_complete_other_formats() {
+        let mut splitter = FrameSplitter::new(Framing::Delimited(b';'), None).unwrap();
+        splitter.json_payloads(false);
+        assert_eq!(feed(&mut splitter, &[b"level: 3\n"]).unwrap(), Vec::<String>::new());
+        assert_eq!(feed(&mut splitter, &[b"name: eu\n;"]).unwrap(), ["level: 3\nname: eu"]);
+    }
+
+    #[test]
+    fn invalid_json_waits_for_the_separator() {
+        let mut splitter = FrameSplitter::new(Framing::Delimited(0x1e), None).unwrap();
+        assert_eq!(feed(&mut splitter, &[b"{\"a\" 1}\n{}\n"]).unwrap(), Vec::<String>::new());
+        assert_eq!(feed(&mut splitter, &[b"\x1e"]).unwrap(), ["{\"a\" 1}\n{}"]);
+    }
+
+    #[test]
+    fn rejects_delimited_payloads_past_the_limit() {
+        let mut splitter = FrameSplitter::new(Framing::Delimited(0x1e), Some(4)).unwrap();
+        assert_eq!(feed(&mut splitter, &[b"[1,2]\x1e"]), Err(LineTooLong { length: 5 }));
+        splitter.reset();
+        assert_eq!(feed(&mut splitter, &[b"[1,", b"2,"]), Err(LineTooLong { length: 5 }));
+    }
+
+    #[test]
+    fn finish_passes_a_trailing_delimited_payload() {
+        let mut splitter = FrameSplitter::new(Framing::Delimited(b';'), None).unwrap();
+        splitter.json_payloads(false);
+        feed(&mut splitter, &[b"a;b "]).unwrap();
+        let mut frames = Vec::new();
+        assert!(splitter.finish(|frame| frames.push(frame.to_vec())));
+        assert_eq!(frames, [b"b"]);
+        assert!(!splitter.finish(|_| panic!("nothing is left")));
+
+        let mut splitter = FrameSplitter::new(Framing::LengthPrefixed, None).unwrap();
+        feed(&mut splitter, &[b"\0\0\0\x05[1]"]).unwrap();
+        assert!(!splitter.finish(|_| panic!("an incomplete payload was passed")));
+    }
+
+    #[test]
+    fn reset_discards_the_partial_payload() {
+        let mut splitter = FrameSplitter::new(Framing::LengthPrefixed, None).unwrap();
+        feed(&mut splitter, &[b"\0\0\0\x09{\"a\""]).unwrap();
+        splitter.reset();
+        assert_eq!(feed(&mut splitter, &[b"\0\0\0\x02{}"]).unwrap(), ["{}"]);
+    }
+}
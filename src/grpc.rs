@@ -0,0 +1,176 @@
+// grpc.rs
+
+use crate::client::SseClient;
+use crate::errors::ConfigError;
+use crate::framing::Framing;
+use crate::listener::{run_with, Controls};
+use crate::models::{EventMeta, ServerConfig, WireFormat};
+use crate::transport::{Connection, Transport};
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use futures::stream::{self, Stream, StreamExt};
+use reqwest::{StatusCode, Version};
+use slog::{warn, Logger};
+use std::future::Future;
+use tonic::{Code, Response, Status};
+
+impl SseClient {
+    /// Listens for configurations streamed by a gRPC server-streaming RPC,
+    /// passing each one to `update_handler` like [`listen`](Self::listen).
+    ///
+    /// `subscribe` calls the RPC, typically through a client generated by
+    /// `tonic-build`, and is called again whenever the listener reconnects.
+    /// Every message is converted into a `ServerConfig` and goes through the
+    /// same machinery as an SSE event: reconnection and backoff, the status
+    /// policy, filters, merging, the cache, metrics and the hooks. The client's
+    /// URL only identifies the stream in logs.
+    ///
+    /// A failed call is handled like an HTTP response with the status gRPC
+    /// gateways map its code to, so e.g. `UNAVAILABLE` (503) is retried while
+    /// `UNAUTHENTICATED` (401) stops the listener under the default
+    /// [status policy](crate::SseClientBuilder::status_policy). A stream that
+    /// fails after it started is a read error, handled according to the
+    /// [error policy](crate::SseClientBuilder::error_policy) and reported as
+    /// `ConfigError::Grpc`. As with SSE, the listener returns once the server
+    /// ends the stream. Requires the `grpc` feature.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::InvalidOptions` without calling the RPC if the
+    /// client decrypts payloads, extracts them from an envelope, or sets a
+    /// payload format or a framing other than SSE, since messages have none
+    /// of these. Otherwise, see
+    /// [`start_listening_for_updates`](crate::start_listening_for_updates).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{ServerConfig, SseClientBuilder};
+    /// use futures::stream;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// // A message type as generated from a `.proto` file.
+    /// struct ConfigMessage {
+    ///     level: i64,
+    /// }
+    ///
+    /// impl From<ConfigMessage> for ServerConfig {
+    ///     fn from(message: ConfigMessage) -> Self {
+    ///         ServerConfig::builder().set("level", message.level).build()
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), config_sdk::ConfigError> {
+    /// let seen = Arc::new(Mutex::new(Vec::new()));
+    /// let handled = Arc::clone(&seen);
+    ///
+    /// SseClientBuilder::new("grpc://config.example.com")
+    ///     .build()?
+    ///     .listen_grpc(
+    ///         // With a generated client: `|| { let mut c = client.clone(); async move { c.watch(request).await } }`
+    ///         || async {
+    ///             let messages = vec![Ok(ConfigMessage { level: 1 }), Ok(ConfigMessage { level: 2 })];
+    ///             Ok(tonic::Response::new(stream::iter(messages)))
+    ///         },
+    ///         move |config: ServerConfig| handled.lock().unwrap().push(config.settings["level"].clone()),
+    ///     )
+    ///     .await?;
+    ///
+    /// assert_eq!(*seen.lock().unwrap(), [1, 2]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn listen_grpc<C, Fut, S, M, F>(&self, subscribe: C, mut update_handler: F) -> Result<(), ConfigError>
+    where
+        C: FnMut() -> Fut + Send,
+        Fut: Future<Output = Result<Response<S>, Status>> + Send,
+        S: Stream<Item = Result<M, Status>> + Send + 'static,
+        M: Into<ServerConfig>,
+        F: FnMut(ServerConfig) + Send + 'static,
+    {
+        let conflicting = [
+            ("decrypt", self.decrypt.is_some()),
+            ("envelope_pointer", self.envelope_pointer.is_some()),
+            ("payload_format", self.payload_format.is_some()),
+            ("framing", self.framing != Framing::Sse),
+        ];
+        if let Some((option, _)) = conflicting.iter().find(|(_, set)| *set) {
+            return Err(ConfigError::InvalidOptions(format!("{} cannot be used with gRPC", option)));
+        }
+        let mut transport = GrpcTransport { subscribe, wire_format: self.wire_format };
+        let dispatch = move |config, _raw: &[u8], _meta: &EventMeta| update_handler(config);
+        run_with(self, &mut transport, dispatch, Controls::default()).await
+    }
+}
+
+/// Opens every connection by calling a server-streaming RPC, and turns its
+/// messages into the SSE events the listener reads.
+struct GrpcTransport<C> {
+    subscribe: C,
+    /// The shape the listener parses configurations in.
+    wire_format: WireFormat,
+}
+
+impl<C, Fut, S, M> Transport for GrpcTransport<C>
+where
+    C: FnMut() -> Fut + Send,
+    Fut: Future<Output = Result<Response<S>, Status>> + Send,
+    S: Stream<Item = Result<M, Status>> + Send + 'static,
+    M: Into<ServerConfig>,
+{
+    fn open<'a>(
+        &'a mut self,
+        url: &'a str,
+        _last_event_id: Option<&'a str>,
+        log: &'a Logger,
+    ) -> BoxFuture<'a, Result<Connection, ConfigError>> {
+        Box::pin(async move {
+            let wire_format = self.wire_format;
+            let (status, body) = match (self.subscribe)().await {
+                Ok(response) => {
+                    let messages = response.into_inner().map(move |message| match message {
+                        Ok(message) => encode(&message.into(), wire_format),
+                        Err(status) => Err(ConfigError::Grpc(Box::new(status))),
+                    });
+                    (StatusCode::OK, messages.boxed())
+                },
+                Err(status) => {
+                    warn!(log, "gRPC call failed"; "code" => ?status.code(), "message" => status.message());
+                    (http_status(status.code()), stream::empty().boxed())
+                },
+            };
+            Ok(Connection { status, url: url.to_string(), version: Version::HTTP_2, content_type: None, body })
+        })
+    }
+}
+
+/// Writes `config` as the `data` field of an SSE event, in the shape the
+/// listener parses.
+fn encode(config: &ServerConfig, wire_format: WireFormat) -> Result<Bytes, ConfigError> {
+    let mut event = b"data: ".to_vec();
+    match wire_format {
+        WireFormat::Flat => serde_json::to_writer(&mut event, &config.settings)?,
+        _ => serde_json::to_writer(&mut event, config)?,
+    }
+    event.extend_from_slice(b"\n\n");
+    Ok(event.into())
+}
+
+/// The HTTP status gRPC gateways answer with for `code`.
+fn http_status(code: Code) -> StatusCode {
+    match code {
+        Code::Ok => StatusCode::OK,
+        Code::Cancelled => StatusCode::from_u16(499).unwrap_or(StatusCode::BAD_REQUEST),
+        Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => StatusCode::BAD_REQUEST,
+        Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        Code::NotFound => StatusCode::NOT_FOUND,
+        Code::AlreadyExists | Code::Aborted => StatusCode::CONFLICT,
+        Code::PermissionDenied => StatusCode::FORBIDDEN,
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+        Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        Code::Unknown | Code::Internal | Code::DataLoss => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
@@ -0,0 +1,454 @@
+// handle.rs
+
+use crate::errors::ConfigError;
+use crate::history::{ChangeHistory, ConfigChangeRecord};
+use crate::metrics::Metrics;
+use crate::rollback::{RollbackRequest, Snapshots};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{watch, Notify};
+use tokio::task::JoinHandle;
+
+/// A handle to a listener running in the background.
+///
+/// Returned by [`SseClient::spawn`](crate::SseClient::spawn) and
+/// [`spawn_listener`](crate::spawn_listener). Dropping the handle does not stop
+/// the listener; use [`ListenerHandle::abort`] for that.
+#[derive(Debug)]
+pub struct ListenerHandle {
+    task: JoinHandle<Result<(), ConfigError>>,
+    ready: watch::Receiver<bool>,
+    remote: Arc<Remote>,
+}
+
+/// What a listener dispatches and how, as returned by
+/// [`ListenerHandle::dispatch_info`].
+///
+/// It reflects the options the listener was started with, to confirm what it
+/// does with the configurations it receives, e.g. when a handler does not
+/// fire.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DispatchInfo {
+    /// How many handlers configurations are dispatched to: one, one per
+    /// worker for [`SseClient::spawn_concurrent`](crate::SseClient::spawn_concurrent),
+    /// or the number of handlers for [`SseClient::spawn_fanout`](crate::SseClient::spawn_fanout).
+    /// Zero if the listener could not be started.
+    pub handlers: usize,
+    /// Whether payloads are dispatched as received, without being parsed.
+    pub raw: bool,
+    /// The query parameters the listener subscribes with, such as those set
+    /// with [`SseClientBuilder::query_param`](crate::SseClientBuilder::query_param).
+    pub subscription: Vec<(String, String)>,
+    /// The event type that removes the configuration instead of carrying
+    /// one. Events of every other type are dispatched.
+    pub removal_event: String,
+    /// The options that can keep a configuration from reaching the handlers,
+    /// by their builder method names, in the order they are checked.
+    pub filters: Vec<&'static str>,
+    /// The options that change a configuration before it reaches the
+    /// handlers, by their builder method names, in the order they apply.
+    pub transforms: Vec<&'static str>,
+}
+
+/// Requests made through a [`ListenerHandle`] to the listener it controls.
+#[derive(Debug)]
+pub(crate) struct Remote {
+    /// Notified when the listener should reconnect right away.
+    pub(crate) reconnect: Notify,
+    /// Notified when the listener is resumed.
+    pub(crate) resumed: Notify,
+    paused: AtomicBool,
+    metrics: Arc<Metrics>,
+    /// The changes retained by the listener, if the client retains any.
+    pub(crate) history: Option<ChangeHistory>,
+    /// The configurations retained to roll back to, if the client retains any.
+    pub(crate) snapshots: Option<Snapshots>,
+    /// The rollback requested last and not yet carried out, if any.
+    rollback: Mutex<Option<RollbackRequest>>,
+    /// Notified when a rollback is requested or cleared.
+    pub(crate) rollback_requested: Notify,
+    dispatch: DispatchInfo,
+}
+
+impl Remote {
+    /// Creates the remote of a listener that reports its pause state in
+    /// `metrics` and retains its changes in `history`.
+    pub(crate) fn new(metrics: Arc<Metrics>, history: Option<ChangeHistory>) -> Self {
+        Remote {
+            reconnect: Notify::new(),
+            resumed: Notify::new(),
+            paused: AtomicBool::new(false),
+            metrics,
+            history,
+            snapshots: None,
+            rollback: Mutex::new(None),
+            rollback_requested: Notify::new(),
+            dispatch: DispatchInfo::default(),
+        }
+    }
+
+    /// Retains configurations to roll back to in `snapshots`.
+    pub(crate) fn retaining(mut self, snapshots: Option<Snapshots>) -> Self {
+        self.snapshots = snapshots;
+        self
+    }
+
+    /// Takes the rollback requested last, for the listener to carry out.
+    pub(crate) fn take_rollback(&self) -> Option<RollbackRequest> {
+        self.rollback.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+
+    fn request_rollback(&self, request: RollbackRequest) {
+        *self.rollback.lock().unwrap_or_else(|e| e.into_inner()) = Some(request);
+        self.rollback_requested.notify_one();
+    }
+
+    /// Records what the listener dispatches, for
+    /// [`ListenerHandle::dispatch_info`].
+    pub(crate) fn dispatching(mut self, dispatch: DispatchInfo) -> Self {
+        self.dispatch = dispatch;
+        self
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Acquire)
+    }
+}
+
+impl Drop for Remote {
+    fn drop(&mut self) {
+        // A listener that is gone no longer counts as paused.
+        if *self.paused.get_mut() {
+            self.metrics.record_resumed();
+        }
+    }
+}
+
+impl ListenerHandle {
+    /// Spawns `listener` on the tokio runtime. `ready` must be set to `true`
+    /// once the first configuration has been dispatched, and the listener should
+    /// follow the requests made through `remote`.
+    pub(crate) fn spawn<L>(listener: L, ready: watch::Receiver<bool>, remote: Arc<Remote>) -> Self
+    where
+        L: Future<Output = Result<(), ConfigError>> + Send + 'static,
+    {
+        ListenerHandle {
+            task: tokio::spawn(listener),
+            ready,
+            remote,
+        }
+    }
+
+    /// Returns a future that resolves once the first configuration has been
+    /// passed to the handler and the handler has returned.
+    ///
+    /// This gives services a single `await` point for readiness gating during
+    /// startup. The future does not borrow the handle, so it can be moved into
+    /// another task, and it can be requested any number of times; once the first
+    /// configuration has been dispatched every new future resolves immediately.
+    ///
+    /// # Errors
+    ///
+    /// Resolves to `ConfigError::NoConfigReceived` if the listener stops before
+    /// dispatching any configuration. The reason it stopped is returned by
+    /// [`ListenerHandle::join`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use config_sdk::{spawn_listener, ServerConfig};
+    ///
+    /// # async fn run() -> Result<(), config_sdk::ConfigError> {
+    /// let handle = spawn_listener("http://example.com/sse", |config: ServerConfig| {
+    ///     println!("{:?}", config);
+    /// }, 5);
+    ///
+    /// // Do not report ready before a configuration has been applied.
+    /// handle.first_config().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn first_config(&self) -> impl Future<Output = Result<(), ConfigError>> + Send + 'static {
+        let mut ready = self.ready.clone();
+        async move {
+            ready
+                .wait_for(|ready| *ready)
+                .await
+                .map(|_| ())
+                .map_err(|_| ConfigError::NoConfigReceived)
+        }
+    }
+
+    /// Returns `true` once the first configuration has been dispatched.
+    pub fn is_ready(&self) -> bool {
+        *self.ready.borrow()
+    }
+
+    /// Returns `true` if the listener has stopped.
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Makes the listener drop its current connection and connect again right
+    /// away.
+    ///
+    /// The new connection is made without a backoff delay and starts a fresh
+    /// retry sequence, and it resumes from the last event ID like any other
+    /// reconnect. If the listener is waiting to retry a failed attempt, it stops
+    /// waiting and retries immediately. Requests made while the listener is
+    /// connecting or running the handler take effect once it is reading again;
+    /// several requests made in the meantime result in a single reconnect.
+    ///
+    /// This gives operators a way to move clients to another server, e.g. after
+    /// a routing change, without restarting them.
+    pub fn reconnect(&self) {
+        self.remote.reconnect.notify_one();
+    }
+
+    /// Stops passing configurations to the handler without disconnecting.
+    ///
+    /// While paused, the listener keeps its connection and keeps reading, so
+    /// its place in the stream is not lost, and configurations are parsed,
+    /// filtered and cached as usual. Instead of being handed to the handler,
+    /// each one replaces the configuration held back so far. Pausing a paused
+    /// listener has no effect. The number of paused listeners is reported in
+    /// `MetricsSnapshot::paused_listeners`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use config_sdk::{spawn_listener, ServerConfig};
+    ///
+    /// # async fn run() {
+    /// let handle = spawn_listener("http://example.com/sse", |config: ServerConfig| {
+    ///     println!("{:?}", config);
+    /// }, 5);
+    ///
+    /// handle.pause();
+    /// // Perform the maintenance without configuration changes.
+    /// handle.resume();
+    /// # }
+    /// ```
+    pub fn pause(&self) {
+        if !self.remote.paused.swap(true, Ordering::AcqRel) {
+            self.remote.metrics.record_paused();
+        }
+    }
+
+    /// Resumes passing configurations to the handler after
+    /// [`pause`](Self::pause).
+    ///
+    /// The most recent configuration received while paused, if any, is passed
+    /// to the handler right away, even if the stream is quiet. Configurations
+    /// received before it are not. Resuming a listener that is not paused has
+    /// no effect.
+    pub fn resume(&self) {
+        if self.remote.paused.swap(false, Ordering::AcqRel) {
+            self.remote.metrics.record_resumed();
+            self.remote.resumed.notify_one();
+        }
+    }
+
+    /// Returns `true` while the listener is paused.
+    pub fn is_paused(&self) -> bool {
+        self.remote.is_paused()
+    }
+
+    /// Returns up to the `n` most recent configuration changes passed to the
+    /// handler, oldest first.
+    ///
+    /// Only configurations that changed at least one top-level key compared
+    /// with the previous one are retained, as is the removal of the
+    /// configuration. Returns an empty list unless the client was built with
+    /// [`SseClientBuilder::change_history`](crate::SseClientBuilder::change_history).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use config_sdk::{ServerConfig, SseClientBuilder};
+    ///
+    /// # async fn run() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/sse")
+    ///     .change_history(50)
+    ///     .build()?;
+    /// let handle = client.spawn(|config: ServerConfig| println!("{:?}", config));
+    ///
+    /// for change in handle.recent_changes(10) {
+    ///     println!("{:?} {:?}: {:?}", change.at, change.id, change.diff);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn recent_changes(&self, n: usize) -> Vec<ConfigChangeRecord> {
+        self.remote.history.as_ref().map_or_else(Vec::new, |history| history.recent(n))
+    }
+
+    /// Passes the configuration retained under `version` to the handler again
+    /// and holds back live configurations until [`clear_rollback`](Self::clear_rollback)
+    /// is called, e.g. while a bad configuration pushed by the server is being
+    /// fixed.
+    ///
+    /// Configurations are retained with
+    /// [`SseClientBuilder::retain_snapshots`](crate::SseClientBuilder::retain_snapshots),
+    /// under their [version](crate::SseClientBuilder::version_key) or else
+    /// the ID of their event; [`retained_versions`](Self::retained_versions)
+    /// lists them. The configuration is passed to the handler with the
+    /// [`EventMeta`](crate::EventMeta) it first arrived with, except that its
+    /// origin is [`ConfigOrigin::Rollback`](crate::ConfigOrigin::Rollback),
+    /// and it is not acknowledged again.
+    ///
+    /// While rolled back, the listener stays connected and keeps reading, and
+    /// live configurations are parsed, filtered, cached and retained as usual,
+    /// but are not passed to the handler: each one replaces the one held back
+    /// so far. Clearing the rollback passes the most recent one to the
+    /// handler, if any arrived in the meantime, so the handler ends up with
+    /// the server's current configuration. Rolling back again while rolled
+    /// back switches to the other version. The rollback happens on the
+    /// listener's task, as soon as it is not busy with a handler call; if the
+    /// listener is [paused](Self::pause), it happens when the listener is
+    /// resumed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::GenericError` if no configuration is retained
+    /// under `version`, which is always the case unless the client retains
+    /// snapshots.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use config_sdk::{ServerConfig, SseClientBuilder};
+    ///
+    /// # fn run() -> Result<(), config_sdk::ConfigError> {
+    /// let handle = SseClientBuilder::new("http://example.com/sse")
+    ///     .retain_snapshots(10)
+    ///     .build()?
+    ///     .spawn(|_: ServerConfig| {});
+    ///
+    /// // Version 42 turned out to be bad: go back to 41 until it is fixed.
+    /// handle.rollback_to("41")?;
+    /// // Then follow the server again.
+    /// handle.clear_rollback();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn rollback_to(&self, version: &str) -> Result<(), ConfigError> {
+        let snapshot = self.remote.snapshots.as_ref().and_then(|snapshots| snapshots.get(version));
+        let Some(snapshot) = snapshot else {
+            return Err(ConfigError::GenericError(format!("No configuration retained with version {}", version)));
+        };
+        self.remote.request_rollback(RollbackRequest::To(snapshot));
+        Ok(())
+    }
+
+    /// Ends a [rollback](Self::rollback_to), passing the most recent live
+    /// configuration held back during it to the handler, if any. Has no
+    /// effect if the listener is not rolled back.
+    pub fn clear_rollback(&self) {
+        self.remote.request_rollback(RollbackRequest::Clear);
+    }
+
+    /// Returns the versions of the configurations that can be
+    /// [rolled back to](Self::rollback_to), the one received longest ago first.
+    pub fn retained_versions(&self) -> Vec<String> {
+        self.remote.snapshots.as_ref().map_or_else(Vec::new, Snapshots::versions)
+    }
+
+    /// Returns what the listener dispatches and how: the number of handlers,
+    /// the subscription, the event type that removes the configuration, and
+    /// the options that filter or transform configurations on their way to
+    /// the handlers.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use config_sdk::{ServerConfig, SseClientBuilder};
+    ///
+    /// # async fn run() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/sse")
+    ///     .query_param("namespace", "billing")
+    ///     .skip_unchanged(["timestamp"])
+    ///     .transform(|config: ServerConfig| config)
+    ///     .build()?;
+    /// let handle = client.spawn(|config: ServerConfig| println!("{:?}", config));
+    ///
+    /// let info = handle.dispatch_info();
+    /// assert_eq!(info.handlers, 1);
+    /// assert_eq!(info.subscription, [("namespace".to_string(), "billing".to_string())]);
+    /// assert_eq!(info.filters, ["skip_unchanged"]);
+    /// assert_eq!(info.transforms, ["transform"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn dispatch_info(&self) -> DispatchInfo {
+        self.remote.dispatch.clone()
+    }
+
+    /// Stops the listener.
+    pub fn abort(&self) {
+        self.task.abort();
+    }
+
+    /// Waits for the listener to stop and returns its result.
+    ///
+    /// By the time it returns, every record the listener logged has been
+    /// written, so awaiting `join` before the process exits, also after
+    /// [`abort`](Self::abort), keeps the last log lines from being lost.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error the listener stopped with, or
+    /// `ConfigError::GenericError` if the listener task panicked or was aborted.
+    pub async fn join(self) -> Result<(), ConfigError> {
+        match self.task.await {
+            Ok(result) => result,
+            Err(e) => Err(ConfigError::GenericError(format!("Listener task failed: {}", e))),
+        }
+    }
+}
+
+/// Creates the channel used to signal that the first configuration has been
+/// dispatched.
+pub(crate) fn ready_channel() -> (watch::Sender<bool>, watch::Receiver<bool>) {
+    watch::channel(false)
+}
+
+/// Marks the listener as ready. Only the first call notifies waiters.
+pub(crate) fn mark_ready(ready: &watch::Sender<bool>) {
+    ready.send_if_modified(|ready| !std::mem::replace(ready, true));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::SseClientBuilder;
+    use crate::models::{ConfigEvent, ConfigOrigin};
+    use crate::test_server::{Reply, TestServer};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rollback_passes_the_retained_configuration_again() {
+        let server = TestServer::start([Reply::events("id: 1\ndata: {\"level\": 3}\n\nid: 2\ndata: {\"level\": -1}\n\n")]).await;
+
+        let (events, seen) = mpsc::channel();
+        let handle = SseClientBuilder::new(&server.url)
+            .retain_snapshots(10)
+            .build()
+            .unwrap()
+            .spawn_for_events(move |event: ConfigEvent| events.send(event).unwrap());
+
+        seen.recv_timeout(Duration::from_secs(5)).unwrap();
+        let bad = seen.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(bad.config.settings["level"], -1);
+        assert_eq!(handle.retained_versions(), ["1", "2"]);
+
+        handle.rollback_to("1").unwrap();
+        let restored = seen.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(restored.meta.origin, ConfigOrigin::Rollback);
+        assert_eq!(restored.config.settings["level"], 3);
+        assert!(handle.rollback_to("3").is_err());
+        handle.abort();
+    }
+}
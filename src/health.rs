@@ -0,0 +1,231 @@
+// health.rs
+
+use crate::metrics::{Metrics, MetricsSnapshot};
+use hyper::header::{HeaderValue, ALLOW, CONTENT_TYPE};
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde_json::{json, Value};
+use slog::{debug, info, Logger};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::net::TcpListener;
+use tokio::task::{JoinHandle, JoinSet};
+
+/// What the status endpoint reports about one listener.
+#[derive(Debug, Default)]
+struct Health {
+    /// The URL of the current connection, if the listener is connected.
+    connected_to: Option<String>,
+    /// When the last configuration was received.
+    last_update: Option<SystemTime>,
+    last_event_id: Option<String>,
+}
+
+/// The health of every listener served by a [`StatusServer`], by the order
+/// they registered in.
+#[derive(Debug, Default)]
+struct Listeners {
+    next_id: u64,
+    health: BTreeMap<u64, Health>,
+}
+
+/// Where the listeners of a client find the status endpoint they share, see
+/// [`SseClientBuilder::status_address`](crate::SseClientBuilder::status_address).
+///
+/// The endpoint is bound when the first listener registers, and stops serving
+/// once the last one is gone.
+pub(crate) type StatusSlot = tokio::sync::Mutex<Weak<StatusServer>>;
+
+/// The HTTP status endpoint of a client, shared by its listeners.
+///
+/// It serves until it is dropped, i.e. until its last listener stops, and
+/// every connection to it is closed then.
+#[derive(Debug)]
+pub(crate) struct StatusServer {
+    listeners: Arc<Mutex<Listeners>>,
+    task: JoinHandle<()>,
+}
+
+/// One listener's entry in a [`StatusServer`], removed when dropped.
+#[derive(Debug)]
+pub(crate) struct ListenerStatus {
+    server: Arc<StatusServer>,
+    id: u64,
+}
+
+impl StatusServer {
+    /// Registers a listener with the server in `slot`, first binding `address`
+    /// and serving the health of the listeners, with the counters of
+    /// `metrics`, if no listener is registered yet.
+    pub(crate) async fn register(
+        slot: &StatusSlot,
+        address: SocketAddr,
+        metrics: Arc<Metrics>,
+        log: &Logger,
+    ) -> std::io::Result<ListenerStatus> {
+        let mut slot = slot.lock().await;
+        let server = match slot.upgrade() {
+            Some(server) => server,
+            None => {
+                let server = Arc::new(StatusServer::bind(address, metrics, log).await?);
+                *slot = Arc::downgrade(&server);
+                server
+            },
+        };
+        let id = {
+            let mut listeners = server.listeners.lock().unwrap_or_else(|e| e.into_inner());
+            let id = listeners.next_id;
+            listeners.next_id += 1;
+            listeners.health.insert(id, Health::default());
+            id
+        };
+        Ok(ListenerStatus { server, id })
+    }
+
+    async fn bind(address: SocketAddr, metrics: Arc<Metrics>, log: &Logger) -> std::io::Result<Self> {
+        let server = TcpListener::bind(address).await?;
+        info!(log, "Serving listener status"; "address" => %server.local_addr()?);
+        let listeners = Arc::new(Mutex::new(Listeners::default()));
+        let task = tokio::spawn(serve(server, listeners.clone(), metrics, log.clone()));
+        Ok(StatusServer { listeners, task })
+    }
+}
+
+impl Drop for StatusServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl ListenerStatus {
+    /// Records that the listener connected to `url`.
+    pub(crate) fn connected(&self, url: &str) {
+        self.update(|health| health.connected_to = Some(url.to_string()));
+    }
+
+    /// Records that the listener lost its connection.
+    pub(crate) fn disconnected(&self) {
+        self.update(|health| health.connected_to = None);
+    }
+
+    /// Records that a configuration was received, the last event ID being
+    /// `last_event_id` afterwards.
+    pub(crate) fn updated(&self, last_event_id: Option<&str>) {
+        self.update(|health| {
+            health.last_update = Some(SystemTime::now());
+            health.last_event_id = last_event_id.map(str::to_string);
+        });
+    }
+
+    fn update(&self, change: impl FnOnce(&mut Health)) {
+        let mut listeners = self.server.listeners.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(health) = listeners.health.get_mut(&self.id) {
+            change(health);
+        }
+    }
+}
+
+impl Drop for ListenerStatus {
+    fn drop(&mut self) {
+        self.server.listeners.lock().unwrap_or_else(|e| e.into_inner()).health.remove(&self.id);
+    }
+}
+
+async fn serve(server: TcpListener, listeners: Arc<Mutex<Listeners>>, metrics: Arc<Metrics>, log: Logger) {
+    // Dropped along with this task, which aborts the connections still open.
+    let mut connections = JoinSet::new();
+    loop {
+        let accepted = tokio::select! {
+            accepted = server.accept() => accepted,
+            Some(_) = connections.join_next() => continue,
+        };
+        let socket = match accepted {
+            Ok((socket, _)) => socket,
+            Err(e) => {
+                debug!(log, "Failed to accept a status request"; "error" => %e);
+                continue;
+            },
+        };
+        let (listeners, metrics) = (listeners.clone(), metrics.clone());
+        let service = service_fn(move |request| {
+            let response = respond(&request, &listeners, &metrics);
+            async move { Ok::<_, Infallible>(response) }
+        });
+        let log = log.clone();
+        connections.spawn(async move {
+            if let Err(e) = Http::new().http1_only(true).serve_connection(socket, service).await {
+                debug!(log, "Status connection failed"; "error" => %e);
+            }
+        });
+    }
+}
+
+/// Answers a request to the status endpoint: the health document for a `GET`
+/// to any path, with a 503 status for `/ready` unless every listener is
+/// connected.
+fn respond(request: &Request<Body>, listeners: &Mutex<Listeners>, metrics: &Metrics) -> Response<Body> {
+    if request.method() != Method::GET && request.method() != Method::HEAD {
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+        response.headers_mut().insert(ALLOW, HeaderValue::from_static("GET, HEAD"));
+        return response;
+    }
+    let listeners = listeners.lock().unwrap_or_else(|e| e.into_inner());
+    let connected = !listeners.health.is_empty() && listeners.health.values().all(|health| health.connected_to.is_some());
+    let document = document(connected, &listeners, metrics.snapshot());
+    let mut response = Response::new(Body::from(document.to_string()));
+    if request.uri().path() == "/ready" && !connected {
+        *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+    }
+    response.headers_mut().insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    response
+}
+
+/// Renders the health of `listeners`, whether they are all `connected`, and
+/// `metrics` as the JSON document served by the endpoint.
+fn document(connected: bool, listeners: &Listeners, metrics: MetricsSnapshot) -> Value {
+    let listeners: Vec<Value> = listeners.health.values().map(listener_document).collect();
+    json!({
+        "connected": connected,
+        "listeners": listeners,
+        "metrics": {
+            "events": metrics.events,
+            "parse_errors": metrics.parse_errors,
+            "skipped": metrics.skipped,
+            "handler_panics": metrics.handler_panics,
+            "connection_attempts": metrics.connection_attempts,
+            "connections": metrics.connections,
+            "tls_connections": metrics.tls_connections,
+            "http2_connections": metrics.http2_connections,
+            "http3_connections": metrics.http3_connections,
+            "reconnects": metrics.reconnects,
+            "log_messages_dropped": metrics.log_messages_dropped,
+            "broadcast_lagged": metrics.broadcast_lagged,
+            "paused_listeners": metrics.paused_listeners,
+            "handler_latency_ms": {
+                "count": metrics.handler_latency.count,
+                "total": metrics.handler_latency.total.as_millis() as u64,
+                "min": metrics.handler_latency.min.as_millis() as u64,
+                "max": metrics.handler_latency.max.as_millis() as u64,
+            },
+        },
+    })
+}
+
+/// Renders the health of one listener.
+fn listener_document(health: &Health) -> Value {
+    let last_update = health
+        .last_update
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|since| since.as_millis() as u64);
+    json!({
+        "connected": health.connected_to.is_some(),
+        "url": health.connected_to,
+        "last_update_ms": last_update,
+        "last_event_id": health.last_event_id,
+    })
+}
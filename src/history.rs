@@ -0,0 +1,96 @@
+// history.rs
+
+use crate::models::{ConfigDiff, ServerConfig};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// A configuration change retained by a listener, as returned by
+/// [`ListenerHandle::recent_changes`](crate::ListenerHandle::recent_changes).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigChangeRecord {
+    /// When the configuration was passed to the handler.
+    pub at: SystemTime,
+    /// The ID of the event that carried the configuration, if it had one.
+    pub id: Option<String>,
+    /// The top-level keys that changed compared with the previous configuration.
+    pub diff: ConfigDiff,
+    /// The payload of the event, if the client retains payloads; see
+    /// [`SseClientBuilder::change_history_payloads`](crate::SseClientBuilder::change_history_payloads).
+    pub payload: Option<Vec<u8>>,
+}
+
+/// The last configuration changes seen by one listener, oldest first.
+#[derive(Debug)]
+pub(crate) struct ChangeHistory {
+    capacity: usize,
+    payloads: bool,
+    state: Mutex<HistoryState>,
+}
+
+#[derive(Debug, Default)]
+struct HistoryState {
+    records: VecDeque<ConfigChangeRecord>,
+    /// The configuration the next one is compared with.
+    previous: Option<ServerConfig>,
+}
+
+impl ChangeHistory {
+    /// Creates a history that keeps at most `capacity` records, with their
+    /// payloads if `payloads` is set.
+    pub(crate) fn new(capacity: usize, payloads: bool) -> Self {
+        ChangeHistory {
+            capacity,
+            payloads,
+            state: Mutex::new(HistoryState {
+                records: VecDeque::with_capacity(capacity),
+                previous: None,
+            }),
+        }
+    }
+
+    /// Records `config` if it differs from the previous configuration,
+    /// evicting the oldest record once the history is full.
+    pub(crate) fn record(&self, config: &ServerConfig, id: Option<&str>, payload: &[u8]) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let diff = match &state.previous {
+            Some(previous) => previous.diff(config),
+            None => ServerConfig::default().diff(config),
+        };
+        state.previous = Some(config.clone());
+        self.push(&mut state, diff, id, self.payloads.then(|| payload.to_vec()));
+    }
+
+    /// Records the removal of the configuration, which the next one is then
+    /// compared with as if it were the first.
+    pub(crate) fn record_removal(&self, id: Option<&str>) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(previous) = state.previous.take() else {
+            return;
+        };
+        let diff = previous.diff(&ServerConfig::default());
+        self.push(&mut state, diff, id, None);
+    }
+
+    fn push(&self, state: &mut HistoryState, diff: ConfigDiff, id: Option<&str>, payload: Option<Vec<u8>>) {
+        if diff.is_empty() || self.capacity == 0 {
+            return;
+        }
+        if state.records.len() == self.capacity {
+            state.records.pop_front();
+        }
+        state.records.push_back(ConfigChangeRecord {
+            at: SystemTime::now(),
+            id: id.map(str::to_string),
+            diff,
+            payload,
+        });
+    }
+
+    /// Returns up to the `n` most recent records, oldest first.
+    pub(crate) fn recent(&self, n: usize) -> Vec<ConfigChangeRecord> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let skip = state.records.len().saturating_sub(n);
+        state.records.iter().skip(skip).cloned().collect()
+    }
+}
@@ -0,0 +1,356 @@
+// incremental.rs
+
+use serde::de::{Error as _, IgnoredAny};
+use serde::Deserialize;
+use serde_json::{from_slice, Error, Value};
+use std::collections::BTreeMap;
+
+/// Decodes the JSON payload of a configuration event as its bytes arrive.
+///
+/// Parsing a payload with `serde_json::from_slice` needs all of it in memory
+/// at once, on top of the settings it is parsed into. This decoder instead
+/// splits the `settings` object, or the payload itself if it is flat, into its
+/// entries while scanning and parses each entry as soon as it is complete, so
+/// only the entry being read is kept as bytes. Peak memory for a large configuration is then bounded by its
+/// largest single setting rather than by the size of the payload.
+///
+/// The scanner only tracks strings and nesting to find where entries end; the
+/// syntax of every entry, and of top-level members other than `settings`, is
+/// still checked by `serde_json`, so the decoder accepts and rejects the same
+/// payloads as parsing them into a `ServerConfig` of the same wire format.
+#[derive(Debug)]
+pub(crate) struct SettingsDecoder {
+    state: State,
+    /// The member or entry being read, prefixed with `{` so that it can be
+    /// parsed as an object of its own once complete.
+    item: Vec<u8>,
+    /// Nesting depth inside the item being read.
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    /// Whether the key of the current top-level member has been read.
+    key_read: bool,
+    /// Number of items completed in the object being read.
+    items: usize,
+    settings: Option<BTreeMap<String, Value>>,
+    /// The top-level members read before any `settings` member, which are the
+    /// settings if the payload turns out to be flat.
+    members: BTreeMap<String, Value>,
+    shape: Shape,
+    error: Option<Error>,
+}
+
+/// Which shapes of payload the decoder accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    /// Enveloped if the payload has a `settings` member, flat otherwise.
+    Either,
+    /// The settings are the `settings` member, which is required.
+    Enveloped,
+    /// The settings are the members of the payload.
+    Flat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Before the opening brace of the payload.
+    Start,
+    /// Reading a member of the top-level object.
+    Member,
+    /// After the `settings` key, before its value.
+    SettingsValue,
+    /// Reading an entry of the `settings` object.
+    Setting,
+    /// After the `settings` object, before the next top-level member.
+    AfterSettings,
+    /// After the closing brace of the payload.
+    End,
+}
+
+/// The shape of a payload, used to report a `settings` member that is not an
+/// object the way `serde_json` would.
+#[derive(Deserialize)]
+struct Payload {
+    #[allow(dead_code)]
+    settings: BTreeMap<String, Value>,
+}
+
+impl Default for SettingsDecoder {
+    fn default() -> Self {
+        SettingsDecoder {
+            state: State::Start,
+            item: Vec::new(),
+            depth: 0,
+            in_string: false,
+            escaped: false,
+            key_read: false,
+            items: 0,
+            settings: None,
+            members: BTreeMap::new(),
+            shape: Shape::Either,
+            error: None,
+        }
+    }
+}
+
+impl SettingsDecoder {
+    /// Only accepts payloads with the settings in a `settings` member.
+    pub(crate) fn enveloped(mut self) -> Self {
+        self.shape = Shape::Enveloped;
+        self
+    }
+
+    /// Reads every member of the payload as a setting, including one named
+    /// `settings`.
+    pub(crate) fn flat(mut self) -> Self {
+        self.shape = Shape::Flat;
+        self
+    }
+
+    /// Feeds the next part of the payload. After an error, the rest of the
+    /// payload is ignored and the error is returned by [`finish`](Self::finish).
+    pub(crate) fn feed(&mut self, bytes: &[u8]) {
+        if self.error.is_some() {
+            return;
+        }
+        for &byte in bytes {
+            if let Err(e) = self.push(byte) {
+                self.error = Some(e);
+                return;
+            }
+        }
+    }
+
+    /// Returns `true` if nothing but whitespace has been fed so far.
+    pub(crate) fn is_blank(&self) -> bool {
+        self.state == State::Start && self.error.is_none()
+    }
+
+    /// Returns the settings of the payload fed so far, which must be complete.
+    pub(crate) fn finish(self) -> Result<BTreeMap<String, Value>, Error> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        if self.state != State::End {
+            return Err(Error::custom("EOF while parsing an object"));
+        }
+        match (self.settings, self.shape) {
+            (Some(settings), _) => Ok(settings),
+            (None, Shape::Enveloped) => Err(Error::missing_field("settings")),
+            (None, _) => Ok(self.members),
+        }
+    }
+
+    fn push(&mut self, byte: u8) -> Result<(), Error> {
+        match self.state {
+            State::Start => match byte {
+                b'{' => self.begin_item(State::Member),
+                _ if byte.is_ascii_whitespace() => {},
+                _ => return Err(Error::custom("expected `{` at the start of the payload")),
+            },
+            State::SettingsValue => match byte {
+                b'{' => {
+                    self.settings = Some(BTreeMap::new());
+                    self.items = 0;
+                    self.begin_item(State::Setting);
+                },
+                _ if byte.is_ascii_whitespace() => {},
+                _ => {
+                    // Not an object: read the member as usual so that parsing it
+                    // reports the type error.
+                    self.item.extend_from_slice(br#"{"settings":"#);
+                    self.key_read = true;
+                    self.state = State::Member;
+                    return self.push_item_byte(byte);
+                },
+            },
+            State::AfterSettings => match byte {
+                b',' => self.begin_item(State::Member),
+                b'}' => self.state = State::End,
+                _ if byte.is_ascii_whitespace() => {},
+                _ => return Err(Error::custom("expected `,` or `}` after the settings")),
+            },
+            State::End => {
+                if !byte.is_ascii_whitespace() {
+                    return Err(Error::custom("trailing characters after the payload"));
+                }
+            },
+            State::Member | State::Setting => return self.push_item_byte(byte),
+        }
+        Ok(())
+    }
+
+    /// Starts reading the next member or entry.
+    fn begin_item(&mut self, state: State) {
+        self.state = state;
+        self.item.clear();
+        self.item.push(b'{');
+        self.depth = 0;
+        self.key_read = false;
+    }
+
+    fn push_item_byte(&mut self, byte: u8) -> Result<(), Error> {
+        if self.in_string {
+            self.item.push(byte);
+            if self.escaped {
+                self.escaped = false;
+            } else if byte == b'\\' {
+                self.escaped = true;
+            } else if byte == b'"' {
+                self.in_string = false;
+            }
+            return Ok(());
+        }
+        match byte {
+            b'"' => self.in_string = true,
+            b'{' | b'[' => self.depth += 1,
+            b'}' | b']' if self.depth > 0 => self.depth -= 1,
+            b':' if self.depth == 0 && self.state == State::Member && !self.key_read => {
+                self.key_read = true;
+                let key: String = from_slice(&self.item[1..])?;
+                if key == "settings" && self.shape != Shape::Flat {
+                    if self.settings.is_some() {
+                        return Err(Error::duplicate_field("settings"));
+                    }
+                    self.members.clear();
+                    self.state = State::SettingsValue;
+                    self.item.clear();
+                    return Ok(());
+                }
+            },
+            b',' | b'}' if self.depth == 0 => return self.end_item(byte == b'}'),
+            _ => {},
+        }
+        self.item.push(byte);
+        Ok(())
+    }
+
+    /// Parses the item that was just completed by `,` or, if `closes`, by `}`.
+    fn end_item(&mut self, closes: bool) -> Result<(), Error> {
+        if self.item[1..].iter().all(u8::is_ascii_whitespace) {
+            // Only an empty object may have no items; anything else is a
+            // missing value or a trailing comma.
+            if !(closes && self.items == 0) {
+                return Err(Error::custom("expected a value"));
+            }
+        } else {
+            self.item.push(b'}');
+            match self.state {
+                State::Setting => {
+                    let entry: BTreeMap<String, Value> = from_slice(&self.item)?;
+                    self.settings.get_or_insert_with(BTreeMap::new).extend(entry);
+                },
+                _ if self.shape != Shape::Flat && self.item.starts_with(br#"{"settings":"#) => {
+                    from_slice::<Payload>(&self.item)?;
+                },
+                _ if self.settings.is_some() || self.shape == Shape::Enveloped => {
+                    from_slice::<IgnoredAny>(&self.item)?;
+                },
+                _ => {
+                    let member: BTreeMap<String, Value> = from_slice(&self.item)?;
+                    self.members.extend(member);
+                },
+            }
+            self.items += 1;
+        }
+
+        match (self.state, closes) {
+            (State::Setting, true) => {
+                self.state = State::AfterSettings;
+                // The settings count as one member of the payload.
+                self.items = 1;
+            },
+            (_, true) => self.state = State::End,
+            (state, false) => self.begin_item(state),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds `payload` into `decoder` one byte at a time, the worst case for
+    /// entries spanning chunks.
+    fn decode(mut decoder: SettingsDecoder, payload: &str) -> Result<BTreeMap<String, Value>, Error> {
+        for byte in payload.as_bytes() {
+            decoder.feed(std::slice::from_ref(byte));
+        }
+        decoder.finish()
+    }
+
+    fn settings(value: Value) -> BTreeMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn decodes_enveloped_and_flat_payloads() {
+        let expected = settings(serde_json::json!({"level": 3, "name": "eu"}));
+        assert_eq!(decode(SettingsDecoder::default(), r#"{"settings": {"level": 3, "name": "eu"}}"#).unwrap(), expected);
+        assert_eq!(decode(SettingsDecoder::default(), r#" { "level" : 3 , "name" : "eu" } "#).unwrap(), expected);
+    }
+
+    #[test]
+    fn strings_and_nesting_do_not_end_entries() {
+        let payload = r#"{"settings": {"a": "}, {\"x\": [1", "b": {"c": [1, {"d": "]"}]}, "e\\": ":"}}"#;
+        let expected = settings(serde_json::json!({"a": "}, {\"x\": [1", "b": {"c": [1, {"d": "]"}]}, "e\\": ":"}));
+        assert_eq!(decode(SettingsDecoder::default(), payload).unwrap(), expected);
+    }
+
+    #[test]
+    fn settings_member_replaces_members_read_before_it() {
+        let payload = r#"{"version": 2, "settings": {"level": 3}, "meta": {"author": "ci"}}"#;
+        assert_eq!(decode(SettingsDecoder::default(), payload).unwrap(), settings(serde_json::json!({"level": 3})));
+        assert_eq!(
+            decode(SettingsDecoder::default().flat(), payload).unwrap(),
+            settings(serde_json::json!({"version": 2, "settings": {"level": 3}, "meta": {"author": "ci"}}))
+        );
+    }
+
+    #[test]
+    fn enveloped_requires_settings() {
+        assert!(decode(SettingsDecoder::default().enveloped(), r#"{"level": 3}"#).is_err());
+        assert_eq!(decode(SettingsDecoder::default().enveloped(), r#"{"settings": {}}"#).unwrap(), BTreeMap::new());
+    }
+
+    #[test]
+    fn accepts_empty_objects() {
+        assert_eq!(decode(SettingsDecoder::default(), "{}").unwrap(), BTreeMap::new());
+        assert_eq!(decode(SettingsDecoder::default(), r#"{"settings": { }}"#).unwrap(), BTreeMap::new());
+    }
+
+    #[test]
+    fn rejects_malformed_payloads() {
+        for payload in [
+            r#"{"settings": 3}"#,
+            r#"{"settings": {}, "settings": {}}"#,
+            r#"{"level": 3,}"#,
+            r#"{"settings": {"level": }}"#,
+            r#"{"level": tru}"#,
+            r#"{"settings": {}, "meta": [}"#,
+            r#"{"level": 3} x"#,
+            r#"[1]"#,
+            r#"{"level": 3"#,
+        ] {
+            assert!(decode(SettingsDecoder::default(), payload).is_err(), "{} was accepted", payload);
+        }
+    }
+
+    #[test]
+    fn is_blank_until_the_payload_starts() {
+        let mut decoder = SettingsDecoder::default();
+        decoder.feed(b" \n ");
+        assert!(decoder.is_blank());
+        decoder.feed(b"{");
+        assert!(!decoder.is_blank());
+    }
+
+    #[test]
+    fn ignores_input_after_an_error() {
+        let mut decoder = SettingsDecoder::default();
+        decoder.feed(b"x");
+        decoder.feed(br#"{"level": 3}"#);
+        assert!(decoder.finish().is_err());
+    }
+}
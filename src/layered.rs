@@ -0,0 +1,211 @@
+// layered.rs
+
+use crate::client::SseClient;
+use crate::errors::ConfigError;
+use crate::listener::listen;
+use crate::models::{ArrayMerge, EventMeta, MergePolicy, ServerConfig};
+use crate::snapshot::Snapshot;
+use serde_json::{Map, Value};
+use std::env;
+use std::path::PathBuf;
+
+/// Separator in the name of an environment variable between the keys of
+/// nested objects, see [`ConfigSource::Env`].
+const ENV_NESTING_SEPARATOR: &str = "__";
+
+/// One layer of a configuration composed by [`SseClient::listen_layered`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConfigSource {
+    /// A JSON file holding a configuration in the client's
+    /// [wire format](crate::SseClientBuilder::wire_format), read once.
+    File(PathBuf),
+    /// The environment variables whose names start with the given prefix,
+    /// read once.
+    ///
+    /// The rest of a variable's name, in lowercase, is the key of the setting.
+    /// A double underscore separates the keys of nested objects, so with the
+    /// prefix `APP_`, `APP_DB__HOST` sets `host` within the `db` object. A
+    /// value that is valid JSON, such as `8080`, `true` or `["a"]`, is used as
+    /// such; any other value is a string. Variables whose name or value is not
+    /// valid Unicode are ignored.
+    Env(String),
+    /// A configuration fetched once from the given URL, as by
+    /// [`SseClient::fetch_snapshot`].
+    HttpSnapshot(String),
+    /// The client's event stream. Every configuration dispatched by the
+    /// listener replaces this layer.
+    SseStream,
+}
+
+impl SseClient {
+    /// Composes a configuration from `sources`, merged in order, and passes it
+    /// to `update_handler` every time it changes.
+    ///
+    /// The static layers, everything but [`ConfigSource::SseStream`], are
+    /// loaded first; if any of them fails to load, its error is returned
+    /// before anything is passed to the handler. Their merge is then passed to
+    /// the handler right away, so the application starts with the baseline even
+    /// while the server is unreachable. With an `SseStream` layer, the listener
+    /// is started next and every configuration it dispatches is merged with
+    /// the static layers again and passed to the handler, until the listener
+    /// stops. Without one, this returns once the baseline has been handled.
+    ///
+    /// Each layer is merged into the ones before it with
+    /// [`MergePolicy::DeepMerge`], arrays replacing arrays, so later sources
+    /// override earlier ones key by key and layers placed after the stream
+    /// override it. Nulls are treated according to the client's
+    /// [`null_values`](crate::SseClientBuilder::null_values). Within the
+    /// stream layer, the client's options apply as with [`listen`](Self::listen),
+    /// including [`merge_updates`](crate::SseClientBuilder::merge_updates).
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::InvalidOptions` if `sources` has more than one
+    /// `SseStream`, the error of the first static layer that fails to load, or
+    /// how the listener stopped, see
+    /// [`start_listening_for_updates`](crate::start_listening_for_updates).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{ConfigSource, ServerConfig, SseClientBuilder};
+    /// use std::env;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), config_sdk::ConfigError> {
+    /// let baseline = env::temp_dir().join("config-sdk-layered-example.json");
+    /// std::fs::write(&baseline, r#"{"settings": {"db": {"host": "localhost", "port": 5432}, "debug": false}}"#)?;
+    /// env::set_var("LAYERED_EXAMPLE_DB__HOST", "db.prod");
+    ///
+    /// let client = SseClientBuilder::new("http://example.com/sse").build()?;
+    /// let sources = [ConfigSource::File(baseline), ConfigSource::Env("LAYERED_EXAMPLE_".to_string())];
+    /// let mut handled = None;
+    /// client.listen_layered(sources, |config: ServerConfig| handled = Some(config)).await?;
+    ///
+    /// let config = handled.unwrap();
+    /// assert_eq!(config.settings["db"]["host"], "db.prod");
+    /// assert_eq!(config.settings["db"]["port"], 5432);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// In production, the stream goes between the baseline and the overrides
+    /// that must win over the server:
+    ///
+    /// ```no_run
+    /// use config_sdk::{ConfigSource, ServerConfig, SseClientBuilder};
+    ///
+    /// # async fn run() -> Result<(), config_sdk::ConfigError> {
+    /// let sources = [
+    ///     ConfigSource::File("/etc/app/defaults.json".into()),
+    ///     ConfigSource::HttpSnapshot("http://example.com/config".to_string()),
+    ///     ConfigSource::SseStream,
+    ///     ConfigSource::Env("APP_".to_string()),
+    /// ];
+    /// SseClientBuilder::new("http://example.com/sse")
+    ///     .build()?
+    ///     .listen_layered(sources, |config: ServerConfig| println!("config is now {:?}", config))
+    ///     .await
+    /// # }
+    /// ```
+    pub async fn listen_layered<I, F>(&self, sources: I, mut update_handler: F) -> Result<(), ConfigError>
+    where
+        I: IntoIterator<Item = ConfigSource>,
+        F: FnMut(ServerConfig) + Send,
+    {
+        let sources: Vec<ConfigSource> = sources.into_iter().collect();
+        let mut streams = sources.iter().filter(|source| **source == ConfigSource::SseStream);
+        if streams.nth(1).is_some() {
+            return Err(ConfigError::InvalidOptions("the event stream can only be layered once".to_string()));
+        }
+
+        // The layers below the stream, merged, and the layers above it.
+        let mut below = ServerConfig::default();
+        let mut above: Option<ServerConfig> = None;
+        for source in &sources {
+            let layer = match source {
+                ConfigSource::SseStream => {
+                    above = Some(ServerConfig::default());
+                    continue;
+                },
+                source => self.load_layer(source).await?,
+            };
+            self.overlay(above.as_mut().unwrap_or(&mut below), &layer);
+        }
+
+        let Some(above) = above else {
+            update_handler(below);
+            return Ok(());
+        };
+        let mut baseline = below.clone();
+        self.overlay(&mut baseline, &above);
+        update_handler(baseline);
+
+        let dispatch = move |config: ServerConfig, _raw: &[u8], _meta: &EventMeta| {
+            let mut merged = below.clone();
+            self.overlay(&mut merged, &config);
+            self.overlay(&mut merged, &above);
+            update_handler(merged);
+        };
+        listen(self, dispatch).await
+    }
+
+    /// Loads the static layer `source`.
+    async fn load_layer(&self, source: &ConfigSource) -> Result<ServerConfig, ConfigError> {
+        match source {
+            ConfigSource::File(path) => Ok(self.wire_format.parse(&tokio::fs::read(path).await?)?),
+            ConfigSource::Env(prefix) => Ok(env_layer(prefix)),
+            ConfigSource::HttpSnapshot(url) => match self.fetch(url, false).await? {
+                Snapshot::Modified(config) => Ok(config),
+                // Not asked for, so not expected either.
+                Snapshot::NotModified => Err(ConfigError::GenericError(format!(
+                    "{} answered 304 Not Modified to an unconditional request",
+                    url
+                ))),
+            },
+            ConfigSource::SseStream => Ok(ServerConfig::default()),
+        }
+    }
+
+    /// Merges `layer` into `config`, the layer winning.
+    fn overlay(&self, config: &mut ServerConfig, layer: &ServerConfig) {
+        config.merge_with(layer, MergePolicy::DeepMerge(ArrayMerge::Replace), self.null_values);
+    }
+}
+
+/// Builds a layer from the environment variables whose names start with
+/// `prefix`.
+fn env_layer(prefix: &str) -> ServerConfig {
+    let mut settings = Map::new();
+    for (name, value) in env::vars_os() {
+        let (Some(name), Some(value)) = (name.to_str(), value.to_str()) else {
+            continue;
+        };
+        let Some(path) = name.strip_prefix(prefix).filter(|path| !path.is_empty()) else {
+            continue;
+        };
+        let value = serde_json::from_str(value).unwrap_or_else(|_| Value::from(value));
+        insert_nested(&mut settings, &path.to_lowercase(), value);
+    }
+    ServerConfig { settings: settings.into_iter().collect() }
+}
+
+/// Stores `value` in `settings` under `path`, whose keys are separated by
+/// [`ENV_NESTING_SEPARATOR`], creating the objects along the way.
+fn insert_nested(settings: &mut Map<String, Value>, path: &str, value: Value) {
+    match path.split_once(ENV_NESTING_SEPARATOR) {
+        Some((key, rest)) => {
+            let nested = settings.entry(key).or_insert_with(|| Value::Object(Map::new()));
+            if !nested.is_object() {
+                *nested = Value::Object(Map::new());
+            }
+            if let Value::Object(nested) = nested {
+                insert_nested(nested, rest, value);
+            }
+        },
+        None => {
+            settings.insert(path.to_string(), value);
+        },
+    }
+}
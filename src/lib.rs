@@ -12,6 +12,9 @@
 //! - **Listening for SSE**: Connect to an SSE endpoint and listen for real-time events.
 //! - **Automatic Reconnection**: Implements exponential backoff strategy for reconnections.
 //! - **Configuration Update Handling**: Parse incoming SSE data into custom `ServerConfig` structures.
+//! - **Typed Settings**: Coerce settings to declared types with a `Schema` and `SseClient::listen_typed`.
+//! - **Multiple Streams**: Run several named listeners side by side with `StreamManager`, and
+//!   compose their configurations by priority or per-key timestamp.
 //! - **Logging**: Utilize built-in logging for monitoring connection status and errors.
 //!
 //! ## Usage
@@ -23,9 +26,9 @@
 //!    and the maximum number of retries.
 //!
 //! ```no_run
-//! use my_sse_client_library::{ServerConfig, start_listening_for_updates};
+//! use config_sdk::{ServerConfig, start_listening_for_updates};
 //!
-//! async fn my_update_handler(config: ServerConfig) {
+//! fn my_update_handler(config: ServerConfig) {
 //!     // Process the incoming configuration update here
 //! }
 //!
@@ -36,13 +39,175 @@
 //! }
 //! ```
 //!
+//! Each configuration arrives as a JSON object in the `data:` field of an event, either
+//! flat (`{"timeout": 30}`) or with the settings in an envelope
+//! (`{"settings": {"timeout": 30}}`); see `ServerConfig` for how the two are told apart.
+//!
+//! If the handler also needs the verbatim payload sent by the server (for auditing or
+//! replay) or the event's `id:`, `event:` and `retry:` fields (for deduplication), use
+//! `start_listening_for_events`, which passes a `ConfigEvent` holding the parsed
+//! `ServerConfig`, the raw `data:` bytes and an `EventMeta`.
+//!
+//! To keep the listener running in the background, use `spawn_listener` (or
+//! `SseClient::spawn`), which returns a `ListenerHandle`. Awaiting
+//! `ListenerHandle::first_config` is a convenient readiness gate: it resolves once the
+//! first configuration has been applied. For the common case of keeping the latest
+//! configuration where the whole application can read it, `spawn_shared` (or
+//! `SseClient::spawn_shared`) writes every update into an `Arc<RwLock<_>>`.
+//!
+//! Applications that read the stream themselves, e.g. from a synchronous event loop, can
+//! feed its bytes to the `ConfigDecoder` returned by `SseClient::decoder`, which parses
+//! them into configurations without any networking or async runtime.
+//!
+//! To layer the stream over a baseline, e.g. defaults from a file and overrides from the
+//! environment, pass an ordered list of `ConfigSource`s to `SseClient::listen_layered`,
+//! which hands the handler their merge every time the stream updates.
+//!
+//! For more control over the connection, configure an `SseClient` through
+//! `SseClientBuilder`:
+//!
+//! ```no_run
+//! use config_sdk::{ServerConfig, SseClientBuilder};
+//!
+//! # async fn run() -> Result<(), config_sdk::ConfigError> {
+//! SseClientBuilder::new("http://example.com/sse")
+//!     .max_retries(3)
+//!     .build()?
+//!     .listen(|config: ServerConfig| println!("{:?}", config))
+//!     .await
+//! # }
+//! ```
+//!
+//! ## Cargo features
+//!
+//! - `native-tls`: enables the `native-tls` backend of `reqwest` and the
+//!   `SseClientBuilder::identity`, `pkcs12_identity` and `pem_identity` methods
+//!   for mutual TLS.
+//! - `rustls-tls`: enables the `rustls` backend of `reqwest`, with `identity` and
+//!   `pem_identity` support for mutual TLS.
+//! - `compression`: adds `SseClientBuilder::compress_cache`, which gzips the configuration
+//!   cache file.
+//! - `config-source`: adds `SseSource`, a source for the `config` crate backed by the
+//!   latest configuration published by `SseClient::spawn_watch`.
+//! - `metrics`: adds `render_prometheus`, which formats the client's `Metrics` in the
+//!   Prometheus text exposition format.
+//! - `unix-socket`: allows connecting to endpoints served over a Unix domain socket,
+//!   either through a `unix://` URL or `SseClientBuilder::unix_socket`. Unix only.
+//! - `replay`: adds `SseClient::replay`, which feeds a recorded SSE stream through the
+//!   listener's parsing and dispatch pipeline for debugging.
+//! - `checksum`: adds `SseClientBuilder::checksum`, which verifies a checksum sent
+//!   along with every configuration before it is applied.
+//! - `testing`: adds `SseClient::listen_to_streams`, which runs the listener on
+//!   in-memory byte streams instead of HTTP connections, for testing code built on it.
+//! - `load-test`: adds `SseClient::load_test`, which loads a server with many concurrent
+//!   listeners and reports their aggregate connection success rate, reconnects and events per
+//!   second.
+//! - `grpc`: adds `SseClient::listen_grpc`, which listens to configurations streamed by a
+//!   gRPC server-streaming RPC through a `tonic` client, with the same retry and
+//!   processing as the SSE listener.
+//! - `yaml` and `toml`: add `PayloadFormat::Yaml` and `PayloadFormat::Toml`, which parse
+//!   configurations sent as YAML or TOML, detected from the response's `Content-Type` or set
+//!   with `SseClientBuilder::payload_format`.
+//! - `http3`: adds `SseClientBuilder::http3`, which tries HTTP/3 over QUIC before
+//!   falling back to TCP. Experimental: reqwest's unstable `http3` feature only builds
+//!   with `RUSTFLAGS="--cfg reqwest_unstable"`, and without that flag this feature adds
+//!   nothing, so `--all-features` builds on stable settings.
+//! - `status-server`: adds `SseClientBuilder::status_address`, which serves the health of
+//!   every listener (connection state, last update, `Last-Event-ID` and metrics) as JSON
+//!   over HTTP, for liveness and readiness probes and debugging.
+//!
 //! Please refer to the `start_listening_for_updates` function documentation for more details
 //! on its parameters and error handling.
 
 mod models;
+mod ack;
+mod affinity;
+mod backoff;
+mod cache;
+mod dedup;
+mod repeat;
+mod rollback;
+mod dry_run;
+mod clock;
+mod endpoints;
+mod maintenance;
+mod event_id;
+mod format;
+mod framing;
+#[cfg(feature = "config-source")]
+mod config_source;
+mod client;
 mod listener;
+mod handle;
+mod history;
+mod manager;
+mod snapshot;
+mod layered;
+mod status;
+mod stale;
+mod parser;
+mod incremental;
+mod utf8;
+mod schema;
+mod pipeline;
+mod decoder;
+mod broadcast;
+mod workers;
+mod transport;
+#[cfg(feature = "replay")]
+mod replay;
+#[cfg(feature = "checksum")]
+mod checksum;
+#[cfg(feature = "testing")]
+mod testing;
+#[cfg(feature = "load-test")]
+mod load;
+#[cfg(feature = "grpc")]
+mod grpc;
+#[cfg(feature = "unix-socket")]
+mod unix;
+#[cfg(feature = "status-server")]
+mod health;
 mod errors;
 mod logger;
+mod metrics;
+#[cfg(test)]
+mod test_server;
 
-pub use models::ServerConfig;
-pub use listener::start_listening_for_updates;
+pub use models::{ArrayFlattening, ArrayMerge, ConfigDiff, ConfigEvent, ConfigOrigin, EventMeta, FlattenOptions, MergePolicy, NullValues, ServerConfig, ServerConfigBuilder, WireFormat};
+pub use ack::DEFAULT_ACK_RETRIES;
+pub use backoff::{DEFAULT_BACKOFF_FACTOR, DEFAULT_INITIAL_DELAY};
+pub use cache::{ConfigStore, FileStore};
+pub use decoder::ConfigDecoder;
+pub use broadcast::{BroadcastEvent, ConfigBroadcast, ConfigSubscriber};
+pub use client::{AddressFamily, SharedConfig, SseClient, SseClientBuilder, DEFAULT_BROADCAST_CAPACITY, DEFAULT_CONCURRENCY, DEFAULT_EVENT_CHANNEL_CAPACITY, DEFAULT_MAX_REDIRECTS, DEFAULT_MAX_RETRIES, DEFAULT_REMOVAL_EVENT, DEFAULT_RETRY_SOON_DELAY, ENV_MAX_RETRIES, ENV_TOKEN, ENV_URL};
+pub use clock::{Clock, TokioClock};
+pub use endpoints::{DEFAULT_ENDPOINT_COOLDOWN, DEFAULT_ENDPOINT_WEIGHT};
+pub use errors::{ConfigError, ConfigValueError, ErrorPolicy, TimeoutKind};
+pub use event_id::EventIdStrategy;
+pub use format::PayloadFormat;
+pub use framing::Framing;
+pub use maintenance::{MaintenanceWindow, DEFAULT_MAINTENANCE_RETRY_INTERVAL};
+pub use schema::{Schema, SettingType, TypedServerConfig};
+pub use logger::{LogFormat, LogOverflow, DEFAULT_LOG_CHANNEL_CAPACITY};
+#[cfg(feature = "config-source")]
+pub use config_source::SseSource;
+pub use metrics::{LatencySnapshot, Metrics, MetricsSnapshot, LATENCY_BUCKETS};
+#[cfg(feature = "metrics")]
+pub use metrics::render_prometheus;
+#[cfg(feature = "replay")]
+pub use replay::ReplayTiming;
+#[cfg(feature = "load-test")]
+pub use load::{LoadReport, LoadTest};
+#[cfg(feature = "checksum")]
+pub use checksum::ChecksumAlgorithm;
+#[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+pub use reqwest::Identity;
+pub use listener::{spawn_listener, spawn_shared, start_listening_for_events, start_listening_for_updates};
+pub use handle::{DispatchInfo, ListenerHandle};
+pub use history::ConfigChangeRecord;
+pub use manager::{ConflictResolution, EffectiveConfig, StreamManager};
+pub use snapshot::Snapshot;
+pub use layered::ConfigSource;
+pub use status::{default_retry_on_status, default_status_policy, ContentTypePolicy, StatusAction};
+pub use reqwest::{Method, StatusCode};
@@ -1,14 +1,37 @@
 // listener.rs
 
-use crate::errors::ConfigError;
-use crate::logger::configure_logging;
-use crate::models::ServerConfig;
+use crate::affinity::CookieJar;
+use crate::client::{RequestBody, SharedConfig, SseClient, SseClientBuilder};
+use crate::endpoints::EndpointSelector;
+#[cfg(feature = "status-server")]
+use crate::health::{ListenerStatus, StatusServer};
+use crate::errors::{ConfigError, ErrorPolicy, TimeoutKind};
+use crate::handle::{mark_ready, ready_channel, ListenerHandle, Remote};
+use crate::logger::{configure_logging, LogGuard};
+use crate::models::{ConfigEvent, ConfigOrigin, EventMeta, ServerConfig};
+use crate::pipeline::{call_handlers, Pipeline};
+use crate::rollback::RollbackRequest;
+use crate::stale::Freshness;
+use crate::status::{ContentTypePolicy, StatusAction};
+use crate::transport::{HttpTransport, Transport};
+use crate::workers::{run_worker, worker_index};
+use futures::future::FutureExt;
 use futures::stream::StreamExt;
-use reqwest::Client;
-use serde_json::from_slice;
-use slog::{info, warn};
-use tokio::time::{sleep, Duration};
+use reqwest::header::{ACCEPT, CONTENT_TYPE, COOKIE, LOCATION};
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode};
+#[cfg(all(feature = "http3", reqwest_unstable))]
+use reqwest::Version;
+use slog::{debug, info, warn, Drain, Logger};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio::time::Instant;
 
+/// How long the client connects over TCP only after an HTTP/3 connection
+/// attempt failed.
+#[cfg(all(feature = "http3", reqwest_unstable))]
+const HTTP3_RETRY_AFTER: Duration = Duration::from_secs(5 * 60);
 
 /// Starts listening for Server-Sent Events (SSE) from the specified URL and
 /// handles updates using the provided update handler function.
@@ -29,90 +52,1045 @@ use tokio::time::{sleep, Duration};
 /// # Errors
 ///
 /// Returns `Err(ConfigError)` if an error occurs while trying to establish a connection,
-/// if there is an issue with the incoming data stream, or if the maximum number of retries
-/// is reached without a successful connection.
+/// if there is an issue with the incoming data stream, or `ConfigError::GaveUp` if the maximum
+/// number of retries is reached without a successful connection. A `4xx` status other than `408` and `429`
+/// is not retried and ends the listener with `ConfigError::HttpStatus` right away.
 ///
 /// # Examples
 ///
-/// ```
-/// async fn update_config(config: ServerConfig) {
+/// ```no_run
+/// use config_sdk::{start_listening_for_updates, ServerConfig};
+///
+/// fn update_config(config: ServerConfig) {
 ///     // Handle the configuration update here
 /// }
 ///
+/// # async fn run() {
 /// let url = "http://example.com/config_stream";
 /// start_listening_for_updates(url, update_config, 5).await.unwrap();
+/// # }
 /// ```
 pub async fn start_listening_for_updates<F>(url: &str, mut update_handler: F, max_retries: u32) -> Result<(), ConfigError>
 where
     F: FnMut(ServerConfig) + Send + 'static,
 {
-    let log = configure_logging();
-    let client = Client::builder()
-        .user_agent("RichieClient/1.0")
-        .build()?;
+    start_listening_for_events(url, move |event: ConfigEvent| update_handler(event.config), max_retries).await
+}
+
+/// Starts listening for Server-Sent Events (SSE) from the specified URL and
+/// hands each update to `event_handler` together with its raw payload.
+///
+/// This behaves exactly like [`start_listening_for_updates`], but instead of only
+/// the parsed `ServerConfig` the handler receives a [`ConfigEvent`] that also holds
+/// the verbatim `data:` bytes sent by the server. Use it when the original payload
+/// has to be archived or replayed, since deserializing into `ServerConfig` does not
+/// preserve key ordering or formatting.
+///
+/// # Errors
+///
+/// Fails under the same conditions as [`start_listening_for_updates`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use config_sdk::{start_listening_for_events, ConfigEvent};
+///
+/// # async fn run() {
+/// let url = "http://example.com/config_stream";
+/// start_listening_for_events(url, |event: ConfigEvent| {
+///     println!("raw payload: {}", String::from_utf8_lossy(&event.raw));
+/// }, 5).await.unwrap();
+/// # }
+/// ```
+pub async fn start_listening_for_events<F>(url: &str, event_handler: F, max_retries: u32) -> Result<(), ConfigError>
+where
+    F: FnMut(ConfigEvent) + Send + 'static,
+{
+    SseClientBuilder::new(url)
+        .max_retries(max_retries)
+        .build()?
+        .listen_for_events(event_handler)
+        .await
+}
+
+/// Starts listening for configuration updates on a background task.
+///
+/// This is the spawned counterpart of [`start_listening_for_updates`]: instead of
+/// running until the listener stops, it returns a [`ListenerHandle`] right away.
+/// Await [`ListenerHandle::first_config`] to find out when the first
+/// configuration has been applied. Must be called from within a tokio runtime.
+///
+/// # Examples
+///
+/// ```no_run
+/// use config_sdk::{spawn_listener, ServerConfig};
+///
+/// # async fn run() -> Result<(), config_sdk::ConfigError> {
+/// let handle = spawn_listener("http://example.com/config_stream", |config: ServerConfig| {
+///     // Handle the configuration update here
+/// }, 5);
+///
+/// handle.first_config().await?;
+/// // The service can now report itself as ready.
+/// # Ok(())
+/// # }
+/// ```
+pub fn spawn_listener<F>(url: &str, update_handler: F, max_retries: u32) -> ListenerHandle
+where
+    F: FnMut(ServerConfig) + Send + 'static,
+{
+    match SseClientBuilder::new(url).max_retries(max_retries).build() {
+        Ok(client) => client.spawn(update_handler),
+        Err(e) => {
+            let (_, ready) = ready_channel();
+            ListenerHandle::spawn(async move { Err(e) }, ready, Arc::new(Remote::new(Default::default(), None)))
+        },
+    }
+}
+
+/// Starts listening for configuration updates from the specified URL on a
+/// background task, keeping the latest configuration in a shared lock.
+///
+/// This is the usual way of wiring the listener into an application: the
+/// listener writes every configuration into the lock, and any part of the
+/// application reads it from there. The lock holds `None` until the first
+/// configuration has arrived; [`ListenerHandle::first_config`] completes at that
+/// point. See [`SseClient::spawn_shared`] to start it with other options.
+///
+/// # Arguments
+///
+/// * `url` - A string slice that holds the URL of the SSE server to connect to.
+/// * `max_retries` - The maximum number of connection attempts to make before giving up.
+///
+/// # Examples
+///
+/// ```no_run
+/// use config_sdk::spawn_shared;
+///
+/// # async fn run() -> Result<(), config_sdk::ConfigError> {
+/// let (handle, shared) = spawn_shared("http://example.com/sse", 5);
+/// handle.first_config().await?;
+///
+/// if let Some(config) = shared.read().unwrap().as_ref() {
+///     println!("level: {}", config.settings["level"]);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn spawn_shared(url: &str, max_retries: u32) -> (ListenerHandle, SharedConfig) {
+    match SseClientBuilder::new(url).max_retries(max_retries).build() {
+        Ok(client) => client.spawn_shared(),
+        Err(e) => {
+            let (_, ready) = ready_channel();
+            let handle = ListenerHandle::spawn(async move { Err(e) }, ready, Arc::new(Remote::new(Default::default(), None)));
+            (handle, SharedConfig::default())
+        },
+    }
+}
+
+/// Runs the connect/read/retry loop for `sse_client`, passing every parsed
+/// configuration to `dispatch` together with the payload it was parsed from and
+/// the SSE fields that accompanied it.
+///
+/// The payload is borrowed from the read buffer, so callers that do not need it
+/// avoid copying it.
+pub(crate) async fn listen<F>(sse_client: &SseClient, dispatch: F) -> Result<(), ConfigError>
+where
+    F: FnMut(ServerConfig, &[u8], &EventMeta) + Send,
+{
+    run(sse_client, dispatch, Controls::default()).await
+}
+
+/// Runs [`listen`], following the requests made through `remote`.
+pub(crate) async fn listen_controlled<F>(sse_client: &SseClient, dispatch: F, remote: &Remote) -> Result<(), ConfigError>
+where
+    F: FnMut(ServerConfig, &[u8], &EventMeta) + Send,
+{
+    run(sse_client, dispatch, Controls { remote: Some(remote), ..Controls::default() }).await
+}
+
+/// Runs [`listen_controlled`], passing every payload to `dispatch` as it was
+/// received instead of parsing it into a configuration.
+///
+/// Fails without connecting if the client has options that need parsed
+/// configurations.
+pub(crate) async fn listen_raw<F>(sse_client: &SseClient, mut dispatch: F, remote: Option<&Remote>) -> Result<(), ConfigError>
+where
+    F: FnMut(&[u8], &EventMeta) + Send,
+{
+    sse_client.check_raw()?;
+    let dispatch = move |_config: ServerConfig, raw: &[u8], meta: &EventMeta| dispatch(raw, meta);
+    run(sse_client, dispatch, Controls { raw: true, remote, ..Controls::default() }).await
+}
+
+/// Runs the listener for `sse_client` and sends every configuration to
+/// `sender`, reading no further from the network while the channel is full.
+///
+/// `ready` is marked once the first configuration has been dispatched, and
+/// the listener follows the requests made through `remote`. Stops with
+/// `Ok(())` once the receiver has been dropped.
+pub(crate) async fn listen_to_channel(
+    sse_client: &SseClient,
+    sender: mpsc::Sender<ConfigEvent>,
+    ready: watch::Sender<bool>,
+    remote: &Remote,
+) -> Result<(), ConfigError> {
+    let outbox = Outbox::new(vec![sender]);
+    let dispatch = |config, raw: &[u8], meta: &EventMeta| {
+        outbox.push(0, ConfigEvent { config, raw: raw.to_vec(), meta: meta.clone() });
+        mark_ready(&ready);
+    };
+    run(sse_client, dispatch, Controls { outbox: Some(&outbox), remote: Some(remote), ..Controls::default() }).await
+}
+
+/// Runs the listener for `sse_client` and calls `handler` on as many workers
+/// as the client's concurrency, picking the worker for each configuration by
+/// its ordering key.
+///
+/// `ready` is marked once the first configuration has been queued, and the
+/// listener follows the requests made through `remote`. Once the listener
+/// stops, the workers finish the configurations queued for them before this
+/// returns. If the client does not catch handler panics, a panic in a worker
+/// stops the listener and is resumed here.
+pub(crate) async fn listen_concurrently<F>(
+    sse_client: &SseClient,
+    handler: F,
+    ready: watch::Sender<bool>,
+    remote: &Remote,
+) -> Result<(), ConfigError>
+where
+    F: Fn(ServerConfig) + Send + Sync + 'static,
+{
+    let (log, _log_guard) = listener_logging(sse_client);
+    let handler = Arc::new(handler);
+    let mut senders = Vec::with_capacity(sse_client.concurrency);
+    let mut workers = Vec::with_capacity(sse_client.concurrency);
+    for _ in 0..sse_client.concurrency {
+        let (sender, receiver) = mpsc::channel(sse_client.event_channel_capacity);
+        let (client, handler, log) = (sse_client.clone(), Arc::clone(&handler), log.clone());
+        senders.push(sender);
+        workers.push(tokio::task::spawn_blocking(move || run_worker(&client, &log, &*handler, receiver)));
+    }
+
+    let outbox = Outbox::new(senders);
+    let dispatch = |config: ServerConfig, raw: &[u8], meta: &EventMeta| {
+        let worker = worker_index(&config, sse_client.ordering_key.as_deref(), sse_client.concurrency);
+        outbox.push(worker, ConfigEvent { config, raw: raw.to_vec(), meta: meta.clone() });
+        mark_ready(&ready);
+    };
+    let controls = Controls { outbox: Some(&outbox), remote: Some(remote), detached: true, ..Controls::default() };
+    let result = run(sse_client, dispatch, controls).await;
+
+    // Closing the queues lets the workers stop once they are drained.
+    drop(outbox);
+    for worker in workers {
+        if let Err(e) = worker.await {
+            if e.is_panic() {
+                std::panic::resume_unwind(e.into_panic());
+            }
+        }
+    }
+    result
+}
+
+/// Runs the listener for `sse_client` and calls every handler of `handlers`
+/// for each configuration, through [`call_handlers`], so a panic in one of
+/// them neither keeps the others from running nor stops the listener.
+///
+/// `ready` is marked once the first configuration has been handled, and the
+/// listener follows the requests made through `remote`.
+pub(crate) async fn listen_fanout<F>(
+    sse_client: &SseClient,
+    mut handlers: Vec<F>,
+    ready: watch::Sender<bool>,
+    remote: &Remote,
+) -> Result<(), ConfigError>
+where
+    F: FnMut(ServerConfig) + Send,
+{
+    let (log, _log_guard) = listener_logging(sse_client);
+    let dispatch = |config: ServerConfig, _raw: &[u8], meta: &EventMeta| {
+        // Reassertions and rollbacks were acknowledged the first time around.
+        let id = meta.id.as_deref().filter(|_| !matches!(meta.origin, ConfigOrigin::Reassert | ConfigOrigin::Rollback));
+        call_handlers(sse_client, &log, id, &config, &mut handlers);
+        mark_ready(&ready);
+    };
+    run(sse_client, dispatch, Controls { remote: Some(remote), detached: true, ..Controls::default() }).await
+}
+
+/// Returns the logger of a listener of `sse_client`: the client's shared
+/// logger if it has one, else a new one along with the guard that flushes it.
+fn listener_logging(sse_client: &SseClient) -> (Logger, Option<LogGuard>) {
+    match &sse_client.shared_log {
+        Some(log) => (log.clone(), None),
+        None => {
+            let (log, guard) = configure_logging(sse_client.log_settings, sse_client.metrics());
+            (log, Some(guard))
+        },
+    }
+}
+
+/// How a listener handles payloads, and the ways for its owner to interact
+/// with it while it runs.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Controls<'a> {
+    /// Whether payloads are dispatched without being parsed, see
+    /// [`Pipeline::raw`].
+    raw: bool,
+    /// Where dispatched configurations are delivered, if they are sent to a
+    /// channel.
+    outbox: Option<&'a Outbox>,
+    /// Requests made through the listener's handle, if it has one.
+    remote: Option<&'a Remote>,
+    /// Whether configurations are dispatched to workers, see
+    /// [`Pipeline::detached`].
+    detached: bool,
+}
+
+/// Configurations dispatched while handling a chunk, waiting to be sent to
+/// bounded channels.
+///
+/// Handlers are synchronous, so a full channel cannot be waited for while a
+/// chunk is being parsed. Instead the events of one chunk are queued here and
+/// sent before the next chunk is read, which bounds the queue by what a single
+/// network read can hold and lets a full channel hold up reading.
+struct Outbox {
+    /// Each event with the index of the sender it goes to.
+    pending: Mutex<VecDeque<(usize, ConfigEvent)>>,
+    senders: Vec<mpsc::Sender<ConfigEvent>>,
+}
+
+impl Outbox {
+    fn new(senders: Vec<mpsc::Sender<ConfigEvent>>) -> Self {
+        Outbox { pending: Mutex::new(VecDeque::new()), senders }
+    }
+
+    fn push(&self, sender: usize, event: ConfigEvent) {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner()).push_back((sender, event));
+    }
+
+    /// Sends the queued events, waiting for room in the channels as needed.
+    /// Returns `false` if a receiver has been dropped.
+    async fn flush(&self) -> bool {
+        loop {
+            let next = self.pending.lock().unwrap_or_else(|e| e.into_inner()).pop_front();
+            let Some((sender, event)) = next else {
+                return true;
+            };
+            if self.senders[sender].send(event).await.is_err() {
+                return false;
+            }
+        }
+    }
+}
+
+/// Sends the events queued in `outbox`, if any. Returns `false` if the
+/// listener should stop because nobody receives them any more.
+async fn deliver(outbox: Option<&Outbox>, log: &Logger) -> bool {
+    let Some(outbox) = outbox else {
+        return true;
+    };
+    let open = outbox.flush().await;
+    if !open {
+        info!(log, "Configuration receiver was dropped, stopping listener");
+    }
+    open
+}
+
+/// Completes when a reconnect is requested through `remote`, or never if
+/// there is no way to request one.
+async fn reconnect_requested(remote: Option<&Remote>) {
+    match remote {
+        Some(remote) => remote.reconnect.notified().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Completes when the listener is resumed through `remote`, or never if
+/// there is no way to resume it.
+async fn resumed(remote: Option<&Remote>) {
+    match remote {
+        Some(remote) => remote.resumed.notified().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Completes with the rollback requested through `remote`, or never if there
+/// is no way to request one.
+async fn rollback_requested(remote: Option<&Remote>) -> RollbackRequest {
+    let Some(remote) = remote else {
+        return std::future::pending().await;
+    };
+    loop {
+        remote.rollback_requested.notified().await;
+        if let Some(request) = remote.take_rollback() {
+            return request;
+        }
+    }
+}
+
+/// Whether the listener has been paused through `remote`.
+fn is_paused(remote: Option<&Remote>) -> bool {
+    remote.is_some_and(Remote::is_paused)
+}
+
+/// Completes once `timeout` has passed on the client's clock, or never if
+/// there is no timeout.
+async fn elapsed(sse_client: &SseClient, timeout: Option<Duration>) {
+    match timeout {
+        Some(timeout) => sse_client.clock.sleep(timeout).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Returns how much longer the first configuration may take to arrive, if
+/// it is due by `due` and has not arrived yet.
+fn first_config_wait(sse_client: &SseClient, due: Option<Instant>, has_config: bool) -> Option<Duration> {
+    due.filter(|_| !has_config).map(|due| due.saturating_duration_since(sse_client.clock.now()))
+}
+
+/// Returns how much longer a connection may stay open, if it expires at
+/// `expires`.
+fn lifetime_left(sse_client: &SseClient, expires: Option<Instant>) -> Option<Duration> {
+    expires.map(|expires| expires.saturating_duration_since(sse_client.clock.now()))
+}
+
+/// Reports that no configuration arrived before the first configuration
+/// deadline and returns the error the listener stops with.
+fn first_config_missed(sse_client: &SseClient, log: &Logger) -> ConfigError {
+    warn!(log, "No configuration received before the deadline, giving up";
+        "deadline_ms" => sse_client.first_config_deadline.unwrap_or_default().as_millis() as u64);
+    let e = ConfigError::NoConfigReceived;
+    sse_client.report_error(&e);
+    e
+}
+
+/// Registers the listener with the client's status endpoint, if it has a
+/// status address, binding it if no other listener has. A failure to bind it
+/// is reported and leaves the listener without one.
+#[cfg(feature = "status-server")]
+async fn start_status_server(sse_client: &SseClient, log: &Logger) -> Option<ListenerStatus> {
+    let address = sse_client.status_address?;
+    match StatusServer::register(&sse_client.status_server, address, sse_client.metrics.clone(), log).await {
+        Ok(status) => Some(status),
+        Err(e) => {
+            warn!(log, "Failed to bind the status endpoint, serving without it"; "address" => %address, "error" => %e);
+            sse_client.report_error(&ConfigError::Io(e));
+            None
+        },
+    }
+}
+
+/// Runs the connect/read/retry loop over HTTP, see [`run_with`].
+async fn run<F>(sse_client: &SseClient, dispatch: F, controls: Controls<'_>) -> Result<(), ConfigError>
+where
+    F: FnMut(ServerConfig, &[u8], &EventMeta) + Send,
+{
+    run_with(sse_client, &mut HttpTransport::new(sse_client), dispatch, controls).await
+}
+
+/// Runs the connect/read/retry loop on the connections opened by `transport`,
+/// sending the events queued in the outbox, if any, before every read.
+pub(crate) async fn run_with<F>(
+    sse_client: &SseClient,
+    transport: &mut impl Transport,
+    dispatch: F,
+    controls: Controls<'_>,
+) -> Result<(), ConfigError>
+where
+    F: FnMut(ServerConfig, &[u8], &EventMeta) + Send,
+{
+    // Declared first so the guard outlives everything that logs, and every record is
+    // flushed when the listener stops.
+    let (log, _log_guard) = listener_logging(sse_client);
+    sse_client.log_options(&log);
+    let mut endpoints = EndpointSelector::new(&sse_client.endpoints, sse_client.endpoint_cooldown);
+    let max_retries = sse_client.max_retries;
+    let mut pipeline = Pipeline::new(sse_client, log.clone(), dispatch);
+    if controls.raw {
+        pipeline = pipeline.raw();
+    }
+    if controls.detached {
+        pipeline = pipeline.detached();
+    }
+    if let Some(history) = controls.remote.and_then(|remote| remote.history.as_ref()) {
+        pipeline = pipeline.history(history);
+    }
+    if let Some(snapshots) = controls.remote.and_then(|remote| remote.snapshots.as_ref()) {
+        pipeline = pipeline.snapshots(snapshots);
+    }
+    let metrics = &sse_client.metrics;
+    let sleep = |delay| sse_client.clock.sleep(delay);
     let mut attempt = 0;
-    const BASE_DELAY: u64 = 2; // Base delay in seconds for the exponential backoff
+    // Unlike `attempt`, these are not reset by a stable connection.
+    let mut total_attempts: u64 = 0;
+    let started_at = sse_client.clock.now();
+    // When the current run of attempts started, for the retry time budget.
+    let mut retrying_since = started_at;
+    // The status of the last response, reported if the listener gives up.
+    let mut last_status = None;
+    // When the first configuration is due, counted from the first connection.
+    let mut first_config_due = None;
+    let mut freshness = Freshness::new(sse_client, started_at);
+    #[cfg(feature = "status-server")]
+    let status_server = start_status_server(sse_client, &log).await;
+
+    pipeline.set_paused(is_paused(controls.remote));
+    pipeline.load_cache().await;
+    if !deliver(controls.outbox, &log).await {
+        return Ok(());
+    }
 
     loop {
         attempt += 1;
+        total_attempts += 1;
+        if attempt == 1 {
+            retrying_since = sse_client.clock.now();
+        }
+        metrics.record_connection_attempt(attempt);
+        let mut retry_soon = false;
+        let (endpoint, selected) = endpoints.select(sse_client.clock.now());
+        let url = endpoint.url.as_str();
+        if selected && endpoints.has_failover() {
+            info!(log, "Selected endpoint"; "url" => url, "weight" => endpoint.weight);
+        }
 
-        match client.get(url)
-            .header("Accept", "text/event-stream")
-            .send()
-            .await {
-            Ok(response) => {
-                if response.status().is_success() {
-                    info!(log, "Connected to SSE server"; "url" => url, "attempt" => format!("{}", attempt));
-                    let mut stream = response.bytes_stream();
-
-                    while let Some(item) = stream.next().await {
-                        match item {
-                            Ok(bytes) => {
-                                let text = String::from_utf8(bytes.to_vec()).unwrap_or_else(|_| "".to_string());
-                                info!(log, "Received SSE data"; "data" => &text);
-
-                                if text.starts_with("data: ") {
-                                    let json_part = text.trim_start_matches("data: ").trim();
-                                    match from_slice::<ServerConfig>(json_part.as_bytes()) {
-                                        Ok(config) => {
-                                            update_handler(config);
-                                            info!(log, "Configuration updated"; "config" => json_part);
-                                        },
-                                        Err(e) => {
-                                            warn!(log, "Failed to parse configuration data"; "error" => %e);
-                                        },
+        // The configuration can turn stale while a slow connection attempt is pending
+        let opened = {
+            let open = transport.open(url, pipeline.last_event_id(), &log);
+            tokio::pin!(open);
+            loop {
+                let stale = freshness.stale_in(sse_client.clock.now());
+                tokio::select! {
+                    opened = &mut open => break opened,
+                    _ = elapsed(sse_client, stale) => freshness.check(sse_client.clock.now(), &log),
+                }
+            }
+        };
+        match opened {
+            Ok(connection) => {
+                let status = connection.status;
+                last_status = Some(status);
+                match (sse_client.status_policy)(status) {
+                    StatusAction::Connect => {
+                        let unexpected = connection.content_type.as_deref()
+                            .filter(|content_type| !sse_client.accepts_content_type(content_type));
+                        if let Some(content_type) = unexpected {
+                            match sse_client.content_type_policy {
+                                ContentTypePolicy::Warn => {
+                                    warn!(log, "Server answered with an unexpected content type, reading the stream anyway";
+                                        "content_type" => content_type, "accepted" => ?sse_client.accepted_content_types);
+                                },
+                                ContentTypePolicy::Reject => {
+                                    warn!(log, "Server answered with an unexpected content type, giving up";
+                                        "content_type" => content_type, "accepted" => ?sse_client.accepted_content_types);
+                                    let e = ConfigError::UnexpectedContentType(content_type.to_string());
+                                    sse_client.report_error(&e);
+                                    return Err(e);
+                                },
+                            }
+                        }
+                        let scheme = connection.url.split_once("://").map_or("", |(scheme, _)| scheme);
+                        let tls = scheme.eq_ignore_ascii_case("https");
+                        info!(log, "Connected to SSE server";
+                            "url" => &connection.url, "scheme" => scheme, "tls" => tls, "version" => ?connection.version,
+                            "attempt" => format!("{}", attempt));
+                        metrics.record_connection(tls, connection.version);
+                        if !tls && url.get(..8).is_some_and(|scheme| scheme.eq_ignore_ascii_case("https://")) {
+                            warn!(log, "Connection was redirected from TLS to plaintext HTTP"; "url" => url, "final_url" => &connection.url);
+                        }
+                        if first_config_due.is_none() {
+                            let now = sse_client.clock.now();
+                            first_config_due = sse_client.first_config_deadline.and_then(|deadline| now.checked_add(deadline));
+                        }
+                        pipeline.start_stream();
+                        pipeline.detect_payload_format(connection.content_type.as_deref());
+                        freshness.connected();
+                        #[cfg(feature = "status-server")]
+                        if let Some(status_server) = &status_server {
+                            status_server.connected(&connection.url);
+                        }
+                        let connected_at = sse_client.clock.now();
+                        // A lifetime too long to add to an instant never runs out
+                        let expires = sse_client.max_connection_lifetime.and_then(|lifetime| connected_at.checked_add(lifetime));
+                        let mut stream = connection.body;
+                        let mut reconnect = false;
+                        let mut requested = false;
+                        let mut expired = false;
+                        let mut received = false;
+                        // Idle time counts from the last read, not from waking up for
+                        // anything else.
+                        let mut last_read = connected_at;
+
+                        loop {
+                            let now = sse_client.clock.now();
+                            let idle_timeout = pipeline.idle_timeout();
+                            let idle_left = idle_timeout
+                                .and_then(|timeout| last_read.checked_add(timeout))
+                                .map(|deadline| deadline.saturating_duration_since(now));
+                            let first_config = first_config_wait(sse_client, first_config_due, pipeline.has_config());
+                            let lifetime = lifetime_left(sse_client, expires);
+                            let reassert = pipeline.reassert_in(now);
+                            let stale = freshness.stale_in(now);
+                            let item = tokio::select! {
+                                item = stream.next() => item,
+                                _ = elapsed(sse_client, stale) => {
+                                    freshness.check(sse_client.clock.now(), &log);
+                                    continue;
+                                },
+                                _ = elapsed(sse_client, reassert) => {
+                                    pipeline.reassert(sse_client.clock.now());
+                                    if !deliver(controls.outbox, &log).await {
+                                        return Ok(());
+                                    }
+                                    continue;
+                                },
+                                _ = reconnect_requested(controls.remote) => {
+                                    requested = true;
+                                    break;
+                                },
+                                _ = resumed(controls.remote) => {
+                                    pipeline.set_paused(is_paused(controls.remote));
+                                    if !deliver(controls.outbox, &log).await {
+                                        return Ok(());
+                                    }
+                                    continue;
+                                },
+                                request = rollback_requested(controls.remote) => {
+                                    pipeline.rollback(request);
+                                    if !deliver(controls.outbox, &log).await {
+                                        return Ok(());
+                                    }
+                                    continue;
+                                },
+                                _ = elapsed(sse_client, first_config) => {
+                                    return Err(first_config_missed(sse_client, &log));
+                                },
+                                _ = elapsed(sse_client, lifetime) => {
+                                    info!(log, "Connection reached its maximum lifetime, reconnecting";
+                                        "url" => url, "last_event_id" => pipeline.last_event_id());
+                                    expired = true;
+                                    break;
+                                },
+                                _ = elapsed(sse_client, idle_left) => {
+                                    warn!(log, "No data received within the idle timeout, reconnecting";
+                                        "idle_timeout_ms" => idle_timeout.unwrap_or_default().as_millis() as u64);
+                                    sse_client.report_error(&ConfigError::Timeout(TimeoutKind::Idle));
+                                    reconnect = true;
+                                    break;
+                                },
+                            };
+                            let Some(item) = item else {
+                                let received = pipeline.received();
+                                if sse_client.flush_on_eof && pipeline.end_stream().await {
+                                    info!(log, "Stream ended with an unterminated event, dispatched it";
+                                        "id" => pipeline.last_event_id());
+                                    if pipeline.received() != received {
+                                        freshness.received(sse_client.clock.now(), &log);
+                                        #[cfg(feature = "status-server")]
+                                        if let Some(status_server) = &status_server {
+                                            status_server.updated(pipeline.last_event_id());
+                                        }
+                                    }
+                                    if !deliver(controls.outbox, &log).await {
+                                        return Ok(());
                                     }
                                 }
-                            },
-                            Err(e) => {
-                                warn!(log, "Error processing SSE data"; "error" => %e);
-                                return Err(ConfigError::Request(e));
-                            },
+                                break;
+                            };
+                            match item {
+                                Ok(bytes) => {
+                                    info!(log, "Received SSE data"; "bytes" => bytes.len());
+                                    // The data may hold secrets, so it is only
+                                    // logged when debugging.
+                                    if log.is_debug_enabled() {
+                                        debug!(log, "Received SSE data"; "data" => %String::from_utf8_lossy(&bytes));
+                                    }
+                                    received = true;
+                                    last_read = sse_client.clock.now();
+
+                                    pipeline.set_paused(is_paused(controls.remote));
+                                    let received = pipeline.received();
+                                    let fed = pipeline.feed(&bytes).await;
+                                    if pipeline.received() != received {
+                                        freshness.received(sse_client.clock.now(), &log);
+                                        #[cfg(feature = "status-server")]
+                                        if let Some(status_server) = &status_server {
+                                            status_server.updated(pipeline.last_event_id());
+                                        }
+                                    }
+                                    if !deliver(controls.outbox, &log).await {
+                                        return Ok(());
+                                    }
+                                    if let Err(e) = fed {
+                                        warn!(log, "SSE line exceeds the maximum line length, reconnecting";
+                                            "length" => e.length, "max_line_length" => sse_client.max_line_length);
+                                        reconnect = true;
+                                        break;
+                                    }
+                                },
+                                // Most likely a pooled connection the server had closed, so the
+                                // transport opens a fresh one and nothing was lost
+                                Err(e) if !received && e.is_connection_closed() => {
+                                    warn!(log, "Server closed the connection before sending any data, reconnecting";
+                                        "error" => %e);
+                                    reconnect = true;
+                                    break;
+                                },
+                                Err(e) => {
+                                    warn!(log, "Error processing SSE data";
+                                        "error" => %e, "error_policy" => ?sse_client.error_policy);
+                                    match sse_client.error_policy {
+                                        ErrorPolicy::Terminate => return Err(e),
+                                        ErrorPolicy::Reconnect => {
+                                            sse_client.report_error(&e);
+                                            reconnect = true;
+                                            break;
+                                        },
+                                    }
+                                },
+                            }
                         }
-                    }
 
-                    // Exit the loop successfully after processing the stream
-                    break;
-                } else {
-                    warn!(log, "Received non-success status from SSE server"; "status" => %response.status(), "url" => %url);
-                    // Instead of breaking, continue to apply retry logic
+                        freshness.disconnected(sse_client.clock.now());
+                        #[cfg(feature = "status-server")]
+                        if let Some(status_server) = &status_server {
+                            status_server.disconnected();
+                        }
+
+                        // A requested reconnect is not a failure, so it skips the backoff
+                        if requested {
+                            info!(log, "Reconnect requested, dropping the connection");
+                            // Requests that arrived together are served by this reconnect
+                            if let Some(remote) = controls.remote {
+                                remote.reconnect.notified().now_or_never();
+                            }
+                            attempt = 0;
+                            continue;
+                        }
+
+                        // Neither is recycling an old connection, which may move to another endpoint
+                        if expired {
+                            endpoints.release();
+                            attempt = 0;
+                            continue;
+                        }
+
+                        // Exit the loop successfully after processing the stream, unless the
+                        // connection was abandoned and has to be re-established
+                        if !reconnect {
+                            break;
+                        }
+
+                        // A connection that stayed up long enough counts as a success, so
+                        // the next one starts over with a fresh backoff sequence
+                        let uptime = sse_client.clock.now().saturating_duration_since(connected_at);
+                        if sse_client.stability_window.is_some_and(|window| uptime >= window) {
+                            info!(log, "Connection was stable, resetting backoff"; "uptime_ms" => uptime.as_millis() as u64);
+                            attempt = 0;
+                            continue;
+                        }
+                        // The connection worked, so the retry time budget only counts from
+                        // here, even if backoff keeps escalating
+                        retrying_since = sse_client.clock.now();
+                    },
+                    StatusAction::Retry => {
+                        warn!(log, "Received non-success status from SSE server"; "status" => %status, "url" => %url);
+                        endpoints.fail(sse_client.clock.now());
+                        // Instead of breaking, continue to apply retry logic
+                    },
+                    StatusAction::RetrySoon => {
+                        info!(log, "SSE server asked to retry later"; "status" => %status, "url" => %url);
+                        endpoints.fail(sse_client.clock.now());
+                        retry_soon = true;
+                    },
+                    StatusAction::Fatal => {
+                        warn!(log, "Received fatal status from SSE server, giving up"; "status" => %status, "url" => %url);
+                        return Err(ConfigError::HttpStatus(status));
+                    },
                 }
             },
+            Err(e @ ConfigError::InsecureConnection(_)) => {
+                sse_client.report_error(&e);
+                return Err(e);
+            },
             Err(e) => {
-                warn!(log, "Failed to connect to SSE server"; "error" => %e, "attempt" => format!("{}", attempt));
+                warn!(log, "Failed to connect to SSE server"; "error" => %e, "attempt" => format!("{}", attempt),
+                    "max_retries" => max_retries, "remaining" => max_retries.saturating_sub(attempt));
+                if let ConfigError::Timeout(_) = e {
+                    sse_client.report_error(&e);
+                }
+                endpoints.fail(sse_client.clock.now());
             },
         }
 
-        if attempt >= max_retries {
-            // Give up after reaching the maximum number of retries
-            return Err(ConfigError::GenericError("Maximum retries reached, giving up.".to_string()));
+        // Attempts made during a maintenance window count toward neither limit, so
+        // both apply afresh, with a fresh backoff sequence, once it is over
+        let maintenance = sse_client.in_maintenance();
+        if maintenance {
+            attempt = 0;
+        }
+        // A budget too long to add to an instant never runs out
+        let retry_deadline = sse_client
+            .max_total_retry_duration
+            .filter(|_| !maintenance)
+            .and_then(|budget| retrying_since.checked_add(budget));
+        let out_of_time = retry_deadline.is_some_and(|deadline| sse_client.clock.now() >= deadline);
+        if !maintenance && (attempt >= max_retries || out_of_time) {
+            // Give up after reaching the maximum number of retries, or running out of time
+            let elapsed = sse_client.clock.now().saturating_duration_since(started_at);
+            if out_of_time {
+                warn!(log, "Retry time budget exhausted, giving up";
+                    "url" => %url, "max_total_retry_duration_ms" => sse_client.max_total_retry_duration.unwrap_or_default().as_millis() as u64,
+                    "total_attempts" => total_attempts, "elapsed_ms" => elapsed.as_millis() as u64,
+                    "last_status" => last_status.map(|status| status.as_u16()));
+            } else {
+                warn!(log, "Maximum retries reached, giving up";
+                    "url" => %url, "max_retries" => max_retries, "total_attempts" => total_attempts,
+                    "elapsed_ms" => elapsed.as_millis() as u64, "last_status" => last_status.map(|status| status.as_u16()));
+            }
+            let e = ConfigError::GaveUp { total_attempts, elapsed, last_status };
+            sse_client.report_error(&e);
+            return Err(e);
         }
 
-        // Calculate the delay for the exponential backoff
-        let delay = BASE_DELAY.pow(attempt) as u64;
-        warn!(log, "Retrying in {} seconds...", delay);
-        sleep(Duration::from_secs(delay)).await;
+        // Calculate the delay for the exponential backoff, unless the server asked
+        // for a quick retry or is down for maintenance
+        let delay = if maintenance {
+            sse_client.maintenance_retry_interval
+        } else if retry_soon {
+            sse_client.retry_soon_delay
+        } else {
+            sse_client.backoff.delay_for_attempt(attempt)
+        };
+        // The last attempt is made when the retry time budget runs out, not after it
+        let delay = retry_deadline.map_or(delay, |deadline| delay.min(deadline.saturating_duration_since(sse_client.clock.now())));
+        if maintenance {
+            info!(log, "Server is in a maintenance window, retrying in {} seconds...", delay.as_secs_f64(); "url" => %url);
+        } else {
+            warn!(log, "Retrying in {} seconds...", delay.as_secs_f64();
+                "attempt" => format!("{}", attempt), "max_retries" => max_retries, "remaining" => max_retries - attempt);
+        }
+        let mut retry = sleep(delay);
+        loop {
+            freshness.check(sse_client.clock.now(), &log);
+            let first_config = first_config_wait(sse_client, first_config_due, pipeline.has_config());
+            let stale = freshness.stale_in(sse_client.clock.now());
+            let reassert = pipeline.reassert_in(sse_client.clock.now());
+            tokio::select! {
+                _ = &mut retry => break,
+                _ = elapsed(sse_client, reassert) => {
+                    pipeline.reassert(sse_client.clock.now());
+                    if !deliver(controls.outbox, &log).await {
+                        return Ok(());
+                    }
+                },
+                _ = elapsed(sse_client, first_config) => {
+                    return Err(first_config_missed(sse_client, &log));
+                },
+                _ = elapsed(sse_client, stale) => {},
+                _ = reconnect_requested(controls.remote) => {
+                    info!(log, "Reconnect requested, retrying now");
+                    break;
+                },
+                // A configuration held back while paused is released without waiting
+                // for the connection
+                _ = resumed(controls.remote) => {
+                    pipeline.set_paused(is_paused(controls.remote));
+                    if !deliver(controls.outbox, &log).await {
+                        return Ok(());
+                    }
+                },
+                request = rollback_requested(controls.remote) => {
+                    pipeline.rollback(request);
+                    if !deliver(controls.outbox, &log).await {
+                        return Ok(());
+                    }
+                },
+            }
+        }
     }
 
     Ok(())
 }
+
+/// Sends the subscription request for `sse_client` to `url`, following up to
+/// `max_redirects` redirects.
+///
+/// Redirects are followed here rather than by `reqwest` so that the
+/// `Authorization` and `Last-Event-ID` headers are sent again on every hop,
+/// including hops to a different host. A `303` redirect, or a `301` or `302`
+/// redirect of a `POST`, switches to a `GET` without a body; other redirects
+/// repeat the request as it was. The returned response is the first one
+/// that is not a redirect, or the last redirect if the limit was reached or it
+/// has no usable `Location` header. Requests over a Unix domain socket are sent
+/// once, without following redirects.
+///
+/// With `fresh`, the first request is sent on a new connection rather than
+/// one from the pool. With `cookies`, every request carries the cookies set
+/// by its host, and the cookies set by every response are stored.
+pub(crate) async fn connect(
+    sse_client: &SseClient,
+    url: &str,
+    last_event_id: Option<&str>,
+    fresh: bool,
+    mut cookies: Option<&mut CookieJar>,
+    log: &Logger,
+) -> Result<Response, ConfigError> {
+    #[cfg(feature = "unix-socket")]
+    if let Some(socket) = &sse_client.unix_socket {
+        return crate::unix::send(sse_client, socket, last_event_id).await;
+    }
+
+    let mut method = sse_client.method.clone();
+    let mut body = sse_client.request_body.as_ref();
+    let resume = Resume { last_event_id, cookie: cookies.as_deref().and_then(|jar| jar.header_for(url)) };
+    let mut response = send(sse_client, method.clone(), url, body, &resume, fresh, log).await?;
+    let mut redirects = 0;
+
+    loop {
+        if let Some(jar) = cookies.as_deref_mut() {
+            jar.store(response.url(), response.headers(), log);
+        }
+        if !response.status().is_redirection() {
+            break;
+        }
+        let location = response.headers()
+            .get(LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .and_then(|location| response.url().join(location).ok());
+        let Some(location) = location else {
+            break;
+        };
+        if redirects >= sse_client.max_redirects {
+            warn!(log, "Too many redirects"; "url" => %response.url(), "max_redirects" => sse_client.max_redirects);
+            break;
+        }
+        if sse_client.require_tls && location.scheme() != "https" {
+            warn!(log, "Refusing to follow redirect to a plaintext URL"; "from" => %response.url(), "to" => %location);
+            return Err(ConfigError::InsecureConnection(location.to_string()));
+        }
+
+        let status = response.status();
+        if status == StatusCode::SEE_OTHER
+            || (method == Method::POST && matches!(status, StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND))
+        {
+            method = Method::GET;
+            body = None;
+        }
+        redirects += 1;
+        info!(log, "Following redirect"; "status" => %status, "from" => %response.url(), "to" => %location, "method" => %method);
+        let resume = Resume { last_event_id, cookie: cookies.as_deref().and_then(|jar| jar.header_for(location.as_str())) };
+        response = send(sse_client, method.clone(), location.as_str(), body, &resume, false, log).await?;
+    }
+
+    Ok(response)
+}
+
+/// Issues a single request to `url` with the headers required by `sse_client`.
+///
+/// With HTTP/3 enabled, the request is first made over QUIC, and made again
+/// over TCP if that fails. An idempotent request sent on a pooled connection
+/// the server had already closed is made again once on a fresh connection;
+/// a `POST` is not, as it may have reached the server. Every request is sent
+/// on a fresh connection if `fresh` is set.
+async fn send(
+    sse_client: &SseClient,
+    method: Method,
+    url: &str,
+    body: Option<&RequestBody>,
+    resume: &Resume<'_>,
+    fresh: bool,
+    log: &Logger,
+) -> Result<Response, ConfigError> {
+    #[cfg(all(feature = "http3", reqwest_unstable))]
+    if sse_client.http3 && url.starts_with("https:") && http3_usable(sse_client) {
+        let request = build_request(&sse_client.http, sse_client, method.clone(), url, body, resume)
+            .version(Version::HTTP_3);
+        match request.send().await {
+            Ok(response) => return Ok(response),
+            Err(e) => {
+                warn!(log, "HTTP/3 connection failed, falling back to TCP"; "url" => url, "error" => %e);
+                *sse_client.http3_failed.lock().unwrap_or_else(|e| e.into_inner()) = Some(sse_client.clock.now());
+            },
+        }
+    }
+
+    if !fresh {
+        let sent = build_request(&sse_client.http, sse_client, method.clone(), url, body, resume)
+            .send()
+            .await
+            .map_err(|e| ConfigError::from_request(e, TimeoutKind::Connect));
+        match sent {
+            Err(e) if e.is_connection_closed() && method.is_idempotent() => {
+                warn!(log, "Server closed the connection before answering, retrying on a fresh connection"; "url" => url, "error" => %e);
+            },
+            sent => return sent,
+        }
+    }
+    build_request(&sse_client.fresh_http, sse_client, method, url, body, resume)
+        .send()
+        .await
+        .map_err(|e| ConfigError::from_request(e, TimeoutKind::Connect))
+}
+
+/// Returns `true` unless an HTTP/3 connection attempt failed less than
+/// `HTTP3_RETRY_AFTER` ago.
+#[cfg(all(feature = "http3", reqwest_unstable))]
+fn http3_usable(sse_client: &SseClient) -> bool {
+    let failed = *sse_client.http3_failed.lock().unwrap_or_else(|e| e.into_inner());
+    failed.is_none_or(|at| sse_client.clock.now().saturating_duration_since(at) >= HTTP3_RETRY_AFTER)
+}
+
+/// What a subscription request tells the server about the stream it
+/// resumes.
+struct Resume<'a> {
+    /// Sent as `Last-Event-ID`.
+    last_event_id: Option<&'a str>,
+    /// Sent as `Cookie`, for the listener to land on the same backend.
+    cookie: Option<String>,
+}
+
+/// Builds a request to `url` on `http` with the headers required by
+/// `sse_client`.
+fn build_request(
+    http: &Client,
+    sse_client: &SseClient,
+    method: Method,
+    url: &str,
+    body: Option<&RequestBody>,
+    resume: &Resume<'_>,
+) -> RequestBuilder {
+    let mut request = http.request(method, url).header(ACCEPT, "text/event-stream");
+    if let Some(token) = &sse_client.bearer_token {
+        request = request.bearer_auth(token);
+    }
+    if let Some(id) = resume.last_event_id {
+        request = request.header("Last-Event-ID", id);
+    }
+    if let Some(cookie) = &resume.cookie {
+        request = request.header(COOKIE, cookie.as_str());
+    }
+    if let Some(body) = body {
+        request = request.header(CONTENT_TYPE, body.content_type.as_str()).body(body.content.clone());
+    }
+    request
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{Reply, TestServer};
+
+    #[tokio::test]
+    async fn spawn_shared_keeps_the_latest_configuration() {
+        let server = TestServer::start([Reply::events("data: {\"level\": 3}\n\n")]).await;
+
+        let (handle, shared) = spawn_shared(&server.url, 5);
+        handle.first_config().await.unwrap();
+
+        let config = shared.read().unwrap();
+        assert_eq!(config.as_ref().unwrap().settings["level"], 3);
+        handle.abort();
+    }
+}
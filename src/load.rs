@@ -0,0 +1,199 @@
+// load.rs
+
+use crate::client::SseClient;
+use crate::logger::configure_logging;
+use crate::metrics::{Metrics, MetricsSnapshot};
+use crate::models::ServerConfig;
+use slog::info;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How [`SseClient::load_test`] loads a server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadTest {
+    clients: usize,
+    stagger: Duration,
+    duration: Duration,
+}
+
+/// What a [`LoadTest`] measured, counted across all its listeners.
+///
+/// Its `Display` implementation renders the summary the load test logs when
+/// it ends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoadReport {
+    /// The number of listeners started.
+    pub clients: usize,
+    /// How long the load test ran, from starting the first listener to
+    /// stopping them all.
+    pub elapsed: Duration,
+    /// Listeners that stopped on their own before the end, e.g. because they
+    /// ran out of retries.
+    pub stopped_early: usize,
+    /// The counters of all listeners added together.
+    pub metrics: MetricsSnapshot,
+}
+
+impl LoadTest {
+    /// Creates a load test of `clients` listeners, all started at once and
+    /// running for a minute.
+    pub fn new(clients: usize) -> Self {
+        LoadTest { clients, stagger: Duration::ZERO, duration: Duration::from_secs(60) }
+    }
+
+    /// Sets how long to wait between starting one listener and the next, so
+    /// the server is not hit by every connection at the same instant.
+    /// Defaults to zero.
+    pub fn stagger(mut self, stagger: Duration) -> Self {
+        self.stagger = stagger;
+        self
+    }
+
+    /// Sets how long the load test runs, counted from starting the first
+    /// listener, staggered starts included. Defaults to a minute.
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+}
+
+impl SseClient {
+    /// Loads the server with many concurrent listeners of this client and
+    /// reports how it held up.
+    ///
+    /// Every listener is a real one, started like with [`spawn`](Self::spawn)
+    /// and a handler that does nothing, so the traffic is exactly that of the
+    /// same number of production clients with the same options: connections,
+    /// retries with backoff, reconnects and `Last-Event-ID` included. When
+    /// the test's duration has passed, every listener is stopped and the
+    /// summary is logged at info level.
+    ///
+    /// The listeners share counters of their own, so the report covers the
+    /// load test alone, and the client's [`metrics`](Self::metrics) are left
+    /// alone. They also share one logger, so its log settings apply to all of
+    /// them; for thousands of listeners it is best to only log warnings.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use config_sdk::{LoadTest, SseClientBuilder};
+    /// use std::time::Duration;
+    ///
+    /// # async fn run() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/sse").build()?;
+    /// let test = LoadTest::new(1000).stagger(Duration::from_millis(5)).duration(Duration::from_secs(60));
+    /// let report = client.load_test(test).await;
+    ///
+    /// assert_eq!(report.connect_success_rate(), 1.0);
+    /// println!("{}", report);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn load_test(&self, test: LoadTest) -> LoadReport {
+        let mut client = self.clone();
+        client.metrics = Arc::new(Metrics::default());
+        let (log, _log_guard) = configure_logging(client.log_settings, client.metrics());
+        client.shared_log = Some(log.clone());
+        // The status endpoint serves the client's own listeners and counters.
+        #[cfg(feature = "status-server")]
+        {
+            client.status_address = None;
+        }
+        info!(log, "Starting load test"; "url" => &client.url, "clients" => test.clients,
+            "stagger_ms" => test.stagger.as_millis() as u64, "duration_ms" => test.duration.as_millis() as u64);
+
+        let started_at = client.clock.now();
+        // A duration too long to add to an instant runs until the future is dropped.
+        let deadline = started_at.checked_add(test.duration);
+        let remaining = || deadline.map_or(Duration::MAX, |deadline| deadline.saturating_duration_since(client.clock.now()));
+        let mut handles = Vec::with_capacity(test.clients);
+        for i in 0..test.clients {
+            if i > 0 && !test.stagger.is_zero() {
+                client.clock.sleep(test.stagger.min(remaining())).await;
+            }
+            if remaining().is_zero() {
+                break;
+            }
+            handles.push(client.spawn(|_: ServerConfig| {}));
+        }
+        client.clock.sleep(remaining()).await;
+
+        let stopped_early = handles.iter().filter(|handle| handle.is_finished()).count();
+        for handle in &handles {
+            handle.abort();
+        }
+        let report = LoadReport {
+            clients: handles.len(),
+            elapsed: client.clock.now().saturating_duration_since(started_at),
+            stopped_early,
+            metrics: client.metrics.snapshot(),
+        };
+        // The listeners log through `log`, so they must be gone before its guard is dropped.
+        for handle in handles {
+            let _ = handle.join().await;
+        }
+        info!(log, "Load test finished"; "report" => %report);
+        report
+    }
+}
+
+impl LoadReport {
+    /// Returns the share of connection attempts that produced an event
+    /// stream, from 0 to 1, or 0 if there were none.
+    pub fn connect_success_rate(&self) -> f64 {
+        match self.metrics.connection_attempts {
+            0 => 0.0,
+            attempts => self.metrics.connections as f64 / attempts as f64,
+        }
+    }
+
+    /// Returns the number of configurations received per second, across all
+    /// listeners.
+    pub fn events_per_second(&self) -> f64 {
+        match self.elapsed.as_secs_f64() {
+            seconds if seconds > 0.0 => self.metrics.events as f64 / seconds,
+            _ => 0.0,
+        }
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} clients for {:.1}s: {}/{} connections succeeded ({:.1}%), {} reconnects, {} events ({:.1}/s), \
+             {} parse errors, {} stopped early",
+            self.clients,
+            self.elapsed.as_secs_f64(),
+            self.metrics.connections,
+            self.metrics.connection_attempts,
+            self.connect_success_rate() * 100.0,
+            self.metrics.reconnects,
+            self.metrics.events,
+            self.events_per_second(),
+            self.metrics.parse_errors,
+            self.stopped_early,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::SseClientBuilder;
+    use crate::test_server::{Reply, TestServer};
+
+    #[tokio::test]
+    async fn every_listener_connects_and_receives() {
+        let server = TestServer::repeat(|| Reply::events("data: {\"level\": 3}\n\n")).await;
+
+        let client = SseClientBuilder::new(&server.url).build().unwrap();
+        let test = LoadTest::new(20).stagger(Duration::from_millis(5)).duration(Duration::from_millis(500));
+        let report = client.load_test(test).await;
+
+        assert_eq!(report.metrics.connections, 20);
+        assert_eq!(report.metrics.events, 20);
+        assert_eq!(report.connect_success_rate(), 1.0);
+    }
+}
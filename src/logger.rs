@@ -1,51 +1,193 @@
-use slog::{Drain, Logger, o};
-use slog_async::Async;
+use crate::metrics::Metrics;
+use slog::{Drain, Logger, OwnedKVList, Record, o};
+use slog_async::{AsyncCore, AsyncError, AsyncGuard};
 use slog_json::Json;
 use slog_term::{FullFormat, TermDecorator};
+use std::path::Path;
+use std::sync::Arc;
 
-/// Configures and returns a `Logger` instance that outputs logs to both the terminal
-/// and `stdout` in JSON format.
+/// Default number of log records buffered per output before the overflow
+/// strategy applies.
+pub const DEFAULT_LOG_CHANNEL_CAPACITY: usize = 128;
+
+/// What happens to a log record when the buffer of the asynchronous logger is
+/// full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogOverflow {
+    /// The record is discarded and counted in
+    /// `MetricsSnapshot::log_messages_dropped`, so logging never slows the
+    /// listener down.
+    #[default]
+    Drop,
+    /// The listener waits until there is room in the buffer, so no record is
+    /// lost.
+    Block,
+}
+
+/// Where and in which format a listener writes its log records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines on the terminal.
+    Term,
+    /// One JSON object per record on `stdout`.
+    Json,
+    /// Both of the above, so every record is written twice.
+    Both,
+}
+
+impl LogFormat {
+    /// The format used when none is configured: [`Json`](LogFormat::Json) when
+    /// running in a container, where a log collector reads `stdout`, and
+    /// [`Both`](LogFormat::Both) otherwise.
+    ///
+    /// A container is recognised by the `KUBERNETES_SERVICE_HOST` environment
+    /// variable or by the `/.dockerenv` or `/run/.containerenv` marker files.
+    pub fn detect() -> Self {
+        let in_container = std::env::var_os("KUBERNETES_SERVICE_HOST").is_some()
+            || Path::new("/.dockerenv").exists()
+            || Path::new("/run/.containerenv").exists();
+        if in_container {
+            LogFormat::Json
+        } else {
+            LogFormat::Both
+        }
+    }
+}
+
+/// How the asynchronous logger of a listener buffers records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LogSettings {
+    pub(crate) channel_capacity: usize,
+    pub(crate) overflow: LogOverflow,
+    /// The output format, or `None` to [detect](LogFormat::detect) it.
+    pub(crate) format: Option<LogFormat>,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        LogSettings {
+            channel_capacity: DEFAULT_LOG_CHANNEL_CAPACITY,
+            overflow: LogOverflow::default(),
+            format: None,
+        }
+    }
+}
+
+/// Hands records to an `AsyncCore` and counts the ones it has no room for.
+struct CountingAsync {
+    core: AsyncCore,
+    metrics: Arc<Metrics>,
+}
+
+impl Drain for CountingAsync {
+    type Ok = ();
+    type Err = AsyncError;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), AsyncError> {
+        match self.core.log(record, values) {
+            Err(AsyncError::Full) => {
+                self.metrics.record_log_message_dropped();
+                Ok(())
+            }
+            result => result,
+        }
+    }
+}
+
+/// Flushes the asynchronous drains of a `Logger` returned by
+/// [`configure_logging`] when dropped.
 ///
-/// This function creates two separate logging drains:
+/// Dropping the guard waits until every record logged so far has been written
+/// and stops the logging threads, so the logger must not be used afterwards.
+/// Holding it for as long as the logger is in use makes sure no record is lost
+/// when the logger goes away, whether or not clones of it are still around.
+#[must_use = "dropping the guard stops the logger"]
+pub struct LogGuard {
+    _console: Option<AsyncGuard>,
+    _json: Option<AsyncGuard>,
+}
+
+/// Wraps `drain` so records are written on a dedicated thread.
+fn asynchronous<D>(drain: D, settings: LogSettings, metrics: &Arc<Metrics>) -> (CountingAsync, AsyncGuard)
+where
+    D: Drain<Err = slog::Never, Ok = ()> + Send + 'static,
+{
+    let (core, guard) = AsyncCore::custom(drain)
+        .chan_size(settings.channel_capacity)
+        .blocking(settings.overflow == LogOverflow::Block)
+        .build_with_guard();
+    (CountingAsync { core, metrics: Arc::clone(metrics) }, guard)
+}
+
+/// Configures and returns a `Logger` instance that outputs logs to the terminal, to `stdout` in
+/// JSON format, or to both, depending on `settings.format`.
+///
+/// This function creates up to two logging drains:
 /// - A terminal drain that formats logs with `slog_term`'s `FullFormat` for human-readable output.
 /// - A JSON drain that formats logs as JSON with `slog_json` for structured logging.
 ///
-/// Both drains are wrapped in asynchronous drains using `slog_async` to improve logging performance
-/// by offloading the work to a dedicated thread. The asynchronous drains are then duplicated,
-/// allowing log messages to be sent to both drains simultaneously.
+/// Each drain is wrapped in an asynchronous drain using `slog_async` to improve logging performance
+/// by offloading the work to a dedicated thread. Each drain buffers up to
+/// `settings.channel_capacity` records; when a buffer is full the record is either dropped and
+/// counted in `metrics`, or the caller blocks, depending on `settings.overflow`. With
+/// [`LogFormat::Both`] the asynchronous drains are then duplicated, allowing log messages to be
+/// sent to both drains simultaneously. Without a configured format, it is
+/// [detected](LogFormat::detect).
 ///
 /// # Returns
 ///
 /// A `Logger` instance configured with the described drains. This logger can be used throughout
-/// the application to log messages, which will appear in both the terminal and `stdout` in the
-/// configured formats.
+/// the application to log messages, which will appear in the configured outputs.
+///
+/// Also returns the [`LogGuard`] of the asynchronous drains. Hold it for as long as the logger is
+/// in use and drop it last: dropping it flushes every buffered record, so nothing is lost on
+/// shutdown.
 ///
 /// # Example
 ///
-/// ```
-/// // Initialize the logger
-/// let log = configure_logging();
+/// ```ignore
+/// // Initialize the logger, and keep the guard until the logger is no longer needed
+/// let (log, _guard) = configure_logging(LogSettings::default(), Arc::new(Metrics::default()));
 ///
 /// // Use the logger
 /// slog::info!(log, "Application started"; "version" => "1.0.0");
 /// ```
 ///
-/// This will produce an output in the terminal in a human-readable format and also output a JSON
-/// formatted log to `stdout`.
-pub fn configure_logging() -> Logger {
-    // Configure terminal logging
-    let decorator = TermDecorator::new().build();
-    let console_drain = FullFormat::new(decorator).build().fuse();
-    // Make the console logging asynchronous
-    let console_drain = Async::new(console_drain).build().fuse();
-
-    // Configure JSON logging
-    let json_drain = Json::new(std::io::stdout())
-        .add_default_keys()
-        .build().fuse();
-    // Make the JSON logging asynchronous
-    let json_drain = Async::new(json_drain).build().fuse();
-
-    // Duplicate logs to both console and JSON output, and return the logger
-    Logger::root(slog::Duplicate::new(console_drain, json_drain).fuse(), o!())
+/// Outside a container, this will produce an output in the terminal in a human-readable format
+/// and also output a JSON formatted log to `stdout`.
+pub fn configure_logging(settings: LogSettings, metrics: Arc<Metrics>) -> (Logger, LogGuard) {
+    let format = settings.format.unwrap_or_else(LogFormat::detect);
+
+    // Configure terminal logging, made asynchronous
+    let console = || {
+        let decorator = TermDecorator::new().build();
+        let console_drain = FullFormat::new(decorator).build().fuse();
+        asynchronous(console_drain, settings, &metrics)
+    };
+
+    // Configure JSON logging, made asynchronous
+    let json = || {
+        let json_drain = Json::new(std::io::stdout())
+            .add_default_keys()
+            .build().fuse();
+        asynchronous(json_drain, settings, &metrics)
+    };
+
+    match format {
+        LogFormat::Term => {
+            let (console_drain, console_guard) = console();
+            (Logger::root(console_drain.fuse(), o!()), LogGuard { _console: Some(console_guard), _json: None })
+        }
+        LogFormat::Json => {
+            let (json_drain, json_guard) = json();
+            (Logger::root(json_drain.fuse(), o!()), LogGuard { _console: None, _json: Some(json_guard) })
+        }
+        LogFormat::Both => {
+            let (console_drain, console_guard) = console();
+            let (json_drain, json_guard) = json();
+            // Duplicate logs to both console and JSON output
+            let log = Logger::root(slog::Duplicate::new(console_drain.fuse(), json_drain.fuse()).fuse(), o!());
+            (log, LogGuard { _console: Some(console_guard), _json: Some(json_guard) })
+        }
+    }
 }
@@ -0,0 +1,69 @@
+// maintenance.rs
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Default delay between connection attempts during a maintenance window.
+pub const DEFAULT_MAINTENANCE_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Nanoseconds in a day.
+const DAY: u128 = 86_400 * 1_000_000_000;
+
+/// A period during which the server is expected to be unavailable, see
+/// [`SseClientBuilder::maintenance_window`](crate::SseClientBuilder::maintenance_window).
+///
+/// # Example
+///
+/// ```
+/// use config_sdk::MaintenanceWindow;
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// // Every night from 23:30 to 00:30 UTC.
+/// let window = MaintenanceWindow::daily(Duration::from_secs(23 * 3600 + 1800), Duration::from_secs(3600));
+///
+/// let at = |hours: u64, minutes: u64| UNIX_EPOCH + Duration::from_secs(hours * 3600 + minutes * 60);
+/// assert!(window.contains(at(23, 45)));
+/// assert!(window.contains(at(24, 15)));
+/// assert!(!window.contains(at(24, 30)));
+/// assert!(!window.contains(at(12, 0)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MaintenanceWindow {
+    kind: WindowKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum WindowKind {
+    /// Starts every day at this offset from midnight UTC and lasts this long.
+    Daily { start: Duration, length: Duration },
+    /// From the first time, inclusive, to the second, exclusive.
+    Between(SystemTime, SystemTime),
+}
+
+impl MaintenanceWindow {
+    /// A window that recurs every day, starting `start` after midnight UTC
+    /// and lasting `length`. It may run past midnight; a length of a day or
+    /// more covers all the time.
+    pub fn daily(start: Duration, length: Duration) -> Self {
+        MaintenanceWindow { kind: WindowKind::Daily { start, length } }
+    }
+
+    /// A single window from `start`, inclusive, to `end`, exclusive.
+    pub fn between(start: SystemTime, end: SystemTime) -> Self {
+        MaintenanceWindow { kind: WindowKind::Between(start, end) }
+    }
+
+    /// Returns `true` if `time` falls within the window.
+    pub fn contains(&self, time: SystemTime) -> bool {
+        match self.kind {
+            WindowKind::Daily { start, length } => {
+                let Ok(since_epoch) = time.duration_since(UNIX_EPOCH) else {
+                    return false;
+                };
+                let since_midnight = since_epoch.as_nanos() % DAY;
+                let into_window = (since_midnight + DAY - start.as_nanos() % DAY) % DAY;
+                into_window < length.as_nanos()
+            },
+            WindowKind::Between(start, end) => start <= time && time < end,
+        }
+    }
+}
@@ -0,0 +1,357 @@
+// manager.rs
+
+use crate::client::SseClientBuilder;
+use crate::errors::ConfigError;
+use crate::handle::ListenerHandle;
+use crate::metrics::{Metrics, MetricsSnapshot};
+use crate::models::ServerConfig;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Runs several named listeners side by side and manages their lifecycle.
+///
+/// Services that subscribe to more than one configuration stream, e.g. a
+/// global, a regional and a per-service one, can add each stream under a name
+/// with its own client settings and handler, and stop them individually or all
+/// at once. Like [`ListenerHandle`], dropping the manager does not stop the
+/// listeners; call [`StreamManager::stop_all`] for that.
+///
+/// The manager also composes the latest configuration of every stream into
+/// an [effective configuration](Self::effective), resolving keys set by more
+/// than one stream as described in [`ConflictResolution`].
+///
+/// # Example
+///
+/// ```no_run
+/// use config_sdk::{ServerConfig, SseClientBuilder, StreamManager};
+///
+/// # async fn run() -> Result<(), config_sdk::ConfigError> {
+/// let mut streams = StreamManager::new();
+/// streams.add("global", SseClientBuilder::new("http://config.internal/sse/global"), |config: ServerConfig| {
+///     println!("global: {:?}", config);
+/// })?;
+/// streams.add("regional", SseClientBuilder::new("http://config.internal/sse/eu"), |config: ServerConfig| {
+///     println!("regional: {:?}", config);
+/// })?;
+///
+/// for name in streams.names() {
+///     streams.handle(name).unwrap().first_config().await?;
+/// }
+/// println!("{} configs received", streams.metrics().events);
+///
+/// streams.stop_all();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct StreamManager {
+    streams: BTreeMap<String, ManagedStream>,
+    layers: Arc<Mutex<Layers>>,
+}
+
+#[derive(Debug)]
+struct ManagedStream {
+    handle: ListenerHandle,
+    metrics: Arc<Metrics>,
+}
+
+/// How [`StreamManager::effective`] picks the value of a key that several
+/// streams set.
+///
+/// Keys are the top-level keys of the configurations; a key holding an object
+/// is resolved as a whole, not merged. For every key, the candidates are the
+/// streams whose latest configuration sets it, and the winner is the candidate
+/// ranking highest by, in this order:
+///
+/// 1. with [`LastWriteWins`](Self::LastWriteWins), its timestamp for the key,
+///    a candidate without one ranking below any candidate with one;
+/// 2. the priority the stream was [added](StreamManager::add_with_priority)
+///    with, the higher the better;
+/// 3. the stream's name, the one sorting first winning.
+///
+/// The last rule makes the outcome independent of the order in which
+/// configurations arrive, so every instance of a service resolves the same
+/// configurations the same way.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConflictResolution {
+    /// The stream with the highest priority wins.
+    #[default]
+    Priority,
+    /// The stream that wrote the key last wins, going by the timestamps
+    /// embedded in its configurations, with priorities breaking ties.
+    ///
+    /// Every configuration may carry a setting named by the string held here,
+    /// an object that maps keys of the configuration to the time they were
+    /// last written, as numbers on the same scale across streams, such as
+    /// milliseconds since the Unix epoch. That setting is not part of the
+    /// effective configuration. A timestamp that is not a number is ignored.
+    LastWriteWins(String),
+}
+
+/// The configuration composed from every stream of a [`StreamManager`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EffectiveConfig {
+    /// The winning value of every key set by any stream.
+    pub config: ServerConfig,
+    /// The name of the stream that won each key.
+    pub sources: BTreeMap<String, String>,
+}
+
+/// How a stream's value for a key ranks: by its timestamp for the key, then
+/// by the stream's priority.
+type Rank = (Option<f64>, i64);
+
+/// The latest configuration of every stream, and how they are composed.
+#[derive(Debug, Default)]
+struct Layers {
+    resolution: ConflictResolution,
+    streams: BTreeMap<String, Layer>,
+}
+
+#[derive(Debug)]
+struct Layer {
+    priority: i64,
+    /// The latest configuration of the stream, once it has dispatched one.
+    config: Option<ServerConfig>,
+}
+
+impl StreamManager {
+    /// Creates a manager without any streams.
+    pub fn new() -> Self {
+        StreamManager::default()
+    }
+
+    /// Sets how keys set by several streams are resolved in the
+    /// [effective configuration](Self::effective). Defaults to
+    /// [`ConflictResolution::Priority`].
+    pub fn set_conflict_resolution(&mut self, resolution: ConflictResolution) {
+        self.layers().resolution = resolution;
+    }
+
+    /// Builds a client from `builder` and starts listening on a background task,
+    /// passing every configuration of the stream to `update_handler`.
+    ///
+    /// The stream has priority 0 in the [effective configuration](Self::effective).
+    /// Must be called from within a tokio runtime.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::GenericError` if a stream named `name` is already
+    /// managed, and any error returned by [`SseClientBuilder::build`]. No
+    /// listener is started in either case.
+    pub fn add<F>(&mut self, name: impl Into<String>, builder: SseClientBuilder, update_handler: F) -> Result<(), ConfigError>
+    where
+        F: FnMut(ServerConfig) + Send + 'static,
+    {
+        self.add_with_priority(name, builder, 0, update_handler)
+    }
+
+    /// Starts a stream like [`add`](Self::add), with `priority` deciding which
+    /// stream wins a key set by several in the
+    /// [effective configuration](Self::effective).
+    ///
+    /// # Errors
+    ///
+    /// Same as [`add`](Self::add).
+    pub fn add_with_priority<F>(
+        &mut self,
+        name: impl Into<String>,
+        builder: SseClientBuilder,
+        priority: i64,
+        mut update_handler: F,
+    ) -> Result<(), ConfigError>
+    where
+        F: FnMut(ServerConfig) + Send + 'static,
+    {
+        let name = name.into();
+        if self.streams.contains_key(&name) {
+            return Err(ConfigError::GenericError(format!("A stream named {} already exists", name)));
+        }
+        let client = builder.build()?;
+        self.layers().streams.insert(name.clone(), Layer { priority, config: None });
+        let layers = Arc::clone(&self.layers);
+        let layer = name.clone();
+        let stream = ManagedStream {
+            handle: client.spawn(move |config: ServerConfig| {
+                // Updated before the handler runs, so the effective configuration
+                // includes this one once the stream is ready.
+                if let Some(layer) = layers.lock().unwrap_or_else(|e| e.into_inner()).streams.get_mut(&layer) {
+                    layer.config = Some(config.clone());
+                }
+                update_handler(config)
+            }),
+            metrics: client.metrics(),
+        };
+        self.streams.insert(name, stream);
+        Ok(())
+    }
+
+    /// Stops the stream named `name` and stops managing it.
+    ///
+    /// Returns `false` if no such stream is managed.
+    pub fn remove(&mut self, name: &str) -> bool {
+        match self.streams.remove(name) {
+            Some(stream) => {
+                stream.handle.abort();
+                self.layers().streams.remove(name);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stops every stream and stops managing them.
+    pub fn stop_all(&mut self) {
+        for (_, stream) in std::mem::take(&mut self.streams) {
+            stream.handle.abort();
+        }
+        self.layers().streams.clear();
+    }
+
+    /// Returns the latest configurations of all managed streams composed into
+    /// one, together with the stream each key was taken from.
+    ///
+    /// Keys set by more than one stream are resolved as described in
+    /// [`ConflictResolution`]. Streams that have not dispatched a
+    /// configuration yet, or that were removed, do not contribute.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use config_sdk::{ConflictResolution, ServerConfig, SseClientBuilder, StreamManager};
+    ///
+    /// # async fn run() -> Result<(), config_sdk::ConfigError> {
+    /// let mut streams = StreamManager::new();
+    /// streams.set_conflict_resolution(ConflictResolution::LastWriteWins("_written".to_string()));
+    /// streams.add("global", SseClientBuilder::new("http://example.com/global"), |_: ServerConfig| {})?;
+    /// streams.add_with_priority("regional", SseClientBuilder::new("http://example.com/eu"), 10, |_: ServerConfig| {})?;
+    /// for name in ["global", "regional"] {
+    ///     streams.handle(name).unwrap().first_config().await?;
+    /// }
+    ///
+    /// let effective = streams.effective();
+    /// for (key, stream) in &effective.sources {
+    ///     println!("{} = {} (from {})", key, effective.config.settings[key], stream);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn effective(&self) -> EffectiveConfig {
+        let layers = self.layers();
+        let timestamps_key = match &layers.resolution {
+            ConflictResolution::LastWriteWins(key) => Some(key.as_str()),
+            ConflictResolution::Priority => None,
+        };
+        // The winner of every key so far, with its rank.
+        let mut winners: BTreeMap<&str, (&str, &Value, Rank)> = BTreeMap::new();
+        // Streams are visited in name order, and only a higher rank replaces
+        // a winner, so ties go to the name sorting first.
+        for (name, layer) in &layers.streams {
+            let Some(config) = &layer.config else {
+                continue;
+            };
+            let timestamps = timestamps_key.and_then(|key| config.settings.get(key)).and_then(Value::as_object);
+            for (key, value) in &config.settings {
+                if Some(key.as_str()) == timestamps_key {
+                    continue;
+                }
+                let timestamp = timestamps.and_then(|timestamps| timestamps.get(key)).and_then(Value::as_f64);
+                let rank = (timestamp, layer.priority);
+                if winners.get(key.as_str()).is_none_or(|(_, _, best)| rank > *best) {
+                    winners.insert(key, (name, value, rank));
+                }
+            }
+        }
+
+        let mut effective = EffectiveConfig::default();
+        for (key, (name, value, _)) in winners {
+            effective.config.settings.insert(key.to_string(), value.clone());
+            effective.sources.insert(key.to_string(), name.to_string());
+        }
+        effective
+    }
+
+    /// Returns the names of the managed streams in sorted order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.streams.keys().map(String::as_str)
+    }
+
+    /// Returns the number of managed streams.
+    pub fn len(&self) -> usize {
+        self.streams.len()
+    }
+
+    /// Returns `true` if no streams are managed.
+    pub fn is_empty(&self) -> bool {
+        self.streams.is_empty()
+    }
+
+    /// Returns the handle of the stream named `name`, which reports whether it
+    /// is ready or has stopped.
+    pub fn handle(&self, name: &str) -> Option<&ListenerHandle> {
+        self.streams.get(name).map(|stream| &stream.handle)
+    }
+
+    /// Returns the counters of the stream named `name`.
+    pub fn stream_metrics(&self, name: &str) -> Option<MetricsSnapshot> {
+        self.streams.get(name).map(|stream| stream.metrics.snapshot())
+    }
+
+    /// Returns the counters of all managed streams added together.
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.streams
+            .values()
+            .map(|stream| stream.metrics.snapshot())
+            .fold(MetricsSnapshot::default(), MetricsSnapshot::merge)
+    }
+
+    /// Returns `true` once every managed stream has dispatched its first
+    /// configuration.
+    pub fn all_ready(&self) -> bool {
+        self.streams.values().all(|stream| stream.handle.is_ready())
+    }
+
+    fn layers(&self) -> MutexGuard<'_, Layers> {
+        self.layers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{Reply, TestServer};
+
+    #[tokio::test]
+    async fn effective_resolves_conflicts_by_timestamp_then_priority() {
+        let global = TestServer::start([Reply::events(
+            "data: {\"level\": 1, \"region\": \"any\", \"limit\": 10, \"_written\": {\"limit\": 200}}\n\n",
+        )])
+        .await;
+        let regional = TestServer::start([Reply::events(
+            "data: {\"level\": 2, \"region\": \"eu\", \"limit\": 20, \"_written\": {\"limit\": 100, \"region\": 100}}\n\n",
+        )])
+        .await;
+
+        let mut streams = StreamManager::new();
+        streams.set_conflict_resolution(ConflictResolution::LastWriteWins("_written".to_string()));
+        streams.add("global", SseClientBuilder::new(&global.url), |_: ServerConfig| {}).unwrap();
+        streams.add_with_priority("regional", SseClientBuilder::new(&regional.url), 10, |_: ServerConfig| {}).unwrap();
+        for name in ["global", "regional"] {
+            streams.handle(name).unwrap().first_config().await.unwrap();
+        }
+
+        let effective = streams.effective();
+        // Written later by the global stream.
+        assert_eq!(effective.config.settings["limit"], 10);
+        assert_eq!(effective.sources["limit"], "global");
+        // Only the regional stream has a timestamp.
+        assert_eq!(effective.sources["region"], "regional");
+        // Neither has a timestamp, so the higher priority wins.
+        assert_eq!(effective.config.settings["level"], 2);
+        assert!(!effective.config.contains_key("_written"));
+
+        streams.stop_all();
+    }
+}
@@ -0,0 +1,403 @@
+// metrics.rs
+
+use reqwest::Version;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds of the buckets of the handler latency histogram. Invocations
+/// slower than the last bound are counted in an extra overflow bucket.
+pub const LATENCY_BUCKETS: [Duration; 12] = [
+    Duration::from_micros(100),
+    Duration::from_micros(250),
+    Duration::from_micros(500),
+    Duration::from_millis(1),
+    Duration::from_micros(2500),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(25),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_secs(1),
+];
+
+/// Number of buckets in a [`LatencySnapshot`], including the overflow bucket.
+const BUCKET_COUNT: usize = LATENCY_BUCKETS.len() + 1;
+
+/// Counters describing the activity of an [`SseClient`](crate::SseClient).
+///
+/// The counters are shared by every listener started from the same client and
+/// are updated as events arrive, so they can be read at any time from another
+/// task, e.g. by a `/metrics` endpoint. All counters are monotonic, except
+/// for the number of paused listeners.
+///
+/// # Example
+///
+/// ```
+/// use config_sdk::SseClientBuilder;
+///
+/// let client = SseClientBuilder::new("http://example.com/sse").build().unwrap();
+/// let metrics = client.metrics();
+///
+/// // Later, from anywhere in the application:
+/// let snapshot = metrics.snapshot();
+/// println!("{} configs received so far", snapshot.events);
+/// ```
+#[derive(Debug, Default)]
+pub struct Metrics {
+    events: AtomicU64,
+    parse_errors: AtomicU64,
+    skipped: AtomicU64,
+    handler_panics: AtomicU64,
+    connection_attempts: AtomicU64,
+    connections: AtomicU64,
+    tls_connections: AtomicU64,
+    http2_connections: AtomicU64,
+    http3_connections: AtomicU64,
+    reconnects: AtomicU64,
+    log_messages_dropped: AtomicU64,
+    broadcast_lagged: AtomicU64,
+    paused_listeners: AtomicU64,
+    handler_latency: LatencyHistogram,
+}
+
+/// A bucketed histogram of handler invocation times.
+#[derive(Debug)]
+struct LatencyHistogram {
+    buckets: [AtomicU64; BUCKET_COUNT],
+    count: AtomicU64,
+    total_nanos: AtomicU64,
+    min_nanos: AtomicU64,
+    max_nanos: AtomicU64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        LatencyHistogram {
+            buckets: Default::default(),
+            count: AtomicU64::new(0),
+            total_nanos: AtomicU64::new(0),
+            min_nanos: AtomicU64::new(u64::MAX),
+            max_nanos: AtomicU64::new(0),
+        }
+    }
+}
+
+/// A point-in-time copy of the values held by [`Metrics`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    /// Configurations successfully parsed and passed to the handler.
+    pub events: u64,
+    /// `data:` payloads that could not be parsed into a `ServerConfig`.
+    pub parse_errors: u64,
+    /// Configurations parsed but not dispatched, e.g. because their version was
+    /// not newer than the last one applied.
+    pub skipped: u64,
+    /// Handler invocations that panicked and were contained by the listener.
+    pub handler_panics: u64,
+    /// Connection attempts made, successful or not.
+    pub connection_attempts: u64,
+    /// Connection attempts that produced an event stream.
+    pub connections: u64,
+    /// Connections that went over TLS, i.e. to an `https` URL. The rest went
+    /// over plaintext HTTP or a Unix domain socket.
+    pub tls_connections: u64,
+    /// Connections that negotiated HTTP/2.
+    pub http2_connections: u64,
+    /// Connections that negotiated HTTP/3. Connections that negotiated
+    /// neither HTTP/2 nor HTTP/3 used HTTP/1.
+    pub http3_connections: u64,
+    /// Connection attempts made after the first attempt of a listener.
+    pub reconnects: u64,
+    /// Log records discarded because the logging buffer was full. A record
+    /// dropped by both the terminal and the JSON output counts twice.
+    pub log_messages_dropped: u64,
+    /// Configurations that subscribers of a
+    /// [`ConfigBroadcast`](crate::ConfigBroadcast) missed because they fell
+    /// behind. A configuration missed by two subscribers counts twice.
+    pub broadcast_lagged: u64,
+    /// Listeners currently paused through
+    /// [`ListenerHandle::pause`](crate::ListenerHandle::pause).
+    pub paused_listeners: u64,
+    /// How long handler invocations took.
+    pub handler_latency: LatencySnapshot,
+}
+
+impl MetricsSnapshot {
+    /// Adds the values of `other` to these values.
+    pub(crate) fn merge(self, other: MetricsSnapshot) -> MetricsSnapshot {
+        MetricsSnapshot {
+            events: self.events + other.events,
+            parse_errors: self.parse_errors + other.parse_errors,
+            skipped: self.skipped + other.skipped,
+            handler_panics: self.handler_panics + other.handler_panics,
+            connection_attempts: self.connection_attempts + other.connection_attempts,
+            connections: self.connections + other.connections,
+            tls_connections: self.tls_connections + other.tls_connections,
+            http2_connections: self.http2_connections + other.http2_connections,
+            http3_connections: self.http3_connections + other.http3_connections,
+            reconnects: self.reconnects + other.reconnects,
+            log_messages_dropped: self.log_messages_dropped + other.log_messages_dropped,
+            broadcast_lagged: self.broadcast_lagged + other.broadcast_lagged,
+            paused_listeners: self.paused_listeners + other.paused_listeners,
+            handler_latency: self.handler_latency.merge(other.handler_latency),
+        }
+    }
+}
+
+/// A point-in-time copy of the handler latency histogram.
+///
+/// Invocation times are measured with the wall clock around every call of the
+/// handler, including calls that panic. Percentiles are estimated from the
+/// [bucket bounds](LATENCY_BUCKETS), so they are only as precise as the bucket
+/// an invocation falls in.
+///
+/// # Example
+///
+/// ```
+/// use config_sdk::SseClientBuilder;
+///
+/// let client = SseClientBuilder::new("http://example.com/sse").build().unwrap();
+/// let latency = client.metrics().snapshot().handler_latency;
+/// if let Some(p99) = latency.p99() {
+///     println!("99% of handler calls finished within {:?}", p99);
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LatencySnapshot {
+    /// Number of handler invocations measured.
+    pub count: u64,
+    /// Sum of all invocation times.
+    pub total: Duration,
+    /// Fastest invocation, or zero if there was none.
+    pub min: Duration,
+    /// Slowest invocation, or zero if there was none.
+    pub max: Duration,
+    /// Number of invocations per bucket. `buckets[i]` counts invocations that
+    /// took at most `LATENCY_BUCKETS[i]` and longer than the previous bound; the
+    /// last entry counts invocations slower than every bound.
+    pub buckets: [u64; BUCKET_COUNT],
+}
+
+impl LatencySnapshot {
+    /// Returns the average invocation time, or `None` if there was none.
+    pub fn mean(&self) -> Option<Duration> {
+        let count = u32::try_from(self.count).ok().filter(|&count| count > 0)?;
+        Some(self.total / count)
+    }
+
+    /// Returns an upper bound of the invocation time within which `quantile`
+    /// (between 0 and 1) of the invocations finished, or `None` if there was no
+    /// invocation.
+    ///
+    /// The estimate is the upper bound of the bucket the quantile falls in,
+    /// capped at the slowest invocation seen.
+    pub fn quantile(&self, quantile: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((quantile.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (i, &count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let bound = LATENCY_BUCKETS.get(i).copied().unwrap_or(self.max);
+                return Some(bound.min(self.max));
+            }
+        }
+        Some(self.max)
+    }
+
+    /// Returns the estimated 99th percentile; see [`quantile`](Self::quantile).
+    pub fn p99(&self) -> Option<Duration> {
+        self.quantile(0.99)
+    }
+
+    fn merge(self, other: LatencySnapshot) -> LatencySnapshot {
+        let mut buckets = self.buckets;
+        for (bucket, count) in buckets.iter_mut().zip(other.buckets) {
+            *bucket += count;
+        }
+        let min = match (self.count, other.count) {
+            (0, _) => other.min,
+            (_, 0) => self.min,
+            _ => self.min.min(other.min),
+        };
+        LatencySnapshot {
+            count: self.count + other.count,
+            total: self.total + other.total,
+            min,
+            max: self.max.max(other.max),
+            buckets,
+        }
+    }
+}
+
+impl Metrics {
+    /// Returns the current value of every counter.
+    ///
+    /// Counters are read individually, so a snapshot taken while a listener is
+    /// running may mix values from slightly different moments.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            events: self.events.load(Ordering::Relaxed),
+            parse_errors: self.parse_errors.load(Ordering::Relaxed),
+            skipped: self.skipped.load(Ordering::Relaxed),
+            handler_panics: self.handler_panics.load(Ordering::Relaxed),
+            connection_attempts: self.connection_attempts.load(Ordering::Relaxed),
+            connections: self.connections.load(Ordering::Relaxed),
+            tls_connections: self.tls_connections.load(Ordering::Relaxed),
+            http2_connections: self.http2_connections.load(Ordering::Relaxed),
+            http3_connections: self.http3_connections.load(Ordering::Relaxed),
+            reconnects: self.reconnects.load(Ordering::Relaxed),
+            log_messages_dropped: self.log_messages_dropped.load(Ordering::Relaxed),
+            broadcast_lagged: self.broadcast_lagged.load(Ordering::Relaxed),
+            paused_listeners: self.paused_listeners.load(Ordering::Relaxed),
+            handler_latency: self.handler_latency.snapshot(),
+        }
+    }
+
+    pub(crate) fn record_event(&self) {
+        self.events.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_parse_error(&self) {
+        self.parse_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_skipped(&self) {
+        self.skipped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_handler_panic(&self) {
+        self.handler_panics.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_paused(&self) {
+        self.paused_listeners.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_resumed(&self) {
+        self.paused_listeners.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_connection_attempt(&self, attempt: u32) {
+        self.connection_attempts.fetch_add(1, Ordering::Relaxed);
+        if attempt > 1 {
+            self.reconnects.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn record_connection(&self, tls: bool, version: Version) {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+        if tls {
+            self.tls_connections.fetch_add(1, Ordering::Relaxed);
+        }
+        if version == Version::HTTP_2 {
+            self.http2_connections.fetch_add(1, Ordering::Relaxed);
+        } else if version == Version::HTTP_3 {
+            self.http3_connections.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn record_log_message_dropped(&self) {
+        self.log_messages_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_broadcast_lagged(&self, skipped: u64) {
+        self.broadcast_lagged.fetch_add(skipped, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_handler_latency(&self, elapsed: Duration) {
+        self.handler_latency.record(elapsed);
+    }
+}
+
+impl LatencyHistogram {
+    fn record(&self, elapsed: Duration) {
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        let bucket = LATENCY_BUCKETS.iter().position(|&bound| elapsed <= bound).unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.total_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.min_nanos.fetch_min(nanos, Ordering::Relaxed);
+        self.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> LatencySnapshot {
+        let count = self.count.load(Ordering::Relaxed);
+        let min_nanos = self.min_nanos.load(Ordering::Relaxed);
+        LatencySnapshot {
+            count,
+            total: Duration::from_nanos(self.total_nanos.load(Ordering::Relaxed)),
+            min: if count == 0 { Duration::ZERO } else { Duration::from_nanos(min_nanos) },
+            max: Duration::from_nanos(self.max_nanos.load(Ordering::Relaxed)),
+            buckets: std::array::from_fn(|i| self.buckets[i].load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Renders `metrics` in the Prometheus text exposition format.
+///
+/// Every counter is emitted with its `# HELP` and `# TYPE` lines, so the output
+/// can be served as-is from a `/metrics` endpoint or appended to the output of an
+/// existing exporter. Requires the `metrics` feature.
+///
+/// # Example
+///
+/// ```
+/// use config_sdk::{render_prometheus, SseClientBuilder};
+///
+/// let client = SseClientBuilder::new("http://example.com/sse").build().unwrap();
+/// let text = render_prometheus(&client.metrics());
+/// assert!(text.contains("# TYPE config_sse_events_total counter"));
+/// assert!(text.contains("config_sse_events_total 0"));
+/// assert!(text.contains("config_sse_handler_duration_seconds_count 0"));
+/// ```
+#[cfg(feature = "metrics")]
+pub fn render_prometheus(metrics: &Metrics) -> String {
+    use std::fmt::Write;
+
+    let snapshot = metrics.snapshot();
+    let counters = [
+        ("config_sse_events_total", "Configuration updates received and dispatched to the handler.", snapshot.events),
+        ("config_sse_parse_errors_total", "Event payloads that could not be parsed.", snapshot.parse_errors),
+        ("config_sse_skipped_total", "Configurations parsed but not dispatched to the handler.", snapshot.skipped),
+        ("config_sse_handler_panics_total", "Handler invocations that panicked.", snapshot.handler_panics),
+        ("config_sse_connection_attempts_total", "Connection attempts to the SSE server.", snapshot.connection_attempts),
+        ("config_sse_connections_total", "Successful connections to the SSE server.", snapshot.connections),
+        ("config_sse_tls_connections_total", "Successful connections to the SSE server over TLS.", snapshot.tls_connections),
+        ("config_sse_http2_connections_total", "Successful connections to the SSE server over HTTP/2.", snapshot.http2_connections),
+        ("config_sse_http3_connections_total", "Successful connections to the SSE server over HTTP/3.", snapshot.http3_connections),
+        ("config_sse_reconnects_total", "Connection attempts made after the first one.", snapshot.reconnects),
+        ("config_sse_log_messages_dropped_total", "Log records dropped because the logging buffer was full.", snapshot.log_messages_dropped),
+        ("config_sse_broadcast_lagged_total", "Configurations missed by broadcast subscribers that fell behind.", snapshot.broadcast_lagged),
+    ];
+
+    let mut out = String::new();
+    for (name, help, value) in counters {
+        // Writing to a String cannot fail.
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} counter", name);
+        let _ = writeln!(out, "{} {}", name, value);
+    }
+
+    let name = "config_sse_paused_listeners";
+    let _ = writeln!(out, "# HELP {} Listeners currently paused.", name);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+    let _ = writeln!(out, "{} {}", name, snapshot.paused_listeners);
+
+    let latency = snapshot.handler_latency;
+    let name = "config_sse_handler_duration_seconds";
+    let _ = writeln!(out, "# HELP {} Time spent in the configuration handler.", name);
+    let _ = writeln!(out, "# TYPE {} histogram", name);
+    let mut cumulative = 0;
+    for (bound, count) in LATENCY_BUCKETS.iter().zip(latency.buckets) {
+        cumulative += count;
+        let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound.as_secs_f64(), cumulative);
+    }
+    let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, latency.count);
+    let _ = writeln!(out, "{}_sum {}", name, latency.total.as_secs_f64());
+    let _ = writeln!(out, "{}_count {}", name, latency.count);
+    out
+}
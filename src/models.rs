@@ -1,6 +1,10 @@
-use serde::{Deserialize, Serialize};
+use crate::errors::{ConfigError, ConfigValueError};
+use serde::de::{DeserializeOwned, DeserializeSeed, Error as _, IgnoredAny, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
 use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
 
 /// Represents the configuration of a server, dynamically structured as a map.
 ///
@@ -19,7 +23,7 @@ use std::collections::BTreeMap;
 /// # Example
 ///
 /// ```
-/// use my_crate::ServerConfig;
+/// use config_sdk::ServerConfig;
 /// use serde_json::Value;
 /// use std::collections::BTreeMap;
 ///
@@ -38,10 +42,889 @@ use std::collections::BTreeMap;
 /// setting. This shows how the struct can be manually constructed, but in practice, it
 /// is more likely to be deserialized directly from JSON data received from a server or
 /// configuration file.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+///
+/// # Wire format
+///
+/// A configuration is deserialized from a JSON object in either of two shapes:
+///
+/// - enveloped, with the settings in a `settings` member:
+///   `{"settings": {"timeout": 30}}`. Other members of the envelope are ignored.
+/// - flat, with the settings as the members of the object itself:
+///   `{"timeout": 30}`.
+///
+/// An object with a `settings` member is always read as an envelope, so a flat
+/// configuration cannot have a setting named `settings`; a listener can be told
+/// which shape its server sends with
+/// [`SseClientBuilder::wire_format`](crate::SseClientBuilder::wire_format).
+/// Anything but an object, such as a string or an array, is rejected with an
+/// error saying so. A configuration is always serialized in the enveloped shape.
+///
+/// ```
+/// use config_sdk::ServerConfig;
+///
+/// let enveloped: ServerConfig = serde_json::from_str(r#"{"settings": {"timeout": 30}}"#).unwrap();
+/// let flat: ServerConfig = serde_json::from_str(r#"{"timeout": 30}"#).unwrap();
+/// assert_eq!(enveloped, flat);
+///
+/// let error = serde_json::from_str::<ServerConfig>(r#""just a string""#).unwrap_err();
+/// assert!(error.to_string().contains("expected a JSON object of settings"));
+/// ```
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
 pub struct ServerConfig {
     /// A map holding the server settings where each key is a setting name (a `String`)
     /// and each value is a `serde_json::Value`, allowing for flexible configuration data
     /// types including numbers, strings, arrays, and objects.
     pub settings: BTreeMap<String, Value>,
 }
+
+impl<'de> Deserialize<'de> for ServerConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(ServerConfigVisitor)
+    }
+}
+
+/// Reads either shape of the [wire format](ServerConfig#wire-format).
+struct ServerConfigVisitor;
+
+impl<'de> Visitor<'de> for ServerConfigVisitor {
+    type Value = ServerConfig;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a JSON object of settings, or one with a `settings` object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<ServerConfig, A::Error> {
+        let mut settings = None;
+        // The settings if the object turns out to be flat.
+        let mut members = BTreeMap::new();
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "settings" {
+                if settings.is_some() {
+                    return Err(A::Error::duplicate_field("settings"));
+                }
+                settings = Some(map.next_value()?);
+                members.clear();
+            } else if settings.is_some() {
+                map.next_value::<IgnoredAny>()?;
+            } else {
+                members.insert(key, map.next_value()?);
+            }
+        }
+        Ok(ServerConfig { settings: settings.unwrap_or(members) })
+    }
+}
+
+/// The shape of the configurations a server sends, see
+/// [`SseClientBuilder::wire_format`](crate::SseClientBuilder::wire_format) and
+/// the [wire format](ServerConfig#wire-format) of `ServerConfig`.
+///
+/// A `WireFormat` is also a [`DeserializeSeed`], for reading configurations of
+/// that shape from elsewhere.
+///
+/// # Example
+///
+/// ```
+/// use config_sdk::WireFormat;
+/// use serde::de::DeserializeSeed;
+/// use serde_json::json;
+///
+/// let flat = json!({"settings": {"level": 3}, "debug": true});
+/// let config = WireFormat::Flat.deserialize(flat).unwrap();
+/// assert_eq!(config.settings["settings"]["level"], 3);
+/// assert_eq!(config.settings["debug"], true);
+///
+/// let enveloped = json!({"settings": {"level": 3}, "debug": true});
+/// let config = WireFormat::Enveloped.deserialize(enveloped).unwrap();
+/// assert_eq!(config.settings["level"], 3);
+/// assert!(!config.settings.contains_key("debug"));
+///
+/// assert!(WireFormat::Enveloped.deserialize(json!({"level": 3})).is_err());
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum WireFormat {
+    /// Either shape: an object with a `settings` member is an envelope, any
+    /// other object holds the settings itself.
+    #[default]
+    Auto,
+    /// `{"settings": {...}}`. A payload without a `settings` member is an error.
+    Enveloped,
+    /// `{...}`. Every member of the object is a setting, including one named
+    /// `settings`.
+    Flat,
+}
+
+/// The settings of an enveloped configuration, without the flat fallback.
+#[derive(Deserialize)]
+struct Envelope {
+    settings: BTreeMap<String, Value>,
+}
+
+impl<'de> DeserializeSeed<'de> for WireFormat {
+    type Value = ServerConfig;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<ServerConfig, D::Error> {
+        match self {
+            WireFormat::Auto => ServerConfig::deserialize(deserializer),
+            WireFormat::Enveloped => Envelope::deserialize(deserializer).map(|envelope| ServerConfig { settings: envelope.settings }),
+            WireFormat::Flat => BTreeMap::deserialize(deserializer).map(|settings| ServerConfig { settings }),
+        }
+    }
+}
+
+impl WireFormat {
+    /// Parses a configuration of this shape from the JSON in `payload`.
+    pub(crate) fn parse(self, payload: &[u8]) -> Result<ServerConfig, serde_json::Error> {
+        let mut deserializer = serde_json::Deserializer::from_slice(payload);
+        let config = self.deserialize(&mut deserializer)?;
+        deserializer.end()?;
+        Ok(config)
+    }
+}
+
+impl ServerConfig {
+    /// Returns a builder for a configuration, setting one setting at a time.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::ServerConfig;
+    /// use serde_json::json;
+    ///
+    /// let config = ServerConfig::builder()
+    ///     .set("timeout", 30)
+    ///     .set("hostname", "example.com")
+    ///     .set("tags", json!(["a", "b"]))
+    ///     .build();
+    ///
+    /// assert_eq!(config.settings["timeout"], 30);
+    /// assert_eq!(config.settings["tags"], json!(["a", "b"]));
+    /// ```
+    pub fn builder() -> ServerConfigBuilder {
+        ServerConfigBuilder::default()
+    }
+
+    /// Sets the top-level setting `key` to `value`, returning its previous
+    /// value, if any.
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<Value>) -> Option<Value> {
+        self.settings.insert(key.into(), value.into())
+    }
+
+    /// Removes the top-level setting `key`, returning its value, if any.
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        self.settings.remove(key)
+    }
+
+    /// Returns the configuration with the top-level setting `key` set to
+    /// `value`, for layering local overrides on a received configuration.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::ServerConfig;
+    ///
+    /// let received = ServerConfig::builder().set("timeout", 30).set("debug", false).build();
+    ///
+    /// let mut local = received.clone().with("debug", true).with("profile", "local");
+    /// local.remove("timeout");
+    ///
+    /// assert_eq!(local, ServerConfig::builder().set("debug", true).set("profile", "local").build());
+    /// ```
+    pub fn with(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.set(key, value);
+        self
+    }
+
+    /// Returns the top-level setting `key` deserialized into a `T`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigValueError::Missing` if there is no such setting, and
+    /// `ConfigValueError::Deserialize` if its value does not deserialize into
+    /// a `T`. A `null` value is not missing; ask for an `Option<T>` to accept it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{ConfigValueError, ServerConfig};
+    /// use std::collections::HashMap;
+    ///
+    /// let config = ServerConfig::builder()
+    ///     .set("timeout", 30)
+    ///     .set("hostname", "example.com")
+    ///     .set("limits", serde_json::json!({ "cpu": 2, "memory": 512 }))
+    ///     .build();
+    ///
+    /// assert_eq!(config.get_u64("timeout").unwrap(), 30);
+    /// assert_eq!(config.get::<HashMap<String, u32>>("limits").unwrap()["memory"], 512);
+    ///
+    /// // Each failure says what went wrong, for operators to fix the configuration.
+    /// assert!(matches!(config.get_bool("debug"), Err(ConfigValueError::Missing(key)) if key == "debug"));
+    /// assert!(matches!(
+    ///     config.get_u64("hostname"),
+    ///     Err(ConfigValueError::WrongType { expected: "unsigned integer", found: "string", .. })
+    /// ));
+    /// assert!(matches!(config.get::<Vec<String>>("limits"), Err(ConfigValueError::Deserialize(_))));
+    /// ```
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<T, ConfigValueError> {
+        T::deserialize(self.value(key)?).map_err(ConfigValueError::Deserialize)
+    }
+
+    /// Returns the top-level setting `key` if it is a string.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigValueError::Missing` if there is no such setting, and
+    /// `ConfigValueError::WrongType` if it is not a string.
+    pub fn get_str(&self, key: &str) -> Result<&str, ConfigValueError> {
+        self.typed(key, "string", Value::as_str)
+    }
+
+    /// Returns the top-level setting `key` if it is a boolean.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigValueError::Missing` if there is no such setting, and
+    /// `ConfigValueError::WrongType` if it is not a boolean.
+    pub fn get_bool(&self, key: &str) -> Result<bool, ConfigValueError> {
+        self.typed(key, "boolean", Value::as_bool)
+    }
+
+    /// Returns the top-level setting `key` if it is an integer that fits an
+    /// `i64`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigValueError::Missing` if there is no such setting, and
+    /// `ConfigValueError::WrongType` if it is not such an integer.
+    pub fn get_i64(&self, key: &str) -> Result<i64, ConfigValueError> {
+        self.typed(key, "integer", Value::as_i64)
+    }
+
+    /// Returns the top-level setting `key` if it is a non-negative integer
+    /// that fits a `u64`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigValueError::Missing` if there is no such setting, and
+    /// `ConfigValueError::WrongType` if it is not such an integer.
+    pub fn get_u64(&self, key: &str) -> Result<u64, ConfigValueError> {
+        self.typed(key, "unsigned integer", Value::as_u64)
+    }
+
+    /// Returns the top-level setting `key` if it is a number, integers
+    /// included.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigValueError::Missing` if there is no such setting, and
+    /// `ConfigValueError::WrongType` if it is not a number.
+    pub fn get_f64(&self, key: &str) -> Result<f64, ConfigValueError> {
+        self.typed(key, "number", Value::as_f64)
+    }
+
+    /// Returns the value of the top-level setting `key`.
+    fn value(&self, key: &str) -> Result<&Value, ConfigValueError> {
+        self.settings.get(key).ok_or_else(|| ConfigValueError::Missing(key.to_string()))
+    }
+
+    /// Returns the value of the top-level setting `key` converted by
+    /// `convert`, which fails if the value is not an `expected`.
+    fn typed<'a, T>(
+        &'a self,
+        key: &str,
+        expected: &'static str,
+        convert: impl FnOnce(&'a Value) -> Option<T>,
+    ) -> Result<T, ConfigValueError> {
+        let value = self.value(key)?;
+        convert(value).ok_or_else(|| ConfigValueError::WrongType { key: key.to_string(), expected, found: kind(value) })
+    }
+
+    /// Returns `true` if the configuration has no settings.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::ServerConfig;
+    /// use serde_json::json;
+    ///
+    /// let config: ServerConfig = serde_json::from_value(json!({
+    ///     "settings": { "timeout": 30, "hostname": "example.com" }
+    /// })).unwrap();
+    ///
+    /// assert!(!config.is_empty());
+    /// assert_eq!(config.len(), 2);
+    /// assert!(config.contains_key("timeout"));
+    /// assert_eq!(config.keys().collect::<Vec<_>>(), ["hostname", "timeout"]);
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.settings.is_empty()
+    }
+
+    /// Returns the number of top-level settings.
+    pub fn len(&self) -> usize {
+        self.settings.len()
+    }
+
+    /// Returns the names of the top-level settings, in sorted order.
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.settings.keys()
+    }
+
+    /// Returns `true` if the configuration has a top-level setting named `key`.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.settings.contains_key(key)
+    }
+
+    /// Returns the value at `path`, whose segments are separated by dots and
+    /// name either a key of an object or the index of an array element.
+    ///
+    /// This reads the keys produced by [`flatten`](Self::flatten): for every
+    /// key of the flattened configuration, `get_path` on the original returns
+    /// the same value, as long as no key of the original contains a dot.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::ServerConfig;
+    /// use serde_json::json;
+    ///
+    /// let config = ServerConfig::builder().set("db", json!({ "hosts": ["a", "b"] })).build();
+    ///
+    /// assert_eq!(config.get_path("db.hosts.1"), Some(&json!("b")));
+    /// assert_eq!(config.get_path("db.port"), None);
+    /// ```
+    pub fn get_path(&self, path: &str) -> Option<&Value> {
+        let mut segments = path.split('.');
+        let first = self.settings.get(segments.next()?)?;
+        segments.try_fold(first, |value, segment| match value {
+            Value::Object(map) => map.get(segment),
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+            _ => None,
+        })
+    }
+
+    /// Returns the configuration with nested objects and arrays flattened into
+    /// dotted keys, for consumers that expect a flat key-value map.
+    ///
+    /// `{"db": {"hosts": ["a", "b"]}}` becomes `{"db.hosts.0": "a",
+    /// "db.hosts.1": "b"}`. Empty objects and arrays are kept as values, so
+    /// nothing is lost. Use [`flatten_with`](Self::flatten_with) for another
+    /// separator or another treatment of arrays.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::ServerConfig;
+    /// use serde_json::json;
+    ///
+    /// let config = ServerConfig::builder().set("db", json!({ "host": "x", "ports": [5432] })).build();
+    /// let flat = config.flatten();
+    ///
+    /// assert_eq!(flat, ServerConfig::builder().set("db.host", "x").set("db.ports.0", 5432).build());
+    /// for key in flat.keys() {
+    ///     assert_eq!(config.get_path(key), flat.settings.get(key));
+    /// }
+    /// ```
+    pub fn flatten(&self) -> ServerConfig {
+        self.flatten_with(&FlattenOptions::default())
+    }
+
+    /// Returns the configuration flattened like by [`flatten`](Self::flatten),
+    /// according to `options`.
+    ///
+    /// Keys that already contain the separator are not escaped, so two paths
+    /// can end up with the same flattened key; the one that comes last in key
+    /// order wins.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{ArrayFlattening, FlattenOptions, ServerConfig};
+    /// use serde_json::json;
+    ///
+    /// let config = ServerConfig::builder().set("db", json!({ "host": "x", "ports": [5432] })).build();
+    ///
+    /// let options = FlattenOptions::new().separator("__").arrays(ArrayFlattening::Keep);
+    /// assert_eq!(config.flatten_with(&options).settings["db__ports"], json!([5432]));
+    ///
+    /// let options = FlattenOptions::new().arrays(ArrayFlattening::Bracketed);
+    /// assert_eq!(config.flatten_with(&options).settings["db.ports[0]"], 5432);
+    /// ```
+    pub fn flatten_with(&self, options: &FlattenOptions) -> ServerConfig {
+        let mut flat = ServerConfig::default();
+        for (key, value) in &self.settings {
+            flatten_into(&mut flat.settings, key.clone(), value, options);
+        }
+        flat
+    }
+
+    /// Merges the settings of `other` into `self` according to `policy`.
+    ///
+    /// Keys present in only one of the two configurations always end up in the
+    /// result. `policy` decides what happens to keys present in both:
+    ///
+    /// - [`MergePolicy::PreferOther`]: the value from `other` replaces the value in
+    ///   `self`.
+    /// - [`MergePolicy::PreferSelf`]: the value in `self` is kept.
+    /// - [`MergePolicy::DeepMerge`]: when both values are JSON objects they are
+    ///   merged recursively with the same rules; when both are arrays they are
+    ///   combined according to the [`ArrayMerge`] strategy; in every other case the
+    ///   value from `other` wins.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{ArrayMerge, MergePolicy, ServerConfig};
+    /// use serde_json::json;
+    ///
+    /// let mut base: ServerConfig = serde_json::from_value(json!({
+    ///     "settings": { "db": { "host": "localhost", "port": 5432 }, "tags": ["a"] }
+    /// })).unwrap();
+    /// let overrides: ServerConfig = serde_json::from_value(json!({
+    ///     "settings": { "db": { "host": "db.prod" }, "tags": ["b"] }
+    /// })).unwrap();
+    ///
+    /// base.merge(&overrides, MergePolicy::DeepMerge(ArrayMerge::Concat));
+    /// assert_eq!(base.settings["db"], json!({ "host": "db.prod", "port": 5432 }));
+    /// assert_eq!(base.settings["tags"], json!(["a", "b"]));
+    /// ```
+    ///
+    /// A `null` in `other` is merged like any other value; use
+    /// [`merge_with`](Self::merge_with) to treat it as a deletion instead.
+    pub fn merge(&mut self, other: &ServerConfig, policy: MergePolicy) {
+        self.merge_with(other, policy, NullValues::Literal);
+    }
+
+    /// Merges the settings of `other` into `self` like [`merge`](Self::merge),
+    /// deciding through `nulls` what a `null` value in `other` means.
+    ///
+    /// With [`NullValues::Delete`], a key whose value in `other` is `null` is
+    /// removed from `self` wherever `policy` would have let the value from
+    /// `other` win: always under [`MergePolicy::PreferOther`], at every level of
+    /// the objects being merged under [`MergePolicy::DeepMerge`], and never under
+    /// [`MergePolicy::PreferSelf`], which keeps every existing value. A `null`
+    /// for a key that `self` does not have is dropped. Values copied from
+    /// `other` as a whole, e.g. an object under a new key, are not inspected.
+    ///
+    /// With [`NullValues::Literal`] this is the same as `merge`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{ArrayMerge, MergePolicy, NullValues, ServerConfig};
+    /// use serde_json::json;
+    ///
+    /// let base: ServerConfig = serde_json::from_value(json!({
+    ///     "settings": { "db": { "host": "localhost", "port": 5432 }, "debug": true }
+    /// })).unwrap();
+    /// let update: ServerConfig = serde_json::from_value(json!({
+    ///     "settings": { "db": { "port": null }, "debug": null, "unknown": null }
+    /// })).unwrap();
+    /// let policy = MergePolicy::DeepMerge(ArrayMerge::Replace);
+    ///
+    /// // Nulls delete the keys they are stored under...
+    /// let mut deleted = base.clone();
+    /// deleted.merge_with(&update, policy, NullValues::Delete);
+    /// assert_eq!(serde_json::to_value(&deleted.settings).unwrap(), json!({ "db": { "host": "localhost" } }));
+    ///
+    /// // ...or are kept as values.
+    /// let mut literal = base.clone();
+    /// literal.merge_with(&update, policy, NullValues::Literal);
+    /// assert_eq!(serde_json::to_value(&literal.settings).unwrap(), json!({
+    ///     "db": { "host": "localhost", "port": null }, "debug": null, "unknown": null
+    /// }));
+    /// ```
+    pub fn merge_with(&mut self, other: &ServerConfig, policy: MergePolicy, nulls: NullValues) {
+        for (key, value) in &other.settings {
+            if nulls == NullValues::Delete && value.is_null() {
+                if policy != MergePolicy::PreferSelf {
+                    self.settings.remove(key);
+                }
+                continue;
+            }
+            match self.settings.get_mut(key) {
+                None => {
+                    self.settings.insert(key.clone(), value.clone());
+                }
+                Some(current) => match policy {
+                    MergePolicy::PreferOther => *current = value.clone(),
+                    MergePolicy::PreferSelf => {}
+                    MergePolicy::DeepMerge(arrays) => deep_merge(current, value, arrays, nulls),
+                },
+            }
+        }
+    }
+
+    /// Lists the top-level keys that differ between `self` and `newer`.
+    ///
+    /// Values are compared as a whole, so a change anywhere inside a nested
+    /// object reports the top-level key that holds it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::ServerConfig;
+    /// use serde_json::json;
+    ///
+    /// let old: ServerConfig = serde_json::from_value(json!({
+    ///     "settings": { "timeout": 30, "hostname": "a.example.com", "debug": true }
+    /// })).unwrap();
+    /// let new: ServerConfig = serde_json::from_value(json!({
+    ///     "settings": { "timeout": 60, "hostname": "a.example.com", "region": "eu" }
+    /// })).unwrap();
+    ///
+    /// let diff = old.diff(&new);
+    /// assert_eq!(diff.added, ["region"]);
+    /// assert_eq!(diff.removed, ["debug"]);
+    /// assert_eq!(diff.changed, ["timeout"]);
+    /// ```
+    pub fn diff(&self, newer: &ServerConfig) -> ConfigDiff {
+        let mut diff = ConfigDiff::default();
+        for (key, value) in &newer.settings {
+            match self.settings.get(key) {
+                None => diff.added.push(key.clone()),
+                Some(current) if current != value => diff.changed.push(key.clone()),
+                Some(_) => {}
+            }
+        }
+        diff.removed = self.keys()
+            .filter(|key| !newer.contains_key(key))
+            .cloned()
+            .collect();
+        diff
+    }
+
+    /// Returns `true` if `self` and `other` hold the same settings, not counting
+    /// the top-level keys listed in `ignored`.
+    ///
+    /// Useful for volatile keys such as a timestamp or checksum that the server
+    /// changes on every push even when nothing else did.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::ServerConfig;
+    /// use serde_json::json;
+    ///
+    /// let a: ServerConfig = serde_json::from_value(json!({
+    ///     "settings": { "timeout": 30, "timestamp": "2024-03-01T10:00:00Z" }
+    /// })).unwrap();
+    /// let b: ServerConfig = serde_json::from_value(json!({
+    ///     "settings": { "timeout": 30, "timestamp": "2024-03-01T10:05:00Z" }
+    /// })).unwrap();
+    ///
+    /// assert!(a.equivalent_ignoring(&b, &["timestamp"]));
+    /// assert!(!a.equivalent_ignoring(&b, &[]));
+    /// ```
+    pub fn equivalent_ignoring(&self, other: &ServerConfig, ignored: &[&str]) -> bool {
+        let relevant = |(key, _): &(&String, &Value)| !ignored.contains(&key.as_str());
+        self.settings.iter().filter(relevant).eq(other.settings.iter().filter(relevant))
+    }
+}
+
+/// The top-level keys that differ between two configurations, as computed by
+/// [`ServerConfig::diff`]. Each list is sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigDiff {
+    /// Keys present only in the newer configuration.
+    pub added: Vec<String>,
+    /// Keys present only in the older configuration.
+    pub removed: Vec<String>,
+    /// Keys present in both configurations with different values.
+    pub changed: Vec<String>,
+}
+
+impl ConfigDiff {
+    /// Returns `true` if both configurations hold the same settings.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Builds a [`ServerConfig`] one setting at a time, see
+/// [`ServerConfig::builder`].
+#[derive(Debug, Clone, Default)]
+pub struct ServerConfigBuilder {
+    settings: BTreeMap<String, Value>,
+}
+
+impl ServerConfigBuilder {
+    /// Sets the top-level setting `key` to `value`, replacing any earlier
+    /// value.
+    pub fn set(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.settings.insert(key.into(), value.into());
+        self
+    }
+
+    /// Sets the top-level setting `key` to `value` serialized as JSON, e.g. a
+    /// struct deriving `Serialize`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::JsonParse` if `value` cannot be represented as
+    /// JSON, e.g. a map with non-string keys.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::ServerConfig;
+    /// use serde::Serialize;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Database {
+    ///     host: String,
+    ///     port: u16,
+    /// }
+    ///
+    /// # fn main() -> Result<(), config_sdk::ConfigError> {
+    /// let config = ServerConfig::builder()
+    ///     .try_set("db", Database { host: "localhost".to_string(), port: 5432 })?
+    ///     .build();
+    ///
+    /// assert_eq!(config.settings["db"]["port"], 5432);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn try_set(self, key: impl Into<String>, value: impl Serialize) -> Result<Self, ConfigError> {
+        let value = serde_json::to_value(value)?;
+        Ok(self.set(key, value))
+    }
+
+    /// Returns the configuration built so far.
+    pub fn build(self) -> ServerConfig {
+        ServerConfig { settings: self.settings }
+    }
+}
+
+/// Describes the kind of `value`, as reported by
+/// [`ConfigValueError::WrongType`].
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_u64() => "unsigned integer",
+        Value::Number(n) if n.is_i64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Stores `value` in `flat` under `key`, or its elements under keys extended
+/// from `key` if it is a non-empty object or an array that is flattened.
+fn flatten_into(flat: &mut BTreeMap<String, Value>, key: String, value: &Value, options: &FlattenOptions) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (name, value) in map {
+                flatten_into(flat, format!("{}{}{}", key, options.separator, name), value, options);
+            }
+        },
+        Value::Array(items) if !items.is_empty() && options.arrays != ArrayFlattening::Keep => {
+            for (i, item) in items.iter().enumerate() {
+                let key = match options.arrays {
+                    ArrayFlattening::Bracketed => format!("{}[{}]", key, i),
+                    _ => format!("{}{}{}", key, options.separator, i),
+                };
+                flatten_into(flat, key, item, options);
+            }
+        },
+        value => {
+            flat.insert(key, value.clone());
+        },
+    }
+}
+
+/// How [`ServerConfig::flatten_with`] turns nested settings into keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlattenOptions {
+    separator: String,
+    arrays: ArrayFlattening,
+}
+
+impl FlattenOptions {
+    /// Creates options that join keys with a dot and index array elements
+    /// like keys, as [`ServerConfig::flatten`] does.
+    pub fn new() -> Self {
+        FlattenOptions::default()
+    }
+
+    /// Sets the separator put between the key of an object and the keys
+    /// within it. Defaults to `"."`.
+    pub fn separator(mut self, separator: impl Into<String>) -> Self {
+        self.separator = separator.into();
+        self
+    }
+
+    /// Sets how arrays are flattened. Defaults to [`ArrayFlattening::Indexed`].
+    pub fn arrays(mut self, arrays: ArrayFlattening) -> Self {
+        self.arrays = arrays;
+        self
+    }
+}
+
+impl Default for FlattenOptions {
+    fn default() -> Self {
+        FlattenOptions { separator: ".".to_string(), arrays: ArrayFlattening::Indexed }
+    }
+}
+
+/// How [`FlattenOptions`] treat arrays.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ArrayFlattening {
+    /// Every element gets its own key, with its index as the last segment, as
+    /// in `hosts.0`.
+    #[default]
+    Indexed,
+    /// Every element gets its own key, with its index in brackets, as in
+    /// `hosts[0]`.
+    Bracketed,
+    /// Arrays are kept as values, elements and all.
+    Keep,
+}
+
+/// Decides how [`ServerConfig::merge`] resolves keys present in both
+/// configurations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Values from the configuration being merged in replace existing values.
+    PreferOther,
+    /// Existing values are kept.
+    PreferSelf,
+    /// Nested objects are merged key by key, arrays according to the given
+    /// strategy, and any other value from the configuration being merged in wins.
+    DeepMerge(ArrayMerge),
+}
+
+/// How [`MergePolicy::DeepMerge`] combines two arrays stored under the same key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArrayMerge {
+    /// The incoming array replaces the existing one.
+    #[default]
+    Replace,
+    /// The incoming elements are appended to the existing array.
+    Concat,
+}
+
+/// What a `null` value means when merging configurations.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NullValues {
+    /// A `null` is a value like any other and overwrites the existing value.
+    #[default]
+    Literal,
+    /// A `null` removes the key it is stored under.
+    Delete,
+}
+
+/// Recursively merges `other` into `current`, with `other` winning conflicts.
+fn deep_merge(current: &mut Value, other: &Value, arrays: ArrayMerge, nulls: NullValues) {
+    match (current, other) {
+        (Value::Object(current), Value::Object(other)) => {
+            for (key, value) in other {
+                if nulls == NullValues::Delete && value.is_null() {
+                    current.remove(key);
+                    continue;
+                }
+                match current.get_mut(key) {
+                    Some(existing) => deep_merge(existing, value, arrays, nulls),
+                    None => {
+                        current.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (Value::Array(current), Value::Array(other)) if arrays == ArrayMerge::Concat => {
+            current.extend(other.iter().cloned());
+        }
+        (current, other) => *current = other.clone(),
+    }
+}
+
+/// A configuration update together with the payload it was parsed from.
+///
+/// `ServerConfig` is a deserialized view of the event data, so key ordering,
+/// whitespace and number formatting from the server are lost. `ConfigEvent`
+/// keeps the verbatim `data:` payload next to the parsed form, which makes it
+/// suitable for audit trails and for replaying the exact stream later.
+///
+/// # Example
+///
+/// ```
+/// use config_sdk::ConfigEvent;
+///
+/// fn archive(event: ConfigEvent) {
+///     // Store the bytes exactly as the server sent them...
+///     let verbatim = String::from_utf8_lossy(&event.raw);
+///     // ...while acting on the structured form.
+///     println!("{} settings, payload: {}", event.config.len(), verbatim);
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigEvent {
+    /// The configuration parsed from the event data.
+    pub config: ServerConfig,
+    /// The `data:` payload exactly as received, without the field name, the
+    /// optional space after it, or the line terminator.
+    pub raw: Vec<u8>,
+    /// The SSE fields that accompanied the payload.
+    pub meta: EventMeta,
+}
+
+/// The SSE fields that accompany an event's data, and where the configuration
+/// came from.
+///
+/// Handlers that need idempotent processing can key on [`EventMeta::id`], which
+/// the server uses to identify the event and which is also what the client would
+/// report as the last event it has seen.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventMeta {
+    /// The last event ID set by the server through an `id:` field.
+    ///
+    /// Following the SSE specification, the ID persists across events until the
+    /// server sends a new one, so an event without its own `id:` field reports
+    /// the ID of the event before it. Until the server has sent an ID, it is
+    /// the one made up by the client's
+    /// [`EventIdStrategy`](crate::EventIdStrategy), if any.
+    pub id: Option<String>,
+    /// The event type from the `event:` field of this event, if any.
+    pub event: Option<String>,
+    /// The reconnection time from the `retry:` field of this event, if any.
+    pub retry: Option<Duration>,
+    /// Where the configuration came from.
+    ///
+    /// A configuration loaded from the cache on startup has no SSE fields of its
+    /// own; its `id` is `None`.
+    pub origin: ConfigOrigin,
+}
+
+/// Where a configuration passed to a handler came from.
+///
+/// Handlers can use it to treat the cold-start configuration differently from
+/// live updates, e.g. by not raising alerts for a configuration that was
+/// already applied before the restart.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ConfigOrigin {
+    /// Loaded from the cache file set with
+    /// [`SseClientBuilder::cache_file`](crate::SseClientBuilder::cache_file)
+    /// before the first connection.
+    Cache,
+    /// Fetched with a single request, see
+    /// [`SseClient::fetch_snapshot`](crate::SseClient::fetch_snapshot). The
+    /// listener does not produce these itself; the variant lets applications that
+    /// feed snapshots and stream events into the same code path tell them apart.
+    Snapshot,
+    /// Received over the event stream.
+    #[default]
+    Stream,
+    /// A configuration dispatched before, passed to the handler again because
+    /// the [reassert interval](crate::SseClientBuilder::reassert_interval)
+    /// passed.
+    Reassert,
+    /// A configuration received before, passed to the handler again because
+    /// the listener was rolled back to it, see
+    /// [`ListenerHandle::rollback_to`](crate::ListenerHandle::rollback_to).
+    Rollback,
+}
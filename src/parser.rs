@@ -0,0 +1,458 @@
+// parser.rs
+
+use std::time::Duration;
+
+/// Splits an SSE byte stream into lines without copying complete lines.
+///
+/// Network chunks rarely line up with SSE line boundaries, so a partial line at
+/// the end of a chunk has to be kept until the rest of it arrives. Complete lines
+/// are handed out as slices borrowed either from the incoming chunk or, when a
+/// line spans chunks, from an internal scratch buffer. The scratch buffer keeps
+/// its capacity between chunks, so a steady stream of events stops allocating
+/// once the buffer has grown to the size of the longest split line.
+///
+/// A parser set up with [`streaming_data`](Self::streaming_data) does not keep
+/// `data` lines that span chunks: their value is handed out in pieces as it
+/// arrives instead, see [`Piece`].
+#[derive(Debug, Default)]
+pub(crate) struct LineParser {
+    /// Bytes of the current, still unterminated line. While a `data` line is
+    /// streamed, this holds at most a trailing `\r`.
+    partial: Vec<u8>,
+    /// Longest line accepted, in bytes, excluding the terminator.
+    max_line_length: Option<usize>,
+    /// Whether `data` lines spanning chunks are streamed, and whether their
+    /// field name is matched ignoring ASCII case.
+    stream_data: Option<bool>,
+    /// The `data` line being streamed, if any.
+    streamed: Option<StreamedLine>,
+}
+
+/// Progress through a `data` line whose value is being streamed.
+#[derive(Debug, Clone, Copy)]
+struct StreamedLine {
+    /// Bytes of the line seen so far.
+    length: usize,
+    /// Whether the space that may follow the colon is still to come.
+    at_value_start: bool,
+}
+
+/// Part of an SSE stream, as handed out by [`LineParser::feed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Piece<'a> {
+    /// A complete line.
+    Line(&'a [u8]),
+    /// A `data` line spanning chunks has started. Its value follows in
+    /// [`DataPart`](Piece::DataPart) pieces.
+    DataStart,
+    /// The next bytes of the value of the `data` line being streamed.
+    DataPart(&'a [u8]),
+    /// The `data` line being streamed has ended.
+    DataEnd,
+}
+
+/// Returned by [`LineParser::feed`] when a line grows past the configured
+/// maximum length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LineTooLong {
+    /// Number of bytes of the line seen so far. For a line that is still
+    /// unterminated this is a lower bound of its final length.
+    pub(crate) length: usize,
+}
+
+impl LineParser {
+    /// Creates a parser with an empty scratch buffer that rejects lines longer
+    /// than `max_line_length` bytes, if set.
+    pub(crate) fn new(max_line_length: Option<usize>) -> Self {
+        LineParser {
+            partial: Vec::new(),
+            max_line_length,
+            stream_data: None,
+            streamed: None,
+        }
+    }
+
+    /// Makes [`feed`](Self::feed) stream the value of `data`
+    /// lines that span chunks instead of collecting them in the scratch buffer.
+    /// With `ignore_case`, the field name is matched ignoring ASCII case.
+    pub(crate) fn streaming_data(mut self, ignore_case: bool) -> Self {
+        self.stream_data = Some(ignore_case);
+        self
+    }
+
+    /// Feeds `chunk` into the parser and calls `on_piece` for every line it
+    /// completes.
+    ///
+    /// Lines are passed without their terminator; `\n`, `\r\n` and a lone `\r`
+    /// are all recognised as line endings, as required by the SSE specification.
+    /// Only lines that span chunks are streamed; a `data` line that is complete
+    /// within a chunk is passed as a [`Piece::Line`]. Without
+    /// [`streaming_data`](Self::streaming_data), every piece is a line.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LineTooLong`] as soon as a line, complete or not, exceeds the
+    /// maximum line length. Lines completed before the offending one have already
+    /// been passed to `on_piece`; the parser should be [`reset`](Self::reset)
+    /// before it is used again.
+    pub(crate) fn feed(&mut self, chunk: &[u8], mut on_piece: impl FnMut(Piece<'_>)) -> Result<(), LineTooLong> {
+        let mut rest = chunk;
+
+        if self.partial.last() == Some(&b'\r') && !rest.is_empty() {
+            // The previous chunk ended in `\r`, which terminates the line on its
+            // own or together with a `\n` at the start of this chunk.
+            self.partial.pop();
+            self.end_line(&mut on_piece);
+            if rest[0] == b'\n' {
+                rest = &rest[1..];
+            }
+        } else if !self.partial.is_empty() || self.streamed.is_some() {
+            // Finish the line left over from the previous chunk first.
+            match find_line_end(rest) {
+                Some((end, next)) => {
+                    self.extend_line(&rest[..end], &mut on_piece)?;
+                    self.end_line(&mut on_piece);
+                    rest = &rest[next..];
+                }
+                None => return self.extend_line(rest, &mut on_piece),
+            }
+        }
+
+        while let Some((end, next)) = find_line_end(rest) {
+            self.check_length(end)?;
+            on_piece(Piece::Line(&rest[..end]));
+            rest = &rest[next..];
+        }
+        self.extend_line(rest, &mut on_piece)
+    }
+
+    /// Appends `bytes` to the current line. A trailing `\r` is a terminator,
+    /// not part of the line, and is kept until the next chunk shows whether a
+    /// `\n` follows it.
+    fn extend_line(&mut self, bytes: &[u8], on_piece: &mut impl FnMut(Piece<'_>)) -> Result<(), LineTooLong> {
+        let content = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+        let Some(mut streamed) = self.streamed else {
+            self.check_length(self.partial.len() + content.len())?;
+            self.partial.extend_from_slice(bytes);
+            self.start_streaming(on_piece);
+            return Ok(());
+        };
+
+        self.check_length(streamed.length + content.len())?;
+        streamed.length += content.len();
+        emit_value(&mut streamed, content, on_piece);
+        self.streamed = Some(streamed);
+        self.partial.extend_from_slice(&bytes[content.len()..]);
+        Ok(())
+    }
+
+    /// Starts streaming the current line if it is a `data` line and data lines
+    /// are streamed, handing out the part of its value received so far.
+    fn start_streaming(&mut self, on_piece: &mut impl FnMut(Piece<'_>)) {
+        let Some(ignore_case) = self.stream_data else {
+            return;
+        };
+        let content = self.partial.strip_suffix(b"\r").unwrap_or(&self.partial);
+        let Some(colon) = content.iter().position(|&b| b == b':') else {
+            return;
+        };
+        let name = &content[..colon];
+        if !(name == b"data" || (ignore_case && name.eq_ignore_ascii_case(b"data"))) {
+            return;
+        }
+
+        let mut streamed = StreamedLine { length: content.len(), at_value_start: true };
+        on_piece(Piece::DataStart);
+        emit_value(&mut streamed, &content[colon + 1..], on_piece);
+        self.streamed = Some(streamed);
+        let terminator = self.partial.len() - content.len();
+        self.partial.drain(..self.partial.len() - terminator);
+    }
+
+    /// Hands out the line that just ended.
+    fn end_line(&mut self, on_piece: &mut impl FnMut(Piece<'_>)) {
+        if self.streamed.take().is_some() {
+            on_piece(Piece::DataEnd);
+        } else {
+            on_piece(Piece::Line(&self.partial));
+        }
+        self.partial.clear();
+    }
+
+    fn check_length(&self, length: usize) -> Result<(), LineTooLong> {
+        match self.max_line_length {
+            Some(limit) if length > limit => Err(LineTooLong { length }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns `true` if part of a line has been fed without its terminator.
+    pub(crate) fn has_partial_line(&self) -> bool {
+        !self.partial.is_empty() || self.streamed.is_some()
+    }
+
+    /// Discards any partially received line, e.g. after a reconnect.
+    pub(crate) fn reset(&mut self) {
+        self.partial.clear();
+        self.streamed = None;
+    }
+}
+
+/// Hands out `value` as the next part of a streamed `data` line, dropping the
+/// single space that may start the value.
+fn emit_value(streamed: &mut StreamedLine, value: &[u8], on_piece: &mut impl FnMut(Piece<'_>)) {
+    let mut value = value;
+    if streamed.at_value_start && !value.is_empty() {
+        streamed.at_value_start = false;
+        value = value.strip_prefix(b" ").unwrap_or(value);
+    }
+    if !value.is_empty() {
+        on_piece(Piece::DataPart(value));
+    }
+}
+
+/// Returns the end of the first line in `bytes` and the offset where the next
+/// line starts.
+fn find_line_end(bytes: &[u8]) -> Option<(usize, usize)> {
+    let end = bytes.iter().position(|&b| b == b'\n' || b == b'\r')?;
+    if bytes[end] == b'\r' {
+        match bytes.get(end + 1) {
+            Some(b'\n') => return Some((end, end + 2)),
+            // A trailing `\r` may be the first half of a `\r\n` split across
+            // chunks; wait for the next chunk before deciding.
+            None => return None,
+            Some(_) => {}
+        }
+    }
+    Some((end, end + 1))
+}
+
+/// A single line of an SSE stream, classified according to the field syntax.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Line<'a> {
+    /// An empty line, which terminates the current event.
+    Blank,
+    /// A line starting with `:`, carrying the text after the colon.
+    Comment(&'a [u8]),
+    /// A `name: value` field. A line without a colon is a field with an empty
+    /// value.
+    Field { name: &'a [u8], value: &'a [u8] },
+}
+
+/// Classifies `line`, which must not include its terminator.
+///
+/// A single space after the colon is part of the field syntax and is removed
+/// from the value.
+pub(crate) fn classify(line: &[u8]) -> Line<'_> {
+    if line.is_empty() {
+        return Line::Blank;
+    }
+    match line.iter().position(|&b| b == b':') {
+        Some(0) => Line::Comment(&line[1..]),
+        Some(colon) => {
+            let value = &line[colon + 1..];
+            Line::Field {
+                name: &line[..colon],
+                value: value.strip_prefix(b" ").unwrap_or(value),
+            }
+        }
+        None => Line::Field { name: line, value: &[] },
+    }
+}
+
+/// Field names defined by the SSE specification.
+const FIELD_NAMES: [&[u8]; 4] = [b"data", b"event", b"id", b"retry"];
+
+impl<'a> Line<'a> {
+    /// Rewrites a field whose name matches one of the SSE field names ignoring
+    /// ASCII case, e.g. `Data`, to the name's specified spelling. Every other
+    /// line is returned unchanged.
+    pub(crate) fn fold_field_case(self) -> Line<'a> {
+        match self {
+            Line::Field { name, value } => {
+                let name = FIELD_NAMES
+                    .into_iter()
+                    .find(|known| known.eq_ignore_ascii_case(name))
+                    .unwrap_or(name);
+                Line::Field { name, value }
+            }
+            line => line,
+        }
+    }
+}
+
+/// Prefix of the comment a server uses to announce its keep-alive interval.
+const HEARTBEAT_INTERVAL_PREFIX: &[u8] = b"heartbeat-interval=";
+
+/// Longest keep-alive interval taken from a server's hint; longer ones are
+/// capped to it.
+const MAX_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Reads a keep-alive interval hint, e.g. `heartbeat-interval=15`, from the
+/// text of a comment line. The interval is given in whole seconds and capped
+/// at a day; any other comment yields `None`.
+pub(crate) fn heartbeat_interval(comment: &[u8]) -> Option<Duration> {
+    let seconds = comment.trim_ascii().strip_prefix(HEARTBEAT_INTERVAL_PREFIX)?;
+    let seconds: u64 = std::str::from_utf8(seconds).ok()?.parse().ok()?;
+    (seconds > 0).then(|| Duration::from_secs(seconds).min(MAX_HEARTBEAT_INTERVAL))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A [`Piece`] that owns its bytes, to be collected across chunks.
+    #[derive(Debug, PartialEq, Eq)]
+    enum Owned {
+        Line(Vec<u8>),
+        DataStart,
+        DataPart(Vec<u8>),
+        DataEnd,
+    }
+
+    fn line(bytes: &str) -> Owned {
+        Owned::Line(bytes.as_bytes().to_vec())
+    }
+
+    fn part(bytes: &str) -> Owned {
+        Owned::DataPart(bytes.as_bytes().to_vec())
+    }
+
+    /// Feeds every chunk of `chunks` into `parser`, collecting the pieces.
+    fn feed(parser: &mut LineParser, chunks: &[&str]) -> Result<Vec<Owned>, LineTooLong> {
+        let mut pieces = Vec::new();
+        for chunk in chunks {
+            parser.feed(chunk.as_bytes(), |piece| {
+                pieces.push(match piece {
+                    Piece::Line(line) => Owned::Line(line.to_vec()),
+                    Piece::DataStart => Owned::DataStart,
+                    Piece::DataPart(part) => Owned::DataPart(part.to_vec()),
+                    Piece::DataEnd => Owned::DataEnd,
+                })
+            })?;
+        }
+        Ok(pieces)
+    }
+
+    #[test]
+    fn recognises_every_line_ending() {
+        let mut parser = LineParser::default();
+        let pieces = feed(&mut parser, &["a\nb\r\nc\rd\n\n"]).unwrap();
+        assert_eq!(pieces, [line("a"), line("b"), line("c"), line("d"), line("")]);
+        assert!(!parser.has_partial_line());
+    }
+
+    #[test]
+    fn joins_lines_split_across_chunks() {
+        let mut parser = LineParser::default();
+        let pieces = feed(&mut parser, &["data: {\"le", "vel\"", ": 3}\n", "\n"]).unwrap();
+        assert_eq!(pieces, [line("data: {\"level\": 3}"), line("")]);
+    }
+
+    #[test]
+    fn crlf_split_across_chunks_ends_one_line() {
+        let mut parser = LineParser::default();
+        let pieces = feed(&mut parser, &["data: a\r", "\ndata: b\r", "\n\r", "\n"]).unwrap();
+        // The `\n` completing each `\r` does not start a blank line.
+        assert_eq!(pieces, [line("data: a"), line("data: b"), line("")]);
+    }
+
+    #[test]
+    fn trailing_cr_waits_for_the_next_chunk() {
+        let mut parser = LineParser::default();
+        assert_eq!(feed(&mut parser, &["id: 1\r"]).unwrap(), []);
+        assert!(parser.has_partial_line());
+        // Anything but `\n` after the `\r` starts the next line.
+        assert_eq!(feed(&mut parser, &["data: x\n"]).unwrap(), [line("id: 1"), line("data: x")]);
+    }
+
+    #[test]
+    fn rejects_lines_past_the_limit() {
+        let mut parser = LineParser::new(Some(4));
+        assert_eq!(feed(&mut parser, &["abcd\r\n"]).unwrap(), [line("abcd")]);
+        assert_eq!(feed(&mut parser, &["abcde\n"]), Err(LineTooLong { length: 5 }));
+
+        // An unterminated line is rejected as soon as it is too long.
+        parser.reset();
+        assert_eq!(feed(&mut parser, &["ab", "cd", "e"]), Err(LineTooLong { length: 5 }));
+
+        // The terminator does not count, even when it arrives on its own.
+        parser.reset();
+        assert_eq!(feed(&mut parser, &["ab", "cd\r", "\n"]).unwrap(), [line("abcd")]);
+    }
+
+    #[test]
+    fn reset_discards_the_partial_line() {
+        let mut parser = LineParser::default();
+        feed(&mut parser, &["data: cut"]).unwrap();
+        parser.reset();
+        assert!(!parser.has_partial_line());
+        assert_eq!(feed(&mut parser, &["off\n"]).unwrap(), [line("off")]);
+    }
+
+    #[test]
+    fn streams_data_lines_that_span_chunks() {
+        let mut parser = LineParser::default().streaming_data(false);
+        let pieces = feed(&mut parser, &["data: {\"le", "vel\"", ": 3}\n\n"]).unwrap();
+        assert_eq!(
+            pieces,
+            [Owned::DataStart, part("{\"le"), part("vel\""), part(": 3}"), Owned::DataEnd, line("")]
+        );
+    }
+
+    #[test]
+    fn streamed_data_drops_the_space_after_the_colon_once() {
+        let mut parser = LineParser::default().streaming_data(false);
+        let pieces = feed(&mut parser, &["data:", " ", " x\n"]).unwrap();
+        assert_eq!(pieces, [Owned::DataStart, part(" x"), Owned::DataEnd]);
+    }
+
+    #[test]
+    fn streamed_data_keeps_a_split_crlf_out_of_the_value() {
+        let mut parser = LineParser::default().streaming_data(false);
+        let pieces = feed(&mut parser, &["data: ab", "c\r", "\n\r\n"]).unwrap();
+        assert_eq!(pieces, [Owned::DataStart, part("ab"), part("c"), Owned::DataEnd, line("")]);
+    }
+
+    #[test]
+    fn streams_only_data_lines_spanning_chunks() {
+        let mut parser = LineParser::default().streaming_data(false);
+        let pieces = feed(&mut parser, &["data: whole\nevent: up", "date\nDATA: a", "b\n"]).unwrap();
+        assert_eq!(pieces, [line("data: whole"), line("event: update"), line("DATA: ab")]);
+
+        let mut parser = LineParser::default().streaming_data(true);
+        let pieces = feed(&mut parser, &["DATA: a", "b\n"]).unwrap();
+        assert_eq!(pieces, [Owned::DataStart, part("a"), part("b"), Owned::DataEnd]);
+    }
+
+    #[test]
+    fn streamed_data_counts_towards_the_limit() {
+        let mut parser = LineParser::new(Some(8)).streaming_data(false);
+        assert_eq!(feed(&mut parser, &["data: ab", "c"]), Err(LineTooLong { length: 9 }));
+    }
+
+    #[test]
+    fn classifies_lines() {
+        assert_eq!(classify(b""), Line::Blank);
+        assert_eq!(classify(b": keep-alive"), Line::Comment(b" keep-alive"));
+        assert_eq!(classify(b"data:  x"), Line::Field { name: b"data", value: b" x" });
+        assert_eq!(classify(b"data:x:y"), Line::Field { name: b"data", value: b"x:y" });
+        assert_eq!(classify(b"data"), Line::Field { name: b"data", value: b"" });
+    }
+
+    #[test]
+    fn folds_the_case_of_known_field_names() {
+        assert_eq!(classify(b"Event: up").fold_field_case(), Line::Field { name: b"event", value: b"up" });
+        assert_eq!(classify(b"Other: x").fold_field_case(), Line::Field { name: b"Other", value: b"x" });
+        assert_eq!(classify(b":Data").fold_field_case(), Line::Comment(b"Data"));
+    }
+
+    #[test]
+    fn reads_heartbeat_interval_hints() {
+        assert_eq!(heartbeat_interval(b" heartbeat-interval=15 "), Some(Duration::from_secs(15)));
+        assert_eq!(heartbeat_interval(b"heartbeat-interval=0"), None);
+        assert_eq!(heartbeat_interval(b"heartbeat-interval=-1"), None);
+        assert_eq!(heartbeat_interval(b"heartbeat-interval=999999999"), Some(MAX_HEARTBEAT_INTERVAL));
+        assert_eq!(heartbeat_interval(b"keep-alive"), None);
+    }
+}
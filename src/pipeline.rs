@@ -0,0 +1,1067 @@
+// pipeline.rs
+
+use crate::ack::acknowledge;
+use crate::client::SseClient;
+use crate::dedup::{ChangeFilter, SeenIds, VersionFilter};
+use crate::dry_run::DryRun;
+use crate::errors::ConfigError;
+use crate::event_id::EventIds;
+use crate::format::PayloadFormat;
+use crate::framing::FrameSplitter;
+use crate::history::ChangeHistory;
+use crate::models::{ArrayMerge, ConfigOrigin, EventMeta, MergePolicy, NullValues, ServerConfig, WireFormat};
+use crate::incremental::SettingsDecoder;
+use crate::repeat::RepeatFilter;
+use crate::rollback::{version_of, Retained, RollbackRequest, Snapshots};
+use crate::parser::{classify, heartbeat_interval, Line, LineParser, LineTooLong, Piece};
+use crate::utf8::Utf8Checker;
+use serde::de::{DeserializeSeed, Error as _};
+use serde_json::Value;
+use slog::{debug, info, warn, Drain, Logger};
+use std::any::Any;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+/// Turns the bytes of an SSE stream into configurations passed to `dispatch`.
+///
+/// The pipeline holds everything a listener keeps between events: the line
+/// parser, the SSE fields of the current event, the filters and the merged
+/// configuration. It does not know where the bytes come from, so the live
+/// listener and the replay of a captured stream share it.
+pub(crate) struct Pipeline<'a, F> {
+    sse_client: &'a SseClient,
+    log: Logger,
+    parser: LineParser,
+    /// Splits the stream into payloads instead of the parser, with a framing
+    /// other than SSE.
+    frames: Option<FrameSplitter>,
+    /// Checks the stream for invalid UTF-8 in strict mode.
+    utf8: Option<Utf8Checker>,
+    events: EventState<'a, F>,
+}
+
+/// The part of the pipeline that handles complete lines.
+struct EventState<'a, F> {
+    sse_client: &'a SseClient,
+    log: Logger,
+    dispatch: F,
+    meta: EventMeta,
+    /// Makes up the IDs of events the server sent without one.
+    event_ids: EventIds,
+    version_filter: Option<VersionFilter>,
+    change_filter: Option<ChangeFilter>,
+    repeat_filter: Option<RepeatFilter>,
+    /// The encoding of the payloads of the current stream.
+    payload_format: PayloadFormat,
+    seen_ids: Option<SeenIds>,
+    /// Whether the current event has an `id:` field of its own, rather than
+    /// the ID of an earlier event.
+    has_own_id: bool,
+    /// The ID of the last event that ended with a blank line. `meta.id` holds
+    /// the ID of the current event, which only takes its place once the event
+    /// is complete, so a stream cut off mid-event resumes before it.
+    last_event_id: Option<String>,
+    /// The values of the `data` lines of the current event, joined by line
+    /// feeds, unless they are decoded as they arrive.
+    data: Vec<u8>,
+    /// Number of `data` lines of the current event.
+    data_lines: usize,
+    /// Whether the data of the current event exceeded the maximum event size
+    /// and is being skipped.
+    oversized: bool,
+    dry_run: Option<DryRun>,
+    accumulated: Option<ServerConfig>,
+    /// Whether dispatched configurations are written to the client's cache.
+    caching: bool,
+    /// Whether payloads are dispatched as they are, without parsing them.
+    raw: bool,
+    /// Keep-alive interval announced by the server on the current stream.
+    heartbeat_interval: Option<Duration>,
+    /// Decodes the `data` lines of the current event once one of them has
+    /// been streamed.
+    decoder: Option<SettingsDecoder>,
+    /// Whether the last configuration dispatched had any settings.
+    has_settings: bool,
+    /// Whether a configuration has been dispatched, or held back while paused.
+    has_config: bool,
+    /// Configurations received, whether they were dispatched or skipped.
+    received: u64,
+    /// Whether configurations are held back instead of being dispatched.
+    paused: bool,
+    /// The most recent configuration held back while paused, with its payload
+    /// and the SSE fields it arrived with.
+    held: Option<Retained>,
+    /// Whether live configurations are held back because the listener was
+    /// rolled back to an earlier one.
+    rolled_back: bool,
+    /// The most recent live configuration held back while rolled back.
+    suppressed: Option<Retained>,
+    /// The last configuration dispatched, with its payload and the SSE fields
+    /// it was dispatched with, kept to be reasserted if the client does so.
+    last_dispatched: Option<Retained>,
+    /// When `dispatch` was last called, for a configuration or a reassertion.
+    dispatched_at: Option<tokio::time::Instant>,
+    /// Where dispatched configurations are recorded, if anywhere.
+    history: Option<&'a ChangeHistory>,
+    /// Where configurations are retained to roll back to, if anywhere.
+    snapshots: Option<&'a Snapshots>,
+    /// Whether `dispatch` only hands configurations to workers, which call the
+    /// handler through [`call_handler`] themselves.
+    detached: bool,
+    /// Where configurations and errors are collected instead of being
+    /// dispatched and reported, if they are.
+    collected: Option<Vec<Result<ServerConfig, ConfigError>>>,
+}
+
+/// A change to the client's cache caused by the lines fed to the pipeline.
+pub(crate) enum CacheUpdate {
+    /// Replace the cached configuration.
+    Save(ServerConfig),
+    /// The configuration was removed; delete the cache.
+    Clear,
+}
+
+impl<'a, F> Pipeline<'a, F>
+where
+    F: FnMut(ServerConfig, &[u8], &EventMeta),
+{
+    pub(crate) fn new(sse_client: &'a SseClient, log: Logger, dispatch: F) -> Self {
+        let mut parser = LineParser::new(sse_client.max_line_length);
+        if sse_client.incremental_parsing {
+            parser = parser.streaming_data(sse_client.lenient_field_case);
+        }
+        let mut frames = FrameSplitter::new(sse_client.framing, sse_client.max_line_length);
+        if let Some(frames) = frames.as_mut() {
+            frames.json_payloads(sse_client.payload_format.unwrap_or_default() == PayloadFormat::Json);
+        }
+        Pipeline {
+            sse_client,
+            log: log.clone(),
+            parser,
+            frames,
+            utf8: sse_client.strict_utf8.then(Utf8Checker::default),
+            events: EventState {
+                sse_client,
+                log,
+                dispatch,
+                meta: EventMeta::default(),
+                event_ids: EventIds::new(sse_client.event_id_strategy),
+                version_filter: sse_client.version_key.clone().map(VersionFilter::new),
+                change_filter: sse_client.skip_unchanged.clone().map(ChangeFilter::new),
+                repeat_filter: sse_client.skip_repeated_payloads.then(RepeatFilter::default),
+                payload_format: sse_client.payload_format.unwrap_or_default(),
+                seen_ids: sse_client.skip_seen_ids.map(SeenIds::new),
+                has_own_id: false,
+                last_event_id: None,
+                data: Vec::new(),
+                data_lines: 0,
+                oversized: false,
+                dry_run: sse_client.dry_run.then(DryRun::default),
+                accumulated: None,
+                caching: sse_client.cache.is_some(),
+                raw: false,
+                heartbeat_interval: None,
+                decoder: None,
+                has_settings: false,
+                has_config: false,
+                received: 0,
+                paused: false,
+                held: None,
+                rolled_back: false,
+                suppressed: None,
+                last_dispatched: None,
+                dispatched_at: None,
+                history: None,
+                snapshots: None,
+                detached: false,
+                collected: None,
+            },
+        }
+    }
+
+    /// Stops the pipeline from writing to the client's cache.
+    pub(crate) fn without_cache(mut self) -> Self {
+        self.events.caching = false;
+        self
+    }
+
+    /// Collects configurations and errors, in the order they occur, instead of
+    /// passing them to `dispatch` and the error hook. They are handed out by
+    /// [`take_collected`](Self::take_collected).
+    pub(crate) fn collecting(mut self) -> Self {
+        self.events.collected = Some(Vec::new());
+        self
+    }
+
+    /// Returns the configurations and errors collected since the last call.
+    pub(crate) fn take_collected(&mut self) -> Vec<Result<ServerConfig, ConfigError>> {
+        self.events.collected.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Dispatches every payload without parsing it as JSON.
+    ///
+    /// The payload, decrypted if the client decrypts events, is passed to
+    /// `dispatch` together with an empty configuration. Nothing that works on
+    /// parsed configurations applies: filters, merging, the dry run and the
+    /// cache are all skipped.
+    pub(crate) fn raw(mut self) -> Self {
+        self.events.raw = true;
+        self.events.caching = false;
+        // A streamed payload is not kept, so there would be nothing to pass on.
+        self.parser = LineParser::new(self.sse_client.max_line_length);
+        self
+    }
+
+    /// Dispatches configurations without timing `dispatch` or catching its
+    /// panics, for a `dispatch` that calls the handlers through
+    /// [`call_handler`] or [`call_handlers`] itself, or hands them to workers
+    /// that do so.
+    pub(crate) fn detached(mut self) -> Self {
+        self.events.detached = true;
+        self
+    }
+
+    /// Records the changes made by dispatched configurations in `history`.
+    pub(crate) fn history(mut self, history: &'a ChangeHistory) -> Self {
+        self.events.history = Some(history);
+        self
+    }
+
+    /// Retains the configurations received in `snapshots`, to roll back to.
+    pub(crate) fn snapshots(mut self, snapshots: &'a Snapshots) -> Self {
+        self.events.snapshots = Some(snapshots);
+        self
+    }
+
+    /// Returns `true` once a configuration has been dispatched, or held back
+    /// because the pipeline is paused.
+    pub(crate) fn has_config(&self) -> bool {
+        self.events.has_config
+    }
+
+    /// Returns the number of configurations received so far, whether they were
+    /// dispatched or skipped as not newer, unchanged or repeated.
+    pub(crate) fn received(&self) -> u64 {
+        self.events.received
+    }
+
+    /// Returns the ID of the last complete event, to be sent as
+    /// `Last-Event-ID`, or `None` if it is empty.
+    pub(crate) fn last_event_id(&self) -> Option<&str> {
+        self.events.last_event_id.as_deref().filter(|id| !id.is_empty())
+    }
+
+    /// Dispatches the cached configuration, if the client has a cache and it
+    /// holds one.
+    pub(crate) async fn load_cache(&mut self) {
+        let Some(cache) = self.sse_client.cache.as_ref().filter(|_| !self.events.raw) else {
+            return;
+        };
+        match cache.load().await {
+            Ok(Some(config)) => {
+                info!(self.log, "Loaded configuration from cache");
+                let raw = serde_json::to_vec(&config).unwrap_or_default();
+                let events = &mut self.events;
+                if let Some(dry_run) = events.dry_run.as_mut() {
+                    dry_run.set_baseline(apply_update(events.sse_client, &mut events.accumulated, config));
+                } else if events.version_filter.as_mut().is_none_or(|filter| filter.admit(&config)) {
+                    let config = apply_update(events.sse_client, &mut events.accumulated, config);
+                    if events.change_filter.as_mut().is_some_and(|filter| !filter.admit(&config)) {
+                        return;
+                    }
+                    events.has_settings = !config.is_empty();
+                    events.sse_client.metrics.record_event();
+                    events.meta.origin = ConfigOrigin::Cache;
+                    events.invoke_handler(config, &raw);
+                    events.meta.origin = ConfigOrigin::Stream;
+                }
+            },
+            Ok(None) => {},
+            Err(e) => {
+                warn!(self.log, "Ignoring unreadable configuration cache"; "error" => %e);
+                self.sse_client.report_error(&e);
+            },
+        }
+    }
+
+    /// Holds back configurations instead of dispatching them while `paused`.
+    /// Unpausing dispatches the most recent configuration held back, if any.
+    pub(crate) fn set_paused(&mut self, paused: bool) {
+        let events = &mut self.events;
+        if events.paused == paused {
+            return;
+        }
+        events.paused = paused;
+        if paused {
+            info!(self.log, "Listener paused, holding back configurations");
+            return;
+        }
+        info!(self.log, "Listener resumed"; "held" => events.held.is_some());
+        if let Some((config, raw, meta)) = events.held.take() {
+            events.dispatch_config(config, &raw, &meta);
+        }
+    }
+
+    /// Carries out a rollback requested through the listener's handle: passes
+    /// the configuration rolled back to to the handler and holds back live
+    /// ones, or ends the rollback and passes the most recent live one held
+    /// back, if any.
+    pub(crate) fn rollback(&mut self, request: RollbackRequest) {
+        let events = &mut self.events;
+        match request {
+            RollbackRequest::To((config, raw, meta)) => {
+                info!(self.log, "Rolling back configuration"; "id" => meta.id.as_deref());
+                events.rolled_back = true;
+                // A live configuration held back while paused is held back by
+                // the rollback instead.
+                if let Some(held) = events.held.take() {
+                    events.suppressed = Some(held);
+                }
+                let meta = EventMeta { origin: ConfigOrigin::Rollback, ..meta };
+                events.dispatch_config(config, &raw, &meta);
+            },
+            RollbackRequest::Clear => {
+                if !std::mem::take(&mut events.rolled_back) {
+                    return;
+                }
+                info!(self.log, "Rollback cleared"; "suppressed" => events.suppressed.is_some());
+                if let Some((config, raw, meta)) = events.suppressed.take() {
+                    events.dispatch_config(config, &raw, &meta);
+                }
+            },
+        }
+    }
+
+    /// Returns how long until the last configuration dispatched is to be
+    /// reasserted, or `None` if it is not: without a reassert interval, before
+    /// the first configuration and while paused.
+    pub(crate) fn reassert_in(&self, now: tokio::time::Instant) -> Option<Duration> {
+        let interval = self.sse_client.reassert_interval.filter(|_| !self.events.paused)?;
+        let due = self.events.dispatched_at? + interval;
+        Some(due.saturating_duration_since(now))
+    }
+
+    /// Dispatches the last configuration dispatched again, with the origin
+    /// [`ConfigOrigin::Reassert`], unless it is not to be reasserted.
+    pub(crate) fn reassert(&mut self, now: tokio::time::Instant) {
+        if self.reassert_in(now).is_none() {
+            return;
+        }
+        let events = &mut self.events;
+        let Some((config, raw, meta)) = events.last_dispatched.as_ref() else {
+            return;
+        };
+        debug!(self.log, "Reasserting configuration"; "id" => meta.id.as_deref());
+        events.dispatched_at = Some(now);
+        let config = config.clone();
+        let meta = EventMeta { origin: ConfigOrigin::Reassert, ..meta.clone() };
+        let dispatch = &mut events.dispatch;
+        if events.detached {
+            dispatch(config, raw, &meta);
+        } else {
+            // Not acknowledged again, so without its ID.
+            call_handler(events.sse_client, &self.log, None, || dispatch(config, raw, &meta));
+        }
+    }
+
+    /// Prepares for a new stream, discarding any partially received event,
+    /// along with its ID. The last event ID is kept.
+    pub(crate) fn start_stream(&mut self) {
+        self.parser.reset();
+        if let Some(frames) = self.frames.as_mut() {
+            frames.reset();
+        }
+        if let Some(utf8) = self.utf8.as_mut() {
+            utf8.reset();
+        }
+        self.events.meta.event = None;
+        self.events.meta.retry = None;
+        self.events.heartbeat_interval = None;
+        self.events.end_event();
+    }
+
+    /// Detects the encoding of the payloads of the stream just started from
+    /// the `Content-Type` it was sent with, unless the client's payload format
+    /// is set or its payloads are parsed incrementally.
+    pub(crate) fn detect_payload_format(&mut self, content_type: Option<&str>) {
+        if self.sse_client.payload_format.is_some() || self.sse_client.incremental_parsing {
+            return;
+        }
+        let detected = content_type.and_then(PayloadFormat::from_content_type);
+        self.events.payload_format = detected.unwrap_or_default();
+        if let Some(frames) = self.frames.as_mut() {
+            frames.json_payloads(self.events.payload_format == PayloadFormat::Json);
+        }
+        debug!(self.log, "Detected payload format"; "content_type" => content_type, "format" => ?self.events.payload_format);
+    }
+
+    /// Completes the event left unterminated when the stream ended, as if the
+    /// server had sent the missing line terminator and blank line, or the
+    /// missing separator. Returns `true` if there was such an event.
+    pub(crate) async fn end_stream(&mut self) -> bool {
+        if let Some(frames) = self.frames.as_mut() {
+            let events = &mut self.events;
+            let mut cache_update = None;
+            let finished = frames.finish(|frame| cache_update = events.handle_frame(frame));
+            update_cache(self.sse_client, &self.log, cache_update).await;
+            return finished;
+        }
+        if !self.parser.has_partial_line() && self.events.meta.event.is_none() && self.events.data_lines == 0 {
+            return false;
+        }
+        // Terminating a line never makes it longer, so this cannot fail.
+        self.feed(b"\n\n").await.ok();
+        true
+    }
+
+    /// Returns how long the current stream may stay silent before it is
+    /// considered dead.
+    ///
+    /// A timeout configured on the client takes precedence; otherwise it is
+    /// three times the keep-alive interval announced by the server, if any.
+    pub(crate) fn idle_timeout(&self) -> Option<Duration> {
+        self.sse_client.idle_timeout.or_else(|| self.events.heartbeat_interval.map(|interval| interval.saturating_mul(3)))
+    }
+
+    /// Feeds the next chunk of the stream and updates the cache with the last
+    /// configuration it completed, or deletes it if the configuration was
+    /// removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LineTooLong`] if a line exceeds the maximum line length. The
+    /// pipeline must be restarted with [`start_stream`](Self::start_stream)
+    /// before it is fed again.
+    pub(crate) async fn feed(&mut self, bytes: &[u8]) -> Result<(), LineTooLong> {
+        let (fed, cache_update) = self.feed_lines(bytes);
+        update_cache(self.sse_client, &self.log, cache_update).await;
+        fed
+    }
+
+    /// Feeds the next chunk of the stream like [`feed`](Self::feed), returning
+    /// how the cache has to change instead of changing it.
+    pub(crate) fn feed_lines(&mut self, bytes: &[u8]) -> (Result<(), LineTooLong>, Option<CacheUpdate>) {
+        if let Some(Err(e)) = self.utf8.as_mut().map(|utf8| utf8.check(bytes)) {
+            warn!(self.log, "Received invalid UTF-8"; "error" => %e);
+            self.events.report_error(e);
+        }
+        let mut cache_update = None;
+        let events = &mut self.events;
+        if let Some(frames) = self.frames.as_mut() {
+            let fed = frames.feed(bytes, |frame| {
+                if let Some(update) = events.handle_frame(frame) {
+                    cache_update = Some(update);
+                }
+            });
+            return (fed, cache_update);
+        }
+        let fed = self.parser.feed(bytes, |piece| {
+            let dispatched = match piece {
+                Piece::Line(line) => events.handle_line(line),
+                Piece::DataStart => {
+                    events.start_streamed_data();
+                    None
+                },
+                Piece::DataPart(part) => {
+                    if let Some(decoder) = events.decoder.as_mut() {
+                        decoder.feed(part);
+                    }
+                    None
+                },
+                Piece::DataEnd => None,
+            };
+            if dispatched.is_some() {
+                cache_update = dispatched;
+            }
+        });
+        (fed, cache_update)
+    }
+}
+
+impl<F> EventState<'_, F>
+where
+    F: FnMut(ServerConfig, &[u8], &EventMeta),
+{
+    /// Handles one complete line. Returns how the cache has to change if the
+    /// line completed a configuration or its removal and the pipeline writes to
+    /// the cache.
+    ///
+    /// The fields of an event are collected until the blank line that ends
+    /// it, whatever their order and with comments in between, and the event
+    /// is handled as a whole then, with all its `data` lines joined by line
+    /// feeds as the SSE specification requires.
+    fn handle_line(&mut self, line: &[u8]) -> Option<CacheUpdate> {
+        let mut line = classify(line);
+        if self.sse_client.lenient_field_case {
+            line = line.fold_field_case();
+        }
+        match line {
+            Line::Blank => {
+                let update = if self.is_removal() {
+                    self.remove_config()
+                } else if self.data_lines > 0 && !self.oversized {
+                    self.handle_event_data()
+                } else {
+                    None
+                };
+                self.last_event_id = self.meta.id.clone();
+                self.end_event();
+                return update;
+            },
+            Line::Comment(comment) => {
+                if let Some(interval) = heartbeat_interval(comment) {
+                    if self.heartbeat_interval != Some(interval) {
+                        info!(self.log, "Server announced its keep-alive interval"; "interval_secs" => interval.as_secs());
+                    }
+                    self.heartbeat_interval = Some(interval);
+                }
+            },
+            Line::Field { name: b"data", value } => self.append_data(value),
+            Line::Field { name: b"id", value } => {
+                // IDs containing NUL are ignored, as required by the SSE specification.
+                if !value.contains(&0) {
+                    self.meta.id = Some(String::from_utf8_lossy(value).into_owned());
+                    self.has_own_id = !value.is_empty();
+                }
+            },
+            Line::Field { name: b"event", value } => {
+                self.meta.event = Some(String::from_utf8_lossy(value).into_owned());
+            },
+            Line::Field { name: b"retry", value } => {
+                if let Some(millis) = std::str::from_utf8(value).ok().and_then(|v| v.parse().ok()) {
+                    self.meta.retry = Some(Duration::from_millis(millis));
+                }
+            },
+            Line::Field { .. } => {},
+        }
+        None
+    }
+
+    /// Handles a payload split off by a framing other than SSE, as an event
+    /// holding nothing but a `data` line with that payload.
+    fn handle_frame(&mut self, payload: &[u8]) -> Option<CacheUpdate> {
+        self.append_data(payload);
+        self.handle_line(b"")
+    }
+
+    /// Adds the value of a `data` line to the current event.
+    fn append_data(&mut self, value: &[u8]) {
+        self.data_lines += 1;
+        match self.decoder.as_mut() {
+            Some(decoder) => {
+                decoder.feed(b"\n");
+                decoder.feed(value);
+            },
+            None if self.oversized => {},
+            None => {
+                let separator = usize::from(self.data_lines > 1);
+                let size = self.data.len() + separator + value.len();
+                if let Some(max_event_size) = self.sse_client.max_event_size.filter(|&max| size > max) {
+                    self.skip_oversized(size, max_event_size);
+                    return;
+                }
+                if separator > 0 {
+                    self.data.push(b'\n');
+                }
+                self.data.extend_from_slice(value);
+            },
+        }
+    }
+
+    /// Discards the data of the current event, which grew to `size` bytes,
+    /// past `max_event_size`, and skips the rest of it.
+    fn skip_oversized(&mut self, size: usize, max_event_size: usize) {
+        warn!(self.log, "Event data exceeds the maximum event size, skipping the event";
+            "size" => size, "max_event_size" => max_event_size, "id" => self.meta.id.as_deref());
+        self.sse_client.metrics.record_parse_error();
+        self.report_error(ConfigError::EventTooLarge(max_event_size));
+        self.oversized = true;
+        self.data.clear();
+    }
+
+    /// Starts a `data` line of the current event whose value is streamed:
+    /// from now on, the event's data is decoded as it arrives, starting with
+    /// the lines collected before.
+    fn start_streamed_data(&mut self) {
+        self.data_lines += 1;
+        if let Some(decoder) = self.decoder.as_mut() {
+            decoder.feed(b"\n");
+            return;
+        }
+        let mut decoder = match self.sse_client.wire_format {
+            WireFormat::Auto => SettingsDecoder::default(),
+            WireFormat::Enveloped => SettingsDecoder::default().enveloped(),
+            WireFormat::Flat => SettingsDecoder::default().flat(),
+        };
+        if self.data_lines > 1 {
+            decoder.feed(&self.data);
+            decoder.feed(b"\n");
+            self.data.clear();
+        }
+        self.decoder = Some(decoder);
+    }
+
+    /// Handles the data of the event that just ended.
+    fn handle_event_data(&mut self) -> Option<CacheUpdate> {
+        if self.decoder.is_some() {
+            return self.handle_streamed_data();
+        }
+        // Taken so the buffer can be borrowed while handling it, and put back
+        // to keep its capacity for the next event.
+        let data = std::mem::take(&mut self.data);
+        let update = self.handle_data(&data);
+        self.data = data;
+        update
+    }
+
+    /// Forgets the fields of the current event, once it has been handled or
+    /// abandoned. The last event ID persists, as it applies to the events
+    /// that follow; the ID of an abandoned event is dropped.
+    fn end_event(&mut self) {
+        self.meta.id.clone_from(&self.last_event_id);
+        self.meta.event = None;
+        self.meta.retry = None;
+        self.has_own_id = false;
+        self.data.clear();
+        self.data_lines = 0;
+        self.oversized = false;
+        self.decoder = None;
+    }
+
+    fn handle_data(&mut self, payload: &[u8]) -> Option<CacheUpdate> {
+        if self.is_removal() {
+            return None;
+        }
+        let sse_client = self.sse_client;
+        let log = &self.log;
+        let metrics = &sse_client.metrics;
+
+        if self.is_seen() || self.is_empty_data(payload) {
+            return None;
+        }
+        if !self.raw && self.repeat_filter.as_mut().is_some_and(|filter| !filter.admit(payload)) {
+            self.received += 1;
+            metrics.record_skipped();
+            info!(log, "Skipping payload identical to the last applied one"; "id" => self.meta.id.as_deref());
+            return None;
+        }
+        let decrypted = match &sse_client.decrypt {
+            Some(decrypt) => match decrypt(payload) {
+                Ok(plaintext) => Some(plaintext),
+                Err(e) => {
+                    metrics.record_parse_error();
+                    warn!(log, "Failed to decrypt configuration data"; "error" => %e);
+                    if let Some(dry_run) = self.dry_run.as_mut() {
+                        dry_run.record_invalid();
+                    }
+                    self.report_error(e);
+                    return None;
+                },
+            },
+            None => None,
+        };
+        if self.raw {
+            self.received += 1;
+            metrics.record_event();
+            let payload = decrypted.as_deref().unwrap_or(payload);
+            self.record_seen();
+            self.invoke_handler(ServerConfig::default(), payload);
+            info!(self.log, "Event received"; "id" => self.meta.id.as_deref());
+            return None;
+        }
+        let plaintext = decrypted.as_deref().unwrap_or(payload);
+        let parsed = match &sse_client.envelope_pointer {
+            Some(pointer) => self.unwrap_envelope(plaintext, pointer),
+            None => self.payload_format.parse(plaintext, sse_client.wire_format),
+        };
+        self.handle_parsed(parsed, payload)
+    }
+
+    /// Returns `true` if `payload` is empty or whitespace and is to be ignored,
+    /// see [`SseClientBuilder::ignore_empty_data`](crate::SseClientBuilder::ignore_empty_data).
+    fn is_empty_data(&self, payload: &[u8]) -> bool {
+        let empty = self.sse_client.ignore_empty_data && payload.trim_ascii().is_empty();
+        if empty {
+            debug!(self.log, "Ignoring event with empty data"; "id" => self.meta.id.as_deref());
+        }
+        empty
+    }
+
+    /// Parses the configuration at `pointer` in the envelope `payload`, and
+    /// passes what is left of the envelope to the envelope hook.
+    fn unwrap_envelope(&self, payload: &[u8], pointer: &str) -> Result<ServerConfig, serde_json::Error> {
+        let mut envelope = self.payload_format.to_value(payload)?;
+        let config = envelope
+            .pointer_mut(pointer)
+            .map(Value::take)
+            .ok_or_else(|| serde_json::Error::custom(format!("envelope has no value at {}", pointer)))?;
+        if let Some(hook) = &self.sse_client.envelope_hook {
+            hook(&envelope);
+        }
+        self.sse_client.wire_format.deserialize(config)
+    }
+
+    /// Handles the data of an event that was decoded while it was streamed.
+    /// The payload itself was not kept, so an empty one is dispatched with it.
+    fn handle_streamed_data(&mut self) -> Option<CacheUpdate> {
+        let decoder = self.decoder.take()?;
+        if self.is_removal() || self.is_seen() {
+            return None;
+        }
+        if decoder.is_blank() && self.is_empty_data(b"") {
+            return None;
+        }
+        let parsed = decoder.finish().map(|settings| ServerConfig { settings });
+        self.handle_parsed(parsed, &[])
+    }
+
+    /// Filters, merges and dispatches the configuration parsed from `payload`,
+    /// or records the error it failed to parse with.
+    fn handle_parsed(&mut self, parsed: Result<ServerConfig, serde_json::Error>, payload: &[u8]) -> Option<CacheUpdate> {
+        #[cfg(feature = "checksum")]
+        let parsed = parsed.map(|config| self.verify_checksum(config)).transpose()?;
+        let parsed = match &self.sse_client.transform {
+            Some(transform) => parsed.map(|config| transform(config)),
+            None => parsed,
+        };
+        if parsed.is_ok() {
+            self.received += 1;
+            if let Some(filter) = self.repeat_filter.as_mut() {
+                filter.record(payload);
+            }
+            self.record_seen();
+        }
+        let sse_client = self.sse_client;
+        let log = &self.log;
+        let metrics = &sse_client.metrics;
+
+        match parsed {
+            Ok(config) => {
+                if self.version_filter.as_mut().is_some_and(|filter| !filter.admit(&config)) {
+                    metrics.record_skipped();
+                    info!(log, "Skipping configuration that is not newer than the last applied version";
+                        "version_key" => sse_client.version_key.as_deref(), "id" => self.meta.id.as_deref());
+                    return None;
+                }
+                // Merged updates are compared as merged, as a partial update says
+                // nothing about the configuration it results in
+                let config = apply_update(sse_client, &mut self.accumulated, config);
+                if self.change_filter.as_mut().is_some_and(|filter| !filter.admit(&config)) {
+                    metrics.record_skipped();
+                    info!(log, "Skipping configuration that is unchanged from the last applied one";
+                        "id" => self.meta.id.as_deref());
+                    return None;
+                }
+                if let Some(dry_run) = self.dry_run.as_mut() {
+                    let diff = dry_run.record_valid(config);
+                    info!(log, "Dry run: configuration is valid";
+                        "added" => ?diff.added, "removed" => ?diff.removed, "changed" => ?diff.changed,
+                        "valid_total" => dry_run.valid, "invalid_total" => dry_run.invalid,
+                        "changed_total" => dry_run.changed);
+                    return None;
+                }
+                metrics.record_event();
+                let to_cache = self.caching.then(|| CacheUpdate::Save(config.clone()));
+                let emptied = self.has_settings && config.is_empty();
+                self.has_settings = !config.is_empty();
+                self.invoke_handler(config, payload);
+                info!(self.log, "Configuration updated"; "bytes" => payload.len());
+                // The configuration may hold secrets, so it is only logged
+                // when debugging.
+                if self.log.is_debug_enabled() {
+                    debug!(self.log, "Configuration updated"; "config" => %String::from_utf8_lossy(payload));
+                }
+                if emptied {
+                    info!(self.log, "Configuration no longer has any settings"; "id" => self.meta.id.as_deref());
+                    sse_client.report_removal();
+                }
+                to_cache
+            },
+            Err(e) => {
+                metrics.record_parse_error();
+                warn!(log, "Failed to parse configuration data"; "error" => %e);
+                if let Some(dry_run) = self.dry_run.as_mut() {
+                    dry_run.record_invalid();
+                    info!(log, "Dry run: configuration is invalid";
+                        "valid_total" => dry_run.valid, "invalid_total" => dry_run.invalid,
+                        "changed_total" => dry_run.changed);
+                }
+                self.report_error(ConfigError::JsonParse(e));
+                None
+            },
+        }
+    }
+
+    /// Checks `config` against its checksum, if the client verifies one, and
+    /// returns it without the checksum setting. A configuration that fails the
+    /// check is recorded like one that fails to parse.
+    #[cfg(feature = "checksum")]
+    fn verify_checksum(&mut self, mut config: ServerConfig) -> Option<ServerConfig> {
+        let Some((key, algorithm)) = &self.sse_client.checksum else {
+            return Some(config);
+        };
+        let Err(e) = crate::checksum::verify(&mut config, key, *algorithm) else {
+            return Some(config);
+        };
+        self.sse_client.metrics.record_parse_error();
+        warn!(self.log, "Configuration failed checksum verification"; "error" => %e, "id" => self.meta.id.as_deref());
+        if let Some(dry_run) = self.dry_run.as_mut() {
+            dry_run.record_invalid();
+        }
+        self.report_error(e);
+        None
+    }
+
+    /// Whether the current event is skipped because its ID was processed
+    /// before. Skipped events are counted and logged.
+    fn is_seen(&self) -> bool {
+        let Some(seen_ids) = self.seen_ids.as_ref().filter(|_| self.has_own_id) else {
+            return false;
+        };
+        let Some(id) = self.meta.id.as_deref().filter(|id| seen_ids.contains(id)) else {
+            return false;
+        };
+        self.sse_client.metrics.record_skipped();
+        info!(self.log, "Skipping event whose ID was already processed"; "id" => id);
+        true
+    }
+
+    /// Remembers the ID of the current event as processed, if it has one.
+    fn record_seen(&mut self) {
+        if let (Some(seen_ids), Some(id), true) = (self.seen_ids.as_mut(), self.meta.id.as_deref(), self.has_own_id) {
+            seen_ids.insert(id);
+        }
+    }
+
+    /// Passes `e` to the error hook, or collects it if the pipeline collects
+    /// its output.
+    fn report_error(&mut self, e: ConfigError) {
+        match self.collected.as_mut() {
+            Some(collected) => collected.push(Err(e)),
+            None => self.sse_client.report_error(&e),
+        }
+    }
+
+    /// Whether the current event is the server's signal that the configuration
+    /// was removed. Raw payloads are passed on whatever their event type.
+    fn is_removal(&self) -> bool {
+        !self.raw && self.meta.event.as_deref() == Some(self.sse_client.removal_event.as_str())
+    }
+
+    /// Handles the removal of the configuration by the server, so that the
+    /// next configuration is treated like the first one.
+    fn remove_config(&mut self) -> Option<CacheUpdate> {
+        if self.dry_run.is_some() {
+            info!(self.log, "Dry run: configuration would be removed"; "id" => self.meta.id.as_deref());
+            return None;
+        }
+        info!(self.log, "Configuration removed by the server"; "id" => self.meta.id.as_deref());
+        self.accumulated = None;
+        self.has_settings = false;
+        // Nothing that was removed may be dispatched again: not by a
+        // reassertion, nor on resuming or clearing a rollback.
+        self.last_dispatched = None;
+        self.dispatched_at = None;
+        self.held = None;
+        self.suppressed = None;
+        if let Some(filter) = self.change_filter.as_mut() {
+            filter.forget();
+        }
+        if let Some(filter) = self.repeat_filter.as_mut() {
+            filter.forget();
+        }
+        if let Some(history) = self.history {
+            history.record_removal(self.meta.id.as_deref());
+        }
+        self.sse_client.report_removal();
+        self.caching.then_some(CacheUpdate::Clear)
+    }
+
+    /// Passes one configuration to `dispatch` through [`call_handler`], so a
+    /// panic in the handler lets the listener carry on with the next event
+    /// unless the client was configured to fail fast.
+    fn invoke_handler(&mut self, config: ServerConfig, raw: &[u8]) {
+        self.has_config = true;
+        let config = match &self.sse_client.flatten_keys {
+            Some(options) => config.flatten_with(options),
+            None => config,
+        };
+        if let Some(collected) = self.collected.as_mut() {
+            collected.push(Ok(config));
+            return;
+        }
+        let meta = match self.event_ids.synthesize(&self.meta, &config, raw) {
+            Some(id) => EventMeta { id: Some(id), ..self.meta.clone() },
+            None => self.meta.clone(),
+        };
+        if let Some(snapshots) = self.snapshots.filter(|_| !self.raw) {
+            if let Some(version) = version_of(self.sse_client.version_key.as_deref(), &config, &meta) {
+                snapshots.record(version, &config, raw, &meta);
+            }
+        }
+        if self.rolled_back {
+            debug!(self.log, "Holding back configuration while rolled back"; "id" => meta.id.as_deref());
+            self.suppressed = Some((config, raw.to_vec(), meta));
+            return;
+        }
+        self.dispatch_config(config, raw, &meta);
+    }
+
+    /// Passes `config` to `dispatch`, or holds it back while paused.
+    fn dispatch_config(&mut self, config: ServerConfig, raw: &[u8], meta: &EventMeta) {
+        if self.paused {
+            self.held = Some((config, raw.to_vec(), meta.clone()));
+            return;
+        }
+        if self.sse_client.reassert_interval.is_some() {
+            self.last_dispatched = Some((config.clone(), raw.to_vec(), meta.clone()));
+            self.dispatched_at = Some(self.sse_client.clock.now());
+        }
+        let dispatch = &mut self.dispatch;
+        if let Some(history) = self.history.filter(|_| !self.raw) {
+            history.record(&config, meta.id.as_deref(), raw);
+        }
+        if self.detached {
+            dispatch(config, raw, meta);
+        } else {
+            // A rollback was acknowledged the first time around.
+            let id = meta.id.as_deref().filter(|_| meta.origin != ConfigOrigin::Rollback);
+            call_handler(self.sse_client, &self.log, id, || dispatch(config, raw, meta));
+        }
+    }
+}
+
+/// Applies `update` to the client's cache, if it has one.
+async fn update_cache(sse_client: &SseClient, log: &Logger, update: Option<CacheUpdate>) {
+    if let (Some(cache), Some(update)) = (&sse_client.cache, update) {
+        let updated = match &update {
+            CacheUpdate::Save(config) => cache.save(config).await,
+            CacheUpdate::Clear => cache.clear().await,
+        };
+        if let Err(e) = updated {
+            warn!(log, "Failed to update configuration cache"; "error" => %e);
+            sse_client.report_error(&e);
+        }
+    }
+}
+
+/// Calls `handler` for the configuration from the event with ID `id`, records
+/// how long it took and acknowledges the event if the client has an ack URL.
+///
+/// Unless the client was configured to fail fast, a panic in the handler is
+/// caught, logged and reported to the error hook, and the event is not
+/// acknowledged.
+pub(crate) fn call_handler(sse_client: &SseClient, log: &Logger, id: Option<&str>, handler: impl FnOnce()) {
+    if let Err(message) = run_handler(sse_client, log, id, handler) {
+        warn!(log, "Configuration handler panicked"; "panic" => &message, "id" => id);
+        sse_client.report_error(&ConfigError::HandlerPanicked(message));
+    } else if let Some(id) = id {
+        acknowledge(sse_client, log, id);
+    }
+}
+
+/// Calls every handler of `handlers` in turn for the configuration from the
+/// event with ID `id`, each like [`call_handler`] does, and acknowledges the
+/// event if none of them panicked.
+///
+/// A panic in one handler does not keep the others from being called. The
+/// panics are logged one by one and reported to the error hook together, as
+/// [`ConfigError::HandlersPanicked`].
+pub(crate) fn call_handlers<F>(sse_client: &SseClient, log: &Logger, id: Option<&str>, config: &ServerConfig, handlers: &mut [F])
+where
+    F: FnMut(ServerConfig),
+{
+    let mut panics = Vec::new();
+    for (index, handler) in handlers.iter_mut().enumerate() {
+        if let Err(message) = run_handler(sse_client, log, id, || handler(config.clone())) {
+            warn!(log, "Configuration handler panicked"; "handler" => index, "panic" => &message, "id" => id);
+            panics.push((index, message));
+        }
+    }
+    if !panics.is_empty() {
+        sse_client.report_error(&ConfigError::HandlersPanicked(panics));
+    } else if let Some(id) = id {
+        acknowledge(sse_client, log, id);
+    }
+}
+
+/// Calls `handler` and records how long it took, catching a panic unless the
+/// client was configured to fail fast. Returns the panic message if it
+/// panicked.
+fn run_handler(sse_client: &SseClient, log: &Logger, id: Option<&str>, handler: impl FnOnce()) -> Result<(), String> {
+    // Handlers are synchronous and block the thread they run on, so the time
+    // they take is measured with the wall clock rather than the injected clock.
+    let started = Instant::now();
+    let outcome = offload(sse_client, || {
+        if sse_client.catch_handler_panics {
+            catch_unwind(AssertUnwindSafe(handler))
+        } else {
+            handler();
+            Ok(())
+        }
+    });
+    let elapsed = started.elapsed();
+    sse_client.metrics.record_handler_latency(elapsed);
+    if sse_client.slow_handler_threshold.is_some_and(|threshold| elapsed > threshold) {
+        warn!(log, "Configuration handler was slow"; "elapsed_ms" => elapsed.as_millis() as u64, "id" => id);
+    }
+
+    outcome.map_err(|panic| {
+        sse_client.metrics.record_handler_panic();
+        panic_message(panic.as_ref())
+    })
+}
+
+/// Runs `f` in place, moving the runtime's other tasks off the current worker
+/// thread first if the client offloads its handler and the runtime allows it.
+fn offload<T>(sse_client: &SseClient, f: impl FnOnce() -> T) -> T {
+    let multi_threaded = Handle::try_current().is_ok_and(|runtime| runtime.runtime_flavor() == RuntimeFlavor::MultiThread);
+    if sse_client.offload_handler && multi_threaded {
+        tokio::task::block_in_place(f)
+    } else {
+        f()
+    }
+}
+
+/// Turns a received configuration into the configuration to dispatch.
+///
+/// When the client merges updates, `update` is merged into the configuration
+/// accumulated so far and a copy of the result is returned. Otherwise `update`
+/// replaces the previous configuration, with top-level `null` values removed
+/// if nulls mean deletion. Either way, the result is merged over the client's
+/// defaults, if any.
+fn apply_update(sse_client: &SseClient, accumulated: &mut Option<ServerConfig>, mut update: ServerConfig) -> ServerConfig {
+    let config = match sse_client.merge_updates {
+        Some(policy) => {
+            let accumulated = accumulated.get_or_insert_default();
+            accumulated.merge_with(&update, policy, sse_client.null_values);
+            accumulated.clone()
+        },
+        None => {
+            if sse_client.null_values == NullValues::Delete {
+                update.settings.retain(|_, value| !value.is_null());
+            }
+            update
+        },
+    };
+    match &sse_client.defaults {
+        Some(defaults) => {
+            let mut complete = defaults.clone();
+            complete.merge_with(&config, MergePolicy::DeepMerge(ArrayMerge::Replace), sse_client.null_values);
+            complete
+        },
+        None => config,
+    }
+}
+
+/// Extracts the message from a panic payload.
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "handler panicked with a non-string payload".to_string()
+    }
+}
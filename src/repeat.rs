@@ -0,0 +1,55 @@
+// repeat.rs
+
+/// Recognizes a `data:` payload that repeats the last one admitted byte for
+/// byte, so it can be skipped without being parsed.
+///
+/// Payloads are compared by hash first, and by their bytes only when the
+/// hashes match, so a collision can never make a changed payload look like a
+/// repeat.
+#[derive(Debug, Default)]
+pub(crate) struct RepeatFilter {
+    /// The hash and bytes of the last payload admitted.
+    last: Option<(u64, Vec<u8>)>,
+    /// The hash of the payload admitted by [`admit`](Self::admit) but not
+    /// [recorded](Self::record) yet.
+    candidate: Option<u64>,
+}
+
+impl RepeatFilter {
+    /// Returns `false` if `payload` is identical to the last payload recorded.
+    /// Otherwise it becomes the candidate for [`record`](Self::record).
+    pub(crate) fn admit(&mut self, payload: &[u8]) -> bool {
+        let hash = fnv1a(payload);
+        if self.last.as_ref().is_some_and(|(last_hash, last)| *last_hash == hash && last == payload) {
+            return false;
+        }
+        self.candidate = Some(hash);
+        true
+    }
+
+    /// Records `payload`, the last one admitted, once it has been applied. A
+    /// payload that fails to apply is not recorded, so it is tried again if
+    /// the server repeats it.
+    pub(crate) fn record(&mut self, payload: &[u8]) {
+        if let Some(hash) = self.candidate.take() {
+            let mut bytes = self.last.take().map(|(_, bytes)| bytes).unwrap_or_default();
+            bytes.clear();
+            bytes.extend_from_slice(payload);
+            self.last = Some((hash, bytes));
+        }
+    }
+
+    /// Forgets the last payload recorded, so the next one is admitted whatever
+    /// it holds.
+    pub(crate) fn forget(&mut self) {
+        self.last = None;
+        self.candidate = None;
+    }
+}
+
+/// The 64-bit FNV-1a hash of `bytes`. Unlike the standard library's hasher,
+/// it is the same in every process and release, so hashes can be compared with
+/// ones stored elsewhere.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3))
+}
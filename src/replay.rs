@@ -0,0 +1,133 @@
+// replay.rs
+
+use crate::client::SseClient;
+use crate::errors::ConfigError;
+use crate::logger::configure_logging;
+use crate::models::{EventMeta, ServerConfig};
+use crate::parser::{classify, Line};
+use crate::pipeline::Pipeline;
+use slog::{info, warn};
+use std::io::BufRead;
+use std::time::Duration;
+
+/// How [`SseClient::replay`] paces the events of a recorded stream.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReplayTiming {
+    /// Feed the recording as fast as it can be read.
+    #[default]
+    Immediate,
+    /// Wait between lines as long as passed between them when the stream was
+    /// recorded, based on the `: ts=` comments in the recording. Recordings
+    /// without timestamps are replayed immediately.
+    Original,
+}
+
+impl SseClient {
+    /// Replays a recorded SSE stream through the same parsing and dispatch
+    /// pipeline as [`listen`](Self::listen), passing every configuration to
+    /// `update_handler`.
+    ///
+    /// `reader` holds the stream exactly as the server sent it. The client's
+    /// filters, merge mode, decryption, dry run and metrics all apply, so a
+    /// recording of a misbehaving stream reproduces what the listener did with
+    /// it. The configuration cache is neither loaded nor updated.
+    ///
+    /// A comment line of the form `: ts=<milliseconds>` records when the lines
+    /// after it were received, in milliseconds since any fixed point such as the
+    /// Unix epoch. With [`ReplayTiming::Original`] the replay waits on the
+    /// client's [`Clock`](crate::Clock) for the time between consecutive
+    /// timestamps. Servers ignore comments, so timestamps do not change the
+    /// meaning of the stream. This crate does not record streams itself; a
+    /// capture such as `curl -N` output can be replayed as is.
+    ///
+    /// A line longer than the client's maximum line length is skipped.
+    ///
+    /// Reading is synchronous, which is fine for files but blocks the runtime
+    /// thread on slower readers.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::Io` if reading from `reader` fails.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{ReplayTiming, ServerConfig, SseClientBuilder};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), config_sdk::ConfigError> {
+    /// let recording = b": ts=1000
+/// data: {\"settings\": {\"level\": 1}}
+///
+/// : ts=1500
+/// data: {\"settings\": {\"level\": 2}}
+///
+/// ";
+    /// let mut seen = Vec::new();
+    ///
+    /// SseClientBuilder::new("http://example.com/sse")
+    ///     .build()?
+    ///     .replay(&recording[..], ReplayTiming::Immediate, |config: ServerConfig| {
+    ///         seen.push(config.settings["level"].clone());
+    ///     })
+    ///     .await?;
+    ///
+    /// assert_eq!(seen, [1, 2]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn replay<R, F>(&self, mut reader: R, timing: ReplayTiming, mut update_handler: F) -> Result<(), ConfigError>
+    where
+        R: BufRead,
+        F: FnMut(ServerConfig),
+    {
+        let (log, _log_guard) = configure_logging(self.log_settings, self.metrics());
+        let mut pipeline = Pipeline::new(self, log.clone(), |config, _raw: &[u8], _meta: &EventMeta| {
+            update_handler(config)
+        })
+        .without_cache();
+        let mut line = Vec::new();
+        let mut last_timestamp = None;
+
+        info!(log, "Replaying recorded SSE stream"; "timing" => ?timing);
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+
+            if let Some(timestamp) = timestamp(&line) {
+                if let (ReplayTiming::Original, Some(last)) = (timing, last_timestamp) {
+                    if timestamp > last {
+                        self.clock.sleep(Duration::from_millis(timestamp - last)).await;
+                    }
+                }
+                last_timestamp = Some(timestamp);
+            }
+
+            if let Err(e) = pipeline.feed(&line).await {
+                warn!(log, "Recorded SSE line exceeds the maximum line length, skipping it";
+                    "length" => e.length, "max_line_length" => self.max_line_length);
+                pipeline.start_stream();
+            }
+        }
+
+        // A recording may end without the line terminator of its last line.
+        pipeline.feed(b"\n").await.ok();
+        info!(log, "Finished replaying recorded SSE stream");
+        Ok(())
+    }
+}
+
+/// Returns the timestamp recorded by a `: ts=<milliseconds>` comment line.
+fn timestamp(line: &[u8]) -> Option<u64> {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    match classify(line) {
+        Line::Comment(text) => {
+            let text = text.strip_prefix(b" ").unwrap_or(text);
+            std::str::from_utf8(text.strip_prefix(b"ts=")?).ok()?.trim().parse().ok()
+        },
+        _ => None,
+    }
+}
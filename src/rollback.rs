@@ -0,0 +1,71 @@
+// rollback.rs
+
+use crate::models::{EventMeta, ServerConfig};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// A configuration as it was dispatched, with its payload and the SSE fields
+/// it was dispatched with.
+pub(crate) type Retained = (ServerConfig, Vec<u8>, EventMeta);
+
+/// The last configurations received by one listener, by version, to roll back
+/// to with [`ListenerHandle::rollback_to`](crate::ListenerHandle::rollback_to).
+#[derive(Debug)]
+pub(crate) struct Snapshots {
+    capacity: usize,
+    /// The version of every configuration and the configuration itself, the
+    /// one received longest ago first.
+    retained: Mutex<VecDeque<(String, Retained)>>,
+}
+
+/// A rollback requested through a [`ListenerHandle`](crate::ListenerHandle),
+/// for the listener to carry out.
+#[derive(Debug)]
+pub(crate) enum RollbackRequest {
+    /// Dispatch this configuration and hold back live ones.
+    To(Retained),
+    /// Go back to live configurations.
+    Clear,
+}
+
+impl Snapshots {
+    /// Creates a store that keeps at most `capacity` configurations.
+    pub(crate) fn new(capacity: usize) -> Self {
+        Snapshots { capacity, retained: Mutex::new(VecDeque::with_capacity(capacity)) }
+    }
+
+    /// Retains `config` under `version`, replacing a configuration retained
+    /// under the same version and forgetting the oldest one once the store is
+    /// full.
+    pub(crate) fn record(&self, version: String, config: &ServerConfig, raw: &[u8], meta: &EventMeta) {
+        let mut retained = self.retained.lock().unwrap_or_else(|e| e.into_inner());
+        retained.retain(|(v, _)| *v != version);
+        if retained.len() == self.capacity {
+            retained.pop_front();
+        }
+        retained.push_back((version, (config.clone(), raw.to_vec(), meta.clone())));
+    }
+
+    /// Returns the configuration retained under `version`, if any.
+    pub(crate) fn get(&self, version: &str) -> Option<Retained> {
+        let retained = self.retained.lock().unwrap_or_else(|e| e.into_inner());
+        retained.iter().find(|(v, _)| v == version).map(|(_, snapshot)| snapshot.clone())
+    }
+
+    /// Returns the versions retained, the one received longest ago first.
+    pub(crate) fn versions(&self) -> Vec<String> {
+        let retained = self.retained.lock().unwrap_or_else(|e| e.into_inner());
+        retained.iter().map(|(version, _)| version.clone()).collect()
+    }
+}
+
+/// Returns the version of `config`: its setting `version_key`, if the client
+/// has one and the configuration holds it, or else the ID of its event.
+pub(crate) fn version_of(version_key: Option<&str>, config: &ServerConfig, meta: &EventMeta) -> Option<String> {
+    match version_key.and_then(|key| config.settings.get(key)) {
+        Some(Value::String(version)) => Some(version.clone()),
+        Some(version) => Some(version.to_string()),
+        None => meta.id.clone(),
+    }
+}
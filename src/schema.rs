@@ -0,0 +1,227 @@
+// schema.rs
+
+use crate::errors::ConfigError;
+use crate::models::ServerConfig;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Number, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// The type a setting is expected to have, see [`Schema`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SettingType {
+    /// `true` or `false`. The strings `"true"` and `"false"` are accepted in any
+    /// ASCII case.
+    Bool,
+    /// A signed 64-bit integer. Floats without a fractional part and strings
+    /// holding an integer are accepted.
+    Integer,
+    /// An unsigned 64-bit integer, coerced like [`Integer`](Self::Integer).
+    Unsigned,
+    /// A finite 64-bit float. Strings holding a number are accepted.
+    Float,
+    /// A string. Numbers and booleans are converted to their JSON text.
+    String,
+    /// A list of strings. Elements are coerced like [`String`](Self::String), and
+    /// a single string is split at commas, with whitespace around each item
+    /// removed.
+    StringList,
+}
+
+impl fmt::Display for SettingType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SettingType::Bool => "boolean",
+            SettingType::Integer => "integer",
+            SettingType::Unsigned => "unsigned integer",
+            SettingType::Float => "float",
+            SettingType::String => "string",
+            SettingType::StringList => "list of strings",
+        })
+    }
+}
+
+impl SettingType {
+    /// Converts `value` to this type, or returns `None` if it cannot be
+    /// represented as one.
+    fn coerce(self, value: &Value) -> Option<Value> {
+        match (self, value) {
+            (SettingType::Bool, Value::Bool(_)) => Some(value.clone()),
+            (SettingType::Bool, Value::String(s)) => {
+                if s.eq_ignore_ascii_case("true") {
+                    Some(Value::Bool(true))
+                } else if s.eq_ignore_ascii_case("false") {
+                    Some(Value::Bool(false))
+                } else {
+                    None
+                }
+            }
+            (SettingType::Integer, Value::Number(n)) => n.as_i64().or_else(|| integral(n.as_f64()?)).map(Value::from),
+            (SettingType::Integer, Value::String(s)) => s.trim().parse::<i64>().ok().map(Value::from),
+            (SettingType::Unsigned, Value::Number(n)) => n.as_u64().or_else(|| integral(n.as_f64()?)).map(Value::from),
+            (SettingType::Unsigned, Value::String(s)) => s.trim().parse::<u64>().ok().map(Value::from),
+            (SettingType::Float, Value::Number(n)) => n.as_f64().and_then(Number::from_f64).map(Value::Number),
+            (SettingType::Float, Value::String(s)) => s.trim().parse().ok().and_then(Number::from_f64).map(Value::Number),
+            (SettingType::String, _) => string(value).map(Value::String),
+            (SettingType::StringList, Value::Array(items)) => {
+                items.iter().map(|item| string(item).map(Value::String)).collect::<Option<_>>().map(Value::Array)
+            }
+            (SettingType::StringList, Value::String(s)) => Some(Value::Array(
+                s.split(',').map(str::trim).filter(|item| !item.is_empty()).map(Value::from).collect(),
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// Returns `value` as an integer if it has no fractional part and fits.
+fn integral<T: TryFrom<i128>>(value: f64) -> Option<T> {
+    // The range check keeps the cast from saturating.
+    if value.fract() == 0.0 && value.abs() < 2f64.powi(64) {
+        T::try_from(value as i128).ok()
+    } else {
+        None
+    }
+}
+
+/// Returns the text of a scalar value.
+fn string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(_) | Value::Bool(_) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// Declares the expected type of individual settings.
+///
+/// Pass a schema to [`SseClient::listen_typed`](crate::SseClient::listen_typed)
+/// to have every configuration coerced into a [`TypedServerConfig`] before it
+/// reaches the handler, or call [`Schema::coerce`] directly.
+///
+/// # Example
+///
+/// ```
+/// use config_sdk::{Schema, ServerConfig, SettingType};
+///
+/// let schema = Schema::new()
+///     .key("timeout", SettingType::Unsigned)
+///     .key("hostname", SettingType::String)
+///     .key("tags", SettingType::StringList);
+///
+/// let config: ServerConfig = serde_json::from_str(
+///     r#"{"settings": {"timeout": "30", "hostname": "example.com", "tags": "a, b", "debug": true}}"#,
+/// ).unwrap();
+/// let (typed, errors) = schema.coerce(&config);
+///
+/// assert!(errors.is_empty());
+/// assert_eq!(typed.get::<u64>("timeout"), Some(30));
+/// assert_eq!(typed.get::<Vec<String>>("tags"), Some(vec!["a".to_string(), "b".to_string()]));
+/// // Keys outside the schema are not part of the typed configuration.
+/// assert_eq!(typed.get::<bool>("debug"), None);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Schema {
+    keys: BTreeMap<String, SettingType>,
+}
+
+impl Schema {
+    /// Creates an empty schema.
+    pub fn new() -> Self {
+        Schema::default()
+    }
+
+    /// Declares that the setting `key` has type `setting_type`, replacing any
+    /// earlier declaration for the same key.
+    pub fn key(mut self, key: impl Into<String>, setting_type: SettingType) -> Self {
+        self.keys.insert(key.into(), setting_type);
+        self
+    }
+
+    /// Coerces the declared settings of `config` to their types.
+    ///
+    /// Every declared key that is present and can be coerced is included in the
+    /// returned configuration. A key that cannot be coerced is left out and
+    /// reported as a `ConfigError::InvalidSetting` in the returned list, so one
+    /// bad value does not reject the rest of the configuration. A declared key
+    /// that is missing or `null` is left out without an error.
+    pub fn coerce(&self, config: &ServerConfig) -> (TypedServerConfig, Vec<ConfigError>) {
+        let mut settings = BTreeMap::new();
+        let mut errors = Vec::new();
+        for (key, &setting_type) in &self.keys {
+            match config.settings.get(key) {
+                None | Some(Value::Null) => {}
+                Some(value) => match setting_type.coerce(value) {
+                    Some(coerced) => {
+                        settings.insert(key.clone(), coerced);
+                    }
+                    None => errors.push(ConfigError::InvalidSetting {
+                        key: key.clone(),
+                        expected: setting_type,
+                        value: value.clone(),
+                    }),
+                },
+            }
+        }
+        (TypedServerConfig { settings }, errors)
+    }
+}
+
+/// A configuration whose settings have been coerced to the types declared in a
+/// [`Schema`].
+///
+/// Only declared keys are present, and each holds a value of its declared
+/// type, so reading it with the matching Rust type always succeeds.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TypedServerConfig {
+    settings: BTreeMap<String, Value>,
+}
+
+impl TypedServerConfig {
+    /// Returns the setting `key` as a `T`, or `None` if it is absent or not
+    /// representable as a `T`.
+    ///
+    /// The matching types are `bool`, `i64`, `u64`, `f64`, `String` and
+    /// `Vec<String>`; other types that deserialize from the same JSON work too.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        T::deserialize(self.settings.get(key)?).ok()
+    }
+
+    /// Returns the coerced settings.
+    pub fn settings(&self) -> &BTreeMap<String, Value> {
+        &self.settings
+    }
+
+    /// Deserializes the coerced settings into a struct with one field per key.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::JsonParse` if the settings do not match `T`, for
+    /// example because a required field's key was missing or failed coercion.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{Schema, ServerConfig, SettingType};
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Settings {
+    ///     timeout: u64,
+    ///     hostname: String,
+    /// }
+    ///
+    /// let schema = Schema::new()
+    ///     .key("timeout", SettingType::Unsigned)
+    ///     .key("hostname", SettingType::String);
+    /// let config: ServerConfig =
+    ///     serde_json::from_str(r#"{"settings": {"timeout": 30.0, "hostname": "example.com"}}"#).unwrap();
+    ///
+    /// let settings: Settings = schema.coerce(&config).0.to_struct().unwrap();
+    /// assert_eq!(settings.timeout, 30);
+    /// ```
+    pub fn to_struct<T: DeserializeOwned>(&self) -> Result<T, ConfigError> {
+        let object: Map<String, Value> = self.settings.clone().into_iter().collect();
+        Ok(serde_json::from_value(Value::Object(object))?)
+    }
+}
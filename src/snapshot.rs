@@ -0,0 +1,112 @@
+// snapshot.rs
+
+use crate::client::SseClient;
+use crate::errors::ConfigError;
+use crate::format::PayloadFormat;
+use crate::models::ServerConfig;
+use reqwest::header::{HeaderValue, ACCEPT, CONTENT_TYPE, IF_MODIFIED_SINCE, LAST_MODIFIED};
+use reqwest::StatusCode;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// The outcome of [`SseClient::fetch_snapshot`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Snapshot {
+    /// The server returned a configuration.
+    Modified(ServerConfig),
+    /// The server answered `304 Not Modified`: the configuration returned by the
+    /// previous fetch of the same URL is still current.
+    NotModified,
+}
+
+/// The `Last-Modified` value of the last successful snapshot, per URL.
+#[derive(Debug, Default)]
+pub(crate) struct LastModified(Mutex<HashMap<String, HeaderValue>>);
+
+impl LastModified {
+    fn get(&self, url: &str) -> Option<HeaderValue> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).get(url).cloned()
+    }
+
+    fn set(&self, url: &str, value: Option<HeaderValue>) {
+        let mut entries = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        match value {
+            Some(value) => entries.insert(url.to_string(), value),
+            None => entries.remove(url),
+        };
+    }
+}
+
+impl SseClient {
+    /// Fetches the current configuration from `url` with a single HTTP request.
+    ///
+    /// The response body is parsed as a `ServerConfig` of the client's
+    /// [wire format](crate::SseClientBuilder::wire_format), encoded as
+    /// [`payload_format`](crate::SseClientBuilder::payload_format) sets or as
+    /// the response's `Content-Type` advertises, JSON by default. When the server
+    /// sends a `Last-Modified` header, it is remembered and sent back as
+    /// `If-Modified-Since` the next time the same URL is fetched through this
+    /// client (or a clone of it). A `304 Not Modified` answer is reported as
+    /// [`Snapshot::NotModified`] without attempting to parse its (empty) body, so
+    /// the caller can keep its current configuration.
+    ///
+    /// The bearer token is sent like for the event stream. Redirects are not
+    /// followed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::Request` if the request fails,
+    /// `ConfigError::HttpStatus` if the server answers with any status other than
+    /// a success or `304`, and `ConfigError::JsonParse` if the body is not a valid
+    /// configuration.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use config_sdk::{Snapshot, SseClientBuilder};
+    ///
+    /// # async fn run() -> Result<(), config_sdk::ConfigError> {
+    /// let client = SseClientBuilder::new("http://example.com/sse").build()?;
+    ///
+    /// match client.fetch_snapshot("http://example.com/config").await? {
+    ///     Snapshot::Modified(config) => println!("new config: {:?}", config),
+    ///     Snapshot::NotModified => println!("unchanged"),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn fetch_snapshot(&self, url: &str) -> Result<Snapshot, ConfigError> {
+        self.fetch(url, true).await
+    }
+
+    /// Fetches the configuration at `url`, sending `If-Modified-Since` if
+    /// `conditional`. Without it, the server has no reason to answer `304 Not
+    /// Modified`.
+    pub(crate) async fn fetch(&self, url: &str, conditional: bool) -> Result<Snapshot, ConfigError> {
+        let mut request = self.http.get(url).header(ACCEPT, "application/json");
+        if let Some(token) = &self.bearer_token {
+            request = request.bearer_auth(token);
+        }
+        if let Some(since) = self.last_modified.get(url).filter(|_| conditional) {
+            request = request.header(IF_MODIFIED_SINCE, since);
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        if status == StatusCode::NOT_MODIFIED {
+            return Ok(Snapshot::NotModified);
+        }
+        if !status.is_success() {
+            return Err(ConfigError::HttpStatus(status));
+        }
+
+        let last_modified = response.headers().get(LAST_MODIFIED).cloned();
+        let format = self.payload_format.unwrap_or_else(|| {
+            let content_type = response.headers().get(CONTENT_TYPE).and_then(|value| value.to_str().ok());
+            content_type.and_then(PayloadFormat::from_content_type).unwrap_or_default()
+        });
+        let config = format.parse(&response.bytes().await?, self.wire_format)?;
+        self.last_modified.set(url, last_modified);
+        Ok(Snapshot::Modified(config))
+    }
+}
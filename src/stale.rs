@@ -0,0 +1,80 @@
+// stale.rs
+
+use crate::client::SseClient;
+use slog::{info, warn, Logger};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Tracks whether a listener's configuration is known to be current, and
+/// calls the client's [`on_stale`](crate::SseClientBuilder::on_stale) and
+/// [`on_fresh`](crate::SseClientBuilder::on_fresh) hooks when that changes.
+///
+/// The configuration is current while the listener is connected, as the
+/// server pushes every change, and when a configuration was just received.
+/// It turns stale once the listener has been without either for longer than
+/// the client's [`stale_after`](crate::SseClientBuilder::stale_after)
+/// threshold, and stays stale until a configuration is received again.
+pub(crate) struct Freshness<'a> {
+    sse_client: &'a SseClient,
+    /// When the configuration was last known to be current.
+    fresh_as_of: Instant,
+    connected: bool,
+    stale: bool,
+}
+
+impl<'a> Freshness<'a> {
+    /// Starts tracking a listener started at `now`, without a configuration.
+    pub(crate) fn new(sse_client: &'a SseClient, now: Instant) -> Self {
+        Freshness { sse_client, fresh_as_of: now, connected: false, stale: false }
+    }
+
+    /// Records that the listener connected.
+    pub(crate) fn connected(&mut self) {
+        self.connected = true;
+    }
+
+    /// Records that the listener lost its connection at `now`.
+    pub(crate) fn disconnected(&mut self, now: Instant) {
+        if self.connected && !self.stale {
+            self.fresh_as_of = now;
+        }
+        self.connected = false;
+    }
+
+    /// Records that a configuration was received at `now`, calling the
+    /// `on_fresh` hook if the configuration had turned stale.
+    pub(crate) fn received(&mut self, now: Instant, log: &Logger) {
+        let unconfirmed = now.saturating_duration_since(self.fresh_as_of);
+        self.fresh_as_of = now;
+        if !std::mem::take(&mut self.stale) {
+            return;
+        }
+        info!(log, "Configuration is current again"; "stale_for_ms" => unconfirmed.as_millis() as u64);
+        if let Some(hook) = &self.sse_client.fresh_hook {
+            hook(unconfirmed);
+        }
+    }
+
+    /// Returns how long until the configuration turns stale, or `None` if it
+    /// cannot right now: while connected, once it is stale already, or
+    /// without a threshold or with one too long to add to an instant.
+    pub(crate) fn stale_in(&self, now: Instant) -> Option<Duration> {
+        let threshold = self.sse_client.stale_after.filter(|_| !self.connected && !self.stale)?;
+        Some(self.fresh_as_of.checked_add(threshold)?.saturating_duration_since(now))
+    }
+
+    /// Marks the configuration as stale and calls the `on_stale` hook, if it
+    /// has turned stale by `now`.
+    pub(crate) fn check(&mut self, now: Instant, log: &Logger) {
+        if self.stale_in(now) != Some(Duration::ZERO) {
+            return;
+        }
+        self.stale = true;
+        let unconfirmed = now.saturating_duration_since(self.fresh_as_of);
+        warn!(log, "Configuration is stale"; "unconfirmed_ms" => unconfirmed.as_millis() as u64,
+            "stale_after_ms" => self.sse_client.stale_after.unwrap_or_default().as_millis() as u64);
+        if let Some(hook) = &self.sse_client.stale_hook {
+            hook(unconfirmed);
+        }
+    }
+}
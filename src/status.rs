@@ -0,0 +1,54 @@
+// status.rs
+
+use reqwest::StatusCode;
+
+/// What the listener does with the HTTP status of a connection attempt.
+///
+/// Returned by the status policy installed with
+/// [`SseClientBuilder::status_policy`](crate::SseClientBuilder::status_policy).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusAction {
+    /// Treat the response as a live event stream and start reading it.
+    Connect,
+    /// Count the attempt as failed and retry after the usual backoff delay.
+    Retry,
+    /// Count the attempt as failed and retry after the short delay set with
+    /// [`SseClientBuilder::retry_soon_delay`](crate::SseClientBuilder::retry_soon_delay)
+    /// instead of the backoff delay.
+    RetrySoon,
+    /// Stop listening and return `ConfigError::HttpStatus`.
+    Fatal,
+}
+
+/// What the listener does when the server answers with a `Content-Type` that
+/// is not one of the accepted ones, see
+/// [`SseClientBuilder::content_type_policy`](crate::SseClientBuilder::content_type_policy).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ContentTypePolicy {
+    /// Log a warning and read the stream anyway.
+    #[default]
+    Warn,
+    /// Stop listening and return `ConfigError::UnexpectedContentType`.
+    Reject,
+}
+
+/// The default status policy: any `2xx` status connects, a `4xx` status is
+/// fatal and everything else is retried with backoff.
+///
+/// A `4xx` status means the request itself is wrong, e.g. a bad token, so
+/// retrying it would only use up the retry budget. `408 Request Timeout` and
+/// `429 Too Many Requests` are the exception and are retried.
+pub fn default_status_policy(status: StatusCode) -> StatusAction {
+    if status.is_success() {
+        StatusAction::Connect
+    } else if default_retry_on_status(status) {
+        StatusAction::Retry
+    } else {
+        StatusAction::Fatal
+    }
+}
+
+/// Whether the default status policy retries a non-`2xx` status.
+pub fn default_retry_on_status(status: StatusCode) -> bool {
+    !status.is_client_error() || matches!(status, StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS)
+}
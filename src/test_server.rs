@@ -0,0 +1,100 @@
+// test_server.rs
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+
+/// How the server answers one connection.
+pub(crate) enum Reply {
+    /// Writes the response and keeps the connection open without sending
+    /// anything else, so the listener only leaves it on an idle timeout.
+    Hold(Vec<u8>),
+    /// Writes the response and closes the connection.
+    Close(Vec<u8>),
+    /// Writes the response and keeps the connection alive, then closes it
+    /// without answering as soon as the next request arrives on it, like a
+    /// server whose keep-alive timeout has just run out.
+    CloseOnReuse(Vec<u8>),
+    /// Accepts the connection but never answers.
+    Silent,
+}
+
+impl Reply {
+    /// Answers with an event stream of `events`, kept open.
+    pub(crate) fn events(events: &str) -> Reply {
+        Reply::Hold(format!("HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\r\n{}", events).into_bytes())
+    }
+
+    /// Answers with an event stream of `events`, closed once they are sent.
+    pub(crate) fn events_then_close(events: &str) -> Reply {
+        let response = format!("HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n{}", events);
+        Reply::Close(response.into_bytes())
+    }
+}
+
+/// A minimal HTTP server on a local port, which reads the request of every
+/// connection and answers it as told.
+pub(crate) struct TestServer {
+    pub(crate) url: String,
+    requests: mpsc::UnboundedReceiver<String>,
+}
+
+impl TestServer {
+    /// Answers the connections with `replies`, one per connection in order,
+    /// and refuses connections once they are used up.
+    pub(crate) async fn start(replies: impl IntoIterator<Item = Reply>) -> TestServer {
+        let mut replies = replies.into_iter().collect::<Vec<_>>().into_iter();
+        TestServer::serve(move |_| replies.next()).await
+    }
+
+    /// Answers every connection with the reply `reply` makes for it.
+    pub(crate) async fn repeat(reply: impl Fn() -> Reply + Send + 'static) -> TestServer {
+        TestServer::serve(move |_| Some(reply())).await
+    }
+
+    /// Answers connection number `n`, counted from 0, with `reply(n)`, and
+    /// refuses connections from the first one it returns `None` for.
+    pub(crate) async fn serve(mut reply: impl FnMut(usize) -> Option<Reply> + Send + 'static) -> TestServer {
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/sse", server.local_addr().unwrap());
+        let (sender, requests) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            for n in 0.. {
+                let Ok((mut socket, _)) = server.accept().await else { return };
+                let Some(reply) = reply(n) else { return };
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    let mut request = [0; 4096];
+                    let Ok(read) = socket.read(&mut request).await else { return };
+                    let _ = sender.send(String::from_utf8_lossy(&request[..read]).to_lowercase());
+                    match reply {
+                        Reply::Hold(response) => {
+                            let _ = socket.write_all(&response).await;
+                            std::future::pending::<()>().await;
+                        },
+                        Reply::Close(response) => {
+                            let _ = socket.write_all(&response).await;
+                        },
+                        Reply::CloseOnReuse(response) => {
+                            let _ = socket.write_all(&response).await;
+                            let _ = socket.read(&mut request).await;
+                        },
+                        Reply::Silent => std::future::pending::<()>().await,
+                    }
+                });
+            }
+        });
+        TestServer { url, requests }
+    }
+
+    /// Returns the next request the server received, lowercased.
+    pub(crate) async fn request(&mut self) -> String {
+        self.requests.recv().await.expect("the server stopped")
+    }
+}
+
+/// Returns a URL on a local port nothing listens on.
+pub(crate) async fn unused_url() -> String {
+    let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    format!("http://{}/sse", server.local_addr().unwrap())
+}
@@ -0,0 +1,104 @@
+// testing.rs
+
+use crate::client::SseClient;
+use crate::errors::ConfigError;
+use crate::listener::{run_with, Controls};
+use crate::models::{EventMeta, ServerConfig};
+use crate::transport::{Connection, Transport};
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use futures::stream::{Stream, StreamExt};
+use reqwest::{StatusCode, Version};
+use slog::Logger;
+use std::io;
+
+impl SseClient {
+    /// Listens like [`listen`](Self::listen), reading the event stream from
+    /// `streams` instead of the server.
+    ///
+    /// Every connection the listener opens reads the next stream of
+    /// `streams`, answered with a `200 OK`: the first stream is read when the
+    /// listener starts, the next one whenever it reconnects. Parsing, filters,
+    /// merging, retries and the error hook all behave as with the server, so
+    /// logic built on the listener can be tested in-process, without any HTTP.
+    /// An `Err` item is handled like a failed read, according to the client's
+    /// [error policy](crate::SseClientBuilder::error_policy).
+    ///
+    /// As with the server, the listener returns once a stream ends without the
+    /// listener having to reconnect. Once `streams` is used up, every further
+    /// connection attempt fails and the listener retries until it gives up.
+    ///
+    /// # Errors
+    ///
+    /// See [`start_listening_for_updates`](crate::start_listening_for_updates).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use config_sdk::{ConfigError, ServerConfig, SseClientBuilder};
+    /// use futures::stream;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), ConfigError> {
+    /// let chunks = ["data: {\"settings\": {\"level\": 1}}\n\nda", "ta: {\"settings\": {\"level\": 2}}\n\n"];
+    /// let seen = Arc::new(Mutex::new(Vec::new()));
+    /// let handled = seen.clone();
+    ///
+    /// SseClientBuilder::new("http://example.com/sse")
+    ///     .build()?
+    ///     .listen_to_streams([stream::iter(chunks.map(Ok::<_, ConfigError>))], move |config: ServerConfig| {
+    ///         handled.lock().unwrap().push(config.settings["level"].clone());
+    ///     })
+    ///     .await?;
+    ///
+    /// assert_eq!(*seen.lock().unwrap(), [1, 2]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn listen_to_streams<I, S, B, E, F>(&self, streams: I, mut update_handler: F) -> Result<(), ConfigError>
+    where
+        I: IntoIterator<Item = S>,
+        I::IntoIter: Send,
+        S: Stream<Item = Result<B, E>> + Send + 'static,
+        B: Into<Bytes>,
+        E: Into<ConfigError>,
+        F: FnMut(ServerConfig) + Send + 'static,
+    {
+        let mut transport = StreamTransport { streams: streams.into_iter() };
+        let dispatch = move |config, _raw: &[u8], _meta: &EventMeta| update_handler(config);
+        run_with(self, &mut transport, dispatch, Controls::default()).await
+    }
+}
+
+/// Opens every connection on the next of a sequence of streams.
+struct StreamTransport<I> {
+    streams: I,
+}
+
+impl<I, S, B, E> Transport for StreamTransport<I>
+where
+    I: Iterator<Item = S> + Send,
+    S: Stream<Item = Result<B, E>> + Send + 'static,
+    B: Into<Bytes>,
+    E: Into<ConfigError>,
+{
+    fn open<'a>(
+        &'a mut self,
+        url: &'a str,
+        _last_event_id: Option<&'a str>,
+        _log: &'a Logger,
+    ) -> BoxFuture<'a, Result<Connection, ConfigError>> {
+        let connection = match self.streams.next() {
+            Some(stream) => Ok(Connection {
+                status: StatusCode::OK,
+                url: url.to_string(),
+                version: Version::HTTP_11,
+                content_type: None,
+                body: stream.map(|item| item.map(Into::into).map_err(Into::into)).boxed(),
+            }),
+            None => Err(ConfigError::Io(io::Error::new(io::ErrorKind::NotConnected, "no streams left to read"))),
+        };
+        Box::pin(async move { connection })
+    }
+}
@@ -0,0 +1,91 @@
+// transport.rs
+
+use crate::affinity::CookieJar;
+use crate::client::SseClient;
+use crate::errors::{ConfigError, TimeoutKind};
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use futures::stream::{BoxStream, StreamExt};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{StatusCode, Version};
+use slog::Logger;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// An open connection to an event stream, or the refusal to open one.
+pub(crate) struct Connection {
+    /// The status the server answered with.
+    pub(crate) status: StatusCode,
+    /// Where the connection ended up, after any redirects.
+    pub(crate) url: String,
+    pub(crate) version: Version,
+    /// The `Content-Type` the server answered with, if any.
+    pub(crate) content_type: Option<String>,
+    /// The bytes of the event stream, read once the status has been accepted.
+    pub(crate) body: BoxStream<'static, Result<Bytes, ConfigError>>,
+}
+
+/// Opens the connections a listener reads its event stream from.
+///
+/// The listener's connect, read and retry loop does not depend on how the
+/// bytes arrive: [`HttpTransport`] subscribes to the server, while tests can
+/// hand the listener streams of their own.
+pub(crate) trait Transport: Send {
+    /// Opens a connection to `url`, asking to resume after the event with ID
+    /// `last_event_id`, if any.
+    fn open<'a>(
+        &'a mut self,
+        url: &'a str,
+        last_event_id: Option<&'a str>,
+        log: &'a Logger,
+    ) -> BoxFuture<'a, Result<Connection, ConfigError>>;
+}
+
+/// Subscribes to the event stream over HTTP, as configured on the client.
+pub(crate) struct HttpTransport<'c> {
+    sse_client: &'c SseClient,
+    /// Set when the server closed a stream before it delivered anything, so
+    /// the next connection is not taken from the pool.
+    fresh: Arc<AtomicBool>,
+    /// The cookies echoed back on reconnect, with sticky sessions.
+    cookies: Option<CookieJar>,
+}
+
+impl<'c> HttpTransport<'c> {
+    pub(crate) fn new(sse_client: &'c SseClient) -> Self {
+        let cookies = sse_client.sticky_sessions.then(CookieJar::default);
+        HttpTransport { sse_client, fresh: Arc::new(AtomicBool::new(false)), cookies }
+    }
+}
+
+impl Transport for HttpTransport<'_> {
+    fn open<'a>(
+        &'a mut self,
+        url: &'a str,
+        last_event_id: Option<&'a str>,
+        log: &'a Logger,
+    ) -> BoxFuture<'a, Result<Connection, ConfigError>> {
+        Box::pin(async move {
+            let fresh = self.fresh.swap(false, Ordering::Relaxed);
+            let response = crate::listener::connect(self.sse_client, url, last_event_id, fresh, self.cookies.as_mut(), log).await?;
+            let stale = Arc::clone(&self.fresh);
+            let mut first = true;
+            Ok(Connection {
+                status: response.status(),
+                url: response.url().to_string(),
+                version: response.version(),
+                content_type: response.headers().get(CONTENT_TYPE).and_then(|value| value.to_str().ok()).map(str::to_string),
+                body: response
+                    .bytes_stream()
+                    .map(move |item| {
+                        let item = item.map_err(|e| ConfigError::from_request(e, TimeoutKind::Read));
+                        if std::mem::take(&mut first) && item.as_ref().is_err_and(ConfigError::is_connection_closed) {
+                            stale.store(true, Ordering::Relaxed);
+                        }
+                        item
+                    })
+                    .boxed(),
+            })
+        })
+    }
+}
@@ -0,0 +1,83 @@
+// unix.rs
+
+use crate::client::{SseClient, USER_AGENT};
+use crate::errors::ConfigError;
+use hyper::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, HOST};
+use hyper::{Body, Request};
+use reqwest::{Response, ResponseBuilderExt, Url};
+use std::io;
+use std::path::Path;
+use tokio::net::UnixStream;
+
+/// Issues the subscription request for `sse_client` over the Unix domain socket
+/// at `socket`.
+///
+/// The request is plain HTTP/1.1, so the server sees the same headers as over
+/// TCP. For a `unix://` URL the request target is `/`, with the URL's query,
+/// and the `Host` header is `localhost`; otherwise both are taken from the
+/// client's URL. The response is converted into a `reqwest::Response` so the
+/// listener can read it like any other.
+pub(crate) async fn send(sse_client: &SseClient, socket: &Path, last_event_id: Option<&str>) -> Result<Response, ConfigError> {
+    let url = Url::parse(&sse_client.url)
+        .map_err(|e| ConfigError::GenericError(format!("Invalid URL {}: {}", sse_client.url, e)))?;
+    let (host, target) = if url.scheme() == "unix" {
+        let target = match url.query() {
+            Some(query) => format!("/?{}", query),
+            None => "/".to_string(),
+        };
+        ("localhost".to_string(), target)
+    } else {
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (host, None) => host.unwrap_or("localhost").to_string(),
+            (None, Some(_)) => "localhost".to_string(),
+        };
+        let target = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        (host, target)
+    };
+
+    let stream = UnixStream::connect(socket).await?;
+    let (mut sender, connection) = hyper::client::conn::handshake(stream).await.map_err(io_error)?;
+    // The connection has to be polled for the response body to make progress.
+    // It finishes on its own once the body has been read or dropped.
+    tokio::spawn(connection);
+
+    let mut request = Request::builder()
+        .method(sse_client.method.clone())
+        .uri(target)
+        .header(HOST, host)
+        .header(ACCEPT, "text/event-stream")
+        .header("User-Agent", USER_AGENT);
+    if let Some(token) = &sse_client.bearer_token {
+        request = request.header(AUTHORIZATION, format!("Bearer {}", token));
+    }
+    if let Some(id) = last_event_id {
+        request = request.header("Last-Event-ID", id);
+    }
+    let body = match &sse_client.request_body {
+        Some(body) => {
+            request = request.header(CONTENT_TYPE, body.content_type.as_str());
+            Body::from(body.content.clone())
+        },
+        None => Body::empty(),
+    };
+    let request = request.body(body).map_err(io_error)?;
+    let response = sender.send_request(request).await.map_err(io_error)?;
+
+    // Rebuild the response so that it carries the client's URL, which is what
+    // the listener reports once connected.
+    let (parts, body) = response.into_parts();
+    let mut builder = hyper::Response::builder().status(parts.status).version(parts.version).url(url);
+    if let Some(headers) = builder.headers_mut() {
+        *headers = parts.headers;
+    }
+    let response = builder.body(body).map_err(io_error)?;
+    Ok(Response::from(response))
+}
+
+fn io_error(e: impl std::error::Error + Send + Sync + 'static) -> ConfigError {
+    ConfigError::Io(io::Error::other(e))
+}
@@ -0,0 +1,157 @@
+// utf8.rs
+
+use crate::errors::ConfigError;
+use std::borrow::Cow;
+
+/// Number of bytes shown on either side of an invalid sequence.
+const CONTEXT: usize = 16;
+
+/// Checks that an SSE stream is valid UTF-8 as its chunks arrive.
+///
+/// A character split across two chunks is not an error: its first bytes are
+/// kept until the next chunk completes it. Offsets count the bytes of the
+/// stream since [`reset`](Self::reset).
+#[derive(Debug, Default)]
+pub(crate) struct Utf8Checker {
+    /// Bytes of the stream checked so far, including `pending`.
+    offset: u64,
+    /// The start of a character that continues in the next chunk.
+    pending: Vec<u8>,
+    /// The last bytes checked before `pending`, shown as context.
+    tail: Vec<u8>,
+}
+
+impl Utf8Checker {
+    /// Starts checking a new stream.
+    pub(crate) fn reset(&mut self) {
+        *self = Utf8Checker::default();
+    }
+
+    /// Checks the next chunk of the stream.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::InvalidUtf8` for the first invalid sequence in
+    /// `chunk`, with its offset in the stream and the bytes around it. The rest
+    /// of the chunk is still checked, so the next chunk is checked from the
+    /// right place.
+    pub(crate) fn check(&mut self, chunk: &[u8]) -> Result<(), ConfigError> {
+        let data: Cow<[u8]> = if self.pending.is_empty() {
+            Cow::Borrowed(chunk)
+        } else {
+            Cow::Owned([self.pending.as_slice(), chunk].concat())
+        };
+        let start = self.offset - self.pending.len() as u64;
+        self.offset += chunk.len() as u64;
+
+        let mut first_error = None;
+        let mut checked = 0;
+        let complete = loop {
+            match std::str::from_utf8(&data[checked..]) {
+                Ok(_) => break data.len(),
+                Err(e) => {
+                    let invalid_at = checked + e.valid_up_to();
+                    let Some(invalid_len) = e.error_len() else {
+                        // The last character is incomplete.
+                        break invalid_at;
+                    };
+                    if first_error.is_none() {
+                        first_error = Some(ConfigError::InvalidUtf8 {
+                            offset: start + invalid_at as u64,
+                            context: self.context(&data, invalid_at, invalid_len),
+                        });
+                    }
+                    checked = invalid_at + invalid_len;
+                },
+            }
+        };
+
+        self.tail.extend_from_slice(&data[complete.saturating_sub(CONTEXT)..complete]);
+        self.tail.drain(..self.tail.len().saturating_sub(CONTEXT));
+        self.pending = data[complete..].to_vec();
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// Renders the bytes around the invalid sequence at `data[at..at + len]`,
+    /// with anything but printable ASCII escaped.
+    fn context(&self, data: &[u8], at: usize, len: usize) -> String {
+        let before = &data[at.saturating_sub(CONTEXT)..at];
+        let earlier = &self.tail[self.tail.len().saturating_sub(CONTEXT - before.len())..];
+        let after = &data[at + len..(at + len + CONTEXT).min(data.len())];
+        format!(
+            "{}{}[{}]{}",
+            earlier.escape_ascii(),
+            before.escape_ascii(),
+            data[at..at + len].escape_ascii(),
+            after.escape_ascii()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the offset and context of an invalid UTF-8 error.
+    fn invalid(result: Result<(), ConfigError>) -> (u64, String) {
+        match result {
+            Err(ConfigError::InvalidUtf8 { offset, context }) => (offset, context),
+            other => panic!("expected invalid UTF-8, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn accepts_characters_split_across_chunks() {
+        let mut checker = Utf8Checker::default();
+        let text = "data: \u{00e9}\u{20ac}\u{1f600}\n".as_bytes();
+        for byte in text {
+            checker.check(std::slice::from_ref(byte)).unwrap();
+        }
+    }
+
+    #[test]
+    fn reports_the_offset_in_the_stream() {
+        let mut checker = Utf8Checker::default();
+        checker.check(b"data: ab").unwrap();
+        let (offset, context) = invalid(checker.check(b"c\xffd\n"));
+        assert_eq!(offset, 9);
+        assert_eq!(context, "data: abc[\\xff]d\\n");
+    }
+
+    #[test]
+    fn reports_a_split_character_that_turns_out_invalid() {
+        let mut checker = Utf8Checker::default();
+        checker.check(b"x\xe2\x82").unwrap();
+        // `\xe2\x82` needs a continuation byte, not `y`.
+        assert_eq!(invalid(checker.check(b"y")).0, 1);
+        // Checking carries on after the invalid sequence.
+        checker.check(b"\xe2\x82\xac").unwrap();
+    }
+
+    #[test]
+    fn reports_only_the_first_error_of_a_chunk() {
+        let mut checker = Utf8Checker::default();
+        assert_eq!(invalid(checker.check(b"\xff\xfe")).0, 0);
+        checker.check(b"ok").unwrap();
+    }
+
+    #[test]
+    fn context_is_limited_on_either_side() {
+        let mut checker = Utf8Checker::default();
+        let mut chunk = vec![b'a'; 40];
+        chunk.push(0xff);
+        chunk.extend_from_slice(&[b'b'; 40]);
+        let (_, context) = invalid(checker.check(&chunk));
+        assert_eq!(context, format!("{}[\\xff]{}", "a".repeat(CONTEXT), "b".repeat(CONTEXT)));
+    }
+
+    #[test]
+    fn reset_starts_a_new_stream() {
+        let mut checker = Utf8Checker::default();
+        checker.check(b"abc\xe2").unwrap();
+        checker.reset();
+        let (offset, context) = invalid(checker.check(b"\x82"));
+        assert_eq!(offset, 0);
+        assert_eq!(context, "[\\x82]");
+    }
+}
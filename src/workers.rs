@@ -0,0 +1,37 @@
+// workers.rs
+
+use crate::client::SseClient;
+use crate::models::{ConfigEvent, ConfigOrigin, ServerConfig};
+use crate::pipeline::call_handler;
+use slog::Logger;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use tokio::sync::mpsc;
+
+/// Picks which of `workers` handles `config`.
+///
+/// Configurations with the same value of the setting named `key` always go to
+/// the same worker, so they are handled in order. Configurations without it,
+/// or all of them if there is no key, go to the first worker.
+pub(crate) fn worker_index(config: &ServerConfig, key: Option<&str>, workers: usize) -> usize {
+    let Some(value) = key.and_then(|key| config.settings.get(key)) else {
+        return 0;
+    };
+    let mut hasher = DefaultHasher::new();
+    value.to_string().hash(&mut hasher);
+    (hasher.finish() % workers as u64) as usize
+}
+
+/// Calls `handler` for every configuration received from `events`, blocking
+/// the thread while waiting for the next one, until the channel is closed.
+pub(crate) fn run_worker<F>(sse_client: &SseClient, log: &Logger, handler: &F, mut events: mpsc::Receiver<ConfigEvent>)
+where
+    F: Fn(ServerConfig),
+{
+    while let Some(event) = events.blocking_recv() {
+        // Reassertions and rollbacks were acknowledged the first time around.
+        let id = event.meta.id.as_deref()
+            .filter(|_| !matches!(event.meta.origin, ConfigOrigin::Reassert | ConfigOrigin::Rollback));
+        call_handler(sse_client, log, id, || handler(event.config));
+    }
+}